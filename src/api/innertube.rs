@@ -255,7 +255,8 @@ fn build_headers(profile: &ClientProfile, visitor_id: Option<&str>) -> HeaderMap
     headers
 }
 
-fn clean_track_title(title: &str) -> String {
+/// Strip video-specific suffixes such as "(Official Video)" from a title
+pub fn clean_track_title(title: &str) -> String {
     title
         .replace("(Official Music Video)", "")
         .replace("(Official Video)", "")
@@ -265,7 +266,8 @@ fn clean_track_title(title: &str) -> String {
         .to_string()
 }
 
-fn clean_track_artist(artist: &str) -> String {
+/// Strip the " - Topic" suffix of auto-generated artist channels
+pub fn clean_track_artist(artist: &str) -> String {
     artist.replace(" - Topic", "").trim().to_string()
 }
 
@@ -1,3 +1,8 @@
 mod innertube;
+mod subsonic;
 
-pub use innertube::{InnertubeClient, LyricLine, SearchResult, StreamInfo};
+pub use innertube::{
+    InnertubeClient, LyricLine, SearchPage, SearchResult, StreamInfo, clean_track_artist,
+    clean_track_title,
+};
+pub use subsonic::{SubsonicClient, SubsonicCredentials, SubsonicSearch, SubsonicSong};
@@ -0,0 +1,510 @@
+use std::time::Duration;
+
+use reqwest::Url;
+use reqwest::blocking::Client;
+use serde_json::Value;
+
+/// Subsonic REST API version the requests are written against
+const API_VERSION: &str = "1.16.1";
+
+/// Name the server shows for this app in its list of players
+const CLIENT_NAME: &str = "musika";
+
+/// How long a request may take before the server is considered unreachable
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Random bytes in a salt, written out as hex
+const SALT_BYTES: usize = 16;
+
+/// Where a Subsonic server is and how to sign in to it.
+/// Only a salted token of the password is kept, never the password itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubsonicCredentials {
+    pub server_url: String,
+    pub username: String,
+    pub token: String,
+    pub salt: String,
+}
+
+impl SubsonicCredentials {
+    pub fn new(server_url: &str, username: &str, password: &str) -> Result<Self, String> {
+        let salt = new_salt()?;
+        Ok(Self {
+            server_url: server_url.trim().trim_end_matches('/').to_string(),
+            username: username.trim().to_string(),
+            token: format!("{:x}", md5::compute(format!("{}{}", password, salt))),
+            salt,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SubsonicSong {
+    pub id: String,
+    pub title: String,
+    pub artist: String,
+    /// Length in seconds, when the server knows it
+    pub duration: Option<i64>,
+    pub cover_art: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SubsonicAlbum {
+    pub id: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SubsonicArtist {
+    pub id: String,
+}
+
+/// Songs, albums and artists matching a search
+#[derive(Debug, Clone)]
+pub struct SubsonicSearch {
+    pub songs: Vec<SubsonicSong>,
+    pub albums: Vec<SubsonicAlbum>,
+    pub artists: Vec<SubsonicArtist>,
+}
+
+/// Client for a Subsonic-compatible server, such as Navidrome
+pub struct SubsonicClient {
+    client: Client,
+    credentials: SubsonicCredentials,
+}
+
+impl SubsonicClient {
+    pub fn new(credentials: SubsonicCredentials) -> Self {
+        let client = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .expect("subsonic http client");
+        Self {
+            client,
+            credentials,
+        }
+    }
+
+    /// Check that the server is reachable and accepts the credentials
+    pub fn ping(&self) -> Result<(), String> {
+        self.get_json("ping", &[]).map(|_| ())
+    }
+
+    /// Search songs, albums and artists; `song_offset` pages through the songs
+    pub fn search3(
+        &self,
+        query: &str,
+        song_offset: usize,
+        song_count: usize,
+        album_count: usize,
+        artist_count: usize,
+    ) -> Result<SubsonicSearch, String> {
+        let value = self.get_json(
+            "search3",
+            &[
+                ("query", query),
+                ("songOffset", &song_offset.to_string()),
+                ("songCount", &song_count.to_string()),
+                ("albumCount", &album_count.to_string()),
+                ("artistCount", &artist_count.to_string()),
+            ],
+        )?;
+        let result = value.get("searchResult3");
+        let items = |key: &str| {
+            result
+                .and_then(|result| result.get(key))
+                .and_then(|items| items.as_array())
+                .cloned()
+                .unwrap_or_default()
+        };
+
+        Ok(SubsonicSearch {
+            songs: items("song").iter().filter_map(parse_song).collect(),
+            albums: items("album")
+                .iter()
+                .filter_map(|album| {
+                    Some(SubsonicAlbum {
+                        id: string_field(album, "id")?,
+                    })
+                })
+                .collect(),
+            artists: items("artist")
+                .iter()
+                .filter_map(|artist| {
+                    Some(SubsonicArtist {
+                        id: string_field(artist, "id")?,
+                    })
+                })
+                .collect(),
+        })
+    }
+
+    /// Songs of an album, in track order
+    pub fn album(&self, id: &str) -> Result<Vec<SubsonicSong>, String> {
+        let value = self.get_json("getAlbum", &[("id", id)])?;
+        Ok(value
+            .pointer("/album/song")
+            .and_then(|songs| songs.as_array())
+            .map(|songs| songs.iter().filter_map(parse_song).collect())
+            .unwrap_or_default())
+    }
+
+    /// Albums of an artist
+    pub fn artist(&self, id: &str) -> Result<Vec<SubsonicAlbum>, String> {
+        let value = self.get_json("getArtist", &[("id", id)])?;
+        Ok(value
+            .pointer("/artist/album")
+            .and_then(|albums| albums.as_array())
+            .map(|albums| {
+                albums
+                    .iter()
+                    .filter_map(|album| {
+                        Some(SubsonicAlbum {
+                            id: string_field(album, "id")?,
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    pub fn song(&self, id: &str) -> Result<SubsonicSong, String> {
+        let value = self.get_json("getSong", &[("id", id)])?;
+        value
+            .get("song")
+            .and_then(parse_song)
+            .ok_or_else(|| "Song not found".to_string())
+    }
+
+    /// URL the audio of a song can be streamed from
+    pub fn stream_url(&self, id: &str) -> Result<String, String> {
+        self.url("stream", &[("id", id)]).map(String::from)
+    }
+
+    /// URL of a cover image
+    pub fn cover_art_url(&self, id: &str) -> Result<String, String> {
+        self.url("getCoverArt", &[("id", id)]).map(String::from)
+    }
+
+    /// Tell the server about a song: with `submission` it was played, for play counts and scrobbling;
+    /// without it, it just started and is shown as playing now
+    pub fn scrobble(&self, id: &str, submission: bool) -> Result<(), String> {
+        let submission = if submission { "true" } else { "false" };
+        self.get_json("scrobble", &[("id", id), ("submission", submission)])
+            .map(|_| ())
+    }
+
+    /// Endpoint URL with the authentication parameters every request carries
+    fn url(&self, method: &str, params: &[(&str, &str)]) -> Result<Url, String> {
+        let mut url = Url::parse(&format!("{}/rest/{}", self.credentials.server_url, method))
+            .map_err(|e| format!("Invalid server address: {}", e))?;
+        url.query_pairs_mut()
+            .append_pair("u", &self.credentials.username)
+            .append_pair("t", &self.credentials.token)
+            .append_pair("s", &self.credentials.salt)
+            .append_pair("v", API_VERSION)
+            .append_pair("c", CLIENT_NAME)
+            .append_pair("f", "json")
+            .extend_pairs(params);
+        Ok(url)
+    }
+
+    /// Call an endpoint and return the body of its `subsonic-response`
+    fn get_json(&self, method: &str, params: &[(&str, &str)]) -> Result<Value, String> {
+        let url = self.url(method, params)?;
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .map_err(|error| format!("Network error: {error}"))?
+            .error_for_status()
+            .map_err(|error| format!("Server error: {error}"))?;
+
+        let mut value: Value = response
+            .json()
+            .map_err(|error| format!("Invalid response: {error}"))?;
+        let body = value
+            .get_mut("subsonic-response")
+            .map(Value::take)
+            .ok_or_else(|| "Not a Subsonic server".to_string())?;
+
+        if body.get("status").and_then(|status| status.as_str()) != Some("ok") {
+            let message = body
+                .pointer("/error/message")
+                .and_then(|message| message.as_str())
+                .unwrap_or("Subsonic error");
+            return Err(message.to_string());
+        }
+
+        Ok(body)
+    }
+}
+
+/// Random salt for the password token, from the system's random source; a new one is made for every sign-in
+fn new_salt() -> Result<String, String> {
+    let mut bytes = [0u8; SALT_BYTES];
+    getrandom::fill(&mut bytes).map_err(|e| format!("Failed to make a sign-in salt: {}", e))?;
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+fn string_field(value: &Value, key: &str) -> Option<String> {
+    match value.get(key)? {
+        Value::String(text) => Some(text.clone()),
+        Value::Number(number) => Some(number.to_string()),
+        _ => None,
+    }
+}
+
+fn parse_song(value: &Value) -> Option<SubsonicSong> {
+    Some(SubsonicSong {
+        id: string_field(value, "id")?,
+        title: string_field(value, "title").unwrap_or_else(|| "Unknown".to_string()),
+        artist: string_field(value, "artist").unwrap_or_else(|| "Unknown".to_string()),
+        duration: value.get("duration").and_then(|duration| duration.as_i64()),
+        cover_art: string_field(value, "coverArt"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+
+    /// Answer one request per response on a local port, reporting each request's path and query
+    fn serve(responses: Vec<(u16, &'static str)>) -> (String, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            for (status, body) in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut header = String::new();
+                while reader.read_line(&mut header).unwrap() > 0 && header != "\r\n" {
+                    header.clear();
+                }
+
+                let target = request_line.split_whitespace().nth(1).unwrap_or_default();
+                let _ = sender.send(target.to_string());
+                write!(
+                    stream,
+                    "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+        });
+
+        (address, receiver)
+    }
+
+    fn client(server_url: &str) -> SubsonicClient {
+        SubsonicClient::new(SubsonicCredentials::new(server_url, "alice", "sesame").unwrap())
+    }
+
+    fn query(target: &str) -> HashMap<String, String> {
+        Url::parse(&format!("http://localhost{}", target))
+            .unwrap()
+            .query_pairs()
+            .into_owned()
+            .collect()
+    }
+
+    const OK: &str = r#"{"subsonic-response":{"status":"ok","version":"1.16.1"}}"#;
+
+    const FAILED: &str = r#"{"subsonic-response":{"status":"failed","version":"1.16.1",
+        "error":{"code":40,"message":"Wrong username or password"}}}"#;
+
+    #[test]
+    fn token_is_md5_of_password_and_salt() {
+        let credentials =
+            SubsonicCredentials::new(" http://music.local/ ", " alice ", "sesame").unwrap();
+
+        assert_eq!(credentials.server_url, "http://music.local");
+        assert_eq!(credentials.username, "alice");
+        assert!(!credentials.salt.is_empty());
+        assert_eq!(
+            credentials.token,
+            format!("{:x}", md5::compute(format!("sesame{}", credentials.salt)))
+        );
+    }
+
+    #[test]
+    fn every_sign_in_gets_a_new_random_salt() {
+        let first = SubsonicCredentials::new("http://music.local", "alice", "sesame").unwrap();
+        let second = SubsonicCredentials::new("http://music.local", "alice", "sesame").unwrap();
+
+        assert_eq!(first.salt.len(), SALT_BYTES * 2);
+        assert!(first.salt.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(first.salt, second.salt);
+        assert_ne!(first.token, second.token);
+    }
+
+    #[test]
+    fn requests_carry_the_token_not_the_password() {
+        let (address, requests) = serve(vec![(200, OK)]);
+        let client = client(&address);
+        client.ping().unwrap();
+
+        let target = requests.recv().unwrap();
+        assert!(target.starts_with("/rest/ping?"));
+        let params = query(&target);
+        assert_eq!(params["u"], "alice");
+        assert_eq!(params["t"], client.credentials.token);
+        assert_eq!(params["s"], client.credentials.salt);
+        assert_eq!(params["v"], API_VERSION);
+        assert_eq!(params["f"], "json");
+        assert!(!params.contains_key("p"));
+        assert!(!target.contains("sesame"));
+    }
+
+    #[test]
+    fn ping_reports_server_errors() {
+        let (address, _requests) = serve(vec![(500, "{}"), (200, "{}")]);
+        let client = client(&address);
+
+        assert!(client.ping().unwrap_err().starts_with("Server error"));
+        assert_eq!(client.ping().unwrap_err(), "Not a Subsonic server");
+    }
+
+    #[test]
+    fn failed_status_returns_the_server_message() {
+        let (address, _requests) = serve(vec![(200, FAILED), (200, FAILED)]);
+        let client = client(&address);
+
+        assert_eq!(client.ping().unwrap_err(), "Wrong username or password");
+        assert_eq!(client.song("1").unwrap_err(), "Wrong username or password");
+    }
+
+    #[test]
+    fn search3_parses_songs_albums_and_artists() {
+        let body = r#"{"subsonic-response":{"status":"ok","version":"1.16.1","searchResult3":{
+            "artist":[{"id":"ar-1","name":"Björk"}],
+            "album":[{"id":"al-1","name":"Post"},{"name":"No id"}],
+            "song":[
+                {"id":"so-1","title":"Hyperballad","artist":"Björk","duration":321,"coverArt":"al-1"},
+                {"id":2,"title":"Untagged"},
+                {"title":"No id"}
+            ]}}}"#;
+        let (address, requests) = serve(vec![(200, body)]);
+
+        let search = client(&address).search3("björk", 20, 10, 5, 3).unwrap();
+
+        let params = query(&requests.recv().unwrap());
+        assert_eq!(params["query"], "björk");
+        assert_eq!(params["songOffset"], "20");
+        assert_eq!(params["songCount"], "10");
+        assert_eq!(search.artists.len(), 1);
+        assert_eq!(search.albums.len(), 1);
+        assert_eq!(search.albums[0].id, "al-1");
+        assert_eq!(search.songs.len(), 2);
+        assert_eq!(search.songs[0].title, "Hyperballad");
+        assert_eq!(search.songs[0].duration, Some(321));
+        assert_eq!(search.songs[0].cover_art.as_deref(), Some("al-1"));
+        assert_eq!(search.songs[1].id, "2");
+        assert_eq!(search.songs[1].artist, "Unknown");
+        assert_eq!(search.songs[1].duration, None);
+    }
+
+    #[test]
+    fn album_and_song_are_parsed() {
+        let album = r#"{"subsonic-response":{"status":"ok","album":{"id":"al-1","song":[
+            {"id":"so-1","title":"Army of Me","artist":"Björk"},
+            {"id":"so-2","title":"Hyperballad","artist":"Björk"}]}}}"#;
+        let song = r#"{"subsonic-response":{"status":"ok","song":
+            {"id":"so-2","title":"Hyperballad","artist":"Björk","duration":321}}}"#;
+        let (address, requests) = serve(vec![(200, album), (200, song)]);
+        let client = client(&address);
+
+        let songs = client.album("al-1").unwrap();
+        assert!(requests.recv().unwrap().starts_with("/rest/getAlbum?"));
+        assert_eq!(
+            songs
+                .iter()
+                .map(|song| song.title.as_str())
+                .collect::<Vec<_>>(),
+            ["Army of Me", "Hyperballad"]
+        );
+
+        let song = client.song("so-2").unwrap();
+        assert_eq!(query(&requests.recv().unwrap())["id"], "so-2");
+        assert_eq!(song.id, "so-2");
+        assert_eq!(song.duration, Some(321));
+    }
+
+    #[test]
+    fn artist_lists_its_albums() {
+        let body = r#"{"subsonic-response":{"status":"ok","artist":{"id":"ar-1","name":"Björk",
+            "album":[{"id":"al-1","name":"Debut"},{"id":"al-2","name":"Post"},{"name":"No id"}]}}}"#;
+        let (address, requests) = serve(vec![(200, body)]);
+
+        let albums = client(&address).artist("ar-1").unwrap();
+
+        let target = requests.recv().unwrap();
+        assert!(target.starts_with("/rest/getArtist?"));
+        assert_eq!(query(&target)["id"], "ar-1");
+        assert_eq!(
+            albums
+                .iter()
+                .map(|album| album.id.as_str())
+                .collect::<Vec<_>>(),
+            ["al-1", "al-2"]
+        );
+    }
+
+    #[test]
+    fn scrobble_says_whether_the_song_was_played() {
+        let (address, requests) = serve(vec![(200, OK), (200, OK), (200, FAILED)]);
+        let client = client(&address);
+
+        client.scrobble("so-1", false).unwrap();
+        let target = requests.recv().unwrap();
+        assert!(target.starts_with("/rest/scrobble?"));
+        let params = query(&target);
+        assert_eq!(params["id"], "so-1");
+        assert_eq!(params["submission"], "false");
+
+        client.scrobble("so-1", true).unwrap();
+        assert_eq!(query(&requests.recv().unwrap())["submission"], "true");
+
+        assert_eq!(
+            client.scrobble("so-1", true).unwrap_err(),
+            "Wrong username or password"
+        );
+    }
+
+    #[test]
+    fn stream_and_cover_urls_are_signed_without_the_password() {
+        let (address, requests) = serve(vec![(200, "audio"), (200, "image")]);
+        let client = client(&address);
+        let stream_url = client.stream_url("so-1").unwrap();
+        let cover_url = client.cover_art_url("al-1").unwrap();
+
+        for (url, method, id) in [
+            (stream_url, "stream", "so-1"),
+            (cover_url, "getCoverArt", "al-1"),
+        ] {
+            assert!(url.starts_with(&format!("{}/rest/{}?", address, method)));
+            assert!(!url.contains("sesame"));
+
+            // The URL is handed to the player or the image loader as is, so it must work on its own
+            let body = reqwest::blocking::get(&url).unwrap().text().unwrap();
+            assert!(!body.is_empty());
+            let target = requests.recv().unwrap();
+            let params = query(&target);
+            assert_eq!(params["id"], id);
+            assert_eq!(params["u"], "alice");
+            assert_eq!(params["t"], client.credentials.token);
+            assert_eq!(params["s"], client.credentials.salt);
+            assert!(!params.contains_key("p"));
+        }
+    }
+}
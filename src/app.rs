@@ -3,14 +3,25 @@ use libadwaita as adw;
 use libadwaita::prelude::*;
 
 use crate::config::{
-    APP_ID, CSS_PATH, ICON_LIBRARY, ICON_SEARCH, ICON_SETTINGS,
+    APP_ID, CSS_PATH, ICON_LIBRARY, ICON_SEARCH, ICON_SETTINGS, LONG_SEEK_STEP_SECS, VOLUME_STEP,
     WINDOW_DEFAULT_HEIGHT, WINDOW_DEFAULT_WIDTH,
 };
-use crate::playback::PlaybackController;
-use crate::storage::Database;
+use crate::local::LocalLibrary;
+use crate::playback::{
+    DownloadManager, OfflineMode, PlaybackController, PlayerChange, saved_equalizer,
+    saved_output_device, start_mpris,
+};
+use crate::sources::Sources;
+use crate::storage::{Database, Song};
+use crate::ui::components::{set_image_sources, show_message_dialog, show_toast, show_undo_toast};
+use crate::ui::now_playing::PLAYBACK_RATE_ACTION;
+use crate::ui::sleep_timer::{
+    SLEEP_TIMER_ACTION, parse_sleep_timer_target, sleep_timer_target,
+};
 use crate::ui::{
-    build_header, build_library_view, build_mini_player, build_now_playing_view,
-    build_search_view, build_settings_view,
+    build_equalizer_view, build_header, build_library_view, build_mini_player, build_now_playing_view,
+    build_offline_banner, build_search_view, build_seek_indicator, build_settings_view,
+    build_shortcuts_dialog, set_shortcut_accels,
 };
 
 /// Build and run the application
@@ -48,11 +59,60 @@ fn on_activate(app: &adw::Application) {
     // Set database on playback controller for recent plays tracking
     playback_controller.set_database(database.clone());
 
+    // The equalizer sounds as it was left
+    playback_controller.set_equalizer(saved_equalizer(&database));
+
+    // Play through the device chosen in Settings whenever it is connected
+    playback_controller.set_output_device(saved_output_device(&database));
+
+    // Offline mode follows the network unless turned on by hand
+    let offline = OfflineMode::new(database.clone());
+    playback_controller.set_offline_mode(offline.clone());
+
+    // Desktop media controls and media keys
+    start_mpris(app, &playback_controller);
+
+    // Track notifications, whose buttons call app actions
+    playback_controller.set_application(app);
+
+    // Every place songs are searched for and streamed from
+    let sources = Sources::new();
+    sources.set_subsonic(database.get_subsonic_credentials());
+    set_image_sources(sources.clone());
+    playback_controller.set_sources(sources.clone());
+
+    // Downloads run in the background for the whole session
+    let downloads = DownloadManager::new(database.clone(), offline.clone(), sources.clone());
+
+    // Music folders are rescanned at startup and whenever they change
+    let local_library = LocalLibrary::new(database.clone());
+
     // Build views
-    let search_view = build_search_view(playback_controller.clone(), database.clone());
-    let library_view = build_library_view(playback_controller.clone(), database.clone());
-    let settings_view = build_settings_view();
+    let search_view = build_search_view(
+        playback_controller.clone(),
+        database.clone(),
+        downloads.clone(),
+        offline.clone(),
+        sources.clone(),
+    );
+    let library_view = build_library_view(
+        playback_controller.clone(),
+        database.clone(),
+        downloads.clone(),
+        offline.clone(),
+        local_library.clone(),
+        sources.clone(),
+    );
+    let settings_view = build_settings_view(
+        playback_controller.clone(),
+        database.clone(),
+        downloads.clone(),
+        offline.clone(),
+        local_library.clone(),
+        sources.clone(),
+    );
     let now_playing_view = build_now_playing_view(playback_controller.clone());
+    let equalizer_view = build_equalizer_view(playback_controller.clone(), database.clone());
 
     // Add views to stack with icons
     let search_page = main_stack.add_titled(&search_view, Some("search"), "Search");
@@ -63,6 +123,7 @@ fn on_activate(app: &adw::Application) {
 
     // Now playing is not in the switcher - only accessible from mini player
     main_stack.add_named(&now_playing_view, Some("now_playing"));
+    main_stack.add_named(&equalizer_view, Some("equalizer"));
 
     let settings_page = main_stack.add_titled(&settings_view, Some("settings"), "Settings");
     settings_page.set_icon_name(Some(ICON_SETTINGS));
@@ -109,14 +170,211 @@ fn on_activate(app: &adw::Application) {
     let root = gtk4::Box::new(gtk4::Orientation::Vertical, 0);
     root.add_css_class("app-root");
     root.append(&header);
-    root.append(&main_stack);
+    root.append(&build_offline_banner(&offline));
+    // Toasts from any view (such as undo for deletions) show over the stack
+    let toast_overlay = adw::ToastOverlay::new();
+    toast_overlay.set_child(Some(&main_stack));
+    toast_overlay.set_vexpand(true);
+    // Keyboard and scroll seeks show where they landed over whichever view is open
+    let seek_overlay = gtk4::Overlay::new();
+    seek_overlay.set_child(Some(&toast_overlay));
+    seek_overlay.add_overlay(&build_seek_indicator(&playback_controller));
+    root.append(&seek_overlay);
     root.append(&mini_player);
     root.append(&switcher_bar);
 
     window.set_content(Some(&root));
+
+    // Keyboard shortcuts work from every view
+    add_app_actions(app, &playback_controller, &database, &main_stack, &search_view);
+    set_shortcut_accels(app);
+
     window.present();
 }
 
+/// App-wide actions for playback and moving between views, used by shortcuts and notifications
+fn add_app_actions(
+    app: &adw::Application,
+    playback: &PlaybackController,
+    database: &Database,
+    stack: &adw::ViewStack,
+    search_view: &gtk4::Box,
+) {
+    let add_action = |name: &str, activate: Box<dyn Fn()>| {
+        let action = gtk4::gio::SimpleAction::new(name, None);
+        action.connect_activate(move |_, _| activate());
+        app.add_action(&action);
+    };
+
+    let controller = playback.clone();
+    add_action("play-pause", Box::new(move || controller.toggle_play_pause()));
+    let controller = playback.clone();
+    add_action("pause", Box::new(move || controller.pause()));
+    let controller = playback.clone();
+    add_action("next", Box::new(move || controller.play_next()));
+    let controller = playback.clone();
+    add_action("previous", Box::new(move || controller.play_previous()));
+    let controller = playback.clone();
+    add_action("seek-forward", Box::new(move || controller.seek_by(controller.seek_step())));
+    let controller = playback.clone();
+    add_action("seek-back", Box::new(move || controller.seek_by(-controller.seek_step())));
+    let controller = playback.clone();
+    add_action("seek-forward-long", Box::new(move || controller.seek_by(LONG_SEEK_STEP_SECS)));
+    let controller = playback.clone();
+    add_action("seek-back-long", Box::new(move || controller.seek_by(-LONG_SEEK_STEP_SECS)));
+
+    // Takes the percentage of the song to jump to
+    let seek_to_percent = gtk4::gio::SimpleAction::new("seek-to-percent", Some(glib::VariantTy::INT32));
+    seek_to_percent.connect_activate(glib::clone!(
+        #[strong]
+        playback,
+        move |_, parameter| {
+            if let Some(percent) = parameter.and_then(|parameter| parameter.get::<i32>()) {
+                playback.seek_to_fraction(percent as f64 / 100.0);
+            }
+        }
+    ));
+    app.add_action(&seek_to_percent);
+
+    // Stateful, so the sleep timer menus mark the timer that is running
+    let sleep_timer = gtk4::gio::SimpleAction::new_stateful(
+        SLEEP_TIMER_ACTION,
+        Some(glib::VariantTy::STRING),
+        &sleep_timer_target(None).to_variant(),
+    );
+    sleep_timer.connect_activate(glib::clone!(
+        #[strong]
+        playback,
+        move |_, parameter| {
+            if let Some(target) = parameter.and_then(|parameter| parameter.get::<String>()) {
+                playback.set_sleep_timer(parse_sleep_timer_target(&target));
+            }
+        }
+    ));
+    playback.connect_changed(glib::clone!(
+        #[weak]
+        sleep_timer,
+        #[strong]
+        playback,
+        move |change| {
+            if change == PlayerChange::SleepTimer {
+                sleep_timer.set_state(&sleep_timer_target(playback.sleep_timer()).to_variant());
+            }
+        }
+    ));
+    app.add_action(&sleep_timer);
+
+    // Stateful, so the speed menu marks the speed of the song playing
+    let playback_rate = gtk4::gio::SimpleAction::new_stateful(
+        PLAYBACK_RATE_ACTION,
+        Some(glib::VariantTy::DOUBLE),
+        &playback.rate().to_variant(),
+    );
+    playback_rate.connect_activate(glib::clone!(
+        #[strong]
+        playback,
+        move |_, parameter| {
+            if let Some(rate) = parameter.and_then(|parameter| parameter.get::<f64>()) {
+                playback.set_rate(rate);
+            }
+        }
+    ));
+    playback.connect_changed(glib::clone!(
+        #[weak]
+        playback_rate,
+        #[strong]
+        playback,
+        move |change| {
+            if matches!(change, PlayerChange::Options | PlayerChange::Track) {
+                playback_rate.set_state(&playback.rate().to_variant());
+            }
+        }
+    ));
+    app.add_action(&playback_rate);
+
+    let controller = playback.clone();
+    add_action("volume-up", Box::new(move || controller.set_volume(controller.volume() + VOLUME_STEP)));
+    let controller = playback.clone();
+    add_action("volume-down", Box::new(move || controller.set_volume(controller.volume() - VOLUME_STEP)));
+
+    add_action("like-current", Box::new(glib::clone!(
+        #[strong]
+        playback,
+        #[strong]
+        database,
+        #[weak]
+        stack,
+        move || {
+            let Some(item) = playback.current_item() else {
+                return;
+            };
+            if database.is_song_liked(&item.video_id) {
+                match database.unlike_song(&item.video_id) {
+                    Ok(undo_id) => show_undo_toast(
+                        &stack,
+                        &format!("Removed \"{}\" from liked songs", item.title),
+                        glib::clone!(
+                            #[weak]
+                            stack,
+                            #[strong]
+                            database,
+                            move || {
+                                if let Err(error) = database.undo(undo_id) {
+                                    show_message_dialog(&stack, "Could not undo", &error);
+                                }
+                            }
+                        ),
+                    ),
+                    Err(error) => show_toast(&stack, &error),
+                }
+                return;
+            }
+            let song = Song {
+                video_id: item.video_id,
+                title: item.title,
+                artist: item.artist,
+                duration: item.duration,
+                thumbnail_url: item.thumbnail_url,
+            };
+            match database.like_song(&song) {
+                Ok(()) => show_toast(&stack, "Added to Liked Songs"),
+                Err(error) => show_toast(&stack, &error),
+            }
+        }
+    )));
+
+    add_action("show-shortcuts", Box::new(glib::clone!(
+        #[weak]
+        stack,
+        move || build_shortcuts_dialog().present(Some(&stack))
+    )));
+
+    add_action("focus-search", Box::new(glib::clone!(
+        #[weak]
+        stack,
+        #[weak]
+        search_view,
+        move || {
+            stack.set_visible_child_name("search");
+            let _ = search_view.activate_action("search.focus", None);
+        }
+    )));
+
+    for (action, page) in [
+        ("show-search", "search"),
+        ("show-library", "library"),
+        ("show-now-playing", "now_playing"),
+        ("show-equalizer", "equalizer"),
+        ("show-settings", "settings"),
+    ] {
+        add_action(action, Box::new(glib::clone!(
+            #[weak]
+            stack,
+            move || stack.set_visible_child_name(page)
+        )));
+    }
+}
+
 fn load_css() {
     let provider = gtk4::CssProvider::new();
     provider.load_from_path(CSS_PATH);
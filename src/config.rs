@@ -4,6 +4,7 @@
 pub const APP_ID: &str = "io.nubit.musika";
 pub const APP_NAME: &str = "Musika";
 pub const APP_VERSION: &str = "0.1.0";
+pub const LOG_DOMAIN: &str = "musika";
 
 // Window defaults
 pub const WINDOW_DEFAULT_WIDTH: i32 = 480;
@@ -29,6 +30,32 @@ pub const QUEUE_MAX_HEIGHT: i32 = 300;
 pub const DEBOUNCE_MS: u64 = 350;
 pub const POLL_INTERVAL_MS: u64 = 100;
 
+// Keyboard playback controls
+pub const DEFAULT_SEEK_STEP_SECS: f64 = 5.0;
+pub const LONG_SEEK_STEP_SECS: f64 = 30.0;
+pub const SEEK_INDICATOR_TIMEOUT_MS: u64 = 1200;
+pub const VOLUME_STEP: f64 = 0.1;
+
+// Playback speed; long tracks such as mixes and podcasts remember their own
+pub const MIN_PLAYBACK_RATE: f64 = 0.5;
+pub const MAX_PLAYBACK_RATE: f64 = 2.0;
+pub const PLAYBACK_RATES: &[f64] = &[0.5, 0.75, 1.0, 1.25, 1.5, 1.75, 2.0];
+pub const LONG_TRACK_SECS: i64 = 20 * 60;
+
+// A play is reported to the server after half the song, or this long if that comes first
+pub const SCROBBLE_AFTER_SECS: i64 = 4 * 60;
+
+// Sleep timer
+pub const SLEEP_TIMER_MINUTES: &[u32] = &[15, 30, 45, 60, 90];
+pub const SLEEP_FADE_OUT_SECS: f64 = 30.0;
+
+// Equalizer gain of each band, in dB
+pub const EQ_MIN_GAIN: f64 = -12.0;
+pub const EQ_MAX_GAIN: f64 = 12.0;
+
+// Downloads
+pub const MAX_CONCURRENT_DOWNLOADS: usize = 2;
+
 // Icons
 pub const ICON_SEARCH: &str = "system-search-symbolic";
 pub const ICON_LIBRARY: &str = "folder-music-symbolic";
@@ -44,6 +71,9 @@ pub const ICON_MUSIC: &str = "audio-x-generic-symbolic";
 pub const ICON_HEART: &str = "heart-outline-symbolic";
 pub const ICON_HEART_FILLED: &str = "heart-filled-symbolic";
 pub const ICON_RECENT: &str = "document-open-recent-symbolic";
+pub const ICON_LOCAL_FILES: &str = "drive-harddisk-symbolic";
+pub const ICON_SLEEP_TIMER: &str = "alarm-symbolic";
+pub const ICON_EQUALIZER: &str = "preferences-desktop-sound-symbolic";
 
 // Default asset paths
 pub const DEFAULT_COVER_PATH: &str = "assets/icon.svg";
@@ -0,0 +1,219 @@
+/// One row of a streaming service export
+#[derive(Debug, Clone, Default)]
+pub struct CsvTrack {
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub duration_secs: Option<i64>,
+}
+
+const TITLE_COLUMNS: &[&str] = &["track name", "track", "title", "song name", "song", "name"];
+const ARTIST_COLUMNS: &[&str] = &["artist name(s)", "artist name", "artist names", "artists", "artist"];
+const ALBUM_COLUMNS: &[&str] = &["album name", "album title", "album"];
+const DURATION_MS_COLUMNS: &[&str] = &["duration (ms)", "duration_ms", "track duration (ms)"];
+const DURATION_COLUMNS: &[&str] = &["duration", "length", "time"];
+
+#[derive(Clone, Copy)]
+enum DurationColumn {
+    Millis(usize),
+    Text(usize),
+}
+
+/// Parse a CSV export with a header row naming track, artist and album columns
+pub fn parse_csv_tracks(data: &str) -> Result<Vec<CsvTrack>, String> {
+    let data = data.trim_start_matches('\u{feff}');
+    let delimiter = detect_delimiter(data);
+    let mut rows = parse_rows(data, delimiter).into_iter();
+
+    let header: Vec<String> = rows
+        .next()
+        .ok_or_else(|| "The file is empty".to_string())?
+        .iter()
+        .map(|column| column.trim().to_lowercase())
+        .collect();
+
+    let title_column = find_column(&header, TITLE_COLUMNS)
+        .ok_or_else(|| "No track or title column found".to_string())?;
+    let artist_column = find_column(&header, ARTIST_COLUMNS);
+    let album_column = find_column(&header, ALBUM_COLUMNS);
+    let duration_column = find_column(&header, DURATION_MS_COLUMNS)
+        .map(DurationColumn::Millis)
+        .or_else(|| find_column(&header, DURATION_COLUMNS).map(DurationColumn::Text));
+
+    let cell = |row: &[String], column: Option<usize>| {
+        column
+            .and_then(|index| row.get(index))
+            .map(|value| value.trim().to_string())
+            .unwrap_or_default()
+    };
+
+    let tracks = rows
+        .filter_map(|row| {
+            let title = cell(&row, Some(title_column));
+            if title.is_empty() {
+                return None;
+            }
+            let duration_secs = match duration_column {
+                Some(DurationColumn::Millis(index)) => row
+                    .get(index)
+                    .and_then(|value| value.trim().parse::<i64>().ok())
+                    .map(|millis| (millis + 500) / 1000),
+                Some(DurationColumn::Text(index)) => row.get(index).and_then(|value| parse_duration_text(value)),
+                None => None,
+            };
+            Some(CsvTrack {
+                title,
+                artist: cell(&row, artist_column),
+                album: cell(&row, album_column),
+                duration_secs: duration_secs.filter(|secs| *secs > 0),
+            })
+        })
+        .collect();
+
+    Ok(tracks)
+}
+
+fn find_column(header: &[String], names: &[&str]) -> Option<usize> {
+    names
+        .iter()
+        .find_map(|name| header.iter().position(|column| column == name))
+}
+
+/// Durations are either `m:ss` text or a plain number of seconds
+fn parse_duration_text(value: &str) -> Option<i64> {
+    let value = value.trim();
+    if value.contains(':') {
+        super::parse_duration(value)
+    } else {
+        value.parse::<f64>().ok().map(|secs| secs.round() as i64)
+    }
+}
+
+/// Pick whichever of comma, semicolon or tab occurs most in the header line,
+/// preferring comma on ties
+fn detect_delimiter(data: &str) -> char {
+    let header = data.lines().next().unwrap_or("");
+    ['\t', ';', ',']
+        .into_iter()
+        .max_by_key(|delimiter| header.matches(*delimiter).count())
+        .unwrap_or(',')
+}
+
+/// RFC 4180 parsing: quoted fields may contain delimiters, newlines and doubled quotes
+fn parse_rows(data: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = data.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            if c == '"' {
+                if chars.peek() == Some(&'"') {
+                    field.push('"');
+                    chars.next();
+                } else {
+                    in_quotes = false;
+                }
+            } else {
+                field.push(c);
+            }
+            continue;
+        }
+
+        match c {
+            '"' => in_quotes = true,
+            '\r' => {}
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                if row.iter().any(|value| !value.trim().is_empty()) {
+                    rows.push(std::mem::take(&mut row));
+                } else {
+                    row.clear();
+                }
+            }
+            c if c == delimiter => row.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        if row.iter().any(|value| !value.trim().is_empty()) {
+            rows.push(row);
+        }
+    }
+
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoted_fields_keep_delimiters_quotes_and_newlines() {
+        let data = "Title,Artist,Album\r\n\
+            \"Hello, Goodbye\",The Beatles,\"Magical Mystery Tour\"\r\n\
+            \r\n\
+            \"The \"\"Heroes\"\" Song\",\"Bowie, David\",\"Line one\nline two\"\r\n\
+            ,No title,Skipped";
+
+        let tracks = parse_csv_tracks(data).unwrap();
+
+        assert_eq!(tracks.len(), 2);
+        assert_eq!(tracks[0].title, "Hello, Goodbye");
+        assert_eq!(tracks[0].artist, "The Beatles");
+        assert_eq!(tracks[1].title, "The \"Heroes\" Song");
+        assert_eq!(tracks[1].artist, "Bowie, David");
+        assert_eq!(tracks[1].album, "Line one\nline two");
+    }
+
+    #[test]
+    fn spotify_exports_use_millisecond_durations() {
+        let data = "\u{feff}\"Track URI\",\"Track Name\",\"Artist Name(s)\",\"Album Name\",\"Track Duration (ms)\"\n\
+            \"spotify:track:1\",\"Teardrop\",\"Massive Attack\",\"Mezzanine\",\"330773\"\n";
+
+        let tracks = parse_csv_tracks(data).unwrap();
+
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].title, "Teardrop");
+        assert_eq!(tracks[0].artist, "Massive Attack");
+        assert_eq!(tracks[0].album, "Mezzanine");
+        assert_eq!(tracks[0].duration_secs, Some(331));
+    }
+
+    #[test]
+    fn apple_music_exports_are_tab_separated_with_seconds() {
+        let data = "Name\tArtist\tComposer\tAlbum\tMovement Name\tTime\n\
+            Glory Box\tPortishead\tBarrow, Gibbons\tDummy\t\t306\n";
+
+        let tracks = parse_csv_tracks(data).unwrap();
+
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].title, "Glory Box");
+        assert_eq!(tracks[0].artist, "Portishead");
+        assert_eq!(tracks[0].album, "Dummy");
+        assert_eq!(tracks[0].duration_secs, Some(306));
+    }
+
+    #[test]
+    fn semicolon_files_with_text_durations_are_read() {
+        let data = "Song;Artist;Length\nJóga;Björk;5:05\nHunter;Björk;0:00\n";
+
+        let tracks = parse_csv_tracks(data).unwrap();
+
+        assert_eq!(tracks[0].duration_secs, Some(305));
+        assert_eq!(tracks[1].duration_secs, None);
+    }
+
+    #[test]
+    fn files_without_a_title_column_are_rejected() {
+        assert!(parse_csv_tracks("").is_err());
+        assert_eq!(
+            parse_csv_tracks("Artist,Album\nBjörk,Post\n").unwrap_err(),
+            "No track or title column found"
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+
+use crate::api::{clean_track_artist, clean_track_title};
+use crate::storage::Song;
+
+use super::matching::normalize;
+use super::parse_duration;
+
+/// Uploads of one track rarely differ by more than an intro or outro
+const DURATION_TOLERANCE_SECS: i64 = 10;
+
+/// Bracketed title parts containing these words describe the upload, not the track
+const UPLOAD_TAG_WORDS: &[&str] = &[
+    "official", "video", "audio", "lyric", "lyrics", "visualizer", "visualiser", "hd", "4k", "mv",
+];
+
+/// Normalized title and artist under which uploads of the same track group together
+fn duplicate_key(song: &Song) -> String {
+    let artist = normalize(&clean_track_artist(&song.artist).replace("VEVO", ""));
+    let mut title = normalize(&strip_upload_tags(&clean_track_title(&song.title)));
+
+    // Official videos are often titled "Artist - Title"
+    if let Some(rest) = title.strip_prefix(&artist).and_then(|rest| rest.strip_prefix(' ')) {
+        if !rest.is_empty() {
+            title = rest.to_string();
+        }
+    }
+
+    format!("{}\u{1f}{}", title, artist)
+}
+
+/// Groups of indices into `songs` that look like the same track, each in list order.
+/// Only groups with more than one entry are returned, ordered by their first entry.
+pub fn find_duplicates(songs: &[Song]) -> Vec<Vec<usize>> {
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut groups_by_key: HashMap<String, Vec<usize>> = HashMap::new();
+
+    for (index, song) in songs.iter().enumerate() {
+        let duration = parse_duration(&song.duration);
+        let candidates = groups_by_key.entry(duplicate_key(song)).or_default();
+
+        // A missing duration matches any version with the same title and artist
+        let existing = candidates.iter().copied().find(|group| {
+            let first = parse_duration(&songs[groups[*group][0]].duration);
+            match (duration, first) {
+                (Some(a), Some(b)) => (a - b).abs() <= DURATION_TOLERANCE_SECS,
+                _ => true,
+            }
+        });
+
+        match existing {
+            Some(group) => groups[group].push(index),
+            None => {
+                candidates.push(groups.len());
+                groups.push(vec![index]);
+            }
+        }
+    }
+
+    groups.retain(|group| group.len() > 1);
+    groups
+}
+
+/// Drops parenthesized or bracketed parts such as "(Official Audio)" or "[Lyrics]"
+fn strip_upload_tags(title: &str) -> String {
+    let mut result = String::with_capacity(title.len());
+    let mut rest = title;
+
+    while let Some(start) = rest.find(['(', '[']) {
+        let close = if rest[start..].starts_with('(') { ')' } else { ']' };
+        let Some(length) = rest[start..].find(close) else {
+            break;
+        };
+        let tag = &rest[start + 1..start + length];
+        result.push_str(&rest[..start]);
+        if !is_upload_tag(tag) {
+            result.push_str(&rest[start..=start + length]);
+        }
+        rest = &rest[start + length + 1..];
+    }
+
+    result.push_str(rest);
+    result
+}
+
+fn is_upload_tag(tag: &str) -> bool {
+    normalize(tag)
+        .split_whitespace()
+        .any(|word| UPLOAD_TAG_WORDS.contains(&word))
+}
@@ -0,0 +1,186 @@
+use std::path::Path;
+
+use super::{PlaylistDocument, PlaylistEntry};
+
+/// Write an extended M3U playlist
+pub fn write_m3u(document: &PlaylistDocument) -> String {
+    let mut output = String::from("#EXTM3U\n");
+
+    if let Some(name) = document.name.as_deref() {
+        output.push_str(&format!("#PLAYLIST:{}\n", single_line(name)));
+    }
+
+    for entry in &document.entries {
+        let Some(location) = entry.location.as_deref() else {
+            continue;
+        };
+        let duration = entry.duration_secs.unwrap_or(-1);
+        output.push_str(&format!(
+            "#EXTINF:{},{} - {}\n{}\n",
+            duration,
+            single_line(&entry.artist),
+            single_line(&entry.title),
+            location
+        ));
+    }
+
+    output
+}
+
+/// Parse a plain or extended M3U playlist
+pub fn parse_m3u(data: &str) -> PlaylistDocument {
+    let mut document = PlaylistDocument::default();
+    let mut pending: Option<PlaylistEntry> = None;
+
+    for raw in data.lines() {
+        let line = raw.trim().trim_start_matches('\u{feff}');
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix("#PLAYLIST:") {
+            let name = name.trim();
+            if !name.is_empty() {
+                document.name = Some(name.to_string());
+            }
+            continue;
+        }
+
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            pending = Some(parse_extinf(info));
+            continue;
+        }
+
+        if line.starts_with('#') {
+            continue;
+        }
+
+        let mut entry = pending.take().unwrap_or_else(|| entry_from_location(line));
+        entry.location = Some(line.to_string());
+        document.entries.push(entry);
+    }
+
+    document
+}
+
+/// Parse `<duration>[ attributes],<artist> - <title>`
+fn parse_extinf(info: &str) -> PlaylistEntry {
+    let (head, display) = info.split_once(',').unwrap_or((info, ""));
+    let duration = head
+        .split_whitespace()
+        .next()
+        .and_then(|value| value.parse::<f64>().ok())
+        .filter(|value| *value > 0.0)
+        .map(|value| value.round() as i64);
+
+    let (artist, title) = split_display_name(display.trim());
+    PlaylistEntry {
+        title,
+        artist,
+        duration_secs: duration,
+        ..PlaylistEntry::default()
+    }
+}
+
+/// Entries without `#EXTINF` fall back to the file name, e.g. `Artist - Title.mp3`
+fn entry_from_location(location: &str) -> PlaylistEntry {
+    if location.contains("://") {
+        return PlaylistEntry::default();
+    }
+
+    let stem = Path::new(location)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("");
+    let (artist, title) = split_display_name(stem);
+    PlaylistEntry {
+        title,
+        artist,
+        ..PlaylistEntry::default()
+    }
+}
+
+fn split_display_name(display: &str) -> (String, String) {
+    match display.split_once(" - ") {
+        Some((artist, title)) => (artist.trim().to_string(), title.trim().to_string()),
+        None => (String::new(), display.trim().to_string()),
+    }
+}
+
+fn single_line(value: &str) -> String {
+    value.replace(['\r', '\n'], " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(
+        title: &str,
+        artist: &str,
+        duration_secs: Option<i64>,
+        location: &str,
+    ) -> PlaylistEntry {
+        PlaylistEntry {
+            title: title.to_string(),
+            artist: artist.to_string(),
+            duration_secs,
+            location: Some(location.to_string()),
+            image: None,
+        }
+    }
+
+    #[test]
+    fn written_playlists_read_back() {
+        let document = PlaylistDocument {
+            name: Some("Road\ntrip".to_string()),
+            entries: vec![
+                entry(
+                    "Hyperballad",
+                    "Björk",
+                    Some(321),
+                    "https://music.youtube.com/watch?v=abc",
+                ),
+                entry("Jóga", "Björk", None, "/music/Björk - Jóga.flac"),
+            ],
+        };
+
+        let parsed = parse_m3u(&write_m3u(&document));
+
+        assert_eq!(parsed.name.as_deref(), Some("Road trip"));
+        assert_eq!(parsed.entries.len(), 2);
+        for (parsed, original) in parsed.entries.iter().zip(&document.entries) {
+            assert_eq!(parsed.title, original.title);
+            assert_eq!(parsed.artist, original.artist);
+            assert_eq!(parsed.duration_secs, original.duration_secs);
+            assert_eq!(parsed.location, original.location);
+        }
+    }
+
+    #[test]
+    fn extinf_attributes_and_file_names_are_understood() {
+        let data = "\u{feff}#EXTM3U\r\n\
+            #EXTINF:187.6 tvg-logo=\"cover.png\",Portishead - Glory Box\r\n\
+            Music/glory.mp3\r\n\
+            # a comment\r\n\
+            Music/Massive Attack - Teardrop.ogg\r\n\
+            #EXTINF:-1,Untitled\r\n\
+            http://radio.example/stream\r\n";
+
+        let entries = parse_m3u(data).entries;
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(
+            (entries[0].artist.as_str(), entries[0].title.as_str()),
+            ("Portishead", "Glory Box")
+        );
+        assert_eq!(entries[0].duration_secs, Some(188));
+        assert_eq!(entries[0].location.as_deref(), Some("Music/glory.mp3"));
+        assert_eq!(
+            (entries[1].artist.as_str(), entries[1].title.as_str()),
+            ("Massive Attack", "Teardrop")
+        );
+        assert_eq!(entries[2].title, "Untitled");
+        assert_eq!(entries[2].duration_secs, None);
+    }
+}
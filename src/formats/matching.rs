@@ -0,0 +1,258 @@
+use crate::api::{SearchResult, clean_track_artist, clean_track_title};
+use crate::sources::Sources;
+
+use super::CsvTrack;
+
+/// Matches at or above this confidence are accepted without review
+pub const AUTO_ACCEPT_CONFIDENCE: f64 = 0.8;
+
+const MAX_CANDIDATES: usize = 5;
+const TITLE_WEIGHT: f64 = 0.55;
+const ARTIST_WEIGHT: f64 = 0.3;
+const DURATION_WEIGHT: f64 = 0.15;
+
+/// Search candidates for one imported track, best first
+#[derive(Debug, Clone)]
+pub struct TrackMatch {
+    pub track: CsvTrack,
+    pub candidates: Vec<(SearchResult, f64)>,
+}
+
+impl TrackMatch {
+    pub fn best(&self) -> Option<&(SearchResult, f64)> {
+        self.candidates.first()
+    }
+
+    pub fn confidence(&self) -> f64 {
+        self.best().map_or(0.0, |(_, confidence)| *confidence)
+    }
+
+    pub fn is_confident(&self) -> bool {
+        self.confidence() >= AUTO_ACCEPT_CONFIDENCE
+    }
+}
+
+/// Search for a track and score each result
+pub fn match_track(sources: &Sources, track: &CsvTrack) -> TrackMatch {
+    let query = format!("{} {}", track.title, track.artist).trim().to_string();
+    let results = sources
+        .search(&query, None)
+        .map(|page| page.results)
+        .unwrap_or_default();
+
+    let mut candidates: Vec<(SearchResult, f64)> = results
+        .into_iter()
+        .take(MAX_CANDIDATES)
+        .map(|result| {
+            let confidence = match_confidence(track, &result);
+            (result, confidence)
+        })
+        .collect();
+    candidates.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+    TrackMatch {
+        track: track.clone(),
+        candidates,
+    }
+}
+
+/// Weighted title, artist and duration similarity in `0.0..=1.0`
+pub fn match_confidence(track: &CsvTrack, result: &SearchResult) -> f64 {
+    let title = title_similarity(&track.title, &result.title);
+    let artist = if track.artist.trim().is_empty() {
+        None
+    } else {
+        Some(artist_similarity(&track.artist, &result.artist))
+    };
+    let duration = track
+        .duration_secs
+        .zip(super::parse_duration(&result.duration))
+        .map(|(expected, actual)| duration_similarity(expected, actual));
+
+    // Missing signals are left out rather than counted as mismatches
+    let mut score = TITLE_WEIGHT * title;
+    let mut weight = TITLE_WEIGHT;
+    if let Some(artist) = artist {
+        score += ARTIST_WEIGHT * artist;
+        weight += ARTIST_WEIGHT;
+    }
+    if let Some(duration) = duration {
+        score += DURATION_WEIGHT * duration;
+        weight += DURATION_WEIGHT;
+    }
+
+    score / weight
+}
+
+fn title_similarity(expected: &str, actual: &str) -> f64 {
+    let expected = normalize(&clean_track_title(expected));
+    let actual = normalize(&clean_track_title(actual));
+    edit_similarity(&expected, &actual).max(token_dice(&expected, &actual))
+}
+
+/// Artists often differ in how features are listed, so score by token containment
+fn artist_similarity(expected: &str, actual: &str) -> f64 {
+    let expected = normalize(&clean_track_artist(expected));
+    let actual = normalize(&clean_track_artist(actual));
+    let expected_tokens: Vec<&str> = expected.split_whitespace().collect();
+    let actual_tokens: Vec<&str> = actual.split_whitespace().collect();
+    let smaller = expected_tokens.len().min(actual_tokens.len());
+    if smaller == 0 {
+        return 0.0;
+    }
+
+    let shared = actual_tokens
+        .iter()
+        .filter(|token| expected_tokens.contains(token))
+        .count()
+        .min(smaller);
+    (shared as f64 / smaller as f64).max(edit_similarity(&expected, &actual))
+}
+
+fn duration_similarity(expected: i64, actual: i64) -> f64 {
+    match (expected - actual).abs() {
+        0..=2 => 1.0,
+        3..=5 => 0.8,
+        6..=15 => 0.4,
+        _ => 0.0,
+    }
+}
+
+/// Lowercase alphanumeric words separated by single spaces
+pub(super) fn normalize(value: &str) -> String {
+    value
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn token_dice(a: &str, b: &str) -> f64 {
+    let a_tokens: Vec<&str> = a.split_whitespace().collect();
+    let b_tokens: Vec<&str> = b.split_whitespace().collect();
+    let total = a_tokens.len() + b_tokens.len();
+    if total == 0 {
+        return 0.0;
+    }
+    let shared = a_tokens.iter().filter(|token| b_tokens.contains(token)).count();
+    2.0 * shared as f64 / total as f64
+}
+
+fn edit_similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 0.0;
+    }
+    1.0 - levenshtein(&a, &b) as f64 / longest as f64
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(title: &str, artist: &str, duration_secs: Option<i64>) -> CsvTrack {
+        CsvTrack {
+            title: title.to_string(),
+            artist: artist.to_string(),
+            album: String::new(),
+            duration_secs,
+        }
+    }
+
+    fn result(title: &str, artist: &str, duration: &str) -> SearchResult {
+        SearchResult {
+            video_id: "ytm:abc".to_string(),
+            title: title.to_string(),
+            artist: artist.to_string(),
+            duration: duration.to_string(),
+            thumbnail_url: None,
+        }
+    }
+
+    fn best_match(track: CsvTrack, result: SearchResult) -> TrackMatch {
+        let confidence = match_confidence(&track, &result);
+        TrackMatch {
+            track,
+            candidates: vec![(result, confidence)],
+        }
+    }
+
+    #[test]
+    fn the_same_song_is_accepted_despite_upload_noise() {
+        let found = best_match(
+            track("Teardrop", "Massive Attack", Some(330)),
+            result(
+                "Teardrop (Official Video)",
+                "Massive Attack - Topic",
+                "5:31",
+            ),
+        );
+        assert_eq!(found.confidence(), 1.0);
+        assert!(found.is_confident());
+    }
+
+    #[test]
+    fn duration_decides_around_the_cutoff() {
+        // Title alone is worth 0.55 of 0.7; ten seconds off keeps the match at 0.87
+        let close = best_match(
+            track("Glory Box", "", Some(300)),
+            result("Glory Box", "Portishead", "5:10"),
+        );
+        assert!(close.confidence() > AUTO_ACCEPT_CONFIDENCE);
+        assert!(close.confidence() < 0.9);
+        assert!(close.is_confident());
+
+        // Twenty seconds off drops it to 0.79, which goes to review
+        let far = best_match(
+            track("Glory Box", "", Some(300)),
+            result("Glory Box", "Portishead", "5:20"),
+        );
+        assert!(far.confidence() < AUTO_ACCEPT_CONFIDENCE);
+        assert!(far.confidence() > 0.75);
+        assert!(!far.is_confident());
+    }
+
+    #[test]
+    fn another_artist_goes_to_review() {
+        let found = best_match(
+            track("Hurt", "Nine Inch Nails", Some(373)),
+            result("Hurt", "Johnny Cash", "3:38"),
+        );
+        assert!(!found.is_confident());
+        assert_eq!(
+            best_match(track("Hurt", "", None), result("Hurt", "", "")).confidence(),
+            1.0
+        );
+    }
+
+    #[test]
+    fn no_candidates_means_no_confidence() {
+        let found = TrackMatch {
+            track: track("Hurt", "", None),
+            candidates: Vec::new(),
+        };
+        assert_eq!(found.confidence(), 0.0);
+        assert!(!found.is_confident());
+    }
+}
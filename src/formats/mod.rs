@@ -0,0 +1,315 @@
+mod csv;
+mod duplicates;
+mod m3u;
+mod matching;
+mod xspf;
+
+use std::path::{Path, PathBuf};
+
+use crate::api::SearchResult;
+use crate::sources::{SUBSONIC_COVER_PREFIX, Sources, YOUTUBE_SCHEME, split_track_uri, track_uri};
+use crate::storage::{Song, SongSource, local_track_id, local_track_path};
+
+pub use csv::{CsvTrack, parse_csv_tracks};
+pub use duplicates::find_duplicates;
+pub use m3u::{parse_m3u, write_m3u};
+pub use matching::{TrackMatch, match_track};
+pub use xspf::{parse_xspf, write_xspf};
+
+const WATCH_URL_PREFIX: &str = "https://music.youtube.com/watch?v=";
+
+/// Playlist file formats that can be imported and exported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaylistFormat {
+    M3u,
+    Xspf,
+}
+
+impl PlaylistFormat {
+    /// Guess the format from a file extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "m3u" | "m3u8" => Some(Self::M3u),
+            "xspf" => Some(Self::Xspf),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::M3u => "m3u",
+            Self::Xspf => "xspf",
+        }
+    }
+}
+
+/// One track of a playlist file
+#[derive(Debug, Clone, Default)]
+pub struct PlaylistEntry {
+    pub title: String,
+    pub artist: String,
+    pub duration_secs: Option<i64>,
+    pub location: Option<String>,
+    pub image: Option<String>,
+}
+
+/// Playlist file contents
+#[derive(Debug, Clone, Default)]
+pub struct PlaylistDocument {
+    pub name: Option<String>,
+    pub entries: Vec<PlaylistEntry>,
+}
+
+/// Songs resolved from playlist entries, plus the entries that could not be matched
+#[derive(Debug, Clone, Default)]
+pub struct ResolvedPlaylist {
+    pub songs: Vec<Song>,
+    pub unresolved: Vec<PlaylistEntry>,
+}
+
+impl PlaylistDocument {
+    /// Build a document from stored playlist songs
+    pub fn from_songs(name: &str, songs: &[Song]) -> Self {
+        let entries = songs
+            .iter()
+            .map(|song| PlaylistEntry {
+                title: song.title.clone(),
+                artist: song.artist.clone(),
+                duration_secs: parse_duration(&song.duration),
+                location: Some(song_location(&song.video_id)),
+                // Subsonic cover references mean nothing to other players
+                image: song
+                    .thumbnail_url
+                    .clone()
+                    .filter(|url| !url.starts_with(SUBSONIC_COVER_PREFIX)),
+            })
+            .collect();
+
+        Self {
+            name: Some(name.to_string()),
+            entries,
+        }
+    }
+
+    pub fn parse(format: PlaylistFormat, data: &str) -> Self {
+        match format {
+            PlaylistFormat::M3u => parse_m3u(data),
+            PlaylistFormat::Xspf => parse_xspf(data),
+        }
+    }
+
+    /// Read relative file locations as relative to `folder`, where the playlist file is
+    pub fn resolve_relative_locations(&mut self, folder: &Path) {
+        for entry in self.entries.iter_mut() {
+            let Some(location) = entry.location.as_deref() else {
+                continue;
+            };
+            if has_uri_scheme(location) || Path::new(location).is_absolute() {
+                continue;
+            }
+            entry.location = Some(folder.join(location).to_string_lossy().into_owned());
+        }
+    }
+
+    pub fn write(&self, format: PlaylistFormat) -> String {
+        match format {
+            PlaylistFormat::M3u => write_m3u(self),
+            PlaylistFormat::Xspf => write_xspf(self),
+        }
+    }
+
+    /// Resolve entries to songs by file path or YouTube URL, or by searching `title artist`
+    pub fn resolve(&self, sources: &Sources) -> ResolvedPlaylist {
+        let mut resolved = ResolvedPlaylist::default();
+
+        for entry in &self.entries {
+            match resolve_entry(sources, entry) {
+                Some(song) => resolved.songs.push(song),
+                None => resolved.unresolved.push(entry.clone()),
+            }
+        }
+
+        resolved
+    }
+}
+
+fn resolve_entry(sources: &Sources, entry: &PlaylistEntry) -> Option<Song> {
+    // Files on this computer, YouTube links and Subsonic track URIs name the track directly
+    let location = entry.location.as_deref();
+    let uri = location
+        .filter(|location| SongSource::of(location) == SongSource::Subsonic)
+        .map(String::from)
+        .or_else(|| location.and_then(local_path_from_location).map(|path| local_track_id(&path)))
+        .or_else(|| {
+            location
+                .and_then(video_id_from_url)
+                .map(|video_id| track_uri(YOUTUBE_SCHEME, &video_id))
+        });
+
+    if let Some(uri) = uri {
+        // Local files carry their own tags; links without a title ask their source
+        if SongSource::of(&uri) == SongSource::Local || entry.title.trim().is_empty() {
+            if let Ok(result) = sources.metadata(&uri) {
+                return Some(song_from_result(result));
+            }
+        }
+        return Some(Song {
+            video_id: uri,
+            title: non_empty_or_unknown(&entry.title),
+            artist: non_empty_or_unknown(&entry.artist),
+            duration: entry.duration_secs.map(format_duration).unwrap_or_default(),
+            thumbnail_url: entry.image.clone().filter(|url| url.starts_with("http")),
+        });
+    }
+
+    let query = format!("{} {}", entry.title, entry.artist).trim().to_string();
+    if query.is_empty() {
+        return None;
+    }
+
+    let result = sources.search(&query, None).ok()?.results.into_iter().next()?;
+    Some(song_from_result(result))
+}
+
+fn song_from_result(result: SearchResult) -> Song {
+    Song {
+        video_id: result.video_id,
+        title: result.title,
+        artist: result.artist,
+        duration: result.duration,
+        thumbnail_url: result.thumbnail_url,
+    }
+}
+
+fn non_empty_or_unknown(value: &str) -> String {
+    if value.trim().is_empty() {
+        "Unknown".to_string()
+    } else {
+        value.trim().to_string()
+    }
+}
+
+/// Where a playlist file points for a song: a file URI for local tracks, a watch URL for
+/// YouTube and the track URI itself for Subsonic songs, which only this app can open
+fn song_location(video_id: &str) -> String {
+    match SongSource::of(video_id) {
+        SongSource::Subsonic => video_id.to_string(),
+        _ => local_track_path(video_id)
+            .and_then(|path| glib::filename_to_uri(path, None).ok())
+            .map(String::from)
+            .unwrap_or_else(|| watch_url(split_track_uri(video_id).1)),
+    }
+}
+
+/// Existing audio file named by a playlist entry, as a `file://` URI or an absolute path
+fn local_path_from_location(location: &str) -> Option<PathBuf> {
+    let path = if location.starts_with("file://") {
+        glib::filename_from_uri(location).ok()?.0
+    } else {
+        PathBuf::from(location)
+    };
+    (path.is_absolute() && path.is_file()).then_some(path)
+}
+
+/// Whether a location starts with a URI scheme such as `https:`, `file:` or `subsonic:`
+/// rather than being a path
+fn has_uri_scheme(location: &str) -> bool {
+    let Some((scheme, _)) = location.split_once(':') else {
+        return false;
+    };
+    scheme.len() > 1
+        && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+}
+
+/// YouTube Music watch URL for a video id
+pub fn watch_url(video_id: &str) -> String {
+    format!("{WATCH_URL_PREFIX}{video_id}")
+}
+
+/// Extract the video id from a YouTube or YouTube Music URL
+pub fn video_id_from_url(url: &str) -> Option<String> {
+    let url = reqwest::Url::parse(url.trim()).ok()?;
+    let host = url.host_str()?.trim_start_matches("www.");
+
+    let video_id = match host {
+        "youtu.be" => url.path_segments()?.next()?.to_string(),
+        "youtube.com" | "m.youtube.com" | "music.youtube.com" => url
+            .query_pairs()
+            .find(|(key, _)| key == "v")
+            .map(|(_, value)| value.into_owned())?,
+        _ => return None,
+    };
+
+    let valid = !video_id.is_empty()
+        && video_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    valid.then_some(video_id)
+}
+
+/// Parse a `m:ss` or `h:mm:ss` duration into seconds
+pub fn parse_duration(text: &str) -> Option<i64> {
+    let mut total = 0i64;
+    let mut parts = 0;
+    for part in text.trim().split(':') {
+        let value: i64 = part.trim().parse().ok()?;
+        total = total * 60 + value;
+        parts += 1;
+    }
+    (2..=3).contains(&parts).then_some(total)
+}
+
+/// Format seconds as `m:ss`, or `h:mm:ss` for long tracks
+pub fn format_duration(seconds: i64) -> String {
+    let hours = seconds / 3600;
+    let mins = (seconds % 3600) / 60;
+    let secs = seconds % 60;
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, mins, secs)
+    } else {
+        format!("{}:{:02}", mins, secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_m3u_paths_are_read_from_the_playlist_folder() {
+        let folder = std::env::temp_dir().join(format!("musika-test-m3u-{}", std::process::id()));
+        std::fs::create_dir_all(folder.join("Music")).unwrap();
+        let song = folder.join("Music").join("Björk - Jóga.flac");
+        std::fs::write(&song, b"").unwrap();
+
+        let mut document = parse_m3u(
+            "#EXTM3U\n\
+             Music/Björk - Jóga.flac\n\
+             /elsewhere/song.mp3\n\
+             https://music.youtube.com/watch?v=abc\n\
+             subsonic:so-1\n",
+        );
+        document.resolve_relative_locations(&folder);
+        let locations: Vec<_> = document
+            .entries
+            .iter()
+            .map(|entry| entry.location.clone().unwrap())
+            .collect();
+
+        assert_eq!(locations[0], song.to_string_lossy());
+        assert_eq!(local_path_from_location(&locations[0]), Some(song));
+        assert_eq!(
+            &locations[1..],
+            [
+                "/elsewhere/song.mp3",
+                "https://music.youtube.com/watch?v=abc",
+                "subsonic:so-1"
+            ]
+        );
+        let _ = std::fs::remove_dir_all(folder);
+    }
+}
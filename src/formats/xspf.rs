@@ -0,0 +1,217 @@
+use super::{PlaylistDocument, PlaylistEntry};
+
+/// Write an XSPF playlist
+pub fn write_xspf(document: &PlaylistDocument) -> String {
+    let mut output = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n",
+    );
+
+    if let Some(name) = document.name.as_deref() {
+        output.push_str(&format!("  <title>{}</title>\n", escape(name)));
+    }
+
+    output.push_str("  <trackList>\n");
+    for entry in &document.entries {
+        output.push_str("    <track>\n");
+        if let Some(location) = entry.location.as_deref() {
+            output.push_str(&format!("      <location>{}</location>\n", escape(location)));
+        }
+        output.push_str(&format!("      <title>{}</title>\n", escape(&entry.title)));
+        output.push_str(&format!("      <creator>{}</creator>\n", escape(&entry.artist)));
+        if let Some(duration) = entry.duration_secs {
+            output.push_str(&format!("      <duration>{}</duration>\n", duration * 1000));
+        }
+        if let Some(image) = entry.image.as_deref() {
+            output.push_str(&format!("      <image>{}</image>\n", escape(image)));
+        }
+        output.push_str("    </track>\n");
+    }
+    output.push_str("  </trackList>\n</playlist>\n");
+
+    output
+}
+
+/// Parse the subset of XSPF used by common players
+pub fn parse_xspf(data: &str) -> PlaylistDocument {
+    let mut document = PlaylistDocument::default();
+
+    let (header, tracks) = match data.find("<trackList") {
+        Some(index) => data.split_at(index),
+        None => (data, ""),
+    };
+    document.name = element_text(header, "title").filter(|name| !name.is_empty());
+
+    let mut rest = tracks;
+    while let Some(start) = find_open_tag(rest, "track") {
+        let after_start = &rest[start..];
+        let Some(end) = after_start.find("</track>") else {
+            break;
+        };
+        let block = &after_start[..end];
+        rest = &after_start[end + "</track>".len()..];
+
+        document.entries.push(PlaylistEntry {
+            title: element_text(block, "title").unwrap_or_default(),
+            artist: element_text(block, "creator").unwrap_or_default(),
+            duration_secs: element_text(block, "duration")
+                .and_then(|value| value.parse::<i64>().ok())
+                .filter(|value| *value > 0)
+                .map(|millis| (millis + 500) / 1000),
+            location: element_text(block, "location").filter(|location| !location.is_empty()),
+            image: element_text(block, "image").filter(|image| !image.is_empty()),
+        });
+    }
+
+    document
+}
+
+/// Byte offset of `<tag>` or `<tag attr=...>`, ignoring tags that merely share a prefix
+fn find_open_tag(xml: &str, tag: &str) -> Option<usize> {
+    let needle = format!("<{tag}");
+    let mut offset = 0;
+    while let Some(found) = xml[offset..].find(&needle) {
+        let index = offset + found;
+        let next = xml[index + needle.len()..].chars().next();
+        if matches!(next, Some('>') | Some('/') | Some(' ') | Some('\t') | Some('\n') | Some('\r')) {
+            return Some(index);
+        }
+        offset = index + needle.len();
+    }
+    None
+}
+
+fn element_text(xml: &str, tag: &str) -> Option<String> {
+    let start = find_open_tag(xml, tag)?;
+    let open_end = start + xml[start..].find('>')?;
+    if xml[..open_end].ends_with('/') {
+        return Some(String::new());
+    }
+
+    let content_start = open_end + 1;
+    let close = format!("</{tag}>");
+    let content_end = content_start + xml[content_start..].find(&close)?;
+    let content = xml[content_start..content_end].trim();
+
+    let content = content
+        .strip_prefix("<![CDATA[")
+        .and_then(|inner| inner.strip_suffix("]]>"))
+        .map(str::to_string)
+        .unwrap_or_else(|| unescape(content));
+    Some(content.trim().to_string())
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn unescape(value: &str) -> String {
+    let mut output = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(index) = rest.find('&') {
+        output.push_str(&rest[..index]);
+        let after = &rest[index..];
+        let Some(end) = after.find(';') else {
+            output.push_str(after);
+            return output;
+        };
+
+        let entity = &after[1..end];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                .and_then(char::from_u32),
+        };
+
+        match decoded {
+            Some(c) => {
+                output.push(c);
+                rest = &after[end + 1..];
+            }
+            None => {
+                output.push('&');
+                rest = &after[1..];
+            }
+        }
+    }
+
+    output.push_str(rest);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn written_playlists_read_back_with_special_characters() {
+        let document = PlaylistDocument {
+            name: Some("Rock & Roll <Live>".to_string()),
+            entries: vec![PlaylistEntry {
+                title: "\"Heroes\"".to_string(),
+                artist: "Simon & Garfunkel's".to_string(),
+                duration_secs: Some(211),
+                location: Some("https://music.youtube.com/watch?v=abc&list=x".to_string()),
+                image: Some("https://example.com/cover.jpg?a=1&b=2".to_string()),
+            }],
+        };
+
+        let xml = write_xspf(&document);
+        assert!(xml.contains("<title>Rock &amp; Roll &lt;Live&gt;</title>"));
+
+        let parsed = parse_xspf(&xml);
+        assert_eq!(parsed.name, document.name);
+        assert_eq!(parsed.entries.len(), 1);
+        let (parsed, original) = (&parsed.entries[0], &document.entries[0]);
+        assert_eq!(parsed.title, original.title);
+        assert_eq!(parsed.artist, original.artist);
+        assert_eq!(parsed.duration_secs, original.duration_secs);
+        assert_eq!(parsed.location, original.location);
+        assert_eq!(parsed.image, original.image);
+    }
+
+    #[test]
+    fn entities_cdata_and_look_alike_tags_are_handled() {
+        let xml = r#"<?xml version="1.0"?>
+            <playlist version="1" xmlns="http://xspf.org/ns/0/">
+              <title>Caf&#233; &#xE9;t&#xe9; &unknown; &amp</title>
+              <trackList>
+                <track>
+                  <titleSort>Ignored</titleSort>
+                  <title><![CDATA[Fish & <Chips>]]></title>
+                  <creator>Sigur R&#243;s</creator>
+                  <duration>1499</duration>
+                  <image/>
+                </track>
+                <track><location>file:///music/a.mp3</location></track>
+              </trackList>
+            </playlist>"#;
+
+        let document = parse_xspf(xml);
+
+        assert_eq!(document.name.as_deref(), Some("Café été &unknown; &amp"));
+        assert_eq!(document.entries.len(), 2);
+        assert_eq!(document.entries[0].title, "Fish & <Chips>");
+        assert_eq!(document.entries[0].artist, "Sigur Rós");
+        assert_eq!(document.entries[0].duration_secs, Some(1));
+        assert_eq!(document.entries[0].image, None);
+        assert_eq!(document.entries[1].title, "");
+        assert_eq!(
+            document.entries[1].location.as_deref(),
+            Some("file:///music/a.mp3")
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use std::cell::{Cell, RefCell};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use glib::ControlFlow;
+
+use crate::config::POLL_INTERVAL_MS;
+use crate::storage::{Database, LocalScanSummary};
+
+use super::scanner::{ScanOutcome, scan_folders};
+
+/// Music from folders on this computer. Folders are scanned in the background,
+/// and a rescan only reads tags from files that changed since the last one.
+#[derive(Clone)]
+pub struct LocalLibrary {
+    database: Database,
+    scanning: Rc<Cell<bool>>,
+    /// A rescan was asked for while a scan was running
+    rescan_pending: Rc<Cell<bool>>,
+    last_result: Rc<RefCell<Option<Result<LocalScanSummary, String>>>>,
+    sender: mpsc::Sender<ScanOutcome>,
+    on_changed: Rc<RefCell<Vec<Box<dyn Fn()>>>>,
+}
+
+impl LocalLibrary {
+    pub fn new(database: Database) -> Self {
+        let (sender, receiver) = mpsc::channel::<ScanOutcome>();
+        let library = Self {
+            database,
+            scanning: Rc::new(Cell::new(false)),
+            rescan_pending: Rc::new(Cell::new(false)),
+            last_result: Rc::new(RefCell::new(None)),
+            sender,
+            on_changed: Rc::new(RefCell::new(Vec::new())),
+        };
+
+        // Poll for finished scans
+        glib::timeout_add_local(
+            Duration::from_millis(POLL_INTERVAL_MS),
+            glib::clone!(
+                #[strong]
+                library,
+                move || {
+                    while let Ok(outcome) = receiver.try_recv() {
+                        library.finish_scan(outcome);
+                    }
+                    ControlFlow::Continue
+                }
+            ),
+        );
+
+        // Pick up files added or changed while the app was closed
+        library.rescan();
+        library
+    }
+
+    /// Call `callback` when a scan starts or finishes, or the folders change
+    pub fn connect_changed(&self, callback: impl Fn() + 'static) {
+        self.on_changed.borrow_mut().push(Box::new(callback));
+    }
+
+    pub fn folders(&self) -> Vec<PathBuf> {
+        self.database.get_local_folders()
+    }
+
+    pub fn is_scanning(&self) -> bool {
+        self.scanning.get()
+    }
+
+    /// What the last finished scan changed, or why it failed
+    pub fn last_result(&self) -> Option<Result<LocalScanSummary, String>> {
+        self.last_result.borrow().clone()
+    }
+
+    /// Start scanning a folder for music
+    pub fn add_folder(&self, folder: &Path) -> Result<(), String> {
+        let mut folders = self.folders();
+        if folders.iter().any(|existing| existing == folder) {
+            return Ok(());
+        }
+        folders.push(folder.to_path_buf());
+        self.database.set_local_folders(&folders)?;
+        self.rescan();
+        Ok(())
+    }
+
+    /// Stop scanning a folder; its tracks leave the library with the next scan
+    pub fn remove_folder(&self, folder: &Path) -> Result<(), String> {
+        let folders: Vec<PathBuf> = self
+            .folders()
+            .into_iter()
+            .filter(|existing| existing != folder)
+            .collect();
+        self.database.set_local_folders(&folders)?;
+        self.rescan();
+        Ok(())
+    }
+
+    /// Scan the folders again, or once more after the running scan when one is in progress
+    pub fn rescan(&self) {
+        if self.scanning.get() {
+            self.rescan_pending.set(true);
+            return;
+        }
+        self.scanning.set(true);
+
+        let folders = self.folders();
+        let known = self.database.get_local_track_stamps();
+        let sender = self.sender.clone();
+        std::thread::spawn(move || {
+            let _ = sender.send(scan_folders(&folders, &known));
+        });
+        self.notify_changed();
+    }
+
+    fn finish_scan(&self, outcome: ScanOutcome) {
+        let result = self
+            .database
+            .apply_local_scan(&outcome.scanned, &outcome.present);
+        *self.last_result.borrow_mut() = Some(result);
+        self.scanning.set(false);
+
+        if self.rescan_pending.replace(false) {
+            self.rescan();
+        } else {
+            self.notify_changed();
+        }
+    }
+
+    fn notify_changed(&self) {
+        for callback in self.on_changed.borrow().iter() {
+            callback();
+        }
+    }
+}
@@ -0,0 +1,5 @@
+mod library;
+mod scanner;
+
+pub use library::LocalLibrary;
+pub use scanner::read_file;
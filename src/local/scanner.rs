@@ -0,0 +1,232 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use lofty::picture::{MimeType, Picture, PictureType};
+use lofty::prelude::{Accessor, AudioFile, TaggedFileExt};
+use lofty::tag::ItemKey;
+
+use crate::formats::format_duration;
+use crate::storage::{Database, LocalTrack, Song, local_track_id};
+
+/// File extensions picked up by a scan
+const AUDIO_EXTENSIONS: &[&str] = &["flac", "mp3", "opus", "m4a"];
+
+/// Image files used as cover art for every track in their folder, when tracks have none embedded
+const FOLDER_COVER_NAMES: &[&str] = &[
+    "cover.jpg",
+    "cover.jpeg",
+    "cover.png",
+    "folder.jpg",
+    "folder.jpeg",
+    "folder.png",
+    "front.jpg",
+    "front.png",
+];
+
+/// What a scan found in the music folders
+pub struct ScanOutcome {
+    /// Tracks that are new or changed since the last scan, with freshly read tags
+    pub scanned: Vec<LocalTrack>,
+    /// Path of every track still on disk, changed or not
+    pub present: HashSet<String>,
+}
+
+/// Walk the folders for audio files, reading tags only from files that are new or
+/// whose modification time or size changed since the last scan.
+/// Tracks under a folder that is missing, such as an unmounted drive, are kept.
+pub fn scan_folders(folders: &[PathBuf], known: &HashMap<String, (i64, i64)>) -> ScanOutcome {
+    let mut outcome = ScanOutcome {
+        scanned: Vec::new(),
+        present: HashSet::new(),
+    };
+    let mut folder_covers = HashMap::new();
+
+    for folder in folders {
+        if !folder.is_dir() {
+            outcome.present.extend(
+                known
+                    .keys()
+                    .filter(|path| Path::new(path).starts_with(folder))
+                    .cloned(),
+            );
+            continue;
+        }
+
+        let mut files = Vec::new();
+        collect_audio_files(folder, &mut files);
+        for path in files {
+            let Ok(meta) = std::fs::metadata(&path) else {
+                continue;
+            };
+            let (mtime, size) = file_stamp(&meta);
+            let file_path = path.to_string_lossy().into_owned();
+
+            if known.get(&file_path) != Some(&(mtime, size)) {
+                outcome
+                    .scanned
+                    .push(read_track(&path, mtime, size, &mut folder_covers));
+            }
+            outcome.present.insert(file_path);
+        }
+    }
+
+    outcome
+}
+
+/// Read the tags of a single file, outside of a scan
+pub fn read_file(path: &Path) -> LocalTrack {
+    let (mtime, size) = std::fs::metadata(path)
+        .map(|meta| file_stamp(&meta))
+        .unwrap_or((0, 0));
+    read_track(path, mtime, size, &mut HashMap::new())
+}
+
+/// Modification time in seconds and size in bytes
+fn file_stamp(meta: &std::fs::Metadata) -> (i64, i64) {
+    let mtime = meta
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|time| time.as_secs() as i64)
+        .unwrap_or(0);
+    (mtime, meta.len() as i64)
+}
+
+/// Collect audio files below a directory, skipping hidden entries and symlinked directories
+fn collect_audio_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let path = entry.path();
+        if file_type.is_dir() {
+            collect_audio_files(&path, files);
+        } else if is_audio_file(&path) && path.is_file() {
+            files.push(path);
+        }
+    }
+}
+
+fn is_audio_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            AUDIO_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+        })
+}
+
+/// Read a file's tags and embedded art, falling back to its name when it has no usable tags
+/// and to the folder's cover image when it has no art
+fn read_track(
+    path: &Path,
+    mtime: i64,
+    size: i64,
+    folder_covers: &mut HashMap<PathBuf, Option<String>>,
+) -> LocalTrack {
+    let mut title = None;
+    let mut artist = None;
+    let mut album = None;
+    let mut duration = String::new();
+    let mut thumbnail_url = None;
+
+    if let Ok(tagged) = lofty::read_from_path(path) {
+        let seconds = tagged.properties().duration().as_secs();
+        if seconds > 0 {
+            duration = format_duration(seconds as i64);
+        }
+
+        if let Some(tag) = tagged.primary_tag().or_else(|| tagged.first_tag()) {
+            title = tag.title().map(|value| value.trim().to_string());
+            artist = tag
+                .artist()
+                .map(|value| value.trim().to_string())
+                .or_else(|| {
+                    tag.get_string(&ItemKey::AlbumArtist)
+                        .map(|value| value.trim().to_string())
+                });
+            album = tag.album().map(|value| value.trim().to_string());
+            thumbnail_url =
+                cover_picture(tag.pictures()).and_then(|picture| save_cover(path, picture));
+        }
+    }
+
+    if thumbnail_url.is_none() {
+        thumbnail_url = path
+            .parent()
+            .and_then(|dir| folder_cover(dir, folder_covers));
+    }
+
+    let title = title.filter(|title| !title.is_empty()).unwrap_or_else(|| {
+        path.file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "Unknown".to_string())
+    });
+
+    LocalTrack {
+        song: Song {
+            video_id: local_track_id(path),
+            title,
+            artist: artist
+                .filter(|artist| !artist.is_empty())
+                .unwrap_or_else(|| "Unknown".to_string()),
+            duration,
+            thumbnail_url,
+        },
+        album: album.filter(|album| !album.is_empty()),
+        file_path: path.to_string_lossy().into_owned(),
+        mtime,
+        size,
+    }
+}
+
+/// The front cover if the file has one, otherwise its first picture
+fn cover_picture(pictures: &[Picture]) -> Option<&Picture> {
+    pictures
+        .iter()
+        .find(|picture| picture.pic_type() == PictureType::CoverFront)
+        .or_else(|| pictures.first())
+}
+
+/// Write embedded art next to the database and return its file URI
+fn save_cover(track_path: &Path, picture: &Picture) -> Option<String> {
+    let extension = match picture.mime_type() {
+        Some(MimeType::Png) => "png",
+        Some(MimeType::Jpeg) => "jpg",
+        _ => "img",
+    };
+
+    let mut hasher = DefaultHasher::new();
+    track_path.hash(&mut hasher);
+    let dir = Database::data_dir().join("covers").join("local");
+    let path = dir.join(format!("{:016x}.{}", hasher.finish(), extension));
+
+    std::fs::create_dir_all(&dir).ok()?;
+    std::fs::write(&path, picture.data()).ok()?;
+    glib::filename_to_uri(&path, None).ok().map(String::from)
+}
+
+/// File URI of a directory's cover image, looked up once per directory
+fn folder_cover(dir: &Path, cache: &mut HashMap<PathBuf, Option<String>>) -> Option<String> {
+    cache
+        .entry(dir.to_path_buf())
+        .or_insert_with(|| {
+            let entries = std::fs::read_dir(dir).ok()?;
+            let cover = entries.flatten().map(|entry| entry.path()).find(|path| {
+                path.file_name()
+                    .map(|name| name.to_string_lossy().to_ascii_lowercase())
+                    .is_some_and(|name| FOLDER_COVER_NAMES.contains(&name.as_str()))
+            })?;
+            glib::filename_to_uri(&cover, None).ok().map(String::from)
+        })
+        .clone()
+}
@@ -1,7 +1,10 @@
 mod api;
 mod app;
 mod config;
+mod formats;
+mod local;
 mod playback;
+mod sources;
 mod storage;
 mod ui;
 
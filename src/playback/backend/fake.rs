@@ -0,0 +1,128 @@
+use std::cell::{Cell, RefCell};
+
+use super::{AudioBackend, BackendEvent, EqGains, EventCallback, emit};
+
+/// Something the controller asked a `FakeBackend` to do
+#[derive(Debug, Clone, PartialEq)]
+pub enum BackendCall {
+    Load(String),
+    Stop,
+    Play,
+    Pause,
+    Seek(i64),
+    Volume(f64),
+    Rate(f64),
+    Equalizer(EqGains),
+    OutputDevice(Option<String>),
+}
+
+/// Backend for tests, which plays nothing and records every call made to it.
+/// Events are fired by hand with `fire`.
+#[derive(Default)]
+pub struct FakeBackend {
+    calls: RefCell<Vec<BackendCall>>,
+    playing: Cell<bool>,
+    position: Cell<i64>,
+    duration: Cell<i64>,
+    load_error: RefCell<Option<String>>,
+    on_event: EventCallback,
+}
+
+impl FakeBackend {
+    /// Calls made since the last `take_calls`
+    pub fn take_calls(&self) -> Vec<BackendCall> {
+        self.calls.take()
+    }
+
+    /// Make the next `load` fail with `error`
+    pub fn fail_next_load(&self, error: &str) {
+        *self.load_error.borrow_mut() = Some(error.to_string());
+    }
+
+    /// Pretend the song loaded is this long and has played this far, in microseconds
+    pub fn set_progress(&self, position: i64, duration: i64) {
+        self.position.set(position);
+        self.duration.set(duration);
+    }
+
+    /// Report `event` to the controller, as a real backend would from the main loop
+    pub fn fire(&self, event: BackendEvent) {
+        emit(&self.on_event, event);
+    }
+
+    fn record(&self, call: BackendCall) {
+        self.calls.borrow_mut().push(call);
+    }
+}
+
+impl AudioBackend for FakeBackend {
+    fn load(&self, uri: &str) -> Result<(), String> {
+        self.record(BackendCall::Load(uri.to_string()));
+        self.playing.set(false);
+        self.set_progress(0, 0);
+        match self.load_error.take() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    fn stop(&self) {
+        self.record(BackendCall::Stop);
+        self.playing.set(false);
+    }
+
+    fn play(&self) {
+        self.record(BackendCall::Play);
+        self.playing.set(true);
+    }
+
+    fn pause(&self) {
+        self.record(BackendCall::Pause);
+        self.playing.set(false);
+    }
+
+    fn is_playing(&self) -> bool {
+        self.playing.get()
+    }
+
+    fn seek(&self, position_usecs: i64) {
+        self.record(BackendCall::Seek(position_usecs));
+        self.position.set(position_usecs);
+    }
+
+    fn position(&self) -> i64 {
+        self.position.get()
+    }
+
+    fn duration(&self) -> i64 {
+        self.duration.get()
+    }
+
+    fn set_volume(&self, volume: f64) {
+        self.record(BackendCall::Volume(volume));
+    }
+
+    fn can_change_rate(&self) -> bool {
+        true
+    }
+
+    fn set_rate(&self, rate: f64) {
+        self.record(BackendCall::Rate(rate));
+    }
+
+    fn can_equalize(&self) -> bool {
+        true
+    }
+
+    fn set_equalizer(&self, gains: &EqGains) {
+        self.record(BackendCall::Equalizer(*gains));
+    }
+
+    fn set_output_device(&self, id: Option<&str>) {
+        self.record(BackendCall::OutputDevice(id.map(String::from)));
+    }
+
+    fn connect_event(&self, callback: Box<dyn Fn(BackendEvent)>) {
+        *self.on_event.borrow_mut() = Some(callback);
+    }
+}
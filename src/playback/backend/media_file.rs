@@ -0,0 +1,147 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use gtk4::gio;
+use gtk4::prelude::*;
+
+use super::{AudioBackend, BackendEvent, EventCallback, emit};
+
+/// Plays through `gtk4::MediaFile`, for systems where the GStreamer pipeline can't be built.
+/// It can't change speed.
+pub struct MediaFileBackend {
+    media: RefCell<Option<gtk4::MediaFile>>,
+    // Counts loaded songs, so a song that was replaced reports nothing
+    generation: Rc<Cell<u64>>,
+    on_event: EventCallback,
+}
+
+impl MediaFileBackend {
+    pub fn new() -> Self {
+        Self {
+            media: RefCell::new(None),
+            generation: Rc::new(Cell::new(0)),
+            on_event: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    fn media(&self) -> Option<gtk4::MediaFile> {
+        self.media.borrow().clone()
+    }
+}
+
+impl AudioBackend for MediaFileBackend {
+    fn load(&self, uri: &str) -> Result<(), String> {
+        self.stop();
+        let generation = self.generation.get();
+
+        let media = gtk4::MediaFile::for_file(&gio::File::for_uri(uri));
+        let current = self.generation.clone();
+        let is_current = move || current.get() == generation;
+
+        media.connect_ended_notify(glib::clone!(
+            #[strong]
+            is_current,
+            #[strong(rename_to = on_event)]
+            self.on_event,
+            move |media| {
+                if media.is_ended() && is_current() {
+                    emit(&on_event, BackendEvent::Ended);
+                }
+            }
+        ));
+        media.connect_playing_notify(glib::clone!(
+            #[strong]
+            is_current,
+            #[strong(rename_to = on_event)]
+            self.on_event,
+            move |media| {
+                if is_current() {
+                    emit(&on_event, BackendEvent::Playing(media.is_playing()));
+                }
+            }
+        ));
+        media.connect_duration_notify(glib::clone!(
+            #[strong]
+            is_current,
+            #[strong(rename_to = on_event)]
+            self.on_event,
+            move |_| {
+                if is_current() {
+                    emit(&on_event, BackendEvent::Duration);
+                }
+            }
+        ));
+        media.connect_error_notify(glib::clone!(
+            #[strong(rename_to = on_event)]
+            self.on_event,
+            move |media| {
+                if let Some(error) = media.error().filter(|_| is_current()) {
+                    emit(&on_event, BackendEvent::Error(error.to_string()));
+                }
+            }
+        ));
+
+        *self.media.borrow_mut() = Some(media);
+        Ok(())
+    }
+
+    fn stop(&self) {
+        self.generation.set(self.generation.get().saturating_add(1));
+        // Taken out first, as pausing notifies right away
+        let media = self.media.borrow_mut().take();
+        if let Some(media) = media {
+            media.pause();
+        }
+    }
+
+    fn play(&self) {
+        if let Some(media) = self.media() {
+            media.play();
+        }
+    }
+
+    fn pause(&self) {
+        if let Some(media) = self.media() {
+            media.pause();
+        }
+    }
+
+    fn is_playing(&self) -> bool {
+        self.media
+            .borrow()
+            .as_ref()
+            .is_some_and(|media| media.is_playing())
+    }
+
+    fn seek(&self, position_usecs: i64) {
+        if let Some(media) = self.media() {
+            media.seek(position_usecs.max(0));
+        }
+    }
+
+    fn position(&self) -> i64 {
+        self.media
+            .borrow()
+            .as_ref()
+            .map(|media| media.timestamp().max(0))
+            .unwrap_or(0)
+    }
+
+    fn duration(&self) -> i64 {
+        self.media
+            .borrow()
+            .as_ref()
+            .map(|media| media.duration().max(0))
+            .unwrap_or(0)
+    }
+
+    fn set_volume(&self, volume: f64) {
+        if let Some(media) = self.media() {
+            media.set_volume(volume);
+        }
+    }
+
+    fn connect_event(&self, callback: Box<dyn Fn(BackendEvent)>) {
+        *self.on_event.borrow_mut() = Some(callback);
+    }
+}
@@ -0,0 +1,120 @@
+#[cfg(test)]
+mod fake;
+mod media_file;
+mod playbin;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::config::LOG_DOMAIN;
+
+use super::EqGains;
+
+#[cfg(test)]
+pub use fake::{BackendCall, FakeBackend};
+pub use media_file::MediaFileBackend;
+pub use playbin::PlaybinBackend;
+
+/// Something a backend reports while a song plays
+#[derive(Debug, Clone, PartialEq)]
+pub enum BackendEvent {
+    /// Playback started, or paused
+    Playing(bool),
+    /// The length of the song became known
+    Duration,
+    /// The song played to its end
+    Ended,
+    Error(String),
+    /// An output device was plugged in or removed
+    OutputDevices,
+}
+
+/// A sound card, headset or other sink that songs can be played through
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputDevice {
+    /// Name the sound server knows the device by, which stays the same between sessions
+    pub id: String,
+    /// Name to show
+    pub name: String,
+}
+
+/// Plays one song at a time for the `PlaybackController`.
+/// Events are only reported for the song loaded last, and always from the main loop.
+pub trait AudioBackend {
+    /// Load the song at `uri`, paused, in place of the one before
+    fn load(&self, uri: &str) -> Result<(), String>;
+
+    /// Stop and unload the song
+    fn stop(&self);
+
+    fn play(&self);
+
+    fn pause(&self);
+
+    fn is_playing(&self) -> bool;
+
+    /// Jump to a position in microseconds
+    fn seek(&self, position_usecs: i64);
+
+    /// Position in microseconds
+    fn position(&self) -> i64;
+
+    /// Length in microseconds, or 0 while it is unknown
+    fn duration(&self) -> i64;
+
+    /// Volume from 0 to 1
+    fn set_volume(&self, volume: f64);
+
+    /// Whether `set_rate` changes the speed
+    fn can_change_rate(&self) -> bool {
+        false
+    }
+
+    /// Play faster or slower, 1 being normal speed
+    fn set_rate(&self, _rate: f64) {}
+
+    /// Whether `set_equalizer` changes the sound
+    fn can_equalize(&self) -> bool {
+        false
+    }
+
+    /// Gain of each equalizer band in dB, 0 leaving it as it is
+    fn set_equalizer(&self, _gains: &EqGains) {}
+
+    /// Whether songs can be played through a device other than the system default
+    fn can_choose_output(&self) -> bool {
+        false
+    }
+
+    /// Devices connected now
+    fn output_devices(&self) -> Vec<OutputDevice> {
+        Vec::new()
+    }
+
+    /// Play through the connected device with this ID, or the system default for `None`.
+    /// The song playing carries on where it was.
+    fn set_output_device(&self, _id: Option<&str>) {}
+
+    /// Call `callback` with each event, in place of the callback before
+    fn connect_event(&self, callback: Box<dyn Fn(BackendEvent)>);
+}
+
+/// GStreamer's `playbin3`, or `gtk4::MediaFile` where the pipeline can't be built
+pub fn default_backend() -> Rc<dyn AudioBackend> {
+    match PlaybinBackend::new() {
+        Ok(backend) => Rc::new(backend),
+        Err(error) => {
+            glib::g_warning!(LOG_DOMAIN, "{}; falling back to GTK media playback", error);
+            Rc::new(MediaFileBackend::new())
+        }
+    }
+}
+
+/// Where a backend keeps the callback given to `connect_event`
+type EventCallback = Rc<RefCell<Option<Box<dyn Fn(BackendEvent)>>>>;
+
+fn emit(callback: &EventCallback, event: BackendEvent) {
+    if let Some(ref callback) = *callback.borrow() {
+        callback(event);
+    }
+}
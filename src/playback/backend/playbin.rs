@@ -0,0 +1,425 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use gstreamer as gst;
+use gstreamer::prelude::*;
+
+use super::{AudioBackend, BackendEvent, EqGains, EventCallback, OutputDevice, emit};
+
+/// Plays through a GStreamer `playbin3` pipeline that is kept from song to song.
+/// Unlike `gtk4::MediaFile` it can change speed, and `scaletempo` keeps the pitch while it does.
+pub struct PlaybinBackend {
+    playbin: gst::Element,
+    // The 10-band equalizer in the effects bin, if it is installed
+    equalizer: Option<gst::Element>,
+    playing: Rc<Cell<bool>>,
+    rate: Rc<Cell<f64>>,
+    // A speed set before the pipeline could seek, applied once it can
+    rate_pending: Rc<Cell<bool>>,
+    known_duration: Rc<Cell<i64>>,
+    // Where to carry on once a song reloaded for another output device has loaded
+    resume_at: Rc<Cell<Option<i64>>>,
+    // Device played through, or `None` for the system default
+    output_device: RefCell<Option<String>>,
+    // Lists sinks as they come and go, when a device provider is installed
+    device_monitor: Option<(gst::DeviceMonitor, gst::bus::BusWatchGuard)>,
+    on_event: EventCallback,
+    _bus_watch: gst::bus::BusWatchGuard,
+}
+
+impl PlaybinBackend {
+    pub fn new() -> Result<Self, String> {
+        gst::init().map_err(|e| format!("Failed to start GStreamer: {}", e))?;
+
+        let playbin = gst::ElementFactory::make("playbin3")
+            .build()
+            .map_err(|e| format!("Failed to create playback pipeline: {}", e))?;
+
+        // Cover art in local files would otherwise open a video window
+        if let Ok(video_sink) = gst::ElementFactory::make("fakesink").build() {
+            playbin.set_property("video-sink", &video_sink);
+        }
+        let equalizer = match build_effects() {
+            Ok((effects, equalizer)) => {
+                playbin.set_property("audio-filter", &effects);
+                equalizer
+            }
+            Err(error) => {
+                eprintln!("{}", error);
+                None
+            }
+        };
+
+        let playing = Rc::new(Cell::new(false));
+        let rate = Rc::new(Cell::new(1.0));
+        let rate_pending = Rc::new(Cell::new(false));
+        let known_duration = Rc::new(Cell::new(0));
+        let resume_at = Rc::new(Cell::new(None));
+        let on_event: EventCallback = Rc::new(RefCell::new(None));
+
+        // Going to the null state for the next song flushes the bus,
+        // so messages about the song before are never seen
+        let bus = playbin
+            .bus()
+            .ok_or_else(|| "Playback pipeline has no bus".to_string())?;
+        let bus_watch = bus
+            .add_watch_local(glib::clone!(
+                #[weak]
+                playbin,
+                #[strong]
+                playing,
+                #[strong]
+                rate,
+                #[strong]
+                rate_pending,
+                #[strong]
+                known_duration,
+                #[strong]
+                resume_at,
+                #[strong]
+                on_event,
+                #[upgrade_or]
+                glib::ControlFlow::Break,
+                move |_, message| {
+                    // Seeks end in AsyncDone too, so only a new length is reported
+                    let report_duration = || {
+                        let duration = query_duration(&playbin);
+                        if known_duration.replace(duration) != duration {
+                            emit(&on_event, BackendEvent::Duration);
+                        }
+                    };
+                    match message.view() {
+                        gst::MessageView::Eos(_) => {
+                            playing.set(false);
+                            emit(&on_event, BackendEvent::Ended);
+                        }
+                        gst::MessageView::Error(error) => {
+                            playing.set(false);
+                            emit(&on_event, BackendEvent::Error(error.error().to_string()));
+                        }
+                        gst::MessageView::AsyncDone(_) => {
+                            // Seeking back also sets the speed
+                            if let Some(position) = resume_at.take() {
+                                if seek_to(&playbin, rate.get(), position) {
+                                    rate_pending.set(false);
+                                }
+                            } else if rate_pending.get() && apply_rate(&playbin, rate.get()) {
+                                rate_pending.set(false);
+                            }
+                            report_duration();
+                        }
+                        gst::MessageView::DurationChanged(_) => report_duration(),
+                        gst::MessageView::StateChanged(change)
+                            if message.src() == Some(playbin.upcast_ref::<gst::Object>()) =>
+                        {
+                            let now_playing = change.current() == gst::State::Playing;
+                            if now_playing != (change.old() == gst::State::Playing) {
+                                emit(&on_event, BackendEvent::Playing(now_playing));
+                            }
+                        }
+                        _ => {}
+                    }
+                    glib::ControlFlow::Continue
+                }
+            ))
+            .map_err(|e| format!("Failed to watch playback pipeline: {}", e))?;
+
+        Ok(Self {
+            playbin,
+            equalizer,
+            playing,
+            rate,
+            rate_pending,
+            known_duration,
+            resume_at,
+            output_device: RefCell::new(None),
+            device_monitor: start_device_monitor(&on_event),
+            on_event,
+            _bus_watch: bus_watch,
+        })
+    }
+
+    fn find_device(&self, id: &str) -> Option<gst::Device> {
+        let (monitor, _) = self.device_monitor.as_ref()?;
+        monitor
+            .devices()
+            .into_iter()
+            .find(|device| device_id(device) == id)
+    }
+}
+
+impl AudioBackend for PlaybinBackend {
+    fn load(&self, uri: &str) -> Result<(), String> {
+        self.stop();
+        self.known_duration.set(0);
+        self.resume_at.set(None);
+        self.rate_pending.set(self.rate.get() != 1.0);
+        self.playbin.set_property("uri", uri);
+        self.playbin
+            .set_state(gst::State::Paused)
+            .map_err(|e| format!("Failed to load song: {}", e))?;
+        Ok(())
+    }
+
+    fn stop(&self) {
+        let _ = self.playbin.set_state(gst::State::Null);
+        self.playing.set(false);
+    }
+
+    fn play(&self) {
+        if self.playbin.set_state(gst::State::Playing).is_ok() {
+            self.playing.set(true);
+        }
+    }
+
+    fn pause(&self) {
+        if self.playbin.set_state(gst::State::Paused).is_ok() {
+            self.playing.set(false);
+        }
+    }
+
+    fn is_playing(&self) -> bool {
+        self.playing.get()
+    }
+
+    fn seek(&self, position_usecs: i64) {
+        seek_to(&self.playbin, self.rate.get(), position_usecs);
+    }
+
+    fn position(&self) -> i64 {
+        self.playbin
+            .query_position::<gst::ClockTime>()
+            .map(|position| position.useconds() as i64)
+            .unwrap_or(0)
+    }
+
+    fn duration(&self) -> i64 {
+        query_duration(&self.playbin)
+    }
+
+    fn set_volume(&self, volume: f64) {
+        self.playbin.set_property("volume", volume);
+    }
+
+    fn can_change_rate(&self) -> bool {
+        true
+    }
+
+    fn set_rate(&self, rate: f64) {
+        if self.rate.replace(rate) == rate && !self.rate_pending.get() {
+            return;
+        }
+        self.rate_pending.set(!apply_rate(&self.playbin, rate));
+    }
+
+    fn can_equalize(&self) -> bool {
+        self.equalizer.is_some()
+    }
+
+    fn set_equalizer(&self, gains: &EqGains) {
+        let Some(ref equalizer) = self.equalizer else {
+            return;
+        };
+        for (band, gain) in gains.iter().enumerate() {
+            equalizer.set_property(&format!("band{}", band), *gain);
+        }
+    }
+
+    fn can_choose_output(&self) -> bool {
+        self.device_monitor.is_some()
+    }
+
+    fn output_devices(&self) -> Vec<OutputDevice> {
+        let Some((ref monitor, _)) = self.device_monitor else {
+            return Vec::new();
+        };
+        // A device offered by more than one provider is listed once
+        let mut devices: Vec<OutputDevice> = Vec::new();
+        for device in monitor.devices() {
+            let id = device_id(&device);
+            if devices.iter().all(|known| known.id != id) {
+                devices.push(OutputDevice {
+                    id,
+                    name: device.display_name().to_string(),
+                });
+            }
+        }
+        devices
+    }
+
+    fn set_output_device(&self, id: Option<&str>) {
+        let sink = id.and_then(|id| {
+            let device = self.find_device(id)?;
+            device
+                .create_element(None)
+                .map_err(|e| eprintln!("Failed to open output device {}: {}", id, e))
+                .ok()
+        });
+        let id = id.filter(|_| sink.is_some()).map(str::to_string);
+        if *self.output_device.borrow() == id {
+            return;
+        }
+        *self.output_device.borrow_mut() = id;
+
+        // playbin only takes another sink while stopped, so a loaded song is reloaded where it was
+        let loaded = self.playbin.current_state() != gst::State::Null;
+        let position = self.position();
+        let _ = self.playbin.set_state(gst::State::Null);
+        self.playbin.set_property("audio-sink", sink.as_ref());
+        if loaded {
+            self.resume_at.set(Some(position));
+            let state = if self.playing.get() {
+                gst::State::Playing
+            } else {
+                gst::State::Paused
+            };
+            if let Err(e) = self.playbin.set_state(state) {
+                emit(
+                    &self.on_event,
+                    BackendEvent::Error(format!("Failed to switch output device: {}", e)),
+                );
+            }
+        }
+    }
+
+    fn connect_event(&self, callback: Box<dyn Fn(BackendEvent)>) {
+        *self.on_event.borrow_mut() = Some(callback);
+    }
+}
+
+impl Drop for PlaybinBackend {
+    fn drop(&mut self) {
+        let _ = self.playbin.set_state(gst::State::Null);
+        if let Some((ref monitor, _)) = self.device_monitor {
+            monitor.stop();
+        }
+    }
+}
+
+/// Watch for audio sinks being plugged in or removed; `None` if no device provider is installed
+fn start_device_monitor(
+    on_event: &EventCallback,
+) -> Option<(gst::DeviceMonitor, gst::bus::BusWatchGuard)> {
+    let monitor = gst::DeviceMonitor::new();
+    monitor.add_filter(
+        Some("Audio/Sink"),
+        Some(&gst::Caps::new_empty_simple("audio/x-raw")),
+    );
+    let watch = monitor
+        .bus()
+        .add_watch_local(glib::clone!(
+            #[strong]
+            on_event,
+            move |_, message| {
+                if matches!(
+                    message.view(),
+                    gst::MessageView::DeviceAdded(_) | gst::MessageView::DeviceRemoved(_)
+                ) {
+                    emit(&on_event, BackendEvent::OutputDevices);
+                }
+                glib::ControlFlow::Continue
+            }
+        ))
+        .map_err(|e| eprintln!("Failed to watch output devices: {}", e))
+        .ok()?;
+    if let Err(e) = monitor.start() {
+        eprintln!("Failed to list output devices: {}", e);
+        return None;
+    }
+    Some((monitor, watch))
+}
+
+/// PulseAudio sinks have an internal name and PipeWire nodes a node name;
+/// unlike the display name, both tell identical devices apart
+fn device_id(device: &gst::Device) -> String {
+    if let Ok(name) = device.try_property::<String>("internal-name") {
+        return name;
+    }
+    device
+        .properties()
+        .and_then(|properties| properties.get::<String>("node.name").ok())
+        .unwrap_or_else(|| device.display_name().to_string())
+}
+
+/// The bin decoded audio passes through before it is played, where effects are inserted.
+/// Speed changes come first so the equalizer works on the sound as it is heard;
+/// each effect that isn't installed is left out, along with the equalizer.
+fn build_effects() -> Result<(gst::Bin, Option<gst::Element>), String> {
+    let effects = gst::Bin::with_name("effects");
+    let mut chain = vec![make_element("audioconvert")?];
+
+    match gst::ElementFactory::make("scaletempo").build() {
+        Ok(scaletempo) => {
+            chain.push(scaletempo);
+            chain.push(make_element("audioconvert")?);
+        }
+        Err(_) => eprintln!("scaletempo is not installed, so speed changes shift the pitch"),
+    }
+
+    let equalizer = match gst::ElementFactory::make("equalizer-10bands").build() {
+        Ok(equalizer) => {
+            chain.push(equalizer.clone());
+            chain.push(make_element("audioconvert")?);
+            Some(equalizer)
+        }
+        Err(_) => {
+            eprintln!("equalizer-10bands is not installed, so the equalizer is turned off");
+            None
+        }
+    };
+
+    effects
+        .add_many(&chain)
+        .map_err(|e| format!("Failed to build audio effects: {}", e))?;
+    gst::Element::link_many(&chain).map_err(|e| format!("Failed to link audio effects: {}", e))?;
+
+    for (element, pad) in [(chain.first(), "sink"), (chain.last(), "src")] {
+        let target = element
+            .and_then(|element| element.static_pad(pad))
+            .ok_or_else(|| format!("Audio effects have no {} pad", pad))?;
+        let ghost = gst::GhostPad::builder_with_target(&target)
+            .map_err(|e| format!("Failed to expose audio effects: {}", e))?
+            .name(pad)
+            .build();
+        effects
+            .add_pad(&ghost)
+            .map_err(|e| format!("Failed to expose audio effects: {}", e))?;
+    }
+
+    Ok((effects, equalizer))
+}
+
+fn make_element(factory: &str) -> Result<gst::Element, String> {
+    gst::ElementFactory::make(factory)
+        .build()
+        .map_err(|e| format!("Failed to create {}: {}", factory, e))
+}
+
+fn query_duration(playbin: &gst::Element) -> i64 {
+    playbin
+        .query_duration::<gst::ClockTime>()
+        .map(|duration| duration.useconds() as i64)
+        .unwrap_or(0)
+}
+
+/// Seek to where the song is at the new speed; fails until the pipeline has loaded
+fn apply_rate(playbin: &gst::Element, rate: f64) -> bool {
+    let Some(position) = playbin.query_position::<gst::ClockTime>() else {
+        return false;
+    };
+    seek_to(playbin, rate, position.useconds() as i64)
+}
+
+/// Jump to a position in microseconds, playing at `rate` from there
+fn seek_to(playbin: &gst::Element, rate: f64, position_usecs: i64) -> bool {
+    playbin
+        .seek(
+            rate,
+            gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE,
+            gst::SeekType::Set,
+            gst::ClockTime::from_useconds(position_usecs.max(0) as u64),
+            gst::SeekType::None,
+            gst::ClockTime::NONE,
+        )
+        .is_ok()
+}
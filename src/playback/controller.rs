@@ -1,18 +1,99 @@
 use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
 use std::rc::Rc;
 use std::time::Duration;
 
 use glib::ControlFlow;
 use gtk4::prelude::*;
+use libadwaita as adw;
 
 use crate::api::{LyricLine, SearchResult, StreamInfo};
-use crate::config::{DEFAULT_COVER_PATH, POLL_INTERVAL_MS};
-use crate::storage::{Database, Song};
-use crate::ui::components::{clear_listbox, load_image_async, load_image_async_with_callback, song_card_row, RgbColor};
+use crate::config::{
+    DEFAULT_COVER_PATH, DEFAULT_SEEK_STEP_SECS, ICON_PAUSE, ICON_PLAY, LONG_TRACK_SECS,
+    MAX_PLAYBACK_RATE, MIN_PLAYBACK_RATE, POLL_INTERVAL_MS, SCROBBLE_AFTER_SECS,
+    SLEEP_FADE_OUT_SECS,
+};
+use crate::formats::parse_duration;
+use crate::sources::Sources;
+use crate::storage::{Database, Song, SongSource};
+use crate::ui::components::{
+    clear_listbox, is_image_url, load_image_async, load_image_async_with_callback, song_card_row, RgbColor,
+};
+
+use super::notifications::{notifications_enabled, notify_track_change};
+use super::offline::{OfflineMode, cache_stream_info, local_stream_info};
+use super::backend::{AudioBackend, BackendEvent, OutputDevice, default_backend};
+use super::equalizer::EqPreset;
+
+/// What plays again when a song or the queue ends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepeatMode {
+    Off,
+    Queue,
+    Track,
+}
+
+/// A change the player tells its listeners about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayerChange {
+    /// Another song started, or the length of the current one became known
+    Track,
+    /// Playback started or paused
+    Status,
+    /// Jumped to a position, in microseconds
+    Seeked(i64),
+    /// A keyboard or scroll seek, and the position it landed at in microseconds
+    Jumped(SeekJump, i64),
+    /// Shuffle, repeat, volume or speed changed
+    Options,
+    /// The sleep timer was set, counted down another second, or ran out
+    SleepTimer,
+    /// Another equalizer preset was chosen, or the playlist playing brought its own
+    Equalizer,
+    /// An output device was plugged in or removed, or another was chosen
+    OutputDevices,
+}
+
+/// How a keyboard or scroll seek moved, which the window points out briefly
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeekJump {
+    /// By this many seconds, negative when going back
+    By(i64),
+    /// To this percentage of the song
+    ToPercent(i64),
+}
+
+/// When the sleep timer pauses playback
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SleepTimer {
+    /// After a fixed number of minutes
+    Minutes(u32),
+    /// When the song playing ends
+    EndOfTrack,
+    /// When the last song in the queue ends
+    EndOfQueue,
+}
+
+const SEEK_STEP_SETTING: &str = "seek_step_secs";
+
+/// Seconds skipped by the short seek shortcuts and each scroll of the progress bar
+pub fn seek_step(database: &Database) -> f64 {
+    database
+        .get_setting(SEEK_STEP_SETTING)
+        .and_then(|value| value.parse::<f64>().ok())
+        .filter(|step| *step > 0.0)
+        .unwrap_or(DEFAULT_SEEK_STEP_SECS)
+}
+
+pub fn set_seek_step(database: &Database, step_secs: f64) -> Result<(), String> {
+    database.set_setting(SEEK_STEP_SETTING, &step_secs.to_string())
+}
 
 #[derive(Clone)]
 pub struct PlaybackController {
-    media: Rc<RefCell<Option<gtk4::MediaFile>>>,
+    backend: Rc<dyn AudioBackend>,
+    // Whether a song is loaded into the backend
+    has_media: Rc<Cell<bool>>,
     title: Rc<RefCell<Option<gtk4::Label>>>,
     artist: Rc<RefCell<Option<gtk4::Label>>>,
     progress: Rc<RefCell<Option<gtk4::Scale>>>,
@@ -52,12 +133,46 @@ pub struct PlaybackController {
     is_now_playing_visible: Rc<Cell<bool>>,
     // Database for recent plays and liked songs
     database: Rc<RefCell<Option<Database>>>,
+    offline: Rc<RefCell<Option<OfflineMode>>>,
+    // Music sources, told about plays so servers can count them
+    sources: Rc<RefCell<Option<Sources>>>,
+    // Song still to be reported as played, once enough of it has been heard
+    scrobble_pending: Rc<RefCell<Option<String>>>,
+    shuffle: Rc<Cell<bool>>,
+    repeat: Rc<Cell<RepeatMode>>,
+    volume: Rc<Cell<f64>>,
+    // Speed of songs, and of the long track playing when it remembers its own
+    rate: Rc<Cell<f64>>,
+    track_rate: Rc<Cell<Option<f64>>>,
+    // Cover shown for the current song
+    current_thumbnail: Rc<RefCell<Option<String>>>,
+    on_changed: Rc<RefCell<Vec<Box<dyn Fn(PlayerChange)>>>>,
+    // Application that track notifications are sent through
+    application: Rc<RefCell<Option<adw::Application>>>,
+    // Pauses playback after a while, fading the volume out first
+    sleep_timer: Rc<Cell<Option<SleepTimer>>>,
+    // When a fixed sleep timer runs out, in glib monotonic microseconds
+    sleep_deadline: Rc<Cell<i64>>,
+    sleep_token: Rc<Cell<u64>>,
+    // Songs that ended since the timer started, which tell when a shuffled queue is done
+    sleep_heard: Rc<RefCell<HashSet<String>>>,
+    // Preset chosen on the equalizer page, and the one the playlist playing overrides it with
+    equalizer: Rc<RefCell<EqPreset>>,
+    playlist_equalizer: Rc<RefCell<Option<EqPreset>>>,
+    // Device chosen in Settings, played through whenever it is connected
+    output_device: Rc<RefCell<Option<OutputDevice>>>,
 }
 
 impl PlaybackController {
     pub fn new() -> Self {
-        Self {
-            media: Rc::new(RefCell::new(None)),
+        Self::with_backend(default_backend())
+    }
+
+    /// A controller playing through `backend`
+    pub fn with_backend(backend: Rc<dyn AudioBackend>) -> Self {
+        let controller = Self {
+            backend,
+            has_media: Rc::new(Cell::new(false)),
             title: Rc::new(RefCell::new(None)),
             artist: Rc::new(RefCell::new(None)),
             progress: Rc::new(RefCell::new(None)),
@@ -95,13 +210,114 @@ impl PlaybackController {
             current_lyric_index: Rc::new(Cell::new(-1)),
             is_now_playing_visible: Rc::new(Cell::new(false)),
             database: Rc::new(RefCell::new(None)),
-        }
+            offline: Rc::new(RefCell::new(None)),
+            sources: Rc::new(RefCell::new(None)),
+            scrobble_pending: Rc::new(RefCell::new(None)),
+            shuffle: Rc::new(Cell::new(false)),
+            repeat: Rc::new(Cell::new(RepeatMode::Off)),
+            volume: Rc::new(Cell::new(1.0)),
+            rate: Rc::new(Cell::new(1.0)),
+            track_rate: Rc::new(Cell::new(None)),
+            current_thumbnail: Rc::new(RefCell::new(None)),
+            on_changed: Rc::new(RefCell::new(Vec::new())),
+            application: Rc::new(RefCell::new(None)),
+            sleep_timer: Rc::new(Cell::new(None)),
+            sleep_deadline: Rc::new(Cell::new(0)),
+            sleep_token: Rc::new(Cell::new(0)),
+            sleep_heard: Rc::new(RefCell::new(HashSet::new())),
+            equalizer: Rc::new(RefCell::new(EqPreset::flat())),
+            playlist_equalizer: Rc::new(RefCell::new(None)),
+            output_device: Rc::new(RefCell::new(None)),
+        };
+
+        // The controller lives as long as the app, so the backend may keep it
+        let events = controller.clone();
+        controller
+            .backend
+            .connect_event(Box::new(move |event| events.on_backend_event(event)));
+        controller
     }
 
     pub fn set_database(&self, db: Database) {
         *self.database.borrow_mut() = Some(db);
     }
 
+    pub fn set_application(&self, app: &adw::Application) {
+        *self.application.borrow_mut() = Some(app.clone());
+    }
+
+    pub fn set_sources(&self, sources: Sources) {
+        *self.sources.borrow_mut() = Some(sources);
+    }
+
+    /// Call `callback` whenever the song, play state, position or playback options change
+    pub fn connect_changed(&self, callback: impl Fn(PlayerChange) + 'static) {
+        self.on_changed.borrow_mut().push(Box::new(callback));
+    }
+
+    fn notify_changed(&self, change: PlayerChange) {
+        for callback in self.on_changed.borrow().iter() {
+            callback(change);
+        }
+    }
+
+    pub fn set_offline_mode(&self, offline: OfflineMode) {
+        offline.connect_changed(glib::clone!(
+            #[strong(rename_to = controller)]
+            self,
+            move |_| controller.update_queue_availability()
+        ));
+        *self.offline.borrow_mut() = Some(offline);
+        self.update_queue_availability();
+    }
+
+    fn is_offline(&self) -> bool {
+        self.offline.borrow().as_ref().is_some_and(|offline| offline.is_offline())
+    }
+
+    /// Offline, only downloaded songs and local tracks can be played
+    fn is_playable(&self, video_id: &str) -> bool {
+        !self.is_offline()
+            || self
+                .database
+                .borrow()
+                .as_ref()
+                .is_some_and(|db| db.local_file(video_id).is_some())
+    }
+
+    /// Plays a downloaded song or local track straight from disk, using the metadata cached for it.
+    /// Offline, a song that is not downloaded is skipped for the next one in the queue that is.
+    /// Returns false when the stream still has to be fetched.
+    pub fn play_downloaded(&self, item: &SearchResult) -> bool {
+        let info = self
+            .database
+            .borrow()
+            .as_ref()
+            .and_then(|db| local_stream_info(db, item));
+        match info {
+            Some(info) => {
+                self.play_stream(&info, item.thumbnail_url.as_deref());
+                true
+            }
+            None if SongSource::of(&item.video_id) == SongSource::Local => {
+                self.show_error("This file is no longer in your music folders");
+                true
+            }
+            None if self.is_offline() => {
+                // The queue skips songs that are not downloaded, so move on to the next one that is
+                let current = self.current_index.get();
+                match self.shift_index(1).filter(|_| self.current_index.get() != current) {
+                    Some(next) => {
+                        self.play_downloaded(&next);
+                    }
+                    None => self.show_error("Not downloaded, so it can't be played offline"),
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn set_now_playing_visible(&self, visible: bool) {
         self.is_now_playing_visible.set(visible);
     }
@@ -231,13 +447,405 @@ impl PlaybackController {
     }
 
     pub fn seek(&self, position_secs: f64) {
-        let binding = self.media.borrow();
-        if let Some(ref media) = *binding {
+        if self.has_media() {
             let position_usecs = (position_secs * 1_000_000.0) as i64;
-            media.seek(position_usecs);
+            self.backend.seek(position_usecs);
+            self.notify_changed(PlayerChange::Seeked(position_usecs));
+        }
+    }
+
+    /// Jump forward, or back for a negative offset, staying within the song
+    pub fn seek_by(&self, offset_secs: f64) {
+        if !self.has_media() {
+            return;
+        }
+        let mut position = self.position() as f64 / 1_000_000.0 + offset_secs;
+        let duration = self.duration();
+        if duration > 0 {
+            position = position.min(duration as f64 / 1_000_000.0);
+        }
+        let position = position.max(0.0);
+        self.seek(position);
+        self.notify_jumped(SeekJump::By(offset_secs.round() as i64), position);
+    }
+
+    /// Jump to a fraction of the song, from 0 at the start to 1 at the end
+    pub fn seek_to_fraction(&self, fraction: f64) {
+        let duration = self.duration();
+        if duration <= 0 {
+            return;
+        }
+        let fraction = fraction.clamp(0.0, 1.0);
+        let position = duration as f64 / 1_000_000.0 * fraction;
+        self.seek(position);
+        self.notify_jumped(SeekJump::ToPercent((fraction * 100.0).round() as i64), position);
+    }
+
+    fn notify_jumped(&self, jump: SeekJump, position_secs: f64) {
+        self.notify_changed(PlayerChange::Jumped(jump, (position_secs * 1_000_000.0) as i64));
+    }
+
+    /// Seek step from Settings
+    pub fn seek_step(&self) -> f64 {
+        self.database
+            .borrow()
+            .as_ref()
+            .map(seek_step)
+            .unwrap_or(DEFAULT_SEEK_STEP_SECS)
+    }
+
+    /// Seek by the seek step when the mouse wheel turns over a progress bar
+    pub fn connect_scroll_seek(&self, scale: &gtk4::Scale) {
+        let scroll = gtk4::EventControllerScroll::new(
+            gtk4::EventControllerScrollFlags::VERTICAL | gtk4::EventControllerScrollFlags::DISCRETE,
+        );
+        // Ahead of the scale's own scrolling, which moves by an arbitrary amount
+        scroll.set_propagation_phase(gtk4::PropagationPhase::Capture);
+        scroll.connect_scroll(glib::clone!(
+            #[strong(rename_to = controller)]
+            self,
+            move |_, _, dy| {
+                if dy == 0.0 {
+                    return glib::Propagation::Proceed;
+                }
+                // Scrolling up moves forward
+                let step = controller.seek_step();
+                controller.seek_by(if dy < 0.0 { step } else { -step });
+                glib::Propagation::Stop
+            }
+        ));
+        scale.add_controller(scroll);
+    }
+
+    /// Position in the current song, in microseconds
+    pub fn position(&self) -> i64 {
+        if self.has_media() {
+            self.backend.position().max(0)
+        } else {
+            0
+        }
+    }
+
+    /// Length of the current song in microseconds, or 0 while it is unknown
+    pub fn duration(&self) -> i64 {
+        if self.has_media() {
+            self.backend.duration().max(0)
+        } else {
+            0
+        }
+    }
+
+    pub fn has_media(&self) -> bool {
+        self.has_media.get()
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.has_media() && self.backend.is_playing()
+    }
+
+    pub fn play(&self) {
+        if self.has_media() && !self.is_playing() {
+            self.toggle_play_pause();
         }
     }
 
+    pub fn pause(&self) {
+        if self.is_playing() {
+            self.toggle_play_pause();
+        }
+    }
+
+    /// Cover of the current song, as shown in the now playing view
+    pub fn current_thumbnail(&self) -> Option<String> {
+        self.current_thumbnail.borrow().clone()
+    }
+
+    pub fn volume(&self) -> f64 {
+        self.volume.get()
+    }
+
+    /// Set the volume from 0 to 1, kept for the songs that follow
+    pub fn set_volume(&self, volume: f64) {
+        let volume = volume.clamp(0.0, 1.0);
+        self.volume.set(volume);
+        self.backend.set_volume(volume);
+        self.notify_changed(PlayerChange::Options);
+    }
+
+    /// Whether the backend can play faster or slower
+    pub fn can_change_rate(&self) -> bool {
+        self.backend.can_change_rate()
+    }
+
+    /// Playback speed of the song playing, 1 being normal
+    pub fn rate(&self) -> f64 {
+        self.track_rate.get().unwrap_or(self.rate.get())
+    }
+
+    /// Change the speed from half to double, keeping the pitch.
+    /// Long tracks such as mixes and podcasts remember their own speed; other songs share one.
+    pub fn set_rate(&self, rate: f64) {
+        if !self.can_change_rate() {
+            return;
+        }
+        let rate = rate.clamp(MIN_PLAYBACK_RATE, MAX_PLAYBACK_RATE);
+        match self.current_item().filter(|item| self.is_long_track(item)) {
+            Some(item) => {
+                self.track_rate.set(Some(rate));
+                if let Some(ref db) = *self.database.borrow() {
+                    let _ = db.set_track_rate(&item.video_id, rate);
+                }
+            }
+            None => self.rate.set(rate),
+        }
+        self.backend.set_rate(rate);
+        self.notify_changed(PlayerChange::Options);
+    }
+
+    /// Whether the backend has an equalizer
+    pub fn can_equalize(&self) -> bool {
+        self.backend.can_equalize()
+    }
+
+    /// Preset chosen on the equalizer page
+    pub fn equalizer(&self) -> EqPreset {
+        self.equalizer.borrow().clone()
+    }
+
+    /// Choose a preset on the equalizer page, which also ends the playlist's override
+    pub fn set_equalizer(&self, preset: EqPreset) {
+        *self.equalizer.borrow_mut() = preset;
+        self.playlist_equalizer.borrow_mut().take();
+        self.apply_equalizer();
+    }
+
+    /// Preset the playlist playing uses in place of the chosen one
+    pub fn playlist_equalizer(&self) -> Option<EqPreset> {
+        self.playlist_equalizer.borrow().clone()
+    }
+
+    /// Play the queue with a playlist's own preset, until another queue is set
+    pub fn set_playlist_equalizer(&self, preset: Option<EqPreset>) {
+        *self.playlist_equalizer.borrow_mut() = preset;
+        self.apply_equalizer();
+    }
+
+    fn apply_equalizer(&self) {
+        let gains = match *self.playlist_equalizer.borrow() {
+            Some(ref preset) => preset.gains,
+            None => self.equalizer.borrow().gains,
+        };
+        self.backend.set_equalizer(&gains);
+        self.notify_changed(PlayerChange::Equalizer);
+    }
+
+    /// Whether songs can be played through a device other than the system default
+    pub fn can_choose_output(&self) -> bool {
+        self.backend.can_choose_output()
+    }
+
+    /// Devices connected now
+    pub fn output_devices(&self) -> Vec<OutputDevice> {
+        self.backend.output_devices()
+    }
+
+    /// Device chosen in Settings, which may not be connected; `None` for the system default
+    pub fn output_device(&self) -> Option<OutputDevice> {
+        self.output_device.borrow().clone()
+    }
+
+    /// Play through `device` whenever it is connected, and the system default while it isn't
+    pub fn set_output_device(&self, device: Option<OutputDevice>) {
+        *self.output_device.borrow_mut() = device;
+        self.route_output();
+    }
+
+    /// Whether the chosen device is connected; true when the system default is chosen
+    pub fn is_output_connected(&self) -> bool {
+        match *self.output_device.borrow() {
+            Some(ref chosen) => self.output_devices().iter().any(|device| device.id == chosen.id),
+            None => true,
+        }
+    }
+
+    fn route_output(&self) {
+        let chosen = self.output_device().filter(|_| self.is_output_connected());
+        self.backend
+            .set_output_device(chosen.as_ref().map(|device| device.id.as_str()));
+        self.notify_changed(PlayerChange::OutputDevices);
+    }
+
+    fn is_long_track(&self, item: &SearchResult) -> bool {
+        let secs = match self.duration() {
+            0 => parse_duration(&item.duration).unwrap_or(0),
+            duration => duration / 1_000_000,
+        };
+        secs >= LONG_TRACK_SECS
+    }
+
+    pub fn shuffle(&self) -> bool {
+        self.shuffle.get()
+    }
+
+    pub fn set_shuffle(&self, shuffle: bool) {
+        if self.shuffle.replace(shuffle) != shuffle {
+            self.notify_changed(PlayerChange::Options);
+        }
+    }
+
+    pub fn repeat(&self) -> RepeatMode {
+        self.repeat.get()
+    }
+
+    pub fn set_repeat(&self, repeat: RepeatMode) {
+        if self.repeat.replace(repeat) != repeat {
+            self.notify_changed(PlayerChange::Options);
+        }
+    }
+
+    pub fn sleep_timer(&self) -> Option<SleepTimer> {
+        self.sleep_timer.get()
+    }
+
+    /// Start the sleep timer, replacing one that is running, or turn it off with `None`
+    pub fn set_sleep_timer(&self, timer: Option<SleepTimer>) {
+        let token = self.sleep_token.get().saturating_add(1);
+        self.sleep_token.set(token);
+        self.sleep_timer.set(timer);
+        self.sleep_heard.borrow_mut().clear();
+        if let Some(SleepTimer::Minutes(minutes)) = timer {
+            self.sleep_deadline
+                .set(glib::monotonic_time() + i64::from(minutes) * 60 * 1_000_000);
+        }
+
+        // Undo the fade of a timer that was turned off part way through it
+        self.backend.set_volume(self.volume.get());
+        self.notify_changed(PlayerChange::SleepTimer);
+
+        if timer.is_some() {
+            self.start_sleep_polling(token);
+        }
+    }
+
+    /// Time left on the sleep timer in microseconds, while it can be told.
+    /// Counting to the end of the queue adds up the lengths of the songs still to come,
+    /// taking songs of unknown length as 0; shuffled, it is only known during the last song.
+    pub fn sleep_remaining(&self) -> Option<i64> {
+        let left_in_song = || {
+            let duration = self.duration();
+            (duration > 0).then(|| (duration - self.position()).max(0))
+        };
+        match self.sleep_timer.get()? {
+            SleepTimer::Minutes(_) => {
+                Some((self.sleep_deadline.get() - glib::monotonic_time()).max(0))
+            }
+            SleepTimer::EndOfTrack => left_in_song(),
+            SleepTimer::EndOfQueue if self.shuffle.get() => {
+                if self.unheard_songs() == 0 {
+                    left_in_song()
+                } else {
+                    None
+                }
+            }
+            SleepTimer::EndOfQueue => {
+                let index = usize::try_from(self.current_index.get()).ok()?;
+                let upcoming: i64 = self
+                    .queue
+                    .borrow()
+                    .iter()
+                    .skip(index + 1)
+                    .filter_map(|item| parse_duration(&item.duration))
+                    .sum();
+                Some(left_in_song()? + upcoming * 1_000_000)
+            }
+        }
+    }
+
+    /// Count the sleep timer down, fading out over its last seconds.
+    /// Only a fixed timer runs out here; the others stop when their song ends.
+    fn start_sleep_polling(&self, token: u64) {
+        let shown_secs = Cell::new(-1);
+        let media_token = Cell::new(self.media_token.get());
+        glib::timeout_add_local(
+            Duration::from_millis(POLL_INTERVAL_MS),
+            glib::clone!(
+                #[strong(rename_to = controller)]
+                self,
+                move || {
+                    if controller.sleep_token.get() != token {
+                        return ControlFlow::Break;
+                    }
+                    // A song that starts part way through the fade gets its volume back;
+                    // a fixed timer turns it down again below
+                    let current_media = controller.media_token.get();
+                    if media_token.replace(current_media) != current_media {
+                        controller.backend.set_volume(controller.volume.get());
+                    }
+
+                    let Some(remaining) = controller.sleep_remaining() else {
+                        return ControlFlow::Continue;
+                    };
+                    if remaining == 0
+                        && matches!(controller.sleep_timer.get(), Some(SleepTimer::Minutes(_)))
+                    {
+                        controller.finish_sleep_timer();
+                        return ControlFlow::Break;
+                    }
+
+                    // The volume setting is kept; only the song playing is turned down
+                    let fade_usecs = SLEEP_FADE_OUT_SECS * 1_000_000.0;
+                    let level = remaining as f64 / fade_usecs;
+                    if level < 1.0 {
+                        controller.backend.set_volume(controller.volume.get() * level);
+                    }
+
+                    let secs = (remaining + 999_999) / 1_000_000;
+                    if shown_secs.replace(secs) != secs {
+                        controller.notify_changed(PlayerChange::SleepTimer);
+                    }
+                    ControlFlow::Continue
+                }
+            ),
+        );
+    }
+
+    /// Whether the sleep timer runs out as the song playing ends
+    fn sleep_ends_with_song(&self) -> bool {
+        match self.sleep_timer.get() {
+            Some(SleepTimer::EndOfTrack) => true,
+            // Shuffle picks songs at random, so the queue is done once each has been heard
+            Some(SleepTimer::EndOfQueue) if self.shuffle.get() => self.unheard_songs() == 0,
+            Some(SleepTimer::EndOfQueue) => {
+                self.current_index.get() + 1 >= self.queue.borrow().len() as i32
+            }
+            _ => false,
+        }
+    }
+
+    /// Songs in the queue, other than the one playing, not heard since the sleep timer started
+    fn unheard_songs(&self) -> usize {
+        let current = self.current_item().map(|current| current.video_id);
+        let heard = self.sleep_heard.borrow();
+        self.queue
+            .borrow()
+            .iter()
+            .filter(|item| Some(&item.video_id) != current.as_ref())
+            .filter(|item| !heard.contains(&item.video_id) && self.is_playable(&item.video_id))
+            .count()
+    }
+
+    /// Pause, and put the volume back for when playback is resumed
+    fn finish_sleep_timer(&self) {
+        if self.is_playing() {
+            self.pause();
+        } else {
+            // The song already ended on its own
+            self.stop_visualizer();
+            self.set_play_icons(ICON_PLAY);
+        }
+        self.set_sleep_timer(None);
+    }
+
     pub fn set_mini_player_widget(&self, widget: gtk4::Box) {
         *self.mini_player.borrow_mut() = Some(widget);
     }
@@ -343,7 +951,7 @@ impl PlaybackController {
     }
 
     fn start_progress_polling(&self) {
-        let media = self.media.clone();
+        let backend = self.backend.clone();
         let media_token = self.media_token.clone();
         let token = media_token.get();
         let current_time = self.current_time.clone();
@@ -354,19 +962,20 @@ impl PlaybackController {
         let lyrics_rows = self.lyrics_rows.clone();
         let lyrics_scroller = self.lyrics_scroller.clone();
         let current_lyric_index = self.current_lyric_index.clone();
+        let sources = self.sources.clone();
+        let scrobble_pending = self.scrobble_pending.clone();
 
         glib::timeout_add_local(Duration::from_millis(POLL_INTERVAL_MS), move || {
             if media_token.get() != token {
                 return ControlFlow::Break;
             }
 
-            let binding = media.borrow();
-            let Some(media_file) = binding.as_ref() else {
-                return ControlFlow::Continue;
-            };
+            let duration = backend.duration();
+            let timestamp = backend.position();
 
-            let duration = media_file.duration();
-            let timestamp = media_file.timestamp();
+            if duration > 0 && timestamp >= scrobble_point(duration) {
+                submit_scrobble(&sources, &scrobble_pending);
+            }
 
             if duration > 0 {
                 let duration_secs = duration as f64 / 1_000_000.0;
@@ -417,10 +1026,6 @@ impl PlaybackController {
         // Enable controls after loading
         self.enable_controls();
         
-        if let Some(media) = self.media.borrow_mut().take() {
-            media.pause();
-        }
-
         let token = self.media_token.get().saturating_add(1);
         self.media_token.set(token);
 
@@ -443,9 +1048,18 @@ impl PlaybackController {
                         .or_else(|| fallback_thumbnail.map(String::from)),
                 };
                 let _ = db.add_recent_play(&song);
+                cache_stream_info(db, &current.video_id, info);
             }
         }
 
+        // Servers show the song as playing now, and count the play once enough of it is heard
+        *self.scrobble_pending.borrow_mut() = self.current_item().map(|current| current.video_id);
+        if let (Some(sources), Some(current)) = (self.sources.borrow().clone(), self.current_item()) {
+            std::thread::spawn(move || {
+                let _ = sources.now_playing(&current.video_id);
+            });
+        }
+
         if let Some(ref progress) = *self.progress.borrow() {
             progress.set_sensitive(false);
             progress.set_value(0.0);
@@ -458,21 +1072,36 @@ impl PlaybackController {
         let cover_token = self.cover_token.get().saturating_add(1);
         self.cover_token.set(cover_token);
 
-        let file = gtk4::gio::File::for_uri(&info.url);
-        let media = gtk4::MediaFile::for_file(&file);
-
-        // Listen for when the song ends to auto-play next
-        let controller = self.clone();
-        let end_token = token;
-        media.connect_ended_notify(move |media| {
-            if media.is_ended() && controller.media_token.get() == end_token {
-                // Song ended, play next
-                controller.play_next();
-            }
+        // Prefer a local track or finished download over the network stream
+        let local_file = self.current_item().and_then(|current| {
+            self.database
+                .borrow()
+                .as_ref()
+                .and_then(|db| db.local_file(&current.video_id))
+        });
+        let uri = match local_file {
+            Some(path) => gtk4::gio::File::for_path(path).uri().to_string(),
+            None => info.url.clone(),
+        };
+        if let Err(error) = self.backend.load(&uri) {
+            self.has_media.set(false);
+            self.show_error(&error);
+            return;
+        }
+        self.has_media.set(true);
+
+        // A long track plays at the speed it was last listened to
+        let track_rate = self.current_item().and_then(|current| {
+            self.database
+                .borrow()
+                .as_ref()
+                .and_then(|db| db.get_track_rate(&current.video_id))
         });
+        self.track_rate.set(track_rate);
 
-        media.play();
-        *self.media.borrow_mut() = Some(media);
+        self.backend.set_volume(self.volume.get());
+        self.backend.set_rate(self.rate());
+        self.backend.play();
 
         // Start progress updates with new token
         self.start_progress_polling();
@@ -507,12 +1136,26 @@ impl PlaybackController {
         }
 
         let chosen_thumbnail = info.thumbnail_url.as_deref().or(fallback_thumbnail);
+        *self.current_thumbnail.borrow_mut() = chosen_thumbnail.map(String::from);
+        self.notify_changed(PlayerChange::Track);
+
+        if let Some(ref app) = *self.application.borrow() {
+            if self.database.borrow().as_ref().is_some_and(notifications_enabled) {
+                notify_track_change(
+                    app,
+                    &info.title,
+                    &info.artist,
+                    chosen_thumbnail,
+                    (self.media_token.clone(), self.media_token.get()),
+                );
+            }
+        }
 
         // Update main cover with color callback
         if let Some(ref cover) = *self.cover.borrow() {
             cover.set_from_file(Some(DEFAULT_COVER_PATH));
             if let Some(url) = chosen_thumbnail {
-                if url.starts_with("http://") || url.starts_with("https://") {
+                if is_image_url(url) {
                     let background = self.background.clone();
                     let progress = self.progress.clone();
                     let play_button = self.play_button.clone();
@@ -750,7 +1393,7 @@ impl PlaybackController {
         if let Some(ref cover) = *self.mini_cover.borrow() {
             cover.set_from_file(Some(DEFAULT_COVER_PATH));
             if let Some(url) = chosen_thumbnail {
-                if url.starts_with("http://") || url.starts_with("https://") {
+                if is_image_url(url) {
                     load_image_async(
                         cover.clone(),
                         url.to_string(),
@@ -761,6 +1404,36 @@ impl PlaybackController {
         }
     }
 
+    fn on_backend_event(&self, event: BackendEvent) {
+        match event {
+            BackendEvent::Ended => {
+                submit_scrobble(&self.sources, &self.scrobble_pending);
+                if let Some(current) = self.current_item() {
+                    self.sleep_heard.borrow_mut().insert(current.video_id);
+                }
+                if self.sleep_ends_with_song() {
+                    self.finish_sleep_timer();
+                    return;
+                }
+                if self.repeat.get() == RepeatMode::Track {
+                    // Every time round counts as another play
+                    *self.scrobble_pending.borrow_mut() =
+                        self.current_item().map(|current| current.video_id);
+                    self.backend.seek(0);
+                    self.backend.play();
+                    return;
+                }
+                // Song ended, play next
+                self.play_next();
+            }
+            BackendEvent::Playing(_) => self.notify_changed(PlayerChange::Status),
+            BackendEvent::Duration => self.notify_changed(PlayerChange::Track),
+            BackendEvent::Error(error) => self.show_error(&error),
+            // Moves to the system default when the chosen device is unplugged, and back when it returns
+            BackendEvent::OutputDevices => self.route_output(),
+        }
+    }
+
     pub fn show_loading(&self, message: &str) {
         if let Some(ref title) = *self.title.borrow() {
             title.set_text("Loading...");
@@ -831,6 +1504,9 @@ impl PlaybackController {
     }
 
     pub fn set_queue(&self, items: Vec<SearchResult>) {
+        if self.playlist_equalizer.borrow_mut().take().is_some() {
+            self.apply_equalizer();
+        }
         *self.queue.borrow_mut() = items;
         self.current_index.set(-1);
         self.queue_rows.borrow_mut().clear();
@@ -848,6 +1524,42 @@ impl PlaybackController {
                 self.queue_rows.borrow_mut().push(row);
             }
         }
+        self.update_queue_availability();
+    }
+
+    /// Add songs to the end of the queue without changing what is playing
+    pub fn append_to_queue(&self, items: Vec<SearchResult>) {
+        if let Some(ref queue_list) = *self.queue_list.borrow() {
+            for item in items.iter() {
+                let row = song_card_row(
+                    &item.title,
+                    &item.artist,
+                    &item.duration,
+                    item.thumbnail_url.as_deref(),
+                );
+                queue_list.append(&row);
+                self.queue_rows.borrow_mut().push(row);
+            }
+        }
+        self.queue.borrow_mut().extend(items);
+        self.update_queue_availability();
+    }
+
+    /// Offline, the queue only shows downloaded songs and local tracks
+    fn update_queue_availability(&self) {
+        let offline = self.is_offline();
+        let downloaded = match (offline, self.database.borrow().as_ref()) {
+            (true, Some(db)) => db.get_downloaded_ids(),
+            _ => HashSet::new(),
+        };
+        let queue = self.queue.borrow();
+        for (row, item) in self.queue_rows.borrow().iter().zip(queue.iter()) {
+            row.set_visible(
+                !offline
+                    || downloaded.contains(&item.video_id)
+                    || SongSource::of(&item.video_id) == SongSource::Local,
+            );
+        }
     }
 
     pub fn set_current_index(&self, index: usize) {
@@ -917,11 +1629,28 @@ impl PlaybackController {
         }
 
         let prev_index = self.current_index.get();
+        let len = queue.len() as i32;
         let mut index = prev_index;
         if index < 0 {
             index = 0;
+        } else if delta > 0 && self.shuffle.get() && len > 1 {
+            // Any other song in the queue
+            index = (index + glib::random_int_range(1, len)) % len;
+        } else if self.repeat.get() == RepeatMode::Queue {
+            index = (index + delta).rem_euclid(len);
         } else {
-            index = (index + delta).clamp(0, (queue.len() - 1) as i32);
+            index = (index + delta).clamp(0, len - 1);
+        }
+
+        // Offline, skip over songs that are not downloaded
+        let step = if delta < 0 { -1 } else { 1 };
+        while index != prev_index && !self.is_playable(&queue[index as usize].video_id) {
+            let next = index + step;
+            if next < 0 || next >= queue.len() as i32 {
+                index = prev_index.max(0);
+                break;
+            }
+            index = next;
         }
         self.current_index.set(index);
         drop(queue);
@@ -932,31 +1661,28 @@ impl PlaybackController {
     }
 
     pub fn toggle_play_pause(&self) {
-        let binding = self.media.borrow();
-        let Some(media) = binding.as_ref() else {
+        if !self.has_media() {
             return;
-        };
+        }
 
-        if media.is_playing() {
-            media.pause();
+        if self.backend.is_playing() {
+            self.backend.pause();
             self.stop_visualizer();
-            // Update both play buttons to play icon
-            if let Some(ref play_button) = *self.play_button.borrow() {
-                play_button.set_icon_name("media-playback-start-symbolic");
-            }
-            if let Some(ref play_button) = *self.mini_play_button.borrow() {
-                play_button.set_icon_name("media-playback-start-symbolic");
-            }
+            self.set_play_icons(ICON_PLAY);
         } else {
-            media.play();
+            self.backend.play();
             self.start_visualizer();
-            // Update both play buttons to pause icon
-            if let Some(ref play_button) = *self.play_button.borrow() {
-                play_button.set_icon_name("media-playback-pause-symbolic");
-            }
-            if let Some(ref play_button) = *self.mini_play_button.borrow() {
-                play_button.set_icon_name("media-playback-pause-symbolic");
-            }
+            self.set_play_icons(ICON_PAUSE);
+        }
+    }
+
+    /// Show `icon` on both play buttons
+    fn set_play_icons(&self, icon: &str) {
+        if let Some(ref play_button) = *self.play_button.borrow() {
+            play_button.set_icon_name(icon);
+        }
+        if let Some(ref play_button) = *self.mini_play_button.borrow() {
+            play_button.set_icon_name(icon);
         }
     }
 
@@ -977,6 +1703,23 @@ impl PlaybackController {
     }
 }
 
+/// Position in microseconds after which a song counts as played
+fn scrobble_point(duration: i64) -> i64 {
+    (duration / 2).min(SCROBBLE_AFTER_SECS * 1_000_000)
+}
+
+/// Report the pending song as played, once
+fn submit_scrobble(sources: &RefCell<Option<Sources>>, pending: &RefCell<Option<String>>) {
+    let Some(uri) = pending.borrow_mut().take() else {
+        return;
+    };
+    if let Some(sources) = sources.borrow().clone() {
+        std::thread::spawn(move || {
+            let _ = sources.scrobble(&uri);
+        });
+    }
+}
+
 fn update_lyrics_position(
     seconds: f64,
     lines: &Rc<RefCell<Vec<LyricLine>>>,
@@ -1083,3 +1826,309 @@ fn format_duration(seconds: i64) -> String {
     let secs = seconds % 60;
     format!("{}:{:02}", mins, secs)
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::playback::backend::{BackendCall, FakeBackend};
+
+    // glib timeouts go to the default main context, which only one thread may use at a time
+    static MAIN_CONTEXT: Mutex<()> = Mutex::new(());
+
+    pub(crate) fn with_controller(test: impl FnOnce(&PlaybackController, &FakeBackend)) {
+        let _lock = MAIN_CONTEXT
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let context = glib::MainContext::default();
+        let _owner = context.acquire().unwrap();
+
+        let backend = Rc::new(FakeBackend::default());
+        let controller = PlaybackController::with_backend(backend.clone());
+        test(&controller, &backend);
+    }
+
+    pub(crate) fn item(id: &str) -> SearchResult {
+        SearchResult {
+            video_id: format!("ytm:{}", id),
+            title: format!("Song {}", id),
+            artist: "Artist".to_string(),
+            duration: "3:00".to_string(),
+            thumbnail_url: None,
+        }
+    }
+
+    pub(crate) fn stream(id: &str) -> StreamInfo {
+        StreamInfo {
+            url: format!("https://stream.example/{}", id),
+            title: format!("Song {}", id),
+            artist: "Artist".to_string(),
+            thumbnail_url: None,
+            lyrics: None,
+        }
+    }
+
+    /// Queue `ids` and start the first, forgetting the calls that made
+    pub(crate) fn start_queue(controller: &PlaybackController, backend: &FakeBackend, ids: &[&str]) {
+        controller.set_queue(ids.iter().map(|id| item(id)).collect());
+        controller.set_current_index(0);
+        controller.play_stream(&stream(ids[0]), None);
+        backend.take_calls();
+    }
+
+    #[test]
+    fn play_stream_loads_and_plays_at_the_chosen_volume() {
+        with_controller(|controller, backend| {
+            controller.set_volume(0.4);
+            controller.set_queue(vec![item("a")]);
+            controller.set_current_index(0);
+            backend.take_calls();
+
+            controller.play_stream(&stream("a"), None);
+
+            assert_eq!(
+                backend.take_calls(),
+                [
+                    BackendCall::Load("https://stream.example/a".to_string()),
+                    BackendCall::Volume(0.4),
+                    BackendCall::Rate(1.0),
+                    BackendCall::Play,
+                ]
+            );
+            assert!(controller.has_media());
+            assert!(controller.is_playing());
+        });
+    }
+
+    #[test]
+    fn play_and_pause_reach_the_backend() {
+        with_controller(|controller, backend| {
+            start_queue(controller, backend, &["a"]);
+
+            controller.pause();
+            assert!(!controller.is_playing());
+            controller.play();
+            assert!(controller.is_playing());
+            assert_eq!(
+                backend.take_calls(),
+                [BackendCall::Pause, BackendCall::Play]
+            );
+        });
+    }
+
+    #[test]
+    fn ended_moves_to_the_next_song() {
+        with_controller(|controller, backend| {
+            let next_calls = Rc::new(Cell::new(0));
+            controller.set_next_callback(glib::clone!(
+                #[strong]
+                next_calls,
+                move || next_calls.set(next_calls.get() + 1)
+            ));
+            start_queue(controller, backend, &["a", "b"]);
+
+            backend.fire(BackendEvent::Ended);
+            assert_eq!(next_calls.get(), 1);
+
+            // Repeating the song plays it again instead
+            controller.set_repeat(RepeatMode::Track);
+            backend.fire(BackendEvent::Ended);
+            assert_eq!(next_calls.get(), 1);
+            assert_eq!(
+                backend.take_calls(),
+                [BackendCall::Seek(0), BackendCall::Play]
+            );
+        });
+    }
+
+    #[test]
+    fn a_song_counts_as_played_after_half_of_it_or_four_minutes() {
+        assert_eq!(scrobble_point(180_000_000), 90_000_000);
+        assert_eq!(
+            scrobble_point(3_600_000_000),
+            SCROBBLE_AFTER_SECS * 1_000_000
+        );
+    }
+
+    #[test]
+    fn a_song_is_reported_as_played_once_when_it_ends() {
+        with_controller(|controller, backend| {
+            start_queue(controller, backend, &["a", "b"]);
+            assert_eq!(
+                controller.scrobble_pending.borrow().as_deref(),
+                Some("ytm:a")
+            );
+
+            backend.fire(BackendEvent::Ended);
+            assert_eq!(*controller.scrobble_pending.borrow(), None);
+
+            // A repeated song counts again on its next time round
+            controller.set_repeat(RepeatMode::Track);
+            backend.fire(BackendEvent::Ended);
+            assert_eq!(
+                controller.scrobble_pending.borrow().as_deref(),
+                Some("ytm:a")
+            );
+        });
+    }
+
+    #[test]
+    fn songs_of_unknown_length_count_as_nothing_until_the_end_of_the_queue() {
+        with_controller(|controller, backend| {
+            let mut queue = vec![item("a"), item("b"), item("c")];
+            queue[1].duration = String::new();
+            controller.set_queue(queue);
+            controller.set_current_index(0);
+            controller.play_stream(&stream("a"), None);
+            backend.set_progress(60_000_000, 180_000_000);
+
+            controller.set_sleep_timer(Some(SleepTimer::EndOfQueue));
+
+            assert_eq!(controller.sleep_remaining(), Some(300_000_000));
+        });
+    }
+
+    #[test]
+    fn a_shuffled_queue_ends_once_every_song_was_heard() {
+        with_controller(|controller, backend| {
+            let next_calls = Rc::new(Cell::new(0));
+            controller.set_next_callback(glib::clone!(
+                #[strong]
+                next_calls,
+                move || next_calls.set(next_calls.get() + 1)
+            ));
+            controller.set_shuffle(true);
+            start_queue(controller, backend, &["a", "b"]);
+            controller.set_current_index(1);
+            controller.play_stream(&stream("b"), None);
+            controller.set_sleep_timer(Some(SleepTimer::EndOfQueue));
+            assert_eq!(controller.sleep_remaining(), None);
+
+            // The last song in the queue ends, but "a" has not been heard yet
+            backend.fire(BackendEvent::Ended);
+            assert_eq!(next_calls.get(), 1);
+            assert_eq!(controller.sleep_timer(), Some(SleepTimer::EndOfQueue));
+
+            controller.set_current_index(0);
+            controller.play_stream(&stream("a"), None);
+            backend.set_progress(60_000_000, 180_000_000);
+            assert_eq!(controller.sleep_remaining(), Some(120_000_000));
+            backend.fire(BackendEvent::Ended);
+            assert_eq!(next_calls.get(), 1);
+            assert_eq!(controller.sleep_timer(), None);
+        });
+    }
+
+    #[test]
+    fn a_song_that_fails_to_load_is_not_played() {
+        with_controller(|controller, backend| {
+            controller.set_queue(vec![item("a")]);
+            controller.set_current_index(0);
+            backend.fail_next_load("No such stream");
+
+            controller.play_stream(&stream("a"), None);
+
+            assert!(!controller.has_media());
+            assert!(!controller.is_playing());
+            assert!(!backend.take_calls().contains(&BackendCall::Play));
+
+            // Nothing is loaded, so there is nothing to play or seek
+            controller.toggle_play_pause();
+            controller.seek(10.0);
+            assert!(backend.take_calls().is_empty());
+        });
+    }
+
+    #[test]
+    fn playback_errors_do_not_skip_the_song() {
+        with_controller(|controller, backend| {
+            let next_calls = Rc::new(Cell::new(0));
+            controller.set_next_callback(glib::clone!(
+                #[strong]
+                next_calls,
+                move || next_calls.set(next_calls.get() + 1)
+            ));
+            start_queue(controller, backend, &["a", "b"]);
+
+            backend.fire(BackendEvent::Error("Stream stopped".to_string()));
+
+            assert_eq!(next_calls.get(), 0);
+            assert_eq!(
+                controller.current_item().map(|item| item.video_id),
+                Some("ytm:a".to_string())
+            );
+        });
+    }
+
+    #[test]
+    fn seeking_stays_within_the_song() {
+        with_controller(|controller, backend| {
+            let seeks = Rc::new(RefCell::new(Vec::new()));
+            let jumps = Rc::new(RefCell::new(Vec::new()));
+            controller.connect_changed(glib::clone!(
+                #[strong]
+                seeks,
+                #[strong]
+                jumps,
+                move |change| match change {
+                    PlayerChange::Seeked(position) => seeks.borrow_mut().push(position),
+                    PlayerChange::Jumped(jump, position) =>
+                        jumps.borrow_mut().push((jump, position)),
+                    _ => {}
+                }
+            ));
+            start_queue(controller, backend, &["a"]);
+            backend.set_progress(50_000_000, 60_000_000);
+
+            controller.seek(30.0);
+            controller.seek_by(45.0);
+            controller.seek_by(-90.0);
+            controller.seek_to_fraction(0.5);
+
+            assert_eq!(
+                backend.take_calls(),
+                [
+                    BackendCall::Seek(30_000_000),
+                    BackendCall::Seek(60_000_000),
+                    BackendCall::Seek(0),
+                    BackendCall::Seek(30_000_000),
+                ]
+            );
+            assert_eq!(*seeks.borrow(), [30_000_000, 60_000_000, 0, 30_000_000]);
+            // Only the keyboard and scroll seeks are pointed out
+            assert_eq!(
+                *jumps.borrow(),
+                [
+                    (SeekJump::By(45), 60_000_000),
+                    (SeekJump::By(-90), 0),
+                    (SeekJump::ToPercent(50), 30_000_000),
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn volume_is_clamped_and_kept_for_the_next_song() {
+        with_controller(|controller, backend| {
+            start_queue(controller, backend, &["a", "b"]);
+
+            controller.set_volume(1.5);
+            controller.set_volume(-0.5);
+            controller.set_volume(0.25);
+            assert_eq!(
+                backend.take_calls(),
+                [
+                    BackendCall::Volume(1.0),
+                    BackendCall::Volume(0.0),
+                    BackendCall::Volume(0.25),
+                ]
+            );
+
+            controller.set_current_index(1);
+            controller.play_stream(&stream("b"), None);
+            assert!(backend.take_calls().contains(&BackendCall::Volume(0.25)));
+            assert_eq!(controller.volume(), 0.25);
+        });
+    }
+}
@@ -0,0 +1,428 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, mpsc};
+use std::time::{Duration, Instant};
+
+use glib::ControlFlow;
+use reqwest::StatusCode;
+use reqwest::blocking::Client;
+use reqwest::header::{CONTENT_TYPE, RANGE};
+
+use crate::api::StreamInfo;
+use crate::config::{MAX_CONCURRENT_DOWNLOADS, POLL_INTERVAL_MS};
+use crate::sources::Sources;
+use crate::storage::{Database, DownloadStatus, Song, SongSource};
+
+use super::offline::{OfflineMode, cache_stream_info};
+
+/// How often a running download reports its progress
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// How long a download may wait for the server to answer or send more bytes before it fails
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+enum DownloadEvent {
+    Progress {
+        video_id: String,
+        downloaded: i64,
+        total: Option<i64>,
+    },
+    Finished {
+        video_id: String,
+        path: PathBuf,
+        size: i64,
+        info: StreamInfo,
+    },
+    /// The worker stopped because the download was paused or removed
+    Stopped {
+        video_id: String,
+        downloaded: i64,
+    },
+    Failed {
+        video_id: String,
+        error: String,
+    },
+}
+
+/// Fetches songs to the audio directory in the background, a few at a time.
+/// The database holds the queue, so downloads survive restarts.
+/// Nothing new starts while offline; queued downloads wait until the network is back.
+#[derive(Clone)]
+pub struct DownloadManager {
+    database: Database,
+    offline: OfflineMode,
+    sources: Sources,
+    /// Stop flags of the downloads that have a worker thread
+    running: Rc<RefCell<HashMap<String, Arc<AtomicBool>>>>,
+    sender: mpsc::Sender<DownloadEvent>,
+    on_changed: Rc<RefCell<Vec<Box<dyn Fn()>>>>,
+    on_progress: Rc<RefCell<Vec<Box<dyn Fn(&str, i64, Option<i64>)>>>>,
+}
+
+impl DownloadManager {
+    pub fn new(database: Database, offline: OfflineMode, sources: Sources) -> Self {
+        let (sender, receiver) = mpsc::channel::<DownloadEvent>();
+        let manager = Self {
+            database,
+            offline,
+            sources,
+            running: Rc::new(RefCell::new(HashMap::new())),
+            sender,
+            on_changed: Rc::new(RefCell::new(Vec::new())),
+            on_progress: Rc::new(RefCell::new(Vec::new())),
+        };
+
+        // Poll for worker events
+        glib::timeout_add_local(
+            Duration::from_millis(POLL_INTERVAL_MS),
+            glib::clone!(
+                #[strong]
+                manager,
+                move || {
+                    while let Ok(event) = receiver.try_recv() {
+                        manager.handle_event(event);
+                    }
+                    ControlFlow::Continue
+                }
+            ),
+        );
+
+        manager.offline.connect_changed(glib::clone!(
+            #[strong]
+            manager,
+            move |offline| {
+                if !offline {
+                    manager.start_queued();
+                    manager.notify_changed();
+                }
+            }
+        ));
+
+        // Resume whatever was still queued when the app last quit
+        manager.start_queued();
+        manager
+    }
+
+    /// Call `callback` when a download is added, removed or changes state
+    pub fn connect_changed(&self, callback: impl Fn() + 'static) {
+        self.on_changed.borrow_mut().push(Box::new(callback));
+    }
+
+    /// Call `callback` with the video ID, bytes written and total size while a download runs
+    pub fn connect_progress(&self, callback: impl Fn(&str, i64, Option<i64>) + 'static) {
+        self.on_progress.borrow_mut().push(Box::new(callback));
+    }
+
+    /// Queue songs for download; returns how many were not already downloaded or queued.
+    /// Local tracks are skipped since they are on disk already.
+    pub fn download(&self, songs: &[Song]) -> Result<usize, String> {
+        let songs: Vec<Song> = songs
+            .iter()
+            .filter(|song| song.source() != SongSource::Local)
+            .cloned()
+            .collect();
+        let queued = self.database.queue_downloads(&songs)?;
+        self.start_queued();
+        self.notify_changed();
+        Ok(queued)
+    }
+
+    /// Stop a queued or running download, keeping what was written so far
+    pub fn pause(&self, video_id: &str) -> Result<(), String> {
+        self.database
+            .set_download_status(video_id, DownloadStatus::Paused)?;
+        if let Some(stop) = self.running.borrow().get(video_id) {
+            stop.store(true, Ordering::Relaxed);
+        }
+        self.start_queued();
+        self.notify_changed();
+        Ok(())
+    }
+
+    /// Queue a paused or failed download again
+    pub fn resume(&self, video_id: &str) -> Result<(), String> {
+        self.database
+            .set_download_status(video_id, DownloadStatus::Queued)?;
+        self.start_queued();
+        self.notify_changed();
+        Ok(())
+    }
+
+    /// Cancel a download and delete its files
+    pub fn remove(&self, video_id: &str) -> Result<(), String> {
+        let download = self.database.delete_download(video_id)?;
+        if let Some(path) = download.and_then(|download| download.file_path) {
+            let _ = std::fs::remove_file(path);
+        }
+        // A running worker still holds the partial file; it is removed once the worker stops
+        match self.running.borrow().get(video_id) {
+            Some(stop) => stop.store(true, Ordering::Relaxed),
+            None => {
+                let _ = std::fs::remove_file(partial_path(video_id));
+            }
+        }
+        self.start_queued();
+        self.notify_changed();
+        Ok(())
+    }
+
+    /// Start workers for queued downloads until the concurrency limit is reached
+    fn start_queued(&self) {
+        if self.offline.is_offline() {
+            return;
+        }
+        let Ok(downloads) = self.database.get_downloads() else {
+            return;
+        };
+
+        for download in downloads {
+            if self.running.borrow().len() >= MAX_CONCURRENT_DOWNLOADS {
+                break;
+            }
+            let video_id = download.song.video_id;
+            if download.status != DownloadStatus::Queued
+                || self.running.borrow().contains_key(&video_id)
+            {
+                continue;
+            }
+            if self
+                .database
+                .set_download_status(&video_id, DownloadStatus::Downloading)
+                .is_err()
+            {
+                continue;
+            }
+
+            let stop = Arc::new(AtomicBool::new(false));
+            self.running
+                .borrow_mut()
+                .insert(video_id.clone(), Arc::clone(&stop));
+
+            let sender = self.sender.clone();
+            let sources = self.sources.clone();
+            std::thread::spawn(move || {
+                let event = match fetch_audio(&sources, &video_id, &stop, &sender) {
+                    Ok(Some((path, size, info))) => DownloadEvent::Finished {
+                        video_id,
+                        path,
+                        size,
+                        info,
+                    },
+                    Ok(None) => {
+                        let downloaded = file_size(&partial_path(&video_id));
+                        DownloadEvent::Stopped {
+                            video_id,
+                            downloaded,
+                        }
+                    }
+                    Err(error) => DownloadEvent::Failed { video_id, error },
+                };
+                let _ = sender.send(event);
+            });
+        }
+    }
+
+    fn handle_event(&self, event: DownloadEvent) {
+        match event {
+            DownloadEvent::Progress {
+                video_id,
+                downloaded,
+                total,
+            } => {
+                let _ = self
+                    .database
+                    .set_download_progress(&video_id, downloaded, total);
+                for callback in self.on_progress.borrow().iter() {
+                    callback(&video_id, downloaded, total);
+                }
+                return;
+            }
+            DownloadEvent::Finished {
+                video_id,
+                path,
+                size,
+                info,
+            } => {
+                self.running.borrow_mut().remove(&video_id);
+                if self.was_removed(&video_id) {
+                    let _ = std::fs::remove_file(&path);
+                } else {
+                    let _ = self
+                        .database
+                        .finish_download(&video_id, &path.to_string_lossy(), size);
+                    // Keep the cover and lyrics for playing the download offline
+                    cache_stream_info(&self.database, &video_id, &info);
+                }
+            }
+            DownloadEvent::Stopped {
+                video_id,
+                downloaded,
+            } => {
+                self.running.borrow_mut().remove(&video_id);
+                if self.was_removed(&video_id) {
+                    let _ = std::fs::remove_file(partial_path(&video_id));
+                } else {
+                    let _ = self
+                        .database
+                        .set_download_progress(&video_id, downloaded, None);
+                }
+            }
+            DownloadEvent::Failed { video_id, error } => {
+                self.running.borrow_mut().remove(&video_id);
+                // A download removed while its worker ran leaves nothing to retry
+                if self.was_removed(&video_id) {
+                    let _ = std::fs::remove_file(partial_path(&video_id));
+                } else {
+                    let _ = self.database.fail_download(&video_id, &error);
+                }
+            }
+        }
+
+        self.start_queued();
+        self.notify_changed();
+    }
+
+    /// Whether the download was removed while its worker was running
+    fn was_removed(&self, video_id: &str) -> bool {
+        self.database
+            .get_download(video_id)
+            .ok()
+            .flatten()
+            .is_none()
+    }
+
+    fn notify_changed(&self) {
+        for callback in self.on_changed.borrow().iter() {
+            callback();
+        }
+    }
+}
+
+/// File a download is written to until it completes
+fn partial_path(video_id: &str) -> PathBuf {
+    Database::audio_dir().join(format!("{}.part", file_stem(video_id)))
+}
+
+/// Name for a download's files, as track URIs hold characters that don't belong in file names
+fn file_stem(video_id: &str) -> String {
+    video_id.replace([':', '/'], "_")
+}
+
+fn file_size(path: &Path) -> i64 {
+    std::fs::metadata(path)
+        .map(|meta| meta.len() as i64)
+        .unwrap_or(0)
+}
+
+/// File extension for the audio container the server sent
+fn audio_extension(content_type: &str) -> &'static str {
+    if content_type.starts_with("audio/webm") {
+        "webm"
+    } else if content_type.starts_with("audio/mp4") {
+        "m4a"
+    } else {
+        "audio"
+    }
+}
+
+/// Downloads the best audio stream of a track, resuming a partial file when there is one.
+/// Returns the finished file, its size and the stream's metadata, or None when stopped before the end.
+fn fetch_audio(
+    sources: &Sources,
+    video_id: &str,
+    stop: &AtomicBool,
+    sender: &mpsc::Sender<DownloadEvent>,
+) -> Result<Option<(PathBuf, i64, StreamInfo)>, String> {
+    let audio_dir = Database::audio_dir();
+    std::fs::create_dir_all(&audio_dir)
+        .map_err(|e| format!("Failed to create audio directory: {}", e))?;
+
+    // Stream URLs expire, so every attempt asks for a fresh one.
+    // The same format is picked each time, which keeps resumed bytes consistent.
+    let info = sources.resolve_stream(video_id)?;
+
+    let partial = partial_path(video_id);
+    let mut downloaded = file_size(&partial);
+
+    // The blocking client applies this to sending the request and to each read on its own,
+    // so a stalled server fails the download without cutting off a long one that keeps going
+    let client = Client::builder()
+        .timeout(READ_TIMEOUT)
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    let mut request = client.get(&info.url);
+    if downloaded > 0 {
+        request = request.header(RANGE, format!("bytes={}-", downloaded));
+    }
+    let mut response = request
+        .send()
+        .map_err(|e| format!("Network error: {}", e))?;
+
+    // A server that ignores the range sends the whole file again
+    let resumed = response.status() == StatusCode::PARTIAL_CONTENT;
+    if !resumed {
+        response = response
+            .error_for_status()
+            .map_err(|e| format!("Download failed: {}", e))?;
+        downloaded = 0;
+    }
+    let total = response
+        .content_length()
+        .map(|length| downloaded + length as i64);
+    let extension = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(audio_extension)
+        .unwrap_or("audio");
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(&partial)
+        .map_err(|e| format!("Failed to open {}: {}", partial.display(), e))?;
+
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    let mut last_report = Instant::now();
+    loop {
+        if stop.load(Ordering::Relaxed) {
+            return Ok(None);
+        }
+        let read = response
+            .read(&mut buffer)
+            .map_err(|e| format!("Network error: {}", e))?;
+        if read == 0 {
+            break;
+        }
+        file.write_all(&buffer[..read])
+            .map_err(|e| format!("Failed to write {}: {}", partial.display(), e))?;
+        downloaded += read as i64;
+
+        if last_report.elapsed() >= PROGRESS_INTERVAL {
+            last_report = Instant::now();
+            let _ = sender.send(DownloadEvent::Progress {
+                video_id: video_id.to_string(),
+                downloaded,
+                total,
+            });
+        }
+    }
+
+    if total.is_some_and(|total| downloaded < total) {
+        return Err("Download ended early".to_string());
+    }
+
+    let path = audio_dir.join(format!("{}.{}", file_stem(video_id), extension));
+    std::fs::rename(&partial, &path)
+        .map_err(|e| format!("Failed to save {}: {}", path.display(), e))?;
+    Ok(Some((path, downloaded, info)))
+}
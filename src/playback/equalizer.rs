@@ -0,0 +1,157 @@
+use serde_json::{Value, json};
+
+use crate::config::{EQ_MAX_GAIN, EQ_MIN_GAIN};
+use crate::storage::Database;
+
+/// Setting holding the preset chosen on the equalizer page, with its gains
+const EQUALIZER_SETTING: &str = "equalizer";
+
+pub const EQ_BANDS: usize = 10;
+
+/// Gain of each band in dB, lowest frequency first
+pub type EqGains = [f64; EQ_BANDS];
+
+/// Centre frequency of each band, as GStreamer's `equalizer-10bands` places them
+pub const EQ_BAND_LABELS: [&str; EQ_BANDS] = [
+    "29", "59", "119", "237", "474", "947", "1.9k", "3.8k", "7.5k", "15k",
+];
+
+/// Name given to gains moved by hand that aren't saved as a preset
+pub const CUSTOM_PRESET: &str = "Custom";
+
+const FLAT_PRESET: &str = "Flat";
+
+const BUILTIN_PRESETS: &[(&str, EqGains)] = &[
+    (FLAT_PRESET, [0.0; EQ_BANDS]),
+    (
+        "Bass Boost",
+        [6.0, 5.0, 4.0, 2.5, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    ),
+    (
+        "Treble Boost",
+        [0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 2.5, 4.0, 5.0, 6.0],
+    ),
+    (
+        "Vocal",
+        [-2.0, -1.5, -1.0, 1.0, 3.0, 3.5, 3.0, 1.5, 0.0, -1.0],
+    ),
+    (
+        "Rock",
+        [4.5, 3.5, 2.0, -0.5, -1.5, -0.5, 1.5, 3.0, 4.0, 4.5],
+    ),
+    (
+        "Electronic",
+        [4.5, 4.0, 1.5, 0.0, -2.0, 1.5, 0.5, 1.5, 4.0, 5.0],
+    ),
+    (
+        "Classical",
+        [3.5, 3.0, 2.5, 2.0, -1.0, -1.0, 0.0, 2.0, 3.0, 3.5],
+    ),
+    (
+        "Spoken Word",
+        [-4.0, -3.0, -1.0, 1.0, 3.0, 4.0, 3.5, 2.0, 0.0, -2.0],
+    ),
+];
+
+/// A named set of band gains, either built in or saved by the user
+#[derive(Debug, Clone, PartialEq)]
+pub struct EqPreset {
+    pub name: String,
+    pub gains: EqGains,
+    pub builtin: bool,
+}
+
+impl EqPreset {
+    /// No change to the sound
+    pub fn flat() -> Self {
+        Self {
+            name: FLAT_PRESET.to_string(),
+            gains: [0.0; EQ_BANDS],
+            builtin: true,
+        }
+    }
+
+    /// Gains moved by hand, not saved under a name
+    pub fn custom(gains: EqGains) -> Self {
+        Self {
+            name: CUSTOM_PRESET.to_string(),
+            gains,
+            builtin: false,
+        }
+    }
+
+    pub fn is_flat(&self) -> bool {
+        self.gains.iter().all(|gain| *gain == 0.0)
+    }
+}
+
+/// Built-in presets followed by the user's own
+pub fn equalizer_presets(database: &Database) -> Vec<EqPreset> {
+    let mut presets: Vec<EqPreset> = BUILTIN_PRESETS
+        .iter()
+        .map(|(name, gains)| EqPreset {
+            name: name.to_string(),
+            gains: *gains,
+            builtin: true,
+        })
+        .collect();
+    match database.get_eq_presets() {
+        Ok(saved) => presets.extend(saved.into_iter().map(|(name, gains)| EqPreset {
+            name,
+            gains: gains_from_slice(&gains),
+            builtin: false,
+        })),
+        Err(e) => eprintln!("{}", e),
+    }
+    presets
+}
+
+/// The built-in or saved preset with this name
+pub fn find_preset(database: &Database, name: &str) -> Option<EqPreset> {
+    equalizer_presets(database)
+        .into_iter()
+        .find(|preset| preset.name == name)
+}
+
+/// Built-in names can't be saved over or deleted
+pub fn is_builtin_preset(name: &str) -> bool {
+    BUILTIN_PRESETS
+        .iter()
+        .any(|(builtin, _)| builtin.eq_ignore_ascii_case(name))
+}
+
+/// The preset last chosen on the equalizer page; flat if there is none
+pub fn saved_equalizer(database: &Database) -> EqPreset {
+    let Some(value) = database
+        .get_setting(EQUALIZER_SETTING)
+        .and_then(|data| serde_json::from_str::<Value>(&data).ok())
+    else {
+        return EqPreset::flat();
+    };
+    let name = value["name"].as_str().unwrap_or(CUSTOM_PRESET);
+    let gains: Vec<f64> = value["gains"]
+        .as_array()
+        .map(|gains| gains.iter().filter_map(Value::as_f64).collect())
+        .unwrap_or_default();
+    EqPreset {
+        name: name.to_string(),
+        gains: gains_from_slice(&gains),
+        builtin: is_builtin_preset(name),
+    }
+}
+
+/// Remember the preset chosen on the equalizer page, keeping its gains
+/// so it still sounds the same if the preset is deleted
+pub fn save_equalizer(database: &Database, preset: &EqPreset) -> Result<(), String> {
+    let value = json!({ "name": preset.name, "gains": preset.gains });
+    database.set_setting(EQUALIZER_SETTING, &value.to_string())
+}
+
+/// Gains for each band, missing bands flat and each kept within range
+pub fn gains_from_slice(gains: &[f64]) -> EqGains {
+    let mut bands = [0.0; EQ_BANDS];
+    for (band, gain) in bands.iter_mut().zip(gains) {
+        *band = gain.clamp(EQ_MIN_GAIN, EQ_MAX_GAIN);
+    }
+    bands
+}
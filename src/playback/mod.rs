@@ -1,3 +1,22 @@
+mod backend;
 mod controller;
+mod downloads;
+mod equalizer;
+mod mpris;
+mod notifications;
+mod offline;
+mod output;
 
-pub use controller::PlaybackController;
+pub use backend::OutputDevice;
+pub use controller::{
+    PlaybackController, PlayerChange, RepeatMode, SeekJump, SleepTimer, seek_step, set_seek_step,
+};
+pub use downloads::DownloadManager;
+pub use equalizer::{
+    CUSTOM_PRESET, EQ_BAND_LABELS, EQ_BANDS, EqGains, EqPreset, equalizer_presets, find_preset,
+    is_builtin_preset, save_equalizer, saved_equalizer,
+};
+pub use mpris::start_mpris;
+pub use notifications::{notifications_enabled, set_notifications_enabled};
+pub use offline::OfflineMode;
+pub use output::{save_output_device, saved_output_device};
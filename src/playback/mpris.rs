@@ -0,0 +1,487 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use gtk4::gio;
+use gtk4::prelude::*;
+use libadwaita as adw;
+
+use crate::config::{APP_ID, APP_NAME, MAX_PLAYBACK_RATE, MIN_PLAYBACK_RATE};
+use crate::formats::parse_duration;
+use crate::ui::components::shareable_image_url;
+
+use super::controller::{PlaybackController, PlayerChange, RepeatMode};
+
+/// Well-known name desktop media controls look for
+const BUS_NAME: &str = "org.mpris.MediaPlayer2.musika";
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+const ROOT_INTERFACE: &str = "org.mpris.MediaPlayer2";
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+
+const INTROSPECTION_XML: &str = r#"
+<node>
+  <interface name="org.mpris.MediaPlayer2">
+    <method name="Raise"/>
+    <method name="Quit"/>
+    <property name="CanQuit" type="b" access="read"/>
+    <property name="CanRaise" type="b" access="read"/>
+    <property name="HasTrackList" type="b" access="read"/>
+    <property name="Identity" type="s" access="read"/>
+    <property name="DesktopEntry" type="s" access="read"/>
+    <property name="SupportedUriSchemes" type="as" access="read"/>
+    <property name="SupportedMimeTypes" type="as" access="read"/>
+  </interface>
+  <interface name="org.mpris.MediaPlayer2.Player">
+    <method name="Next"/>
+    <method name="Previous"/>
+    <method name="Pause"/>
+    <method name="PlayPause"/>
+    <method name="Stop"/>
+    <method name="Play"/>
+    <method name="Seek">
+      <arg name="Offset" type="x" direction="in"/>
+    </method>
+    <method name="SetPosition">
+      <arg name="TrackId" type="o" direction="in"/>
+      <arg name="Position" type="x" direction="in"/>
+    </method>
+    <method name="OpenUri">
+      <arg name="Uri" type="s" direction="in"/>
+    </method>
+    <signal name="Seeked">
+      <arg name="Position" type="x"/>
+    </signal>
+    <property name="PlaybackStatus" type="s" access="read"/>
+    <property name="LoopStatus" type="s" access="readwrite"/>
+    <property name="Rate" type="d" access="readwrite"/>
+    <property name="Shuffle" type="b" access="readwrite"/>
+    <property name="Metadata" type="a{sv}" access="read"/>
+    <property name="Volume" type="d" access="readwrite"/>
+    <property name="Position" type="x" access="read"/>
+    <property name="MinimumRate" type="d" access="read"/>
+    <property name="MaximumRate" type="d" access="read"/>
+    <property name="CanGoNext" type="b" access="read"/>
+    <property name="CanGoPrevious" type="b" access="read"/>
+    <property name="CanPlay" type="b" access="read"/>
+    <property name="CanPause" type="b" access="read"/>
+    <property name="CanSeek" type="b" access="read"/>
+    <property name="CanControl" type="b" access="read"/>
+  </interface>
+</node>
+"#;
+
+/// Publish the player on the session bus, so desktop media controls and media keys can drive it
+pub fn start_mpris(app: &adw::Application, controller: &PlaybackController) {
+    let node = match gio::DBusNodeInfo::for_xml(INTROSPECTION_XML) {
+        Ok(node) => node,
+        Err(error) => {
+            eprintln!("Failed to parse MPRIS interface: {}", error);
+            return;
+        }
+    };
+
+    let _ = gio::bus_own_name(
+        gio::BusType::Session,
+        BUS_NAME,
+        gio::BusNameOwnerFlags::NONE,
+        glib::clone!(
+            #[weak]
+            app,
+            #[strong]
+            controller,
+            move |connection, _| register(&connection, &node, &app, &controller)
+        ),
+        |_, _| {},
+        |_, name| eprintln!("Lost the D-Bus name {}", name),
+    );
+}
+
+fn register(
+    connection: &gio::DBusConnection,
+    node: &gio::DBusNodeInfo,
+    app: &adw::Application,
+    controller: &PlaybackController,
+) {
+    for name in [ROOT_INTERFACE, PLAYER_INTERFACE] {
+        let Some(interface) = node.lookup_interface(name) else {
+            continue;
+        };
+        let registration = connection
+            .register_object(OBJECT_PATH, &interface)
+            .method_call(glib::clone!(
+                #[weak]
+                app,
+                #[strong]
+                controller,
+                move |_, _, _, _, method, params, invocation| {
+                    call_method(&app, &controller, method, &params, invocation);
+                }
+            ))
+            .property(glib::clone!(
+                #[strong]
+                controller,
+                move |_, _, _, _, property| get_property(&controller, property)
+            ))
+            .set_property(glib::clone!(
+                #[strong]
+                controller,
+                move |_, _, _, _, property, value| set_property(&controller, property, &value)
+            ))
+            .build();
+        if let Err(error) = registration {
+            eprintln!("Failed to register {}: {}", name, error);
+        }
+    }
+
+    controller.connect_changed(glib::clone!(
+        #[strong]
+        connection,
+        #[strong]
+        controller,
+        move |change| {
+            if let PlayerChange::Seeked(position) = change {
+                let params = glib::Variant::tuple_from_iter([position.to_variant()]);
+                let _ = connection.emit_signal(
+                    None,
+                    OBJECT_PATH,
+                    PLAYER_INTERFACE,
+                    "Seeked",
+                    Some(&params),
+                );
+            }
+            let changed = changed_properties(change);
+            if !changed.is_empty() {
+                emit_properties_changed(&connection, &controller, changed);
+            }
+        }
+    ));
+}
+
+/// Player properties whose values a change affects
+fn changed_properties(change: PlayerChange) -> &'static [&'static str] {
+    match change {
+        // Whether there is a song to go on from or seek in comes and goes with the song
+        PlayerChange::Track => &[
+            "Metadata",
+            "CanGoNext",
+            "CanGoPrevious",
+            "CanPlay",
+            "CanPause",
+            "CanSeek",
+        ],
+        PlayerChange::Status => &["PlaybackStatus"],
+        PlayerChange::Options => &["LoopStatus", "Shuffle", "Volume", "Rate"],
+        PlayerChange::Seeked(_)
+        | PlayerChange::Jumped(..)
+        | PlayerChange::SleepTimer
+        | PlayerChange::Equalizer
+        | PlayerChange::OutputDevices => &[],
+    }
+}
+
+fn emit_properties_changed(
+    connection: &gio::DBusConnection,
+    controller: &PlaybackController,
+    properties: &[&str],
+) {
+    let changed = glib::VariantDict::new(None);
+    for property in properties {
+        changed.insert_value(property, &get_property(controller, property));
+    }
+    let params = glib::Variant::tuple_from_iter([
+        PLAYER_INTERFACE.to_variant(),
+        changed.end(),
+        Vec::<String>::new().to_variant(),
+    ]);
+    let _ = connection.emit_signal(
+        None,
+        OBJECT_PATH,
+        PROPERTIES_INTERFACE,
+        "PropertiesChanged",
+        Some(&params),
+    );
+}
+
+fn call_method(
+    app: &adw::Application,
+    controller: &PlaybackController,
+    method: &str,
+    params: &glib::Variant,
+    invocation: gio::DBusMethodInvocation,
+) {
+    match method {
+        "Raise" => {
+            if let Some(window) = app.active_window() {
+                window.present();
+            }
+        }
+        "Quit" => app.quit(),
+        "Next" => controller.play_next(),
+        "Previous" => controller.play_previous(),
+        "Pause" => controller.pause(),
+        "Play" => controller.play(),
+        "PlayPause" => controller.toggle_play_pause(),
+        "Stop" => {
+            controller.pause();
+            controller.seek(0.0);
+        }
+        "Seek" => {
+            if let Some((offset,)) = params.get::<(i64,)>() {
+                let position = (controller.position() + offset).max(0);
+                let duration = controller.duration();
+                if duration > 0 && position > duration {
+                    controller.play_next();
+                } else {
+                    controller.seek(position as f64 / 1_000_000.0);
+                }
+            }
+        }
+        "SetPosition" => {
+            // Requests for a song that is no longer playing are ignored, as the spec asks
+            if let Some((track_id, position)) = params.get::<(glib::variant::ObjectPath, i64)>() {
+                let duration = controller.duration();
+                if track_id.as_str() == track_id_of(controller).as_str()
+                    && position >= 0
+                    && (duration == 0 || position <= duration)
+                {
+                    controller.seek(position as f64 / 1_000_000.0);
+                }
+            }
+        }
+        _ => {
+            invocation.return_dbus_error(
+                "org.mpris.MediaPlayer2.Error.NotSupported",
+                &format!("{} is not supported", method),
+            );
+            return;
+        }
+    }
+    invocation.return_value(None);
+}
+
+fn get_property(controller: &PlaybackController, property: &str) -> glib::Variant {
+    match property {
+        "CanQuit" | "CanRaise" => true.to_variant(),
+        "HasTrackList" => false.to_variant(),
+        "Identity" => APP_NAME.to_variant(),
+        "DesktopEntry" => APP_ID.to_variant(),
+        "SupportedUriSchemes" | "SupportedMimeTypes" => Vec::<String>::new().to_variant(),
+        "PlaybackStatus" => {
+            let status = if controller.is_playing() {
+                "Playing"
+            } else if controller.has_media() {
+                "Paused"
+            } else {
+                "Stopped"
+            };
+            status.to_variant()
+        }
+        "LoopStatus" => {
+            let status = match controller.repeat() {
+                RepeatMode::Off => "None",
+                RepeatMode::Queue => "Playlist",
+                RepeatMode::Track => "Track",
+            };
+            status.to_variant()
+        }
+        "Rate" => controller.rate().to_variant(),
+        // Without a backend that changes speed, only normal speed is offered
+        "MinimumRate" if controller.can_change_rate() => MIN_PLAYBACK_RATE.to_variant(),
+        "MaximumRate" if controller.can_change_rate() => MAX_PLAYBACK_RATE.to_variant(),
+        "MinimumRate" | "MaximumRate" => 1.0f64.to_variant(),
+        "Shuffle" => controller.shuffle().to_variant(),
+        "Metadata" => metadata(controller),
+        "Volume" => controller.volume().to_variant(),
+        "Position" => controller.position().to_variant(),
+        "CanGoNext" | "CanGoPrevious" | "CanPlay" | "CanPause" => {
+            controller.current_item().is_some().to_variant()
+        }
+        "CanSeek" => controller.has_media().to_variant(),
+        "CanControl" => true.to_variant(),
+        // gio only asks for properties named in the introspection data
+        _ => ().to_variant(),
+    }
+}
+
+fn set_property(controller: &PlaybackController, property: &str, value: &glib::Variant) -> bool {
+    match property {
+        "LoopStatus" => {
+            let mode = match value.get::<String>().as_deref() {
+                Some("None") => RepeatMode::Off,
+                Some("Playlist") => RepeatMode::Queue,
+                Some("Track") => RepeatMode::Track,
+                _ => return false,
+            };
+            controller.set_repeat(mode);
+            true
+        }
+        "Shuffle" => value
+            .get::<bool>()
+            .map(|shuffle| controller.set_shuffle(shuffle))
+            .is_some(),
+        "Volume" => value
+            .get::<f64>()
+            .map(|volume| controller.set_volume(volume))
+            .is_some(),
+        "Rate" => match value.get::<f64>() {
+            // The spec treats a rate of 0 as a pause
+            Some(rate) if rate <= 0.0 => {
+                controller.pause();
+                true
+            }
+            Some(rate) => {
+                controller.set_rate(rate);
+                true
+            }
+            None => false,
+        },
+        _ => false,
+    }
+}
+
+/// The current song's `a{sv}` metadata, or only the no-track ID when nothing is queued
+fn metadata(controller: &PlaybackController) -> glib::Variant {
+    let dict = glib::VariantDict::new(None);
+    dict.insert_value("mpris:trackid", &track_id_of(controller).to_variant());
+
+    if let Some(item) = controller.current_item() {
+        dict.insert_value("xesam:title", &item.title.to_variant());
+        dict.insert_value("xesam:artist", &vec![item.artist.clone()].to_variant());
+
+        let length = match controller.duration() {
+            0 => parse_duration(&item.duration).unwrap_or(0) * 1_000_000,
+            duration => duration,
+        };
+        if length > 0 {
+            dict.insert_value("mpris:length", &length.to_variant());
+        }
+        let art = controller
+            .current_thumbnail()
+            .or(item.thumbnail_url)
+            .and_then(|url| shareable_image_url(&url));
+        if let Some(art) = art {
+            dict.insert_value("mpris:artUrl", &art.to_variant());
+        }
+    }
+
+    dict.end()
+}
+
+/// D-Bus object path naming the current song; the same song always gets the same path
+fn track_id_of(controller: &PlaybackController) -> glib::variant::ObjectPath {
+    let path = match controller.current_item() {
+        Some(item) => {
+            let mut hasher = DefaultHasher::new();
+            item.video_id.hash(&mut hasher);
+            format!("/io/nubit/musika/track/{:016x}", hasher.finish())
+        }
+        None => NO_TRACK.to_string(),
+    };
+    glib::variant::ObjectPath::try_from(path).expect("valid track object path")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::playback::controller::tests::{start_queue, with_controller};
+
+    #[test]
+    fn a_new_song_updates_what_can_be_done_with_it() {
+        let changed = changed_properties(PlayerChange::Track);
+        for property in ["Metadata", "CanGoNext", "CanPlay", "CanSeek"] {
+            assert!(changed.contains(&property), "{} is not updated", property);
+        }
+        assert!(changed_properties(PlayerChange::Seeked(0)).is_empty());
+    }
+
+    /// Read a player property the way a desktop media control does
+    fn get(client: &gio::DBusConnection, owner: &str, property: &str) -> glib::Variant {
+        client
+            .call_sync(
+                Some(owner),
+                OBJECT_PATH,
+                PROPERTIES_INTERFACE,
+                "Get",
+                Some(&(PLAYER_INTERFACE, property).to_variant()),
+                Some(glib::VariantTy::new("(v)").unwrap()),
+                gio::DBusCallFlags::NONE,
+                5000,
+                gio::Cancellable::NONE,
+            )
+            .unwrap()
+            .child_value(0)
+            .as_variant()
+            .unwrap()
+    }
+
+    #[test]
+    fn media_controls_read_the_song_and_toggle_playback_over_dbus() {
+        let bus = gio::TestDBus::new(gio::TestDBusFlags::NONE);
+        bus.up();
+        let address = bus.bus_address().unwrap().to_string();
+        let connect = |address: &str| {
+            gio::DBusConnection::for_address_sync(
+                address,
+                gio::DBusConnectionFlags::AUTHENTICATION_CLIENT
+                    | gio::DBusConnectionFlags::MESSAGE_BUS_CONNECTION,
+                None,
+                gio::Cancellable::NONE,
+            )
+            .unwrap()
+        };
+
+        with_controller(|controller, backend| {
+            start_queue(controller, backend, &["a"]);
+            let app = adw::Application::builder().application_id(APP_ID).build();
+            let node = gio::DBusNodeInfo::for_xml(INTROSPECTION_XML).unwrap();
+
+            // Calls are answered from the context the object was registered in
+            let context = glib::MainContext::new();
+            context
+                .with_thread_default(|| {
+                    let server = connect(&address);
+                    register(&server, &node, &app, controller);
+                    let owner = server.unique_name().unwrap().to_string();
+
+                    let client = std::thread::spawn(move || {
+                        let client = connect(&address);
+                        let metadata =
+                            glib::VariantDict::new(Some(&get(&client, &owner, "Metadata")));
+                        let title = metadata.lookup::<String>("xesam:title").unwrap();
+                        let before = get(&client, &owner, "PlaybackStatus").get::<String>();
+                        client
+                            .call_sync(
+                                Some(&owner),
+                                OBJECT_PATH,
+                                PLAYER_INTERFACE,
+                                "PlayPause",
+                                None,
+                                None,
+                                gio::DBusCallFlags::NONE,
+                                5000,
+                                gio::Cancellable::NONE,
+                            )
+                            .unwrap();
+                        let after = get(&client, &owner, "PlaybackStatus").get::<String>();
+                        (title, before, after)
+                    });
+                    while !client.is_finished() {
+                        while context.iteration(false) {}
+                        std::thread::sleep(Duration::from_millis(1));
+                    }
+                    let (title, before, after) = client.join().unwrap();
+
+                    assert_eq!(title.as_deref(), Some("Song a"));
+                    assert_eq!(before.as_deref(), Some("Playing"));
+                    assert_eq!(after.as_deref(), Some("Paused"));
+                    assert!(!controller.is_playing());
+                })
+                .unwrap();
+        });
+
+        bus.down();
+    }
+}
@@ -0,0 +1,62 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use gtk4::gio;
+use gtk4::prelude::*;
+use libadwaita as adw;
+
+use crate::storage::Database;
+use crate::ui::components::{is_image_url, load_image_bytes_async};
+
+const NOTIFICATIONS_SETTING: &str = "track_notifications";
+
+/// Each new song replaces the notification of the one before
+const NOTIFICATION_ID: &str = "now-playing";
+
+/// Whether a notification is shown when a song starts; on unless turned off
+pub fn notifications_enabled(database: &Database) -> bool {
+    database.get_setting(NOTIFICATIONS_SETTING).as_deref() != Some("0")
+}
+
+pub fn set_notifications_enabled(database: &Database, enabled: bool) -> Result<(), String> {
+    database.set_setting(NOTIFICATIONS_SETTING, if enabled { "1" } else { "0" })
+}
+
+/// Show the song that just started, unless the window is in front where it can be seen anyway.
+/// `token_guard` drops the notification if another song starts while the cover loads.
+pub(super) fn notify_track_change(
+    app: &adw::Application,
+    title: &str,
+    artist: &str,
+    thumbnail_url: Option<&str>,
+    token_guard: (Rc<Cell<u64>>, u64),
+) {
+    let in_front = app
+        .active_window()
+        .is_some_and(|window| window.is_visible() && window.is_active());
+    if in_front {
+        return;
+    }
+
+    let notification = gio::Notification::new(title);
+    notification.set_body(Some(artist));
+    notification.add_button("Pause", "app.pause");
+    notification.add_button("Next", "app.next");
+
+    let Some(url) = thumbnail_url.filter(|url| is_image_url(url)) else {
+        app.send_notification(Some(NOTIFICATION_ID), &notification);
+        return;
+    };
+    let app = app.clone();
+    load_image_bytes_async(url.to_string(), move |payload| {
+        let (guard, token) = token_guard;
+        if guard.get() != token {
+            return;
+        }
+        if let Some(payload) = payload {
+            let icon = gio::BytesIcon::new(&glib::Bytes::from_owned(payload));
+            notification.set_icon(&icon);
+        }
+        app.send_notification(Some(NOTIFICATION_ID), &notification);
+    });
+}
@@ -0,0 +1,147 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use gtk4::gio;
+use gtk4::prelude::*;
+use serde_json::{Value, json};
+
+use crate::api::{LyricLine, SearchResult, StreamInfo};
+use crate::storage::{Database, TrackInfo};
+
+const OFFLINE_SETTING: &str = "offline_mode";
+
+/// Whether the app keeps off the network, because there is none or because the user chose to.
+/// Offline, only downloaded songs play and network-only features are hidden.
+#[derive(Clone)]
+pub struct OfflineMode {
+    database: Database,
+    forced: Rc<Cell<bool>>,
+    network_available: Rc<Cell<bool>>,
+    on_changed: Rc<RefCell<Vec<Box<dyn Fn(bool)>>>>,
+}
+
+impl OfflineMode {
+    pub fn new(database: Database) -> Self {
+        let monitor = gio::NetworkMonitor::default();
+        let forced = database.get_setting(OFFLINE_SETTING).as_deref() == Some("1");
+        let mode = Self {
+            database,
+            forced: Rc::new(Cell::new(forced)),
+            network_available: Rc::new(Cell::new(monitor.is_network_available())),
+            on_changed: Rc::new(RefCell::new(Vec::new())),
+        };
+
+        monitor.connect_network_changed(glib::clone!(
+            #[strong]
+            mode,
+            move |_, available| {
+                mode.update(|| mode.network_available.set(available));
+            }
+        ));
+
+        mode
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.forced.get() || !self.network_available.get()
+    }
+
+    /// Whether the user turned offline mode on, as opposed to the network being gone
+    pub fn is_forced(&self) -> bool {
+        self.forced.get()
+    }
+
+    /// Turn offline mode on or off by hand; remembered across restarts
+    pub fn set_forced(&self, forced: bool) -> Result<(), String> {
+        self.database
+            .set_setting(OFFLINE_SETTING, if forced { "1" } else { "0" })?;
+        self.update(|| self.forced.set(forced));
+        Ok(())
+    }
+
+    /// Call `callback` with the new state whenever the app goes offline or back online,
+    /// or offline mode is turned on or off by hand
+    pub fn connect_changed(&self, callback: impl Fn(bool) + 'static) {
+        self.on_changed.borrow_mut().push(Box::new(callback));
+    }
+
+    /// Notifies listeners when the change affects the offline state or how it was reached
+    fn update(&self, change: impl FnOnce()) {
+        let before = (self.is_offline(), self.is_forced());
+        change();
+        let offline = self.is_offline();
+        if (offline, self.is_forced()) != before {
+            for callback in self.on_changed.borrow().iter() {
+                callback(offline);
+            }
+        }
+    }
+}
+
+/// Remember a stream's metadata and lyrics so the song can be shown without network
+pub fn cache_stream_info(database: &Database, video_id: &str, info: &StreamInfo) {
+    let track = TrackInfo {
+        title: info.title.clone(),
+        artist: info.artist.clone(),
+        thumbnail_url: info.thumbnail_url.clone(),
+        lyrics: info.lyrics.as_deref().map(lyrics_to_json),
+    };
+    let _ = database.save_track_info(video_id, &track);
+}
+
+/// Stream info pointing at a local track or downloaded song.
+/// Local tracks use their tags; downloads use whatever was cached when they were fetched.
+pub fn local_stream_info(database: &Database, item: &SearchResult) -> Option<StreamInfo> {
+    let path = database.local_file(&item.video_id)?;
+    let url = gio::File::for_path(&path).uri().to_string();
+
+    if let Some(track) = database.get_local_track(&item.video_id) {
+        return Some(StreamInfo {
+            url,
+            title: track.song.title,
+            artist: track.song.artist,
+            thumbnail_url: track.song.thumbnail_url,
+            lyrics: None,
+        });
+    }
+
+    Some(match database.get_track_info(&item.video_id) {
+        Some(track) => StreamInfo {
+            url,
+            title: track.title,
+            artist: track.artist,
+            thumbnail_url: track.thumbnail_url.or_else(|| item.thumbnail_url.clone()),
+            lyrics: track.lyrics.as_deref().and_then(lyrics_from_json),
+        },
+        None => StreamInfo {
+            url,
+            title: item.title.clone(),
+            artist: item.artist.clone(),
+            thumbnail_url: item.thumbnail_url.clone(),
+            lyrics: None,
+        },
+    })
+}
+
+fn lyrics_to_json(lines: &[LyricLine]) -> String {
+    let lines: Vec<Value> = lines
+        .iter()
+        .map(|line| json!({ "time": line.timestamp, "text": line.text }))
+        .collect();
+    Value::Array(lines).to_string()
+}
+
+fn lyrics_from_json(data: &str) -> Option<Vec<LyricLine>> {
+    let value: Value = serde_json::from_str(data).ok()?;
+    let lines = value
+        .as_array()?
+        .iter()
+        .filter_map(|line| {
+            Some(LyricLine {
+                timestamp: line.get("time")?.as_f64()?,
+                text: line.get("text")?.as_str()?.to_string(),
+            })
+        })
+        .collect();
+    Some(lines)
+}
@@ -0,0 +1,30 @@
+use serde_json::{Value, json};
+
+use crate::storage::Database;
+
+use super::backend::OutputDevice;
+
+/// Setting holding the output device chosen in Settings, kept while it is unplugged
+const OUTPUT_DEVICE_SETTING: &str = "output_device";
+
+/// The output device chosen in Settings; `None` plays through the system default
+pub fn saved_output_device(database: &Database) -> Option<OutputDevice> {
+    let value: Value = serde_json::from_str(&database.get_setting(OUTPUT_DEVICE_SETTING)?).ok()?;
+    let id = value["id"].as_str().filter(|id| !id.is_empty())?;
+    Some(OutputDevice {
+        id: id.to_string(),
+        name: value["name"].as_str().unwrap_or(id).to_string(),
+    })
+}
+
+/// Remember the output device, with its name so Settings can show it while it is unplugged
+pub fn save_output_device(
+    database: &Database,
+    device: Option<&OutputDevice>,
+) -> Result<(), String> {
+    let value = match device {
+        Some(device) => json!({ "id": device.id, "name": device.name }).to_string(),
+        None => String::new(),
+    };
+    database.set_setting(OUTPUT_DEVICE_SETTING, &value)
+}
@@ -0,0 +1,54 @@
+use std::path::Path;
+
+use crate::api::{SearchResult, StreamInfo};
+use crate::local::read_file;
+
+use super::{LOCAL_SCHEME, MusicSource};
+
+/// Audio files on this computer, read straight from disk.
+/// They are found by scanning music folders rather than by searching the source.
+pub struct LocalFiles;
+
+impl LocalFiles {
+    fn read(&self, id: &str) -> Result<SearchResult, String> {
+        let path = Path::new(id);
+        if !path.is_file() {
+            return Err(format!("File not found: {}", path.display()));
+        }
+        let song = read_file(path).song;
+        Ok(SearchResult {
+            video_id: song.video_id,
+            title: song.title,
+            artist: song.artist,
+            duration: song.duration,
+            thumbnail_url: song.thumbnail_url,
+        })
+    }
+}
+
+impl MusicSource for LocalFiles {
+    fn scheme(&self) -> &'static str {
+        LOCAL_SCHEME
+    }
+
+    fn name(&self) -> &'static str {
+        "Local files"
+    }
+
+    fn resolve_stream(&self, id: &str) -> Result<StreamInfo, String> {
+        let track = self.read(id)?;
+        let url =
+            glib::filename_to_uri(id, None).map_err(|e| format!("Failed to open {}: {}", id, e))?;
+        Ok(StreamInfo {
+            url: url.to_string(),
+            title: track.title,
+            artist: track.artist,
+            thumbnail_url: track.thumbnail_url,
+            lyrics: None,
+        })
+    }
+
+    fn metadata(&self, id: &str) -> Result<SearchResult, String> {
+        self.read(id)
+    }
+}
@@ -0,0 +1,380 @@
+mod local;
+mod subsonic;
+mod youtube;
+
+use std::sync::{Arc, RwLock};
+use std::thread;
+
+use serde_json::{Map, Value};
+
+use crate::api::{SearchPage, SearchResult, StreamInfo, SubsonicCredentials};
+
+pub use local::LocalFiles;
+pub use subsonic::SUBSONIC_COVER_PREFIX;
+pub use youtube::YouTubeMusic;
+
+use subsonic::SubsonicServer;
+
+/// URI scheme of YouTube Music tracks, followed by the video ID
+pub const YOUTUBE_SCHEME: &str = "ytm";
+
+/// URI scheme of files on this computer, followed by the absolute path
+pub const LOCAL_SCHEME: &str = "file";
+
+/// URI scheme of songs on the Subsonic server set up in Settings, followed by the server's song ID
+pub const SUBSONIC_SCHEME: &str = "subsonic";
+
+/// A place songs can be found and played from.
+/// Tracks are named by a URI, `<scheme>:<id>`, where the ID only has to mean something to the source.
+pub trait MusicSource: Send + Sync {
+    /// URI scheme of the tracks this source provides
+    fn scheme(&self) -> &'static str;
+
+    /// Name shown when the source can't be reached
+    fn name(&self) -> &'static str;
+
+    /// Search for songs; `continuation` asks for the page after an earlier one.
+    /// Results carry full track URIs.
+    fn search(&self, _query: &str, _continuation: Option<&str>) -> Result<SearchPage, String> {
+        Ok(SearchPage {
+            results: Vec::new(),
+            continuation: None,
+        })
+    }
+
+    /// Playable URL and metadata for a track
+    fn resolve_stream(&self, id: &str) -> Result<StreamInfo, String>;
+
+    /// Title, artist and cover of a track, without resolving its stream
+    fn metadata(&self, id: &str) -> Result<SearchResult, String>;
+
+    /// Report that a track started playing, for sources that show what is playing now
+    fn now_playing(&self, _id: &str) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Report that a track was played, for sources that keep play counts.
+    /// Called once half the track, or a few minutes of it, has been heard.
+    fn scrobble(&self, _id: &str) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Next-page token of each source, by scheme, from a continuation made by `Sources::search`
+fn continuation_tokens(continuation: &str) -> Map<String, Value> {
+    match serde_json::from_str(continuation) {
+        Ok(Value::Object(tokens)) => tokens,
+        _ => Map::new(),
+    }
+}
+
+/// Build a track URI from a scheme and a source-specific ID
+pub fn track_uri(scheme: &str, id: &str) -> String {
+    format!("{}:{}", scheme, id)
+}
+
+/// Split a track URI into its scheme and ID.
+/// Bare IDs from before track URIs existed are YouTube video IDs.
+pub fn split_track_uri(uri: &str) -> (&str, &str) {
+    uri.split_once(':').unwrap_or((YOUTUBE_SCHEME, uri))
+}
+
+/// Search results from every source
+#[derive(Debug, Clone)]
+pub struct SourcesPage {
+    pub results: Vec<SearchResult>,
+    /// Asks every source that has more results for its next page
+    pub continuation: Option<String>,
+    /// Sources that could not be searched, each with the reason
+    pub failures: Vec<String>,
+}
+
+/// Every music source the app knows, picked by the scheme of a track URI.
+/// Cheap to clone and safe to move into worker threads.
+#[derive(Clone)]
+pub struct Sources {
+    /// Sources that are always there
+    builtin: Arc<Vec<Arc<dyn MusicSource>>>,
+    /// The server set up in Settings, replaced whenever the settings change
+    subsonic: Arc<RwLock<Option<Arc<SubsonicServer>>>>,
+}
+
+impl Sources {
+    pub fn new() -> Self {
+        let builtin: Vec<Arc<dyn MusicSource>> =
+            vec![Arc::new(YouTubeMusic::new()), Arc::new(LocalFiles)];
+        Self {
+            builtin: Arc::new(builtin),
+            subsonic: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    #[cfg(test)]
+    fn with_builtin(builtin: Vec<Arc<dyn MusicSource>>) -> Self {
+        Self {
+            builtin: Arc::new(builtin),
+            subsonic: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Use another Subsonic server, or none
+    pub fn set_subsonic(&self, credentials: Option<SubsonicCredentials>) {
+        let server = credentials.map(|credentials| Arc::new(SubsonicServer::new(credentials)));
+        if let Ok(mut subsonic) = self.subsonic.write() {
+            *subsonic = server;
+        }
+    }
+
+    fn subsonic(&self) -> Option<Arc<SubsonicServer>> {
+        self.subsonic
+            .read()
+            .ok()
+            .and_then(|subsonic| subsonic.clone())
+    }
+
+    /// Every source in use, in the order their search results are shown
+    fn all(&self) -> Vec<Arc<dyn MusicSource>> {
+        let mut sources = self.builtin.to_vec();
+        if let Some(subsonic) = self.subsonic() {
+            sources.push(subsonic);
+        }
+        sources
+    }
+
+    /// The source a track URI belongs to, with the source-specific ID
+    fn source_for<'a>(&self, uri: &'a str) -> Result<(Arc<dyn MusicSource>, &'a str), String> {
+        let (scheme, id) = split_track_uri(uri);
+        if scheme == SUBSONIC_SCHEME && self.subsonic().is_none() {
+            return Err("No Subsonic server is set up".to_string());
+        }
+        self.all()
+            .into_iter()
+            .find(|source| source.scheme() == scheme)
+            .map(|source| (source, id))
+            .ok_or_else(|| format!("No music source for \"{}\" tracks", scheme))
+    }
+
+    /// Signed URL of a cover stored as `subsonic-cover:<id>`, while a server is set up
+    pub fn cover_url(&self, reference: &str) -> Option<String> {
+        self.subsonic()?.cover_url(reference)
+    }
+
+    /// Search every source at once, results of each source in turn.
+    /// The continuation holds the next-page token of each source that has more,
+    /// and fails only when every source searched failed.
+    pub fn search(&self, query: &str, continuation: Option<&str>) -> Result<SourcesPage, String> {
+        let requests: Vec<(Arc<dyn MusicSource>, Option<String>)> = match continuation {
+            Some(continuation) => {
+                let tokens = continuation_tokens(continuation);
+                self.all()
+                    .into_iter()
+                    .filter_map(|source| {
+                        let token = tokens.get(source.scheme())?.as_str()?.to_string();
+                        Some((source, Some(token)))
+                    })
+                    .collect()
+            }
+            None => self
+                .all()
+                .into_iter()
+                .map(|source| (source, None))
+                .collect(),
+        };
+
+        let pages: Vec<Result<SearchPage, String>> = thread::scope(|scope| {
+            let searches: Vec<_> = requests
+                .iter()
+                .map(|(source, token)| scope.spawn(move || source.search(query, token.as_deref())))
+                .collect();
+            searches
+                .into_iter()
+                .map(|search| {
+                    search
+                        .join()
+                        .unwrap_or_else(|_| Err("Search stopped unexpectedly".to_string()))
+                })
+                .collect()
+        });
+
+        let mut results = Vec::new();
+        let mut tokens = Map::new();
+        let mut failures = Vec::new();
+        for ((source, _), page) in requests.iter().zip(pages) {
+            match page {
+                Ok(page) => {
+                    results.extend(page.results);
+                    if let Some(token) = page.continuation {
+                        tokens.insert(source.scheme().to_string(), Value::String(token));
+                    }
+                }
+                Err(error) => failures.push(format!("{}: {}", source.name(), error)),
+            }
+        }
+
+        if !requests.is_empty() && failures.len() == requests.len() {
+            return Err(failures.join("\n"));
+        }
+        Ok(SourcesPage {
+            results,
+            continuation: (!tokens.is_empty()).then(|| Value::Object(tokens).to_string()),
+            failures,
+        })
+    }
+
+    /// Playable URL and metadata for a track URI
+    pub fn resolve_stream(&self, uri: &str) -> Result<StreamInfo, String> {
+        let (source, id) = self.source_for(uri)?;
+        source.resolve_stream(id)
+    }
+
+    /// Title, artist and cover for a track URI
+    pub fn metadata(&self, uri: &str) -> Result<SearchResult, String> {
+        let (source, id) = self.source_for(uri)?;
+        source.metadata(id)
+    }
+
+    /// Report that a track URI started playing
+    pub fn now_playing(&self, uri: &str) -> Result<(), String> {
+        let (source, id) = self.source_for(uri)?;
+        source.now_playing(id)
+    }
+
+    /// Report that a track URI was played
+    pub fn scrobble(&self, uri: &str) -> Result<(), String> {
+        let (source, id) = self.source_for(uri)?;
+        source.scrobble(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// Source with a number of one-song pages, or one that can't be reached
+    struct FakeSource {
+        scheme: &'static str,
+        pages: usize,
+        reachable: bool,
+        asked: Mutex<Vec<Option<String>>>,
+    }
+
+    impl FakeSource {
+        fn new(scheme: &'static str, pages: usize, reachable: bool) -> Arc<Self> {
+            Arc::new(Self {
+                scheme,
+                pages,
+                reachable,
+                asked: Mutex::new(Vec::new()),
+            })
+        }
+
+        fn asked(&self) -> Vec<Option<String>> {
+            self.asked.lock().unwrap().clone()
+        }
+    }
+
+    impl MusicSource for FakeSource {
+        fn scheme(&self) -> &'static str {
+            self.scheme
+        }
+
+        fn name(&self) -> &'static str {
+            self.scheme
+        }
+
+        fn search(&self, _query: &str, continuation: Option<&str>) -> Result<SearchPage, String> {
+            self.asked
+                .lock()
+                .unwrap()
+                .push(continuation.map(String::from));
+            if !self.reachable {
+                return Err("Network error".to_string());
+            }
+            let page = continuation.map_or(0, |token| token.parse().unwrap());
+            Ok(SearchPage {
+                results: vec![SearchResult {
+                    video_id: track_uri(self.scheme, &page.to_string()),
+                    title: String::new(),
+                    artist: String::new(),
+                    duration: String::new(),
+                    thumbnail_url: None,
+                }],
+                continuation: (page + 1 < self.pages).then(|| (page + 1).to_string()),
+            })
+        }
+
+        fn resolve_stream(&self, _id: &str) -> Result<StreamInfo, String> {
+            unimplemented!()
+        }
+
+        fn metadata(&self, _id: &str) -> Result<SearchResult, String> {
+            unimplemented!()
+        }
+    }
+
+    fn ids(page: &SourcesPage) -> Vec<&str> {
+        page.results
+            .iter()
+            .map(|result| result.video_id.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn every_source_keeps_its_own_next_page() {
+        let first = FakeSource::new("a", 3, true);
+        let second = FakeSource::new("b", 2, true);
+        let sources = Sources::with_builtin(vec![first.clone(), second.clone()]);
+
+        let page = sources.search("query", None).unwrap();
+        assert_eq!(ids(&page), ["a:0", "b:0"]);
+        let page = sources
+            .search("query", page.continuation.as_deref())
+            .unwrap();
+        assert_eq!(ids(&page), ["a:1", "b:1"]);
+        let page = sources
+            .search("query", page.continuation.as_deref())
+            .unwrap();
+        assert_eq!(ids(&page), ["a:2"]);
+        assert_eq!(page.continuation, None);
+
+        // A source that ran out of results is not asked again
+        assert_eq!(second.asked().len(), 2);
+        assert_eq!(
+            first.asked(),
+            [None, Some("1".to_string()), Some("2".to_string())]
+        );
+    }
+
+    #[test]
+    fn a_failing_source_is_reported_with_the_others_results() {
+        let working = FakeSource::new("a", 2, true);
+        let failing = FakeSource::new("b", 2, false);
+        let sources = Sources::with_builtin(vec![failing.clone(), working.clone()]);
+
+        let page = sources.search("query", None).unwrap();
+        assert_eq!(ids(&page), ["a:0"]);
+        assert_eq!(page.failures, ["b: Network error"]);
+
+        let page = sources
+            .search("query", page.continuation.as_deref())
+            .unwrap();
+        assert_eq!(ids(&page), ["a:1"]);
+        assert!(page.failures.is_empty());
+        assert_eq!(failing.asked().len(), 1);
+    }
+
+    #[test]
+    fn search_fails_only_when_every_source_fails() {
+        let sources = Sources::with_builtin(vec![
+            FakeSource::new("a", 1, false),
+            FakeSource::new("b", 1, false),
+        ]);
+
+        assert_eq!(
+            sources.search("query", None).unwrap_err(),
+            "a: Network error\nb: Network error"
+        );
+    }
+}
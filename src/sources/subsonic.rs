@@ -0,0 +1,155 @@
+use std::collections::HashSet;
+
+use crate::api::{
+    SearchPage, SearchResult, StreamInfo, SubsonicClient, SubsonicCredentials, SubsonicSearch,
+    SubsonicSong,
+};
+use crate::formats::format_duration;
+
+use super::{MusicSource, SUBSONIC_SCHEME, track_uri};
+
+/// Songs asked for per page of search results
+const SEARCH_PAGE_SIZE: usize = 20;
+
+/// Albums whose songs are added to the first page when an album or artist matches the query
+const MAX_EXPANDED_ALBUMS: usize = 3;
+
+/// Prefix of the cover of a Subsonic song, followed by the server's cover art ID.
+/// Stored instead of a `getCoverArt` URL, which carries the sign-in token.
+pub const SUBSONIC_COVER_PREFIX: &str = "subsonic-cover:";
+
+/// A Subsonic-compatible server such as Navidrome, as set up in Settings
+pub struct SubsonicServer {
+    client: SubsonicClient,
+}
+
+impl SubsonicServer {
+    pub fn new(credentials: SubsonicCredentials) -> Self {
+        Self {
+            client: SubsonicClient::new(credentials),
+        }
+    }
+
+    /// Signed URL of a cover stored as `subsonic-cover:<id>`
+    pub fn cover_url(&self, reference: &str) -> Option<String> {
+        let id = reference.strip_prefix(SUBSONIC_COVER_PREFIX)?;
+        self.client.cover_art_url(id).ok()
+    }
+
+    /// Songs of the albums and artists that matched a search, up to a few albums' worth
+    fn expanded_songs(&self, found: SubsonicSearch) -> Vec<SubsonicSong> {
+        let mut album_ids: Vec<String> = found.albums.into_iter().map(|album| album.id).collect();
+        for artist in found.artists {
+            let albums = self.client.artist(&artist.id).unwrap_or_default();
+            album_ids.extend(albums.into_iter().map(|album| album.id));
+        }
+
+        album_ids
+            .iter()
+            .take(MAX_EXPANDED_ALBUMS)
+            .flat_map(|id| self.client.album(id).unwrap_or_default())
+            .collect()
+    }
+}
+
+impl MusicSource for SubsonicServer {
+    fn scheme(&self) -> &'static str {
+        SUBSONIC_SCHEME
+    }
+
+    fn name(&self) -> &'static str {
+        "Subsonic"
+    }
+
+    fn search(&self, query: &str, continuation: Option<&str>) -> Result<SearchPage, String> {
+        let offset = continuation
+            .and_then(|token| token.parse::<usize>().ok())
+            .unwrap_or(0);
+        // Albums and artists only widen the first page
+        let (album_count, artist_count) = match continuation {
+            Some(_) => (0, 0),
+            None => (MAX_EXPANDED_ALBUMS, 1),
+        };
+        let mut found =
+            self.client
+                .search3(query, offset, SEARCH_PAGE_SIZE, album_count, artist_count)?;
+        let mut songs = std::mem::take(&mut found.songs);
+        let has_more = songs.len() == SEARCH_PAGE_SIZE;
+        songs.extend(self.expanded_songs(found));
+
+        let mut seen = HashSet::new();
+        let results = songs
+            .into_iter()
+            .filter(|song| seen.insert(song.id.clone()))
+            .map(search_result)
+            .collect();
+
+        Ok(SearchPage {
+            results,
+            continuation: has_more.then(|| (offset + SEARCH_PAGE_SIZE).to_string()),
+        })
+    }
+
+    fn resolve_stream(&self, id: &str) -> Result<StreamInfo, String> {
+        let result = search_result(self.client.song(id)?);
+        Ok(StreamInfo {
+            url: self.client.stream_url(id)?,
+            title: result.title,
+            artist: result.artist,
+            thumbnail_url: result.thumbnail_url,
+            lyrics: None,
+        })
+    }
+
+    fn metadata(&self, id: &str) -> Result<SearchResult, String> {
+        Ok(search_result(self.client.song(id)?))
+    }
+
+    fn now_playing(&self, id: &str) -> Result<(), String> {
+        self.client.scrobble(id, false)
+    }
+
+    fn scrobble(&self, id: &str) -> Result<(), String> {
+        self.client.scrobble(id, true)
+    }
+}
+
+fn search_result(song: SubsonicSong) -> SearchResult {
+    SearchResult {
+        video_id: track_uri(SUBSONIC_SCHEME, &song.id),
+        title: song.title,
+        artist: song.artist,
+        duration: song.duration.map(format_duration).unwrap_or_default(),
+        thumbnail_url: song
+            .cover_art
+            .map(|cover| format!("{}{}", SUBSONIC_COVER_PREFIX, cover)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn covers_are_stored_without_credentials() {
+        let song = SubsonicSong {
+            id: "so-1".to_string(),
+            title: "Hyperballad".to_string(),
+            artist: "Björk".to_string(),
+            duration: Some(321),
+            cover_art: Some("al-1".to_string()),
+        };
+        let result = search_result(song);
+        assert_eq!(result.video_id, "subsonic:so-1");
+        assert_eq!(result.thumbnail_url.as_deref(), Some("subsonic-cover:al-1"));
+
+        let server = SubsonicServer::new(
+            SubsonicCredentials::new("http://music.local", "alice", "sesame").unwrap(),
+        );
+        let signed = server.cover_url("subsonic-cover:al-1").unwrap();
+        assert!(signed.starts_with("http://music.local/rest/getCoverArt?"));
+        assert!(signed.contains("&t=") && signed.ends_with("&id=al-1"));
+        assert!(!signed.contains("sesame"));
+        assert_eq!(server.cover_url("https://example.com/cover.jpg"), None);
+    }
+}
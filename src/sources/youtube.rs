@@ -0,0 +1,67 @@
+use std::sync::Mutex;
+
+use crate::api::{InnertubeClient, SearchPage, SearchResult, StreamInfo};
+
+use super::{MusicSource, YOUTUBE_SCHEME, track_uri};
+
+/// YouTube Music, through the Innertube API
+pub struct YouTubeMusic {
+    /// Clients not in use by a request; each keeps its own visitor ID
+    idle: Mutex<Vec<InnertubeClient>>,
+}
+
+impl YouTubeMusic {
+    pub fn new() -> Self {
+        Self {
+            idle: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Run a request on an idle client, so slow requests don't hold up others
+    fn with_client<T>(&self, request: impl FnOnce(&mut InnertubeClient) -> T) -> T {
+        let mut client = self
+            .idle
+            .lock()
+            .ok()
+            .and_then(|mut idle| idle.pop())
+            .unwrap_or_else(InnertubeClient::new);
+        let result = request(&mut client);
+        if let Ok(mut idle) = self.idle.lock() {
+            idle.push(client);
+        }
+        result
+    }
+}
+
+impl MusicSource for YouTubeMusic {
+    fn scheme(&self) -> &'static str {
+        YOUTUBE_SCHEME
+    }
+
+    fn name(&self) -> &'static str {
+        "YouTube Music"
+    }
+
+    fn search(&self, query: &str, continuation: Option<&str>) -> Result<SearchPage, String> {
+        let mut page = self.with_client(|client| client.search_music_page(query, continuation))?;
+        for result in page.results.iter_mut() {
+            result.video_id = track_uri(YOUTUBE_SCHEME, &result.video_id);
+        }
+        Ok(page)
+    }
+
+    fn resolve_stream(&self, id: &str) -> Result<StreamInfo, String> {
+        self.with_client(|client| client.stream_info(id))
+    }
+
+    fn metadata(&self, id: &str) -> Result<SearchResult, String> {
+        let info = self.resolve_stream(id)?;
+        Ok(SearchResult {
+            video_id: track_uri(YOUTUBE_SCHEME, id),
+            title: info.title,
+            artist: info.artist,
+            duration: String::new(),
+            thumbnail_url: info.thumbnail_url,
+        })
+    }
+}
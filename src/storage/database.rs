@@ -1,13 +1,66 @@
-use std::cell::RefCell;
-use std::path::PathBuf;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use rusqlite::{Connection, params};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{Connection, OptionalExtension, params, params_from_iter};
+use serde_json::{Value, json};
+
+use crate::api::SubsonicCredentials;
+use crate::sources::{
+    LOCAL_SCHEME, SUBSONIC_COVER_PREFIX, SUBSONIC_SCHEME, YOUTUBE_SCHEME, split_track_uri, track_uri,
+};
+
+use super::smart_playlist::{SmartPlaylist, SmartRule, rules_from_json, rules_to_json};
+
+/// Identifier written into every library export document
+pub const LIBRARY_EXPORT_FORMAT: &str = "musika-library";
+
+/// Current version of the library export document.
+/// Version 1 held bare YouTube video IDs, 2 added play counts and smart playlists,
+/// 3 stores track URIs such as `ytm:<id>` or `file:<path>`, and 4 embeds playlist covers.
+pub const LIBRARY_EXPORT_VERSION: i64 = 4;
+
+/// First export version carrying play counts and smart playlists
+const EXPORT_VERSION_PLAY_STATS: i64 = 2;
+
+const PLAYLIST_COLUMNS: &str =
+    "id, name, description, cover_path, folder_id, pinned, created_at, updated_at, last_played_at";
+
+const PLAYLIST_SORT_SETTING: &str = "playlist_sort";
+
+const LOCAL_FOLDERS_SETTING: &str = "local_folders";
+
+const SUBSONIC_SERVER_SETTING: &str = "subsonic_server";
+
+/// Bumped whenever `library_fts` changes shape, so the index is rebuilt once
+const LIBRARY_FTS_VERSION: &str = "1";
+
+const LIBRARY_FTS_VERSION_SETTING: &str = "library_fts_version";
+
+/// How long removed rows are kept for undo
+const UNDO_RETENTION_SECS: i64 = 10 * 60;
+
+/// Every song the library knows about, whether liked, played or in a playlist
+const LIBRARY_TRACKS_SQL: &str = "
+    SELECT video_id, title, artist, duration, thumbnail_url FROM (
+        SELECT video_id, title, artist, duration, thumbnail_url FROM liked_songs
+        UNION ALL
+        SELECT video_id, title, artist, duration, thumbnail_url FROM play_stats
+        UNION ALL
+        SELECT video_id, title, artist, duration, thumbnail_url FROM playlist_songs
+        UNION ALL
+        SELECT video_id, title, artist, duration, thumbnail_url FROM tracks
+    ) GROUP BY video_id";
 
 /// Song data structure used for liked songs, recent plays, and playlist songs
 #[derive(Debug, Clone)]
 pub struct Song {
+    /// Track URI, such as `ytm:<video id>` or `file:<path>`
     pub video_id: String,
     pub title: String,
     pub artist: String,
@@ -15,6 +68,53 @@ pub struct Song {
     pub thumbnail_url: Option<String>,
 }
 
+impl Song {
+    pub fn source(&self) -> SongSource {
+        SongSource::of(&self.video_id)
+    }
+}
+
+/// Where a song's audio comes from, told apart by the scheme of its track URI
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SongSource {
+    YouTube,
+    /// A file on this computer, usually found in one of the local music folders
+    Local,
+    /// A song on the Subsonic server set up in Settings
+    Subsonic,
+}
+
+impl SongSource {
+    pub fn of(video_id: &str) -> Self {
+        match split_track_uri(video_id).0 {
+            LOCAL_SCHEME => SongSource::Local,
+            SUBSONIC_SCHEME => SongSource::Subsonic,
+            _ => SongSource::YouTube,
+        }
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            SongSource::YouTube => "youtube",
+            SongSource::Local => "local",
+            SongSource::Subsonic => "subsonic",
+        }
+    }
+}
+
+/// Track URI of a file on this computer
+pub fn local_track_id(path: &Path) -> String {
+    track_uri(LOCAL_SCHEME, &path.to_string_lossy())
+}
+
+/// Path of the file behind a local track's URI
+pub fn local_track_path(video_id: &str) -> Option<&Path> {
+    match split_track_uri(video_id) {
+        (LOCAL_SCHEME, path) => Some(Path::new(path)),
+        _ => None,
+    }
+}
+
 /// Liked song with timestamp
 #[derive(Debug, Clone)]
 pub struct LikedSong {
@@ -35,9 +135,71 @@ pub struct RecentPlay {
 pub struct Playlist {
     pub id: i64,
     pub name: String,
+    pub description: String,
+    /// Local image chosen by the user; a collage of song covers is used otherwise
+    pub cover_path: Option<String>,
+    pub folder_id: Option<i64>,
+    pub pinned: bool,
+    pub created_at: i64,
+    /// Last change to the name, details or songs
+    pub updated_at: i64,
+    pub last_played_at: Option<i64>,
+}
+
+/// Folder grouping playlists in the library; folders do not nest
+#[derive(Debug, Clone)]
+pub struct PlaylistFolder {
+    pub id: i64,
+    pub name: String,
+    pub expanded: bool,
     pub created_at: i64,
 }
 
+/// Order of the playlists list in the library
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaylistSort {
+    Name,
+    Created,
+    Modified,
+    LastPlayed,
+}
+
+impl PlaylistSort {
+    pub const ALL: [PlaylistSort; 4] = [
+        PlaylistSort::Name,
+        PlaylistSort::Created,
+        PlaylistSort::Modified,
+        PlaylistSort::LastPlayed,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PlaylistSort::Name => "Name",
+            PlaylistSort::Created => "Recently created",
+            PlaylistSort::Modified => "Recently modified",
+            PlaylistSort::LastPlayed => "Recently played",
+        }
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            PlaylistSort::Name => "name",
+            PlaylistSort::Created => "created",
+            PlaylistSort::Modified => "modified",
+            PlaylistSort::LastPlayed => "last_played",
+        }
+    }
+
+    fn order_by(self) -> &'static str {
+        match self {
+            PlaylistSort::Name => "name COLLATE NOCASE ASC",
+            PlaylistSort::Created => "created_at DESC",
+            PlaylistSort::Modified => "updated_at DESC",
+            PlaylistSort::LastPlayed => "COALESCE(last_played_at, 0) DESC, updated_at DESC",
+        }
+    }
+}
+
 /// Song in a playlist with position
 #[derive(Debug, Clone)]
 pub struct PlaylistSong {
@@ -47,10 +209,126 @@ pub struct PlaylistSong {
     pub position: i32,
 }
 
+/// How an imported library document is combined with the existing data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportMode {
+    /// Keep existing rows and add whatever is missing
+    Merge,
+    /// Remove liked songs, recent plays and playlists before importing
+    Replace,
+}
+
+/// Number of rows written by a library import
+#[derive(Debug, Clone, Default)]
+pub struct ImportSummary {
+    pub liked_songs: usize,
+    pub recent_plays: usize,
+    pub playlists: usize,
+    pub playlist_songs: usize,
+    pub smart_playlists: usize,
+}
+
+/// State of an offline download
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DownloadStatus {
+    Queued,
+    Downloading,
+    Paused,
+    Done,
+    Failed,
+}
+
+impl DownloadStatus {
+    fn key(self) -> &'static str {
+        match self {
+            DownloadStatus::Queued => "queued",
+            DownloadStatus::Downloading => "downloading",
+            DownloadStatus::Paused => "paused",
+            DownloadStatus::Done => "done",
+            DownloadStatus::Failed => "failed",
+        }
+    }
+
+    fn from_key(key: &str) -> Self {
+        match key {
+            "downloading" => DownloadStatus::Downloading,
+            "paused" => DownloadStatus::Paused,
+            "done" => DownloadStatus::Done,
+            "failed" => DownloadStatus::Failed,
+            _ => DownloadStatus::Queued,
+        }
+    }
+}
+
+/// Song saved, or being saved, for offline playback
+#[derive(Debug, Clone)]
+pub struct Download {
+    pub song: Song,
+    pub status: DownloadStatus,
+    /// Audio file, set once the download is done
+    pub file_path: Option<String>,
+    pub downloaded_bytes: i64,
+    pub total_bytes: Option<i64>,
+    pub error: Option<String>,
+    pub created_at: i64,
+}
+
+/// Metadata and lyrics remembered from the last time a song was streamed or downloaded
+#[derive(Debug, Clone)]
+pub struct TrackInfo {
+    pub title: String,
+    pub artist: String,
+    pub thumbnail_url: Option<String>,
+    /// Synced lyrics as a JSON array of `{ "time", "text" }` objects
+    pub lyrics: Option<String>,
+}
+
+/// Audio file found in a local music folder, with the tags read from it
+#[derive(Debug, Clone)]
+pub struct LocalTrack {
+    pub song: Song,
+    pub album: Option<String>,
+    pub file_path: String,
+    /// Modification time and size when the tags were read, to skip unchanged files on rescan
+    pub mtime: i64,
+    pub size: i64,
+}
+
+/// What a folder scan changed in the local library
+#[derive(Debug, Clone, Default)]
+pub struct LocalScanSummary {
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+}
+
+/// Handle for undoing a destructive action, returned by the method that performed it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UndoId(u64);
+
+/// A row copied out of a table just before it was deleted
+struct TrashedRow {
+    table: &'static str,
+    columns: Vec<String>,
+    values: Vec<SqlValue>,
+}
+
+/// Rows removed by one action, in the order they must be inserted back
+struct UndoEntry {
+    id: UndoId,
+    created_at: i64,
+    rows: Vec<TrashedRow>,
+    /// Playlist whose `updated_at` is put back after the rows are restored
+    playlist: Option<(i64, i64)>,
+}
+
 /// Database handle for SQLite operations
 #[derive(Clone)]
 pub struct Database {
     conn: Rc<RefCell<Connection>>,
+    undo_log: Rc<RefCell<Vec<UndoEntry>>>,
+    next_undo_id: Rc<Cell<u64>>,
+    covers_dir: PathBuf,
 }
 
 impl Database {
@@ -67,8 +345,24 @@ impl Database {
         let conn = Connection::open(&db_path)
             .map_err(|e| format!("Failed to open database: {}", e))?;
 
+        Self::with_connection(conn, Self::data_dir().join("covers"))
+    }
+
+    /// Empty database kept in memory, for tests
+    #[cfg(test)]
+    pub(crate) fn in_memory() -> Result<Self, String> {
+        let conn = Connection::open_in_memory()
+            .map_err(|e| format!("Failed to open database: {}", e))?;
+        let covers_dir = std::env::temp_dir().join(format!("musika-test-covers-{}", std::process::id()));
+        Self::with_connection(conn, covers_dir)
+    }
+
+    fn with_connection(conn: Connection, covers_dir: PathBuf) -> Result<Self, String> {
         let db = Self {
             conn: Rc::new(RefCell::new(conn)),
+            undo_log: Rc::new(RefCell::new(Vec::new())),
+            next_undo_id: Rc::new(Cell::new(0)),
+            covers_dir,
         };
 
         db.init_tables()?;
@@ -76,6 +370,11 @@ impl Database {
     }
 
     fn get_db_path() -> Result<PathBuf, String> {
+        Ok(Self::data_dir().join("musika.db"))
+    }
+
+    /// Application data directory, ~/.local/share/musika unless XDG_DATA_HOME is set
+    pub fn data_dir() -> PathBuf {
         let data_dir = std::env::var("XDG_DATA_HOME")
             .map(PathBuf::from)
             .unwrap_or_else(|_| {
@@ -83,7 +382,44 @@ impl Database {
                 PathBuf::from(home).join(".local/share")
             });
 
-        Ok(data_dir.join("musika").join("musika.db"))
+        data_dir.join("musika")
+    }
+
+    /// Directory holding custom playlist covers
+    pub fn covers_dir(&self) -> &Path {
+        &self.covers_dir
+    }
+
+    /// Copies image bytes into the covers directory under a name unique to this copy
+    pub fn store_playlist_cover(&self, playlist_id: i64, image: &[u8], extension: &str) -> Result<String, String> {
+        std::fs::create_dir_all(&self.covers_dir)
+            .map_err(|e| format!("Failed to create covers directory: {}", e))?;
+
+        let extension: String = extension
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .take(5)
+            .collect::<String>()
+            .to_lowercase();
+        // Unique per copy, so a cover restored from an export is never overwritten by another playlist's
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos())
+            .unwrap_or(0);
+        let destination = self.covers_dir.join(format!(
+            "playlist-{}-{}.{}",
+            playlist_id,
+            stamp,
+            if extension.is_empty() { "img" } else { &extension }
+        ));
+        std::fs::write(&destination, image)
+            .map_err(|e| format!("Failed to save cover {}: {}", destination.display(), e))?;
+        Ok(destination.to_string_lossy().to_string())
+    }
+
+    /// Directory holding downloaded audio files
+    pub fn audio_dir() -> PathBuf {
+        Self::data_dir().join("audio")
     }
 
     fn init_tables(&self) -> Result<(), String> {
@@ -128,11 +464,210 @@ impl Database {
                 FOREIGN KEY (playlist_id) REFERENCES playlists(id) ON DELETE CASCADE
             );
 
+            CREATE TABLE IF NOT EXISTS play_stats (
+                video_id TEXT PRIMARY KEY,
+                title TEXT NOT NULL,
+                artist TEXT NOT NULL,
+                duration TEXT NOT NULL,
+                thumbnail_url TEXT,
+                play_count INTEGER NOT NULL DEFAULT 0,
+                last_played_at INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS playlist_folders (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL,
+                expanded INTEGER NOT NULL DEFAULT 1,
+                created_at INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS app_settings (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS smart_playlists (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL,
+                rules TEXT NOT NULL,
+                match_all INTEGER NOT NULL DEFAULT 1,
+                created_at INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS downloads (
+                video_id TEXT PRIMARY KEY,
+                title TEXT NOT NULL,
+                artist TEXT NOT NULL,
+                duration TEXT NOT NULL,
+                thumbnail_url TEXT,
+                status TEXT NOT NULL,
+                file_path TEXT,
+                downloaded_bytes INTEGER NOT NULL DEFAULT 0,
+                total_bytes INTEGER,
+                error TEXT,
+                created_at INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS track_info (
+                video_id TEXT PRIMARY KEY,
+                title TEXT NOT NULL,
+                artist TEXT NOT NULL,
+                thumbnail_url TEXT,
+                lyrics TEXT,
+                updated_at INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS tracks (
+                video_id TEXT PRIMARY KEY,
+                source TEXT NOT NULL,
+                file_path TEXT NOT NULL UNIQUE,
+                title TEXT NOT NULL,
+                artist TEXT NOT NULL,
+                album TEXT,
+                duration TEXT NOT NULL,
+                thumbnail_url TEXT,
+                mtime INTEGER NOT NULL,
+                size INTEGER NOT NULL,
+                scanned_at INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS track_rates (
+                video_id TEXT PRIMARY KEY,
+                rate REAL NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS eq_presets (
+                name TEXT PRIMARY KEY,
+                gains TEXT NOT NULL
+            );
+
+            -- Songs saved before track URIs were YouTube video IDs
+            UPDATE liked_songs SET video_id = 'ytm:' || video_id WHERE instr(video_id, ':') = 0;
+            UPDATE recent_plays SET video_id = 'ytm:' || video_id WHERE instr(video_id, ':') = 0;
+            UPDATE playlist_songs SET video_id = 'ytm:' || video_id WHERE instr(video_id, ':') = 0;
+            UPDATE play_stats SET video_id = 'ytm:' || video_id WHERE instr(video_id, ':') = 0;
+            UPDATE downloads SET video_id = 'ytm:' || video_id WHERE instr(video_id, ':') = 0;
+            UPDATE track_info SET video_id = 'ytm:' || video_id WHERE instr(video_id, ':') = 0;
+
+            -- Downloads cut short when the app last quit pick up where they left off
+            UPDATE downloads SET status = 'queued' WHERE status = 'downloading';
+
             CREATE INDEX IF NOT EXISTS idx_recent_plays_date ON recent_plays(played_at DESC);
             CREATE INDEX IF NOT EXISTS idx_playlist_songs_playlist ON playlist_songs(playlist_id, position);
+
+            -- Seed play counts from history recorded before play_stats existed
+            INSERT OR IGNORE INTO play_stats (video_id, title, artist, duration, thumbnail_url, play_count, last_played_at)
+                SELECT video_id, title, artist, duration, thumbnail_url, 1, MAX(played_at)
+                FROM recent_plays GROUP BY video_id;
+
+            CREATE VIRTUAL TABLE IF NOT EXISTS library_fts USING fts5(
+                video_id UNINDEXED,
+                title,
+                artist,
+                tokenize = 'unicode61 remove_diacritics 2'
+            );
             "
         ).map_err(|e| format!("Failed to create tables: {}", e))?;
 
+        // Subsonic covers used to be saved as getCoverArt URLs, which carry the sign-in token.
+        // The cover ID is the last query parameter; IDs never need escaping in practice.
+        for table in ["liked_songs", "recent_plays", "playlist_songs", "play_stats", "downloads", "track_info"] {
+            conn.execute(
+                &format!(
+                    "UPDATE {table} SET thumbnail_url = ?1 || substr(thumbnail_url, instr(thumbnail_url, '&id=') + 4)
+                     WHERE thumbnail_url LIKE '%/rest/getCoverArt?%&id=%'"
+                ),
+                params![SUBSONIC_COVER_PREFIX],
+            ).map_err(|e| format!("Failed to migrate {} covers: {}", table, e))?;
+        }
+
+        ensure_column(&conn, "playlists", "description", "TEXT NOT NULL DEFAULT ''")?;
+        ensure_column(&conn, "playlists", "cover_path", "TEXT")?;
+        ensure_column(&conn, "playlists", "folder_id", "INTEGER REFERENCES playlist_folders(id) ON DELETE SET NULL")?;
+        ensure_column(&conn, "playlists", "pinned", "INTEGER NOT NULL DEFAULT 0")?;
+        ensure_column(&conn, "playlists", "updated_at", "INTEGER NOT NULL DEFAULT 0")?;
+        ensure_column(&conn, "playlists", "last_played_at", "INTEGER")?;
+        ensure_column(&conn, "playlists", "eq_preset", "TEXT")?;
+
+        conn.execute_batch(
+            "
+            UPDATE playlists SET updated_at = created_at WHERE updated_at = 0;
+
+            -- Track the last modification of a playlist's details or songs
+            CREATE TRIGGER IF NOT EXISTS playlists_touch_details
+            AFTER UPDATE OF name, description, cover_path ON playlists BEGIN
+                UPDATE playlists SET updated_at = CAST(strftime('%s', 'now') AS INTEGER) WHERE id = new.id;
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS playlist_songs_touch_insert AFTER INSERT ON playlist_songs BEGIN
+                UPDATE playlists SET updated_at = CAST(strftime('%s', 'now') AS INTEGER) WHERE id = new.playlist_id;
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS playlist_songs_touch_update AFTER UPDATE ON playlist_songs BEGIN
+                UPDATE playlists SET updated_at = CAST(strftime('%s', 'now') AS INTEGER) WHERE id = new.playlist_id;
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS playlist_songs_touch_delete AFTER DELETE ON playlist_songs BEGIN
+                UPDATE playlists SET updated_at = CAST(strftime('%s', 'now') AS INTEGER) WHERE id = old.playlist_id;
+            END;
+            "
+        ).map_err(|e| format!("Failed to migrate playlists: {}", e))?;
+
+        // Keep the search index in step with every table that stores songs
+        for table in ["liked_songs", "play_stats", "playlist_songs", "tracks"] {
+            conn.execute_batch(&format!(
+                "
+                CREATE TRIGGER IF NOT EXISTS {table}_fts_insert AFTER INSERT ON {table} BEGIN
+                    DELETE FROM library_fts WHERE video_id = new.video_id;
+                    INSERT INTO library_fts (video_id, title, artist)
+                        VALUES (new.video_id, new.title, new.artist);
+                END;
+
+                CREATE TRIGGER IF NOT EXISTS {table}_fts_update
+                AFTER UPDATE OF video_id, title, artist ON {table} BEGIN
+                    DELETE FROM library_fts WHERE video_id IN (old.video_id, new.video_id);
+                    INSERT INTO library_fts (video_id, title, artist)
+                        SELECT video_id, title, artist FROM ({tracks})
+                        WHERE video_id IN (old.video_id, new.video_id);
+                END;
+
+                CREATE TRIGGER IF NOT EXISTS {table}_fts_delete AFTER DELETE ON {table} BEGIN
+                    DELETE FROM library_fts WHERE video_id = old.video_id
+                        AND NOT EXISTS (SELECT 1 FROM liked_songs WHERE video_id = old.video_id)
+                        AND NOT EXISTS (SELECT 1 FROM play_stats WHERE video_id = old.video_id)
+                        AND NOT EXISTS (SELECT 1 FROM playlist_songs WHERE video_id = old.video_id)
+                        AND NOT EXISTS (SELECT 1 FROM tracks WHERE video_id = old.video_id);
+                END;
+                ",
+                tracks = LIBRARY_TRACKS_SQL
+            )).map_err(|e| format!("Failed to create search triggers: {}", e))?;
+        }
+
+        // The triggers keep the index current; rebuild only when it is new, emptied or outdated
+        let indexed_version: Option<String> = conn.query_row(
+            "SELECT value FROM app_settings WHERE key = ?1",
+            params![LIBRARY_FTS_VERSION_SETTING],
+            |row| row.get(0),
+        ).optional().map_err(|e| format!("Failed to read search index version: {}", e))?;
+        let index_empty: bool = conn.query_row(
+            "SELECT NOT EXISTS(SELECT 1 FROM library_fts)",
+            [],
+            |row| row.get(0),
+        ).map_err(|e| format!("Failed to inspect search index: {}", e))?;
+
+        if index_empty || indexed_version.as_deref() != Some(LIBRARY_FTS_VERSION) {
+            conn.execute_batch(&format!(
+                "DELETE FROM library_fts;
+                 INSERT INTO library_fts (video_id, title, artist)
+                     SELECT video_id, title, artist FROM ({});",
+                LIBRARY_TRACKS_SQL
+            )).map_err(|e| format!("Failed to build search index: {}", e))?;
+            conn.execute(
+                "INSERT OR REPLACE INTO app_settings (key, value) VALUES (?1, ?2)",
+                params![LIBRARY_FTS_VERSION_SETTING, LIBRARY_FTS_VERSION],
+            ).map_err(|e| format!("Failed to save search index version: {}", e))?;
+        }
+
         Ok(())
     }
 
@@ -163,16 +698,119 @@ impl Database {
         Ok(())
     }
 
-    /// Remove a song from liked songs
-    pub fn unlike_song(&self, video_id: &str) -> Result<(), String> {
-        let conn = self.conn.borrow();
-        conn.execute(
+    /// Remove a song from liked songs, keeping the row so it can be undone
+    pub fn unlike_song(&self, video_id: &str) -> Result<UndoId, String> {
+        let mut conn = self.conn.borrow_mut();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        let rows = trash_rows(&tx, "liked_songs", "video_id = ?1", params![video_id])?;
+        tx.execute(
             "DELETE FROM liked_songs WHERE video_id = ?1",
             params![video_id],
         ).map_err(|e| format!("Failed to unlike song: {}", e))?;
+
+        tx.commit()
+            .map_err(|e| format!("Failed to commit unlike: {}", e))?;
+        Ok(self.push_undo(rows, None))
+    }
+
+    /// Like several songs in one transaction; songs already liked keep when they were liked
+    pub fn like_songs(&self, songs: &[Song]) -> Result<(), String> {
+        let mut conn = self.conn.borrow_mut();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        let liked_at = Self::current_timestamp();
+        for song in songs {
+            tx.execute(
+                "INSERT OR IGNORE INTO liked_songs (video_id, title, artist, duration, thumbnail_url, liked_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    song.video_id,
+                    song.title,
+                    song.artist,
+                    song.duration,
+                    song.thumbnail_url,
+                    liked_at
+                ],
+            ).map_err(|e| format!("Failed to like song: {}", e))?;
+        }
+
+        tx.commit()
+            .map_err(|e| format!("Failed to commit likes: {}", e))?;
         Ok(())
     }
 
+    /// Unlike several songs in one transaction, keeping the rows so they can be undone together
+    pub fn unlike_songs(&self, video_ids: &[String]) -> Result<UndoId, String> {
+        let mut conn = self.conn.borrow_mut();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        let mut rows = Vec::new();
+        for video_id in video_ids {
+            rows.extend(trash_rows(&tx, "liked_songs", "video_id = ?1", params![video_id])?);
+            tx.execute(
+                "DELETE FROM liked_songs WHERE video_id = ?1",
+                params![video_id],
+            ).map_err(|e| format!("Failed to unlike song: {}", e))?;
+        }
+
+        tx.commit()
+            .map_err(|e| format!("Failed to commit unlike: {}", e))?;
+        Ok(self.push_undo(rows, None))
+    }
+
+    /// Keep the first video ID of each group of liked songs and unlike the rest in one
+    /// transaction. When merging, the kept song also takes the oldest like time in its group.
+    pub fn resolve_liked_duplicates(&self, groups: &[Vec<String>], merge: bool) -> Result<UndoId, String> {
+        let mut conn = self.conn.borrow_mut();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        let mut rows = Vec::new();
+        for group in groups {
+            let Some((keep, remove)) = group.split_first() else {
+                continue;
+            };
+            // The kept row is saved too, so undo also restores its like time
+            rows.extend(trash_rows(&tx, "liked_songs", "video_id = ?1", params![keep])?);
+            let mut first_liked_at: Option<i64> = None;
+            for video_id in remove {
+                let liked_at: Option<i64> = tx.query_row(
+                    "SELECT liked_at FROM liked_songs WHERE video_id = ?1",
+                    params![video_id],
+                    |row| row.get(0),
+                ).optional().map_err(|e| format!("Failed to read like time: {}", e))?;
+                first_liked_at = match (first_liked_at, liked_at) {
+                    (Some(first), Some(liked_at)) => Some(first.min(liked_at)),
+                    (first, liked_at) => first.or(liked_at),
+                };
+                rows.extend(trash_rows(&tx, "liked_songs", "video_id = ?1", params![video_id])?);
+                tx.execute(
+                    "DELETE FROM liked_songs WHERE video_id = ?1",
+                    params![video_id],
+                ).map_err(|e| format!("Failed to remove duplicate: {}", e))?;
+            }
+
+            if merge && let Some(liked_at) = first_liked_at {
+                tx.execute(
+                    "UPDATE liked_songs SET liked_at = MIN(liked_at, ?1) WHERE video_id = ?2",
+                    params![liked_at, keep],
+                ).map_err(|e| format!("Failed to update kept song: {}", e))?;
+            }
+        }
+
+        tx.commit()
+            .map_err(|e| format!("Failed to commit duplicate removal: {}", e))?;
+        Ok(self.push_undo(rows, None))
+    }
+
     /// Check if a song is liked
     pub fn is_song_liked(&self, video_id: &str) -> bool {
         let conn = self.conn.borrow();
@@ -241,6 +879,27 @@ impl Database {
             ],
         ).map_err(|e| format!("Failed to add recent play: {}", e))?;
 
+        // Play counts outlive the trimmed history
+        conn.execute(
+            "INSERT INTO play_stats (video_id, title, artist, duration, thumbnail_url, play_count, last_played_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, 1, ?6)
+             ON CONFLICT(video_id) DO UPDATE SET
+                 title = excluded.title,
+                 artist = excluded.artist,
+                 duration = excluded.duration,
+                 thumbnail_url = COALESCE(excluded.thumbnail_url, play_stats.thumbnail_url),
+                 play_count = play_stats.play_count + 1,
+                 last_played_at = excluded.last_played_at",
+            params![
+                song.video_id,
+                song.title,
+                song.artist,
+                song.duration,
+                song.thumbnail_url,
+                Self::current_timestamp()
+            ],
+        ).map_err(|e| format!("Failed to update play stats: {}", e))?;
+
         // Keep only the last 50 entries
         conn.execute(
             "DELETE FROM recent_plays WHERE id NOT IN (
@@ -300,7 +959,7 @@ impl Database {
         let created_at = Self::current_timestamp();
 
         conn.execute(
-            "INSERT INTO playlists (name, created_at) VALUES (?1, ?2)",
+            "INSERT INTO playlists (name, created_at, updated_at) VALUES (?1, ?2, ?2)",
             params![name, created_at],
         ).map_err(|e| format!("Failed to create playlist: {}", e))?;
 
@@ -308,7 +967,13 @@ impl Database {
         Ok(Playlist {
             id,
             name: name.to_string(),
+            description: String::new(),
+            cover_path: None,
+            folder_id: None,
+            pinned: false,
             created_at,
+            updated_at: created_at,
+            last_played_at: None,
         })
     }
 
@@ -322,54 +987,130 @@ impl Database {
         Ok(())
     }
 
-    /// Delete a playlist
-    pub fn delete_playlist(&self, playlist_id: i64) -> Result<(), String> {
+    /// Set the description shown under a playlist's name
+    pub fn set_playlist_description(&self, playlist_id: i64, description: &str) -> Result<(), String> {
+        let conn = self.conn.borrow();
+        conn.execute(
+            "UPDATE playlists SET description = ?1 WHERE id = ?2",
+            params![description, playlist_id],
+        ).map_err(|e| format!("Failed to update playlist description: {}", e))?;
+        Ok(())
+    }
+
+    /// Set or clear the custom cover image of a playlist
+    pub fn set_playlist_cover(&self, playlist_id: i64, cover_path: Option<&str>) -> Result<(), String> {
         let conn = self.conn.borrow();
-        // Delete songs first (foreign key constraint)
         conn.execute(
+            "UPDATE playlists SET cover_path = ?1 WHERE id = ?2",
+            params![cover_path, playlist_id],
+        ).map_err(|e| format!("Failed to update playlist cover: {}", e))?;
+        Ok(())
+    }
+
+    /// Delete a playlist, keeping it and its songs so the deletion can be undone
+    pub fn delete_playlist(&self, playlist_id: i64) -> Result<UndoId, String> {
+        let mut conn = self.conn.borrow_mut();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        let updated_at: i64 = tx.query_row(
+            "SELECT updated_at FROM playlists WHERE id = ?1",
+            params![playlist_id],
+            |row| row.get(0),
+        ).map_err(|e| format!("Failed to find playlist: {}", e))?;
+        let mut rows = trash_rows(&tx, "playlists", "id = ?1", params![playlist_id])?;
+        rows.extend(trash_rows(&tx, "playlist_songs", "playlist_id = ?1", params![playlist_id])?);
+
+        // Delete songs first (foreign key constraint)
+        tx.execute(
             "DELETE FROM playlist_songs WHERE playlist_id = ?1",
             params![playlist_id],
         ).map_err(|e| format!("Failed to delete playlist songs: {}", e))?;
 
-        conn.execute(
+        tx.execute(
             "DELETE FROM playlists WHERE id = ?1",
             params![playlist_id],
         ).map_err(|e| format!("Failed to delete playlist: {}", e))?;
-        Ok(())
+
+        tx.commit()
+            .map_err(|e| format!("Failed to commit playlist deletion: {}", e))?;
+        Ok(self.push_undo(rows, Some((playlist_id, updated_at))))
     }
 
-    /// Get all playlists
-    pub fn get_playlists(&self) -> Result<Vec<Playlist>, String> {
+    /// Pin a playlist to the top of the library, or unpin it
+    pub fn set_playlist_pinned(&self, playlist_id: i64, pinned: bool) -> Result<(), String> {
         let conn = self.conn.borrow();
-        let mut stmt = conn.prepare(
-            "SELECT id, name, created_at FROM playlists ORDER BY created_at DESC"
-        ).map_err(|e| format!("Failed to prepare query: {}", e))?;
-
-        let playlists = stmt.query_map([], |row| {
-            Ok(Playlist {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                created_at: row.get(2)?,
-            })
-        }).map_err(|e| format!("Failed to query playlists: {}", e))?;
+        conn.execute(
+            "UPDATE playlists SET pinned = ?1 WHERE id = ?2",
+            params![pinned, playlist_id],
+        ).map_err(|e| format!("Failed to pin playlist: {}", e))?;
+        Ok(())
+    }
 
-        playlists.collect::<Result<Vec<_>, _>>()
-            .map_err(|e| format!("Failed to collect playlists: {}", e))
+    /// Move a playlist into a folder, or out of any folder with `None`
+    pub fn set_playlist_folder(&self, playlist_id: i64, folder_id: Option<i64>) -> Result<(), String> {
+        let conn = self.conn.borrow();
+        conn.execute(
+            "UPDATE playlists SET folder_id = ?1 WHERE id = ?2",
+            params![folder_id, playlist_id],
+        ).map_err(|e| format!("Failed to move playlist: {}", e))?;
+        Ok(())
     }
 
-    /// Get playlist by ID
-    pub fn get_playlist(&self, playlist_id: i64) -> Result<Option<Playlist>, String> {
+    /// Record that a playlist was just played
+    pub fn mark_playlist_played(&self, playlist_id: i64) -> Result<(), String> {
+        let conn = self.conn.borrow();
+        conn.execute(
+            "UPDATE playlists SET last_played_at = ?1 WHERE id = ?2",
+            params![Self::current_timestamp(), playlist_id],
+        ).map_err(|e| format!("Failed to update playlist: {}", e))?;
+        Ok(())
+    }
+
+    /// Equalizer preset a playlist plays with, in place of the one chosen on the equalizer page
+    pub fn get_playlist_eq_preset(&self, playlist_id: i64) -> Option<String> {
+        let conn = self.conn.borrow();
+        conn.query_row(
+            "SELECT eq_preset FROM playlists WHERE id = ?1",
+            params![playlist_id],
+            |row| row.get(0),
+        ).ok().flatten()
+    }
+
+    pub fn set_playlist_eq_preset(&self, playlist_id: i64, preset: Option<&str>) -> Result<(), String> {
+        let conn = self.conn.borrow();
+        conn.execute(
+            "UPDATE playlists SET eq_preset = ?1 WHERE id = ?2",
+            params![preset, playlist_id],
+        ).map_err(|e| format!("Failed to update playlist equalizer: {}", e))?;
+        Ok(())
+    }
+
+    /// Get all playlists in the chosen sort order
+    pub fn get_playlists(&self) -> Result<Vec<Playlist>, String> {
+        let sort = self.get_playlist_sort();
+        let conn = self.conn.borrow();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM playlists ORDER BY {}",
+            PLAYLIST_COLUMNS,
+            sort.order_by()
+        )).map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let playlists = stmt.query_map([], playlist_from_row)
+            .map_err(|e| format!("Failed to query playlists: {}", e))?;
+
+        playlists.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect playlists: {}", e))
+    }
+
+    /// Get playlist by ID
+    pub fn get_playlist(&self, playlist_id: i64) -> Result<Option<Playlist>, String> {
         let conn = self.conn.borrow();
         let result = conn.query_row(
-            "SELECT id, name, created_at FROM playlists WHERE id = ?1",
+            &format!("SELECT {} FROM playlists WHERE id = ?1", PLAYLIST_COLUMNS),
             params![playlist_id],
-            |row| {
-                Ok(Playlist {
-                    id: row.get(0)?,
-                    name: row.get(1)?,
-                    created_at: row.get(2)?,
-                })
-            },
+            playlist_from_row,
         );
 
         match result {
@@ -379,6 +1120,98 @@ impl Database {
         }
     }
 
+    /// Get the chosen playlist sort order
+    pub fn get_playlist_sort(&self) -> PlaylistSort {
+        self.get_setting(PLAYLIST_SORT_SETTING)
+            .and_then(|key| PlaylistSort::ALL.into_iter().find(|sort| sort.key() == key))
+            .unwrap_or(PlaylistSort::Created)
+    }
+
+    /// Remember the playlist sort order
+    pub fn set_playlist_sort(&self, sort: PlaylistSort) -> Result<(), String> {
+        self.set_setting(PLAYLIST_SORT_SETTING, sort.key())
+    }
+
+    // ==================== Playlist Folders ====================
+
+    /// Create a playlist folder
+    pub fn create_folder(&self, name: &str) -> Result<PlaylistFolder, String> {
+        let conn = self.conn.borrow();
+        let created_at = Self::current_timestamp();
+
+        conn.execute(
+            "INSERT INTO playlist_folders (name, created_at) VALUES (?1, ?2)",
+            params![name, created_at],
+        ).map_err(|e| format!("Failed to create folder: {}", e))?;
+
+        Ok(PlaylistFolder {
+            id: conn.last_insert_rowid(),
+            name: name.to_string(),
+            expanded: true,
+            created_at,
+        })
+    }
+
+    /// Rename a playlist folder
+    pub fn rename_folder(&self, folder_id: i64, new_name: &str) -> Result<(), String> {
+        let conn = self.conn.borrow();
+        conn.execute(
+            "UPDATE playlist_folders SET name = ?1 WHERE id = ?2",
+            params![new_name, folder_id],
+        ).map_err(|e| format!("Failed to rename folder: {}", e))?;
+        Ok(())
+    }
+
+    /// Remember whether a folder is expanded in the library
+    pub fn set_folder_expanded(&self, folder_id: i64, expanded: bool) -> Result<(), String> {
+        let conn = self.conn.borrow();
+        conn.execute(
+            "UPDATE playlist_folders SET expanded = ?1 WHERE id = ?2",
+            params![expanded, folder_id],
+        ).map_err(|e| format!("Failed to update folder: {}", e))?;
+        Ok(())
+    }
+
+    /// Delete a folder, keeping its playlists outside of any folder
+    pub fn delete_folder(&self, folder_id: i64) -> Result<(), String> {
+        let mut conn = self.conn.borrow_mut();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        tx.execute(
+            "UPDATE playlists SET folder_id = NULL WHERE folder_id = ?1",
+            params![folder_id],
+        ).map_err(|e| format!("Failed to empty folder: {}", e))?;
+        tx.execute(
+            "DELETE FROM playlist_folders WHERE id = ?1",
+            params![folder_id],
+        ).map_err(|e| format!("Failed to delete folder: {}", e))?;
+
+        tx.commit()
+            .map_err(|e| format!("Failed to commit folder removal: {}", e))
+    }
+
+    /// Get all playlist folders ordered by name
+    pub fn get_folders(&self) -> Result<Vec<PlaylistFolder>, String> {
+        let conn = self.conn.borrow();
+        let mut stmt = conn.prepare(
+            "SELECT id, name, expanded, created_at FROM playlist_folders ORDER BY name COLLATE NOCASE ASC"
+        ).map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let folders = stmt.query_map([], |row| {
+            Ok(PlaylistFolder {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                expanded: row.get(2)?,
+                created_at: row.get(3)?,
+            })
+        }).map_err(|e| format!("Failed to query folders: {}", e))?;
+
+        folders.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect folders: {}", e))
+    }
+
     // ==================== Playlist Songs ====================
 
     /// Add a song to a playlist
@@ -411,55 +1244,186 @@ impl Database {
         Ok(())
     }
 
-    /// Remove a song from a playlist
-    pub fn remove_song_from_playlist(&self, playlist_id: i64, song_id: i64) -> Result<(), String> {
-        let conn = self.conn.borrow();
-        conn.execute(
+    /// Add several songs to a playlist in one transaction, returning how many were new
+    pub fn add_songs_to_playlist(&self, playlist_id: i64, songs: &[Song]) -> Result<usize, String> {
+        let mut conn = self.conn.borrow_mut();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        let added = append_playlist_songs(&tx, playlist_id, songs)?;
+
+        tx.commit()
+            .map_err(|e| format!("Failed to commit playlist songs: {}", e))?;
+        Ok(added)
+    }
+
+    /// Remove a song from a playlist, keeping the entry so it can be undone
+    pub fn remove_song_from_playlist(&self, playlist_id: i64, song_id: i64) -> Result<UndoId, String> {
+        let mut conn = self.conn.borrow_mut();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        let updated_at: i64 = tx.query_row(
+            "SELECT updated_at FROM playlists WHERE id = ?1",
+            params![playlist_id],
+            |row| row.get(0),
+        ).map_err(|e| format!("Failed to find playlist: {}", e))?;
+        let rows = trash_rows(
+            &tx,
+            "playlist_songs",
+            "id = ?1 AND playlist_id = ?2",
+            params![song_id, playlist_id],
+        )?;
+        tx.execute(
             "DELETE FROM playlist_songs WHERE id = ?1 AND playlist_id = ?2",
             params![song_id, playlist_id],
         ).map_err(|e| format!("Failed to remove song from playlist: {}", e))?;
-        Ok(())
+
+        tx.commit()
+            .map_err(|e| format!("Failed to commit song removal: {}", e))?;
+        Ok(self.push_undo(rows, Some((playlist_id, updated_at))))
+    }
+
+    /// Remove several songs from a playlist in one transaction, undone together
+    pub fn remove_songs_from_playlist(&self, playlist_id: i64, song_ids: &[i64]) -> Result<UndoId, String> {
+        let mut conn = self.conn.borrow_mut();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        let updated_at: i64 = tx.query_row(
+            "SELECT updated_at FROM playlists WHERE id = ?1",
+            params![playlist_id],
+            |row| row.get(0),
+        ).map_err(|e| format!("Failed to find playlist: {}", e))?;
+        let mut rows = Vec::new();
+        for song_id in song_ids {
+            rows.extend(trash_rows(
+                &tx,
+                "playlist_songs",
+                "id = ?1 AND playlist_id = ?2",
+                params![song_id, playlist_id],
+            )?);
+            tx.execute(
+                "DELETE FROM playlist_songs WHERE id = ?1 AND playlist_id = ?2",
+                params![song_id, playlist_id],
+            ).map_err(|e| format!("Failed to remove song from playlist: {}", e))?;
+        }
+
+        tx.commit()
+            .map_err(|e| format!("Failed to commit song removal: {}", e))?;
+        Ok(self.push_undo(rows, Some((playlist_id, updated_at))))
     }
 
-    /// Swap positions of two songs within a playlist
-    pub fn swap_playlist_song_positions(
+    /// Keep the first entry of each group of playlist song IDs and remove the rest in one
+    /// transaction. When merging, the kept entry also takes the earliest position in its group.
+    pub fn resolve_playlist_duplicates(
         &self,
         playlist_id: i64,
-        song_id: i64,
-        other_song_id: i64,
-    ) -> Result<(), String> {
+        groups: &[Vec<i64>],
+        merge: bool,
+    ) -> Result<UndoId, String> {
         let mut conn = self.conn.borrow_mut();
         let tx = conn
             .transaction()
             .map_err(|e| format!("Failed to start transaction: {}", e))?;
 
-        let position: i32 = tx
-            .query_row(
-                "SELECT position FROM playlist_songs WHERE id = ?1 AND playlist_id = ?2",
-                params![song_id, playlist_id],
-                |row| row.get(0),
-            )
-            .map_err(|e| format!("Failed to get song position: {}", e))?;
+        let updated_at: i64 = tx.query_row(
+            "SELECT updated_at FROM playlists WHERE id = ?1",
+            params![playlist_id],
+            |row| row.get(0),
+        ).map_err(|e| format!("Failed to find playlist: {}", e))?;
+
+        let mut rows = Vec::new();
+        for group in groups {
+            let Some((keep, remove)) = group.split_first() else {
+                continue;
+            };
+            // The kept row is saved too, so undo also restores its position
+            rows.extend(trash_rows(
+                &tx,
+                "playlist_songs",
+                "id = ?1 AND playlist_id = ?2",
+                params![keep, playlist_id],
+            )?);
+            let mut first_position: Option<i64> = None;
+            for song_id in remove {
+                let removed = trash_rows(
+                    &tx,
+                    "playlist_songs",
+                    "id = ?1 AND playlist_id = ?2",
+                    params![song_id, playlist_id],
+                )?;
+                let position: Option<i64> = tx.query_row(
+                    "SELECT position FROM playlist_songs WHERE id = ?1 AND playlist_id = ?2",
+                    params![song_id, playlist_id],
+                    |row| row.get(0),
+                ).optional().map_err(|e| format!("Failed to read song position: {}", e))?;
+                first_position = match (first_position, position) {
+                    (Some(first), Some(position)) => Some(first.min(position)),
+                    (first, position) => first.or(position),
+                };
+                tx.execute(
+                    "DELETE FROM playlist_songs WHERE id = ?1 AND playlist_id = ?2",
+                    params![song_id, playlist_id],
+                ).map_err(|e| format!("Failed to remove duplicate: {}", e))?;
+                rows.extend(removed);
+            }
+
+            if merge && let Some(position) = first_position {
+                tx.execute(
+                    "UPDATE playlist_songs SET position = MIN(position, ?1) WHERE id = ?2 AND playlist_id = ?3",
+                    params![position, keep, playlist_id],
+                ).map_err(|e| format!("Failed to move kept song: {}", e))?;
+            }
+        }
 
-        let other_position: i32 = tx
-            .query_row(
-                "SELECT position FROM playlist_songs WHERE id = ?1 AND playlist_id = ?2",
-                params![other_song_id, playlist_id],
-                |row| row.get(0),
-            )
-            .map_err(|e| format!("Failed to get other song position: {}", e))?;
+        tx.commit()
+            .map_err(|e| format!("Failed to commit duplicate removal: {}", e))?;
+        Ok(self.push_undo(rows, Some((playlist_id, updated_at))))
+    }
 
-        tx.execute(
-            "UPDATE playlist_songs SET position = ?1 WHERE id = ?2 AND playlist_id = ?3",
-            params![other_position, song_id, playlist_id],
-        )
-        .map_err(|e| format!("Failed to update song position: {}", e))?;
+    /// Move a song to `new_index` within a playlist and renumber every position from zero
+    pub fn move_playlist_song(
+        &self,
+        playlist_id: i64,
+        song_id: i64,
+        new_index: usize,
+    ) -> Result<(), String> {
+        let mut conn = self.conn.borrow_mut();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
 
-        tx.execute(
-            "UPDATE playlist_songs SET position = ?1 WHERE id = ?2 AND playlist_id = ?3",
-            params![position, other_song_id, playlist_id],
-        )
-        .map_err(|e| format!("Failed to update other song position: {}", e))?;
+        let mut song_ids: Vec<i64> = {
+            let mut stmt = tx
+                .prepare("SELECT id FROM playlist_songs WHERE playlist_id = ?1 ORDER BY position ASC, id ASC")
+                .map_err(|e| format!("Failed to prepare query: {}", e))?;
+            let ids = stmt
+                .query_map(params![playlist_id], |row| row.get(0))
+                .map_err(|e| format!("Failed to query playlist songs: {}", e))?;
+            ids.collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("Failed to collect playlist songs: {}", e))?
+        };
+
+        let current_index = song_ids
+            .iter()
+            .position(|id| *id == song_id)
+            .ok_or_else(|| "Song is not in the playlist".to_string())?;
+        song_ids.remove(current_index);
+        song_ids.insert(new_index.min(song_ids.len()), song_id);
+
+        {
+            let mut stmt = tx
+                .prepare("UPDATE playlist_songs SET position = ?1 WHERE id = ?2 AND playlist_id = ?3")
+                .map_err(|e| format!("Failed to prepare update: {}", e))?;
+            for (position, id) in song_ids.iter().enumerate() {
+                stmt.execute(params![position as i64, id, playlist_id])
+                    .map_err(|e| format!("Failed to update song position: {}", e))?;
+            }
+        }
 
         tx.commit()
             .map_err(|e| format!("Failed to commit song reorder: {}", e))?;
@@ -504,6 +1468,22 @@ impl Database {
         ).unwrap_or(0)
     }
 
+    /// Thumbnails of the first songs in a playlist, used for its cover collage
+    pub fn get_playlist_thumbnails(&self, playlist_id: i64, limit: usize) -> Vec<String> {
+        let conn = self.conn.borrow();
+        let Ok(mut stmt) = conn.prepare(
+            "SELECT thumbnail_url FROM playlist_songs
+             WHERE playlist_id = ?1 AND thumbnail_url IS NOT NULL AND thumbnail_url != ''
+             ORDER BY position ASC LIMIT ?2"
+        ) else {
+            return Vec::new();
+        };
+
+        stmt.query_map(params![playlist_id, limit as i64], |row| row.get(0))
+            .and_then(|rows| rows.collect::<Result<Vec<String>, _>>())
+            .unwrap_or_default()
+    }
+
     /// Check if a song is in a playlist
     pub fn is_song_in_playlist(&self, playlist_id: i64, video_id: &str) -> bool {
         let conn = self.conn.borrow();
@@ -513,4 +1493,1566 @@ impl Database {
             |_| Ok(()),
         ).is_ok()
     }
+
+    // ==================== Smart Playlists ====================
+
+    /// Create a smart playlist from rules
+    pub fn create_smart_playlist(
+        &self,
+        name: &str,
+        rules: &[SmartRule],
+        match_all: bool,
+    ) -> Result<SmartPlaylist, String> {
+        let conn = self.conn.borrow();
+        let created_at = Self::current_timestamp();
+
+        conn.execute(
+            "INSERT INTO smart_playlists (name, rules, match_all, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![name, rules_to_json(rules), match_all, created_at],
+        ).map_err(|e| format!("Failed to create smart playlist: {}", e))?;
+
+        Ok(SmartPlaylist {
+            id: conn.last_insert_rowid(),
+            name: name.to_string(),
+            rules: rules.to_vec(),
+            match_all,
+            created_at,
+        })
+    }
+
+    /// Replace the name and rules of a smart playlist
+    pub fn update_smart_playlist(
+        &self,
+        smart_playlist_id: i64,
+        name: &str,
+        rules: &[SmartRule],
+        match_all: bool,
+    ) -> Result<(), String> {
+        let conn = self.conn.borrow();
+        conn.execute(
+            "UPDATE smart_playlists SET name = ?1, rules = ?2, match_all = ?3 WHERE id = ?4",
+            params![name, rules_to_json(rules), match_all, smart_playlist_id],
+        ).map_err(|e| format!("Failed to update smart playlist: {}", e))?;
+        Ok(())
+    }
+
+    /// Delete a smart playlist
+    pub fn delete_smart_playlist(&self, smart_playlist_id: i64) -> Result<(), String> {
+        let conn = self.conn.borrow();
+        conn.execute(
+            "DELETE FROM smart_playlists WHERE id = ?1",
+            params![smart_playlist_id],
+        ).map_err(|e| format!("Failed to delete smart playlist: {}", e))?;
+        Ok(())
+    }
+
+    /// Get all smart playlists
+    pub fn get_smart_playlists(&self) -> Result<Vec<SmartPlaylist>, String> {
+        let conn = self.conn.borrow();
+        let mut stmt = conn.prepare(
+            "SELECT id, name, rules, match_all, created_at FROM smart_playlists ORDER BY created_at DESC"
+        ).map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let playlists = stmt.query_map([], |row| {
+            Ok(SmartPlaylist {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                rules: rules_from_json(&row.get::<_, String>(2)?),
+                match_all: row.get(3)?,
+                created_at: row.get(4)?,
+            })
+        }).map_err(|e| format!("Failed to query smart playlists: {}", e))?;
+
+        playlists.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect smart playlists: {}", e))
+    }
+
+    /// Get smart playlist by ID
+    pub fn get_smart_playlist(&self, smart_playlist_id: i64) -> Result<Option<SmartPlaylist>, String> {
+        let conn = self.conn.borrow();
+        conn.query_row(
+            "SELECT id, name, rules, match_all, created_at FROM smart_playlists WHERE id = ?1",
+            params![smart_playlist_id],
+            |row| {
+                Ok(SmartPlaylist {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    rules: rules_from_json(&row.get::<_, String>(2)?),
+                    match_all: row.get(3)?,
+                    created_at: row.get(4)?,
+                })
+            },
+        ).optional().map_err(|e| format!("Failed to get smart playlist: {}", e))
+    }
+
+    /// Evaluate a smart playlist over every known song, most recently played or liked first
+    pub fn get_smart_playlist_songs(&self, smart_playlist: &SmartPlaylist) -> Result<Vec<Song>, String> {
+        if smart_playlist.rules.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let now = Self::current_timestamp();
+        let mut conditions = Vec::with_capacity(smart_playlist.rules.len());
+        let mut values = Vec::new();
+        for rule in &smart_playlist.rules {
+            let (condition, value) = rule.to_sql(now);
+            conditions.push(format!("({})", condition));
+            values.extend(value);
+        }
+        let joiner = if smart_playlist.match_all { " AND " } else { " OR " };
+
+        let sql = format!(
+            "WITH library_tracks AS ({})
+             SELECT t.video_id, t.title, t.artist, t.duration, t.thumbnail_url
+             FROM library_tracks t
+             LEFT JOIN liked_songs l ON l.video_id = t.video_id
+             LEFT JOIN play_stats s ON s.video_id = t.video_id
+             WHERE {}
+             ORDER BY MAX(COALESCE(s.last_played_at, 0), COALESCE(l.liked_at, 0)) DESC, t.title COLLATE NOCASE",
+            LIBRARY_TRACKS_SQL,
+            conditions.join(joiner)
+        );
+
+        let conn = self.conn.borrow();
+        let mut stmt = conn.prepare(&sql)
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let songs = stmt.query_map(params_from_iter(values), |row| {
+            Ok(Song {
+                video_id: row.get(0)?,
+                title: row.get(1)?,
+                artist: row.get(2)?,
+                duration: row.get(3)?,
+                thumbnail_url: row.get(4)?,
+            })
+        }).map_err(|e| format!("Failed to query smart playlist songs: {}", e))?;
+
+        songs.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect smart playlist songs: {}", e))
+    }
+
+    // ==================== Library Search ====================
+
+    /// Search titles and artists of every stored song, best match first
+    pub fn search_library(&self, query: &str, limit: usize) -> Result<Vec<Song>, String> {
+        let Some(fts_query) = fts_query(query) else {
+            return Ok(Vec::new());
+        };
+
+        let conn = self.conn.borrow();
+        let mut stmt = conn.prepare(&format!(
+            "WITH library_tracks AS ({})
+             SELECT t.video_id, t.title, t.artist, t.duration, t.thumbnail_url
+             FROM library_fts f
+             JOIN library_tracks t ON t.video_id = f.video_id
+             WHERE library_fts MATCH ?1
+             ORDER BY f.rank
+             LIMIT ?2",
+            LIBRARY_TRACKS_SQL
+        )).map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let songs = stmt.query_map(params![fts_query, limit as i64], |row| {
+            Ok(Song {
+                video_id: row.get(0)?,
+                title: row.get(1)?,
+                artist: row.get(2)?,
+                duration: row.get(3)?,
+                thumbnail_url: row.get(4)?,
+            })
+        }).map_err(|e| format!("Failed to search library: {}", e))?;
+
+        songs.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect library results: {}", e))
+    }
+
+    /// Video IDs of every stored song matching a search query
+    pub fn search_library_ids(&self, query: &str) -> Result<HashSet<String>, String> {
+        let Some(fts_query) = fts_query(query) else {
+            return Ok(HashSet::new());
+        };
+
+        let conn = self.conn.borrow();
+        let mut stmt = conn.prepare(
+            "SELECT video_id FROM library_fts WHERE library_fts MATCH ?1"
+        ).map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let ids = stmt.query_map(params![fts_query], |row| row.get(0))
+            .map_err(|e| format!("Failed to search library: {}", e))?;
+
+        ids.collect::<Result<HashSet<_>, _>>()
+            .map_err(|e| format!("Failed to collect library results: {}", e))
+    }
+
+    // ==================== Settings ====================
+
+    /// Read an application setting
+    pub fn get_setting(&self, key: &str) -> Option<String> {
+        let conn = self.conn.borrow();
+        conn.query_row(
+            "SELECT value FROM app_settings WHERE key = ?1",
+            params![key],
+            |row| row.get(0),
+        ).ok()
+    }
+
+    /// Store an application setting
+    pub fn set_setting(&self, key: &str, value: &str) -> Result<(), String> {
+        let conn = self.conn.borrow();
+        conn.execute(
+            "INSERT OR REPLACE INTO app_settings (key, value) VALUES (?1, ?2)",
+            params![key, value],
+        ).map_err(|e| format!("Failed to save setting: {}", e))?;
+        Ok(())
+    }
+
+    // ==================== Downloads ====================
+
+    /// Queue songs for download, retrying failed ones; returns how many were queued
+    pub fn queue_downloads(&self, songs: &[Song]) -> Result<usize, String> {
+        let mut conn = self.conn.borrow_mut();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        let mut queued = 0;
+        for song in songs {
+            queued += tx.execute(
+                "INSERT OR IGNORE INTO downloads (video_id, title, artist, duration, thumbnail_url, status, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    song.video_id,
+                    song.title,
+                    song.artist,
+                    song.duration,
+                    song.thumbnail_url,
+                    DownloadStatus::Queued.key(),
+                    Self::current_timestamp()
+                ],
+            ).map_err(|e| format!("Failed to queue download: {}", e))?;
+            queued += tx.execute(
+                "UPDATE downloads SET status = ?1, error = NULL WHERE video_id = ?2 AND status = ?3",
+                params![DownloadStatus::Queued.key(), song.video_id, DownloadStatus::Failed.key()],
+            ).map_err(|e| format!("Failed to queue download: {}", e))?;
+        }
+
+        tx.commit()
+            .map_err(|e| format!("Failed to queue downloads: {}", e))?;
+        Ok(queued)
+    }
+
+    /// Move a download to another state, clearing any earlier error
+    pub fn set_download_status(&self, video_id: &str, status: DownloadStatus) -> Result<(), String> {
+        let conn = self.conn.borrow();
+        conn.execute(
+            "UPDATE downloads SET status = ?1, error = NULL WHERE video_id = ?2",
+            params![status.key(), video_id],
+        ).map_err(|e| format!("Failed to update download: {}", e))?;
+        Ok(())
+    }
+
+    /// Record how much of a download has been written so far
+    pub fn set_download_progress(
+        &self,
+        video_id: &str,
+        downloaded_bytes: i64,
+        total_bytes: Option<i64>,
+    ) -> Result<(), String> {
+        let conn = self.conn.borrow();
+        conn.execute(
+            "UPDATE downloads SET downloaded_bytes = ?1, total_bytes = COALESCE(?2, total_bytes)
+             WHERE video_id = ?3",
+            params![downloaded_bytes, total_bytes, video_id],
+        ).map_err(|e| format!("Failed to update download: {}", e))?;
+        Ok(())
+    }
+
+    /// Mark a download as done with the file it was saved to
+    pub fn finish_download(&self, video_id: &str, file_path: &str, size: i64) -> Result<(), String> {
+        let conn = self.conn.borrow();
+        conn.execute(
+            "UPDATE downloads SET status = ?1, file_path = ?2, downloaded_bytes = ?3, total_bytes = ?3, error = NULL
+             WHERE video_id = ?4",
+            params![DownloadStatus::Done.key(), file_path, size, video_id],
+        ).map_err(|e| format!("Failed to update download: {}", e))?;
+        Ok(())
+    }
+
+    /// Mark a download as failed
+    pub fn fail_download(&self, video_id: &str, error: &str) -> Result<(), String> {
+        let conn = self.conn.borrow();
+        conn.execute(
+            "UPDATE downloads SET status = ?1, error = ?2 WHERE video_id = ?3",
+            params![DownloadStatus::Failed.key(), error, video_id],
+        ).map_err(|e| format!("Failed to update download: {}", e))?;
+        Ok(())
+    }
+
+    /// Get every download, oldest first
+    pub fn get_downloads(&self) -> Result<Vec<Download>, String> {
+        let conn = self.conn.borrow();
+        let mut stmt = conn.prepare(
+            "SELECT video_id, title, artist, duration, thumbnail_url, status, file_path,
+                    downloaded_bytes, total_bytes, error, created_at
+             FROM downloads ORDER BY created_at ASC, rowid ASC"
+        ).map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let downloads = stmt.query_map([], download_from_row)
+            .map_err(|e| format!("Failed to query downloads: {}", e))?;
+
+        downloads.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect downloads: {}", e))
+    }
+
+    /// Get a single download
+    pub fn get_download(&self, video_id: &str) -> Result<Option<Download>, String> {
+        let conn = self.conn.borrow();
+        conn.query_row(
+            "SELECT video_id, title, artist, duration, thumbnail_url, status, file_path,
+                    downloaded_bytes, total_bytes, error, created_at
+             FROM downloads WHERE video_id = ?1",
+            params![video_id],
+            download_from_row,
+        )
+        .optional()
+        .map_err(|e| format!("Failed to get download: {}", e))
+    }
+
+    /// Audio file of a finished download, if it is still on disk
+    pub fn downloaded_file(&self, video_id: &str) -> Option<PathBuf> {
+        let conn = self.conn.borrow();
+        let path: Option<String> = conn.query_row(
+            "SELECT file_path FROM downloads WHERE video_id = ?1 AND status = ?2",
+            params![video_id, DownloadStatus::Done.key()],
+            |row| row.get(0),
+        ).ok().flatten();
+        path.map(PathBuf::from).filter(|path| path.is_file())
+    }
+
+    /// Forget a download, returning it so its files can be removed
+    pub fn delete_download(&self, video_id: &str) -> Result<Option<Download>, String> {
+        let download = self.get_download(video_id)?;
+        let conn = self.conn.borrow();
+        conn.execute("DELETE FROM downloads WHERE video_id = ?1", params![video_id])
+            .map_err(|e| format!("Failed to delete download: {}", e))?;
+        Ok(download)
+    }
+
+    /// Video IDs of finished downloads
+    pub fn get_downloaded_ids(&self) -> HashSet<String> {
+        let conn = self.conn.borrow();
+        let Ok(mut stmt) = conn.prepare("SELECT video_id FROM downloads WHERE status = ?1") else {
+            return HashSet::new();
+        };
+        stmt.query_map(params![DownloadStatus::Done.key()], |row| row.get(0))
+            .map(|ids| ids.filter_map(Result::ok).collect())
+            .unwrap_or_default()
+    }
+
+    /// Bytes written by all downloads, finished or not
+    pub fn get_downloads_size(&self) -> i64 {
+        let conn = self.conn.borrow();
+        conn.query_row(
+            "SELECT COALESCE(SUM(downloaded_bytes), 0) FROM downloads",
+            [],
+            |row| row.get(0),
+        ).unwrap_or(0)
+    }
+
+    // ==================== Track Info ====================
+
+    /// Remember a song's metadata and lyrics for offline playback
+    pub fn save_track_info(&self, video_id: &str, info: &TrackInfo) -> Result<(), String> {
+        let conn = self.conn.borrow();
+        conn.execute(
+            "INSERT OR REPLACE INTO track_info (video_id, title, artist, thumbnail_url, lyrics, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                video_id,
+                info.title,
+                info.artist,
+                info.thumbnail_url,
+                info.lyrics,
+                Self::current_timestamp()
+            ],
+        ).map_err(|e| format!("Failed to save track info: {}", e))?;
+        Ok(())
+    }
+
+    /// Metadata and lyrics remembered for a song
+    pub fn get_track_info(&self, video_id: &str) -> Option<TrackInfo> {
+        let conn = self.conn.borrow();
+        conn.query_row(
+            "SELECT title, artist, thumbnail_url, lyrics FROM track_info WHERE video_id = ?1",
+            params![video_id],
+            |row| {
+                Ok(TrackInfo {
+                    title: row.get(0)?,
+                    artist: row.get(1)?,
+                    thumbnail_url: row.get(2)?,
+                    lyrics: row.get(3)?,
+                })
+            },
+        ).ok()
+    }
+
+    // ==================== Playback Rates ====================
+
+    /// Speed a long track was last played at
+    pub fn get_track_rate(&self, video_id: &str) -> Option<f64> {
+        let conn = self.conn.borrow();
+        conn.query_row(
+            "SELECT rate FROM track_rates WHERE video_id = ?1",
+            params![video_id],
+            |row| row.get(0),
+        ).ok()
+    }
+
+    /// Remember the speed a long track is played at
+    pub fn set_track_rate(&self, video_id: &str, rate: f64) -> Result<(), String> {
+        let conn = self.conn.borrow();
+        conn.execute(
+            "INSERT OR REPLACE INTO track_rates (video_id, rate) VALUES (?1, ?2)",
+            params![video_id, rate],
+        ).map_err(|e| format!("Failed to save playback speed: {}", e))?;
+        Ok(())
+    }
+
+    // ==================== Equalizer Presets ====================
+
+    /// Presets the user saved, by name, with the gain of each band in dB
+    pub fn get_eq_presets(&self) -> Result<Vec<(String, Vec<f64>)>, String> {
+        let conn = self.conn.borrow();
+        let mut stmt = conn
+            .prepare("SELECT name, gains FROM eq_presets ORDER BY name COLLATE NOCASE")
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        let presets = stmt
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+            .map_err(|e| format!("Failed to query equalizer presets: {}", e))?
+            .filter_map(|row| row.ok())
+            .map(|(name, gains)| (name, serde_json::from_str(&gains).unwrap_or_default()))
+            .collect();
+        Ok(presets)
+    }
+
+    /// Save a preset, replacing the one of the same name
+    pub fn save_eq_preset(&self, name: &str, gains: &[f64]) -> Result<(), String> {
+        let conn = self.conn.borrow();
+        conn.execute(
+            "INSERT OR REPLACE INTO eq_presets (name, gains) VALUES (?1, ?2)",
+            params![name, json!(gains).to_string()],
+        ).map_err(|e| format!("Failed to save equalizer preset: {}", e))?;
+        Ok(())
+    }
+
+    /// Delete a preset; playlists that used it go back to the equalizer page's choice
+    pub fn delete_eq_preset(&self, name: &str) -> Result<(), String> {
+        let conn = self.conn.borrow();
+        conn.execute("DELETE FROM eq_presets WHERE name = ?1", params![name])
+            .map_err(|e| format!("Failed to delete equalizer preset: {}", e))?;
+        conn.execute("UPDATE playlists SET eq_preset = NULL WHERE eq_preset = ?1", params![name])
+            .map_err(|e| format!("Failed to update playlists: {}", e))?;
+        Ok(())
+    }
+
+    // ==================== Local Library ====================
+
+    /// Folders scanned for local music
+    pub fn get_local_folders(&self) -> Vec<PathBuf> {
+        self.get_setting(LOCAL_FOLDERS_SETTING)
+            .and_then(|data| serde_json::from_str::<Vec<String>>(&data).ok())
+            .map(|folders| folders.into_iter().map(PathBuf::from).collect())
+            .unwrap_or_default()
+    }
+
+    /// Replace the folders scanned for local music
+    pub fn set_local_folders(&self, folders: &[PathBuf]) -> Result<(), String> {
+        let folders: Vec<String> = folders
+            .iter()
+            .map(|folder| folder.to_string_lossy().into_owned())
+            .collect();
+        self.set_setting(LOCAL_FOLDERS_SETTING, &json!(folders).to_string())
+    }
+
+    /// Modification time and size of every local track, keyed by path
+    pub fn get_local_track_stamps(&self) -> HashMap<String, (i64, i64)> {
+        let conn = self.conn.borrow();
+        let Ok(mut stmt) = conn.prepare("SELECT file_path, mtime, size FROM tracks WHERE source = ?1") else {
+            return HashMap::new();
+        };
+        stmt.query_map(params![SongSource::Local.key()], |row| {
+            Ok((row.get(0)?, (row.get(1)?, row.get(2)?)))
+        })
+        .map(|stamps| stamps.filter_map(Result::ok).collect())
+        .unwrap_or_default()
+    }
+
+    /// Store the tracks a scan read and drop the ones whose files are gone.
+    /// `present` holds the path of every file the scan found, changed or not.
+    pub fn apply_local_scan(
+        &self,
+        scanned: &[LocalTrack],
+        present: &HashSet<String>,
+    ) -> Result<LocalScanSummary, String> {
+        let known = self.get_local_track_stamps();
+        let mut conn = self.conn.borrow_mut();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        let mut summary = LocalScanSummary::default();
+        let now = Self::current_timestamp();
+        for track in scanned {
+            tx.execute(
+                "INSERT OR REPLACE INTO tracks
+                    (video_id, source, file_path, title, artist, album, duration, thumbnail_url, mtime, size, scanned_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                params![
+                    track.song.video_id,
+                    SongSource::Local.key(),
+                    track.file_path,
+                    track.song.title,
+                    track.song.artist,
+                    track.album,
+                    track.song.duration,
+                    track.song.thumbnail_url,
+                    track.mtime,
+                    track.size,
+                    now
+                ],
+            ).map_err(|e| format!("Failed to save local track: {}", e))?;
+            if known.contains_key(&track.file_path) {
+                summary.updated += 1;
+            } else {
+                summary.added += 1;
+            }
+        }
+
+        for path in known.keys().filter(|path| !present.contains(*path)) {
+            summary.removed += tx.execute(
+                "DELETE FROM tracks WHERE file_path = ?1",
+                params![path],
+            ).map_err(|e| format!("Failed to remove local track: {}", e))?;
+        }
+
+        tx.commit()
+            .map_err(|e| format!("Failed to save local tracks: {}", e))?;
+        Ok(summary)
+    }
+
+    /// Get every local track, sorted by artist, album and title
+    pub fn get_local_tracks(&self) -> Result<Vec<LocalTrack>, String> {
+        let conn = self.conn.borrow();
+        let mut stmt = conn.prepare(
+            "SELECT video_id, title, artist, duration, thumbnail_url, album, file_path, mtime, size
+             FROM tracks WHERE source = ?1
+             ORDER BY artist COLLATE NOCASE, album COLLATE NOCASE, title COLLATE NOCASE"
+        ).map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let tracks = stmt.query_map(params![SongSource::Local.key()], local_track_from_row)
+            .map_err(|e| format!("Failed to query local tracks: {}", e))?;
+
+        tracks.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect local tracks: {}", e))
+    }
+
+    /// Get a single local track
+    pub fn get_local_track(&self, video_id: &str) -> Option<LocalTrack> {
+        let conn = self.conn.borrow();
+        conn.query_row(
+            "SELECT video_id, title, artist, duration, thumbnail_url, album, file_path, mtime, size
+             FROM tracks WHERE video_id = ?1",
+            params![video_id],
+            local_track_from_row,
+        ).ok()
+    }
+
+    /// Audio file on disk for a song: the file of a local track, or a finished download
+    pub fn local_file(&self, video_id: &str) -> Option<PathBuf> {
+        match local_track_path(video_id) {
+            Some(path) => path.is_file().then(|| path.to_path_buf()),
+            None => self.downloaded_file(video_id),
+        }
+    }
+
+    // ==================== Subsonic ====================
+
+    /// The Subsonic server set up in Settings, if any
+    pub fn get_subsonic_credentials(&self) -> Option<SubsonicCredentials> {
+        let data = self.get_setting(SUBSONIC_SERVER_SETTING)?;
+        let value: Value = serde_json::from_str(&data).ok()?;
+        let text = |key: &str| value.get(key).and_then(|value| value.as_str()).map(String::from);
+        Some(SubsonicCredentials {
+            server_url: text("server_url")?,
+            username: text("username")?,
+            token: text("token")?,
+            salt: text("salt")?,
+        })
+    }
+
+    /// Save the Subsonic server to connect to, or forget it
+    pub fn set_subsonic_credentials(&self, credentials: Option<&SubsonicCredentials>) -> Result<(), String> {
+        let Some(credentials) = credentials else {
+            let conn = self.conn.borrow();
+            conn.execute("DELETE FROM app_settings WHERE key = ?1", params![SUBSONIC_SERVER_SETTING])
+                .map_err(|e| format!("Failed to save setting: {}", e))?;
+            return Ok(());
+        };
+        let data = json!({
+            "server_url": credentials.server_url,
+            "username": credentials.username,
+            "token": credentials.token,
+            "salt": credentials.salt,
+        });
+        self.set_setting(SUBSONIC_SERVER_SETTING, &data.to_string())
+    }
+
+    // ==================== Undo ====================
+
+    /// Keep removed rows for undo, dropping entries older than the retention window
+    fn push_undo(&self, rows: Vec<TrashedRow>, playlist: Option<(i64, i64)>) -> UndoId {
+        let id = UndoId(self.next_undo_id.get());
+        self.next_undo_id.set(id.0 + 1);
+
+        let now = Self::current_timestamp();
+        let mut log = self.undo_log.borrow_mut();
+        log.retain(|entry| now - entry.created_at < UNDO_RETENTION_SECS);
+        log.push(UndoEntry {
+            id,
+            created_at: now,
+            rows,
+            playlist,
+        });
+        id
+    }
+
+    /// Put back the exact rows removed by an action, including ids, positions and timestamps
+    pub fn undo(&self, id: UndoId) -> Result<(), String> {
+        let mut log = self.undo_log.borrow_mut();
+        let index = log
+            .iter()
+            .position(|entry| entry.id == id)
+            .ok_or_else(|| "This change can no longer be undone".to_string())?;
+
+        self.restore(&log[index])?;
+        // Forget the rows only once they are safely back, so a failed undo can be retried
+        log.remove(index);
+        Ok(())
+    }
+
+    fn restore(&self, entry: &UndoEntry) -> Result<(), String> {
+        let mut conn = self.conn.borrow_mut();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        for row in &entry.rows {
+            let placeholders = vec!["?"; row.values.len()].join(", ");
+            tx.execute(
+                &format!(
+                    "INSERT OR REPLACE INTO {} ({}) VALUES ({})",
+                    row.table,
+                    row.columns.join(", "),
+                    placeholders
+                ),
+                params_from_iter(row.values.iter()),
+            ).map_err(|e| format!("Failed to restore {}: {}", row.table, e))?;
+        }
+
+        // Inserting songs touches the playlist, so put its modification time back last
+        if let Some((playlist_id, updated_at)) = entry.playlist {
+            let changed = tx.execute(
+                "UPDATE playlists SET updated_at = ?1 WHERE id = ?2",
+                params![updated_at, playlist_id],
+            ).map_err(|e| format!("Failed to restore playlist: {}", e))?;
+            if changed == 0 {
+                return Err("The playlist no longer exists".to_string());
+            }
+        }
+
+        tx.commit()
+            .map_err(|e| format!("Failed to commit undo: {}", e))?;
+        Ok(())
+    }
+
+    // ==================== Backup ====================
+
+    /// Serialize liked songs, plays, playlists and smart playlists into a versioned JSON document
+    pub fn export_json(&self) -> Result<String, String> {
+        let liked_songs: Vec<Value> = self
+            .get_liked_songs()?
+            .iter()
+            .map(|liked| {
+                let mut value = song_to_json(&liked.song);
+                value["liked_at"] = json!(liked.liked_at);
+                value
+            })
+            .collect();
+
+        let recent_plays: Vec<Value> = self
+            .get_recent_plays()?
+            .iter()
+            .map(|play| {
+                let mut value = song_to_json(&play.song);
+                value["played_at"] = json!(play.played_at);
+                value
+            })
+            .collect();
+
+        // Playlists refer to their folder by its position in this list
+        let folders = self.get_folders()?;
+        let folder_index = |folder_id: Option<i64>| {
+            folder_id.and_then(|id| folders.iter().position(|folder| folder.id == id))
+        };
+
+        let mut playlists = Vec::new();
+        for playlist in self.get_playlists()? {
+            let songs: Vec<Value> = self
+                .get_playlist_songs(playlist.id)?
+                .iter()
+                .map(|playlist_song| song_to_json(&playlist_song.song))
+                .collect();
+            playlists.push(json!({
+                "name": playlist.name,
+                "description": playlist.description,
+                "cover": playlist.cover_path.as_deref().and_then(cover_to_json),
+                "folder": folder_index(playlist.folder_id),
+                "pinned": playlist.pinned,
+                "created_at": playlist.created_at,
+                "songs": songs,
+            }));
+        }
+
+        let play_stats = self.export_play_stats()?;
+
+        let smart_playlists: Vec<Value> = self
+            .get_smart_playlists()?
+            .iter()
+            .map(|smart_playlist| {
+                json!({
+                    "name": smart_playlist.name,
+                    "rules": serde_json::from_str::<Value>(&rules_to_json(&smart_playlist.rules))
+                        .unwrap_or_default(),
+                    "match_all": smart_playlist.match_all,
+                    "created_at": smart_playlist.created_at,
+                })
+            })
+            .collect();
+
+        let document = json!({
+            "format": LIBRARY_EXPORT_FORMAT,
+            "version": LIBRARY_EXPORT_VERSION,
+            "exported_at": Self::current_timestamp(),
+            "liked_songs": liked_songs,
+            "recent_plays": recent_plays,
+            "play_stats": play_stats,
+            "folders": folders
+                .iter()
+                .map(|folder| json!({
+                    "name": folder.name,
+                    "expanded": folder.expanded,
+                    "created_at": folder.created_at,
+                }))
+                .collect::<Vec<_>>(),
+            "playlists": playlists,
+            "playlist_sort": self.get_playlist_sort().key(),
+            "smart_playlists": smart_playlists,
+        });
+
+        serde_json::to_string_pretty(&document)
+            .map_err(|e| format!("Failed to serialize library: {}", e))
+    }
+
+    fn export_play_stats(&self) -> Result<Vec<Value>, String> {
+        let conn = self.conn.borrow();
+        let mut stmt = conn.prepare(
+            "SELECT video_id, title, artist, duration, thumbnail_url, play_count, last_played_at
+             FROM play_stats ORDER BY last_played_at DESC"
+        ).map_err(|e| format!("Failed to prepare query: {}", e))?;
+
+        let stats = stmt.query_map([], |row| {
+            let song = Song {
+                video_id: row.get(0)?,
+                title: row.get(1)?,
+                artist: row.get(2)?,
+                duration: row.get(3)?,
+                thumbnail_url: row.get(4)?,
+            };
+            let mut value = song_to_json(&song);
+            value["play_count"] = json!(row.get::<_, i64>(5)?);
+            value["last_played_at"] = json!(row.get::<_, i64>(6)?);
+            Ok(value)
+        }).map_err(|e| format!("Failed to query play stats: {}", e))?;
+
+        stats.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect play stats: {}", e))
+    }
+
+    /// Import a document produced by `export_json` in a single transaction
+    pub fn import_json(&self, data: &str, mode: ImportMode) -> Result<ImportSummary, String> {
+        let document: Value = serde_json::from_str(data)
+            .map_err(|e| format!("Invalid library file: {}", e))?;
+
+        if document.get("format").and_then(Value::as_str) != Some(LIBRARY_EXPORT_FORMAT) {
+            return Err("Not a Musika library export".to_string());
+        }
+        let version = document.get("version").and_then(Value::as_i64).unwrap_or(0);
+        if !(1..=LIBRARY_EXPORT_VERSION).contains(&version) {
+            return Err(format!("Unsupported library export version: {}", version));
+        }
+
+        let mut conn = self.conn.borrow_mut();
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+        let mut summary = ImportSummary::default();
+
+        if mode == ImportMode::Replace {
+            tx.execute_batch(
+                "DELETE FROM playlist_songs;
+                 DELETE FROM playlists;
+                 DELETE FROM playlist_folders;
+                 DELETE FROM liked_songs;
+                 DELETE FROM recent_plays;"
+            ).map_err(|e| format!("Failed to clear library: {}", e))?;
+
+            // Older exports carry no play counts, so those survive replacing from them
+            if version >= EXPORT_VERSION_PLAY_STATS {
+                tx.execute_batch(
+                    "DELETE FROM play_stats;
+                     DELETE FROM smart_playlists;"
+                ).map_err(|e| format!("Failed to clear play stats: {}", e))?;
+            }
+        }
+
+        for item in json_array(&document, "liked_songs") {
+            let Some(song) = song_from_json(item) else {
+                continue;
+            };
+            let liked_at = item
+                .get("liked_at")
+                .and_then(Value::as_i64)
+                .unwrap_or_else(Self::current_timestamp);
+            summary.liked_songs += tx.execute(
+                "INSERT OR IGNORE INTO liked_songs (video_id, title, artist, duration, thumbnail_url, liked_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    song.video_id,
+                    song.title,
+                    song.artist,
+                    song.duration,
+                    song.thumbnail_url,
+                    liked_at
+                ],
+            ).map_err(|e| format!("Failed to import liked song: {}", e))?;
+        }
+
+        for item in json_array(&document, "recent_plays") {
+            let Some(song) = song_from_json(item) else {
+                continue;
+            };
+            let played_at = item.get("played_at").and_then(Value::as_i64).unwrap_or(0);
+
+            // Keep whichever play of the song is newer
+            let has_newer: bool = tx.query_row(
+                "SELECT EXISTS(SELECT 1 FROM recent_plays WHERE video_id = ?1 AND played_at >= ?2)",
+                params![song.video_id, played_at],
+                |row| row.get(0),
+            ).map_err(|e| format!("Failed to check recent play: {}", e))?;
+            if has_newer {
+                continue;
+            }
+
+            tx.execute(
+                "DELETE FROM recent_plays WHERE video_id = ?1",
+                params![song.video_id],
+            ).map_err(|e| format!("Failed to remove existing recent play: {}", e))?;
+            tx.execute(
+                "INSERT INTO recent_plays (video_id, title, artist, duration, thumbnail_url, played_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    song.video_id,
+                    song.title,
+                    song.artist,
+                    song.duration,
+                    song.thumbnail_url,
+                    played_at
+                ],
+            ).map_err(|e| format!("Failed to import recent play: {}", e))?;
+            summary.recent_plays += 1;
+        }
+
+        tx.execute(
+            "DELETE FROM recent_plays WHERE id NOT IN (
+                SELECT id FROM recent_plays ORDER BY played_at DESC LIMIT 50
+            )",
+            [],
+        ).map_err(|e| format!("Failed to clean recent plays: {}", e))?;
+
+        for item in json_array(&document, "play_stats") {
+            let Some(song) = song_from_json(item) else {
+                continue;
+            };
+            let play_count = item.get("play_count").and_then(Value::as_i64).unwrap_or(0);
+            let last_played_at = item.get("last_played_at").and_then(Value::as_i64).unwrap_or(0);
+
+            // Merging an export of this same library must not count its plays twice
+            tx.execute(
+                "INSERT INTO play_stats (video_id, title, artist, duration, thumbnail_url, play_count, last_played_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                 ON CONFLICT(video_id) DO UPDATE SET
+                     play_count = MAX(play_stats.play_count, excluded.play_count),
+                     last_played_at = MAX(play_stats.last_played_at, excluded.last_played_at)",
+                params![
+                    song.video_id,
+                    song.title,
+                    song.artist,
+                    song.duration,
+                    song.thumbnail_url,
+                    play_count,
+                    last_played_at
+                ],
+            ).map_err(|e| format!("Failed to import play stats: {}", e))?;
+        }
+
+        // When merging, playlists go into a folder of the same name if there is one
+        let mut folder_ids = Vec::new();
+        for item in json_array(&document, "folders") {
+            let Some(name) = item
+                .get("name")
+                .and_then(Value::as_str)
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+            else {
+                folder_ids.push(None);
+                continue;
+            };
+
+            let existing: Option<i64> = if mode == ImportMode::Merge {
+                tx.query_row(
+                    "SELECT id FROM playlist_folders WHERE name = ?1 ORDER BY created_at ASC LIMIT 1",
+                    params![name],
+                    |row| row.get(0),
+                ).optional().map_err(|e| format!("Failed to look up folder: {}", e))?
+            } else {
+                None
+            };
+
+            let folder_id = match existing {
+                Some(id) => id,
+                None => {
+                    let expanded = item.get("expanded").and_then(Value::as_bool).unwrap_or(true);
+                    let created_at = item
+                        .get("created_at")
+                        .and_then(Value::as_i64)
+                        .unwrap_or_else(Self::current_timestamp);
+                    tx.execute(
+                        "INSERT INTO playlist_folders (name, expanded, created_at) VALUES (?1, ?2, ?3)",
+                        params![name, expanded, created_at],
+                    ).map_err(|e| format!("Failed to import folder: {}", e))?;
+                    tx.last_insert_rowid()
+                }
+            };
+            folder_ids.push(Some(folder_id));
+        }
+
+        for item in json_array(&document, "playlists") {
+            let Some(name) = item
+                .get("name")
+                .and_then(Value::as_str)
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+            else {
+                continue;
+            };
+            let created_at = item
+                .get("created_at")
+                .and_then(Value::as_i64)
+                .unwrap_or_else(Self::current_timestamp);
+            let description = item.get("description").and_then(Value::as_str).unwrap_or("");
+            let cover = item.get("cover").and_then(cover_from_json);
+            // Exports before version 4 only point at a file, which may be missing on this machine
+            let cover_path = item
+                .get("cover_path")
+                .and_then(Value::as_str)
+                .filter(|path| Path::new(path).is_file());
+            let folder_id = item
+                .get("folder")
+                .and_then(Value::as_u64)
+                .and_then(|index| folder_ids.get(index as usize).copied().flatten());
+            let pinned = item.get("pinned").and_then(Value::as_bool).unwrap_or(false);
+
+            // When merging, songs are appended to a playlist with the same name
+            let existing: Option<i64> = if mode == ImportMode::Merge {
+                tx.query_row(
+                    "SELECT id FROM playlists WHERE name = ?1 ORDER BY created_at ASC LIMIT 1",
+                    params![name],
+                    |row| row.get(0),
+                ).optional().map_err(|e| format!("Failed to look up playlist: {}", e))?
+            } else {
+                None
+            };
+
+            let playlist_id = match existing {
+                Some(id) => id,
+                None => {
+                    tx.execute(
+                        "INSERT INTO playlists (name, description, cover_path, folder_id, pinned, created_at, updated_at)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)",
+                        params![name, description, cover_path, folder_id, pinned, created_at],
+                    ).map_err(|e| format!("Failed to import playlist: {}", e))?;
+                    summary.playlists += 1;
+                    let playlist_id = tx.last_insert_rowid();
+
+                    if let Some((image, extension)) = &cover {
+                        let path = self.store_playlist_cover(playlist_id, image, extension)?;
+                        tx.execute(
+                            "UPDATE playlists SET cover_path = ?1 WHERE id = ?2",
+                            params![path, playlist_id],
+                        ).map_err(|e| format!("Failed to import playlist cover: {}", e))?;
+                    }
+                    playlist_id
+                }
+            };
+
+            let songs: Vec<Song> = json_array(item, "songs")
+                .iter()
+                .filter_map(song_from_json)
+                .collect();
+            summary.playlist_songs += append_playlist_songs(&tx, playlist_id, &songs)?;
+        }
+
+        // Merging keeps the sort order chosen here
+        let playlist_sort = document
+            .get("playlist_sort")
+            .and_then(Value::as_str)
+            .and_then(|key| PlaylistSort::ALL.into_iter().find(|sort| sort.key() == key));
+        if let Some(sort) = playlist_sort.filter(|_| mode == ImportMode::Replace) {
+            tx.execute(
+                "INSERT OR REPLACE INTO app_settings (key, value) VALUES (?1, ?2)",
+                params![PLAYLIST_SORT_SETTING, sort.key()],
+            ).map_err(|e| format!("Failed to import playlist sort: {}", e))?;
+        }
+
+        for item in json_array(&document, "smart_playlists") {
+            let Some(name) = item
+                .get("name")
+                .and_then(Value::as_str)
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+            else {
+                continue;
+            };
+
+            // When merging, a smart playlist with the same name is kept as it is
+            if mode == ImportMode::Merge {
+                let exists: bool = tx.query_row(
+                    "SELECT EXISTS(SELECT 1 FROM smart_playlists WHERE name = ?1)",
+                    params![name],
+                    |row| row.get(0),
+                ).map_err(|e| format!("Failed to look up smart playlist: {}", e))?;
+                if exists {
+                    continue;
+                }
+            }
+
+            let rules = rules_from_json(&item.get("rules").cloned().unwrap_or_default().to_string());
+            let match_all = item.get("match_all").and_then(Value::as_bool).unwrap_or(true);
+            let created_at = item
+                .get("created_at")
+                .and_then(Value::as_i64)
+                .unwrap_or_else(Self::current_timestamp);
+            tx.execute(
+                "INSERT INTO smart_playlists (name, rules, match_all, created_at) VALUES (?1, ?2, ?3, ?4)",
+                params![name, rules_to_json(&rules), match_all, created_at],
+            ).map_err(|e| format!("Failed to import smart playlist: {}", e))?;
+            summary.smart_playlists += 1;
+        }
+
+        tx.commit()
+            .map_err(|e| format!("Failed to commit library import: {}", e))?;
+
+        // Rows removed before a replace would clash with the imported ones
+        if mode == ImportMode::Replace {
+            self.undo_log.borrow_mut().clear();
+        }
+
+        Ok(summary)
+    }
+}
+
+/// Copy the rows of `table` matching `condition` so they can be restored later
+fn trash_rows(
+    conn: &Connection,
+    table: &'static str,
+    condition: &str,
+    params: impl rusqlite::Params,
+) -> Result<Vec<TrashedRow>, String> {
+    let mut stmt = conn
+        .prepare(&format!("SELECT * FROM {} WHERE {}", table, condition))
+        .map_err(|e| format!("Failed to prepare query: {}", e))?;
+    let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+
+    let rows = stmt.query_map(params, |row| {
+        (0..columns.len())
+            .map(|index| row.get::<_, SqlValue>(index))
+            .collect::<rusqlite::Result<Vec<_>>>()
+    }).map_err(|e| format!("Failed to query {}: {}", table, e))?;
+
+    rows.map(|values| {
+        values.map(|values| TrashedRow {
+            table,
+            columns: columns.clone(),
+            values,
+        })
+    })
+    .collect::<Result<Vec<_>, _>>()
+    .map_err(|e| format!("Failed to collect {}: {}", table, e))
+}
+
+/// Append songs to the end of a playlist, skipping ones it already contains
+fn append_playlist_songs(conn: &Connection, playlist_id: i64, songs: &[Song]) -> Result<usize, String> {
+    let mut position: i32 = conn.query_row(
+        "SELECT COALESCE(MAX(position), -1) + 1 FROM playlist_songs WHERE playlist_id = ?1",
+        params![playlist_id],
+        |row| row.get(0),
+    ).map_err(|e| format!("Failed to get playlist position: {}", e))?;
+
+    let mut added = 0;
+    for song in songs {
+        let exists: bool = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM playlist_songs WHERE playlist_id = ?1 AND video_id = ?2)",
+            params![playlist_id, song.video_id],
+            |row| row.get(0),
+        ).map_err(|e| format!("Failed to check playlist song: {}", e))?;
+        if exists {
+            continue;
+        }
+
+        conn.execute(
+            "INSERT INTO playlist_songs (playlist_id, video_id, title, artist, duration, thumbnail_url, position)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                playlist_id,
+                song.video_id,
+                song.title,
+                song.artist,
+                song.duration,
+                song.thumbnail_url,
+                position
+            ],
+        ).map_err(|e| format!("Failed to add song to playlist: {}", e))?;
+        position += 1;
+        added += 1;
+    }
+
+    Ok(added)
+}
+
+fn playlist_from_row(row: &rusqlite::Row) -> rusqlite::Result<Playlist> {
+    Ok(Playlist {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        description: row.get(2)?,
+        cover_path: row.get(3)?,
+        folder_id: row.get(4)?,
+        pinned: row.get(5)?,
+        created_at: row.get(6)?,
+        updated_at: row.get(7)?,
+        last_played_at: row.get(8)?,
+    })
+}
+
+fn download_from_row(row: &rusqlite::Row) -> rusqlite::Result<Download> {
+    let status: String = row.get(5)?;
+    Ok(Download {
+        song: Song {
+            video_id: row.get(0)?,
+            title: row.get(1)?,
+            artist: row.get(2)?,
+            duration: row.get(3)?,
+            thumbnail_url: row.get(4)?,
+        },
+        status: DownloadStatus::from_key(&status),
+        file_path: row.get(6)?,
+        downloaded_bytes: row.get(7)?,
+        total_bytes: row.get(8)?,
+        error: row.get(9)?,
+        created_at: row.get(10)?,
+    })
+}
+
+fn local_track_from_row(row: &rusqlite::Row) -> rusqlite::Result<LocalTrack> {
+    Ok(LocalTrack {
+        song: Song {
+            video_id: row.get(0)?,
+            title: row.get(1)?,
+            artist: row.get(2)?,
+            duration: row.get(3)?,
+            thumbnail_url: row.get(4)?,
+        },
+        album: row.get(5)?,
+        file_path: row.get(6)?,
+        mtime: row.get(7)?,
+        size: row.get(8)?,
+    })
+}
+
+/// Add a column to an existing table when a database from an older version lacks it
+fn ensure_column(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<(), String> {
+    let exists: bool = conn
+        .query_row(
+            &format!("SELECT EXISTS(SELECT 1 FROM pragma_table_info('{}') WHERE name = ?1)", table),
+            params![column],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Failed to inspect {}: {}", table, e))?;
+    if !exists {
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])
+            .map_err(|e| format!("Failed to add {}.{}: {}", table, column, e))?;
+    }
+    Ok(())
+}
+
+/// Turn free text into an FTS5 query matching every word as a prefix
+fn fts_query(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+fn song_to_json(song: &Song) -> Value {
+    json!({
+        "video_id": song.video_id,
+        "title": song.title,
+        "artist": song.artist,
+        "duration": song.duration,
+        "thumbnail_url": song.thumbnail_url,
+    })
+}
+
+fn song_from_json(value: &Value) -> Option<Song> {
+    let text = |key: &str| value.get(key).and_then(Value::as_str).map(str::to_string);
+    let video_id = text("video_id").filter(|id| !id.is_empty())?;
+    // Exports from before track URIs hold bare YouTube video IDs
+    let video_id = if video_id.contains(':') {
+        video_id
+    } else {
+        track_uri(YOUTUBE_SCHEME, &video_id)
+    };
+
+    Some(Song {
+        video_id,
+        title: text("title").unwrap_or_else(|| "Unknown".to_string()),
+        artist: text("artist").unwrap_or_else(|| "Unknown".to_string()),
+        duration: text("duration").unwrap_or_default(),
+        thumbnail_url: text("thumbnail_url"),
+    })
+}
+
+/// Embeds a cover image so the export can be restored on another machine
+fn cover_to_json(path: &str) -> Option<Value> {
+    let image = std::fs::read(path).ok()?;
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("img");
+    Some(json!({
+        "extension": extension,
+        "data": BASE64.encode(image),
+    }))
+}
+
+fn cover_from_json(value: &Value) -> Option<(Vec<u8>, String)> {
+    let data = value.get("data").and_then(Value::as_str)?;
+    let image = BASE64.decode(data).ok().filter(|image| !image.is_empty())?;
+    let extension = value.get("extension").and_then(Value::as_str).unwrap_or("img");
+    Some((image, extension.to_string()))
+}
+
+fn json_array<'a>(value: &'a Value, key: &str) -> &'a [Value] {
+    value
+        .get(key)
+        .and_then(Value::as_array)
+        .map_or(&[], |items| items.as_slice())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::SmartRuleKind;
+
+    fn song(video_id: &str, title: &str, artist: &str) -> Song {
+        Song {
+            video_id: video_id.to_string(),
+            title: title.to_string(),
+            artist: artist.to_string(),
+            duration: "3:00".to_string(),
+            thumbnail_url: None,
+        }
+    }
+
+    fn play_count(db: &Database, video_id: &str) -> Option<i64> {
+        db.conn
+            .borrow()
+            .query_row(
+                "SELECT play_count FROM play_stats WHERE video_id = ?1",
+                params![video_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .unwrap()
+    }
+
+    #[test]
+    fn liking_songs_again_keeps_when_they_were_liked() {
+        let db = Database::in_memory().unwrap();
+        let liked = song("ytm:abc", "Hyperballad", "Björk");
+        db.like_song(&liked).unwrap();
+        db.conn
+            .borrow()
+            .execute("UPDATE liked_songs SET liked_at = 1000", [])
+            .unwrap();
+
+        db.like_songs(&[liked, song("ytm:def", "Jóga", "Björk")])
+            .unwrap();
+
+        let songs = db.get_liked_songs().unwrap();
+        assert_eq!(songs.len(), 2);
+        assert_eq!(songs[0].song.video_id, "ytm:def");
+        assert!(songs[0].liked_at > 1000);
+        assert_eq!(songs[1].song.video_id, "ytm:abc");
+        assert_eq!(songs[1].liked_at, 1000);
+    }
+
+    #[test]
+    fn replace_import_keeps_play_stats_and_smart_playlists() {
+        let db = Database::in_memory().unwrap();
+        let track = song("ytm:abc", "Hyperballad", "Björk");
+        db.add_recent_play(&track).unwrap();
+        db.add_recent_play(&track).unwrap();
+        let rules = vec![SmartRule::new(SmartRuleKind::PlayedMoreThan, "1").unwrap()];
+        db.create_smart_playlist("Favourites", &rules, true).unwrap();
+
+        let export = db.export_json().unwrap();
+        db.import_json(&export, ImportMode::Replace).unwrap();
+
+        assert_eq!(play_count(&db, "ytm:abc"), Some(2));
+        let smart_playlists = db.get_smart_playlists().unwrap();
+        assert_eq!(smart_playlists.len(), 1);
+        assert_eq!(smart_playlists[0].name, "Favourites");
+        assert_eq!(smart_playlists[0].rules, rules);
+    }
+
+    #[test]
+    fn merge_import_does_not_count_plays_twice() {
+        let db = Database::in_memory().unwrap();
+        let track = song("ytm:abc", "Hyperballad", "Björk");
+        db.add_recent_play(&track).unwrap();
+        db.create_smart_playlist("Liked", &[SmartRule::IsLiked], true).unwrap();
+
+        let export = db.export_json().unwrap();
+        let summary = db.import_json(&export, ImportMode::Merge).unwrap();
+
+        assert_eq!(play_count(&db, "ytm:abc"), Some(1));
+        assert_eq!(summary.smart_playlists, 0);
+        assert_eq!(db.get_smart_playlists().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn replacing_from_an_older_export_keeps_play_stats() {
+        let db = Database::in_memory().unwrap();
+        db.add_recent_play(&song("ytm:abc", "Hyperballad", "Björk")).unwrap();
+        db.create_smart_playlist("Liked", &[SmartRule::IsLiked], true).unwrap();
+
+        let old_export = json!({
+            "format": LIBRARY_EXPORT_FORMAT,
+            "version": 1,
+            "liked_songs": [],
+            "recent_plays": [],
+            "playlists": [],
+        });
+        db.import_json(&old_export.to_string(), ImportMode::Replace).unwrap();
+
+        assert_eq!(play_count(&db, "ytm:abc"), Some(1));
+        assert_eq!(db.get_smart_playlists().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn playlist_details_survive_a_round_trip() {
+        let db = Database::in_memory().unwrap();
+        let cover = db.store_playlist_cover(0, b"png", "PNG").unwrap();
+        let playlist = db.create_playlist("Road trip").unwrap();
+        db.set_playlist_description(playlist.id, "Songs for the car").unwrap();
+        db.set_playlist_cover(playlist.id, Some(&cover)).unwrap();
+
+        let export = db.export_json().unwrap();
+        // The cover travels inside the export, so it is restored on a machine without the file
+        std::fs::remove_file(&cover).unwrap();
+        db.import_json(&export, ImportMode::Replace).unwrap();
+
+        let playlists = db.get_playlists().unwrap();
+        assert_eq!(playlists.len(), 1);
+        assert_eq!(playlists[0].description, "Songs for the car");
+        let restored = playlists[0].cover_path.clone().unwrap();
+        assert_ne!(restored, cover);
+        assert!(restored.ends_with(".png"));
+        assert!(Path::new(&restored).starts_with(db.covers_dir()));
+        assert_eq!(std::fs::read(&restored).unwrap(), b"png");
+        let _ = std::fs::remove_file(&restored);
+    }
+
+    #[test]
+    fn folders_pinning_and_sort_survive_a_round_trip() {
+        let db = Database::in_memory().unwrap();
+        let folder = db.create_folder("Moods").unwrap();
+        db.set_folder_expanded(folder.id, false).unwrap();
+        let filed = db.create_playlist("Calm").unwrap();
+        db.set_playlist_folder(filed.id, Some(folder.id)).unwrap();
+        let pinned = db.create_playlist("Daily").unwrap();
+        db.set_playlist_pinned(pinned.id, true).unwrap();
+        db.set_playlist_sort(PlaylistSort::Name).unwrap();
+
+        let export = db.export_json().unwrap();
+        db.set_playlist_sort(PlaylistSort::Created).unwrap();
+        db.import_json(&export, ImportMode::Replace).unwrap();
+
+        let folders = db.get_folders().unwrap();
+        assert_eq!(folders.len(), 1);
+        assert_eq!(folders[0].name, "Moods");
+        assert!(!folders[0].expanded);
+        let playlists = db.get_playlists().unwrap();
+        let calm = playlists.iter().find(|playlist| playlist.name == "Calm").unwrap();
+        assert_eq!(calm.folder_id, Some(folders[0].id));
+        let daily = playlists.iter().find(|playlist| playlist.name == "Daily").unwrap();
+        assert!(daily.pinned);
+        assert_eq!(db.get_playlist_sort(), PlaylistSort::Name);
+    }
+
+    #[test]
+    fn merge_import_reuses_folders_with_the_same_name() {
+        let db = Database::in_memory().unwrap();
+        let folder = db.create_folder("Moods").unwrap();
+        let playlist = db.create_playlist("Calm").unwrap();
+        db.set_playlist_folder(playlist.id, Some(folder.id)).unwrap();
+
+        let export = db.export_json().unwrap();
+        db.import_json(&export, ImportMode::Merge).unwrap();
+
+        assert_eq!(db.get_folders().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn failed_undo_can_be_retried() {
+        let db = Database::in_memory().unwrap();
+        db.like_song(&song("ytm:abc", "Hyperballad", "Björk")).unwrap();
+        let undo = db.unlike_song("ytm:abc").unwrap();
+
+        db.conn.borrow().execute("DROP TABLE liked_songs", []).unwrap();
+        assert!(db.undo(undo).is_err());
+
+        db.init_tables().unwrap();
+        db.undo(undo).unwrap();
+        assert!(db.is_song_liked("ytm:abc"));
+        assert!(db.undo(undo).is_err());
+    }
+
+    fn local_track(video_id: &str, title: &str, artist: &str) -> LocalTrack {
+        LocalTrack {
+            song: song(video_id, title, artist),
+            album: None,
+            file_path: video_id.trim_start_matches("file:").to_string(),
+            mtime: 0,
+            size: 0,
+        }
+    }
+
+    #[test]
+    fn library_queries_include_scanned_tracks() {
+        let db = Database::in_memory().unwrap();
+        db.like_song(&song("ytm:abc", "Hyperballad", "Björk")).unwrap();
+        let track = local_track("file:/music/joga.flac", "Jóga", "Björk");
+        let present = HashSet::from([track.file_path.clone()]);
+        db.apply_local_scan(&[track], &present).unwrap();
+
+        let found = db.search_library("joga", 10).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].video_id, "file:/music/joga.flac");
+
+        let smart_playlist = db
+            .create_smart_playlist("Björk", &[SmartRule::ArtistIs("Björk".to_string())], true)
+            .unwrap();
+        let songs = db.get_smart_playlist_songs(&smart_playlist).unwrap();
+        let mut ids: Vec<&str> = songs.iter().map(|song| song.video_id.as_str()).collect();
+        ids.sort();
+        assert_eq!(ids, ["file:/music/joga.flac", "ytm:abc"]);
+    }
+
+    #[test]
+    fn older_exports_get_youtube_track_uris() {
+        let db = Database::in_memory().unwrap();
+        let old_export = json!({
+            "format": LIBRARY_EXPORT_FORMAT,
+            "version": 1,
+            "liked_songs": [{ "video_id": "abc", "title": "Hyperballad", "artist": "Björk" }],
+            "playlists": [{ "name": "Mix", "songs": [{ "video_id": "def", "title": "Jóga" }] }],
+        });
+        db.import_json(&old_export.to_string(), ImportMode::Merge).unwrap();
+
+        assert!(db.is_song_liked("ytm:abc"));
+        let playlist = &db.get_playlists().unwrap()[0];
+        assert_eq!(db.get_playlist_songs(playlist.id).unwrap()[0].song.video_id, "ytm:def");
+
+        let export: Value = serde_json::from_str(&db.export_json().unwrap()).unwrap();
+        assert_eq!(export["version"], json!(LIBRARY_EXPORT_VERSION));
+        assert_eq!(export["liked_songs"][0]["video_id"], json!("ytm:abc"));
+    }
+
+    #[test]
+    fn signed_subsonic_covers_are_replaced_by_references() {
+        let db = Database::in_memory().unwrap();
+        let mut track = song("subsonic:so-1", "Hyperballad", "Björk");
+        track.thumbnail_url = Some(
+            "https://music.local/rest/getCoverArt?u=alice&t=0123abcd&s=f00d&v=1.16.1&c=musika&f=json&id=al-1"
+                .to_string(),
+        );
+        db.like_song(&track).unwrap();
+
+        db.init_tables().unwrap();
+
+        let liked = db.get_liked_songs().unwrap();
+        assert_eq!(liked[0].song.thumbnail_url.as_deref(), Some("subsonic-cover:al-1"));
+    }
+
+    #[test]
+    fn search_index_follows_renamed_songs() {
+        let db = Database::in_memory().unwrap();
+        db.add_recent_play(&song("ytm:abc", "Untitled", "Björk")).unwrap();
+        db.add_recent_play(&song("ytm:abc", "Hyperballad", "Björk")).unwrap();
+
+        assert!(db.search_library_ids("untitled").unwrap().is_empty());
+        assert!(db.search_library_ids("hyperballad").unwrap().contains("ytm:abc"));
+    }
+
+    #[test]
+    fn search_index_is_rebuilt_only_when_needed() {
+        let db = Database::in_memory().unwrap();
+        db.like_song(&song("ytm:abc", "Hyperballad", "Björk")).unwrap();
+
+        // An up-to-date index is left alone on startup
+        db.conn
+            .borrow()
+            .execute("DELETE FROM library_fts WHERE video_id = 'ytm:abc'", [])
+            .unwrap();
+        db.like_song(&song("ytm:def", "Jóga", "Björk")).unwrap();
+        db.init_tables().unwrap();
+        assert!(db.search_library_ids("hyperballad").unwrap().is_empty());
+
+        // An outdated one is rebuilt from every stored song
+        db.set_setting(LIBRARY_FTS_VERSION_SETTING, "0").unwrap();
+        db.init_tables().unwrap();
+        assert!(db.search_library_ids("hyperballad").unwrap().contains("ytm:abc"));
+        assert_eq!(db.get_setting(LIBRARY_FTS_VERSION_SETTING).as_deref(), Some(LIBRARY_FTS_VERSION));
+    }
 }
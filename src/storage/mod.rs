@@ -1,2 +1,4 @@
 pub mod database;
+pub mod smart_playlist;
 pub use database::*;
+pub use smart_playlist::*;
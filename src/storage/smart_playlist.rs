@@ -0,0 +1,248 @@
+use rusqlite::types::Value as SqlValue;
+use serde_json::{Value, json};
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Playlist whose songs are selected by rules instead of being added by hand
+#[derive(Debug, Clone)]
+pub struct SmartPlaylist {
+    pub id: i64,
+    pub name: String,
+    pub rules: Vec<SmartRule>,
+    /// Songs must match every rule when true, any rule when false
+    pub match_all: bool,
+    pub created_at: i64,
+}
+
+/// Kind of a smart playlist rule, used by the rule editor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmartRuleKind {
+    LikedWithinDays,
+    IsLiked,
+    PlayedMoreThan,
+    NeverPlayed,
+    PlayedWithinDays,
+    ArtistIs,
+    TitleContains,
+}
+
+/// What kind of value a rule takes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmartRuleInput {
+    None,
+    Number,
+    Text,
+}
+
+impl SmartRuleKind {
+    pub const ALL: [SmartRuleKind; 7] = [
+        SmartRuleKind::LikedWithinDays,
+        SmartRuleKind::IsLiked,
+        SmartRuleKind::PlayedMoreThan,
+        SmartRuleKind::NeverPlayed,
+        SmartRuleKind::PlayedWithinDays,
+        SmartRuleKind::ArtistIs,
+        SmartRuleKind::TitleContains,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SmartRuleKind::LikedWithinDays => "Liked in the last N days",
+            SmartRuleKind::IsLiked => "Is liked",
+            SmartRuleKind::PlayedMoreThan => "Played more than N times",
+            SmartRuleKind::NeverPlayed => "Never played",
+            SmartRuleKind::PlayedWithinDays => "Played in the last N days",
+            SmartRuleKind::ArtistIs => "Artist is",
+            SmartRuleKind::TitleContains => "Title contains",
+        }
+    }
+
+    pub fn input(self) -> SmartRuleInput {
+        match self {
+            SmartRuleKind::IsLiked | SmartRuleKind::NeverPlayed => SmartRuleInput::None,
+            SmartRuleKind::LikedWithinDays
+            | SmartRuleKind::PlayedMoreThan
+            | SmartRuleKind::PlayedWithinDays => SmartRuleInput::Number,
+            SmartRuleKind::ArtistIs | SmartRuleKind::TitleContains => SmartRuleInput::Text,
+        }
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            SmartRuleKind::LikedWithinDays => "liked_within_days",
+            SmartRuleKind::IsLiked => "is_liked",
+            SmartRuleKind::PlayedMoreThan => "played_more_than",
+            SmartRuleKind::NeverPlayed => "never_played",
+            SmartRuleKind::PlayedWithinDays => "played_within_days",
+            SmartRuleKind::ArtistIs => "artist_is",
+            SmartRuleKind::TitleContains => "title_contains",
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.key() == key)
+    }
+}
+
+/// A single condition of a smart playlist
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SmartRule {
+    LikedWithinDays(i64),
+    IsLiked,
+    PlayedMoreThan(i64),
+    NeverPlayed,
+    PlayedWithinDays(i64),
+    ArtistIs(String),
+    TitleContains(String),
+}
+
+impl SmartRule {
+    /// Build a rule from editor input, returning None when the value is invalid
+    pub fn new(kind: SmartRuleKind, value: &str) -> Option<Self> {
+        let value = value.trim();
+        let number = || value.parse::<i64>().ok().filter(|number| *number >= 0);
+        let text = || (!value.is_empty()).then(|| value.to_string());
+
+        match kind {
+            SmartRuleKind::LikedWithinDays => number().map(SmartRule::LikedWithinDays),
+            SmartRuleKind::IsLiked => Some(SmartRule::IsLiked),
+            SmartRuleKind::PlayedMoreThan => number().map(SmartRule::PlayedMoreThan),
+            SmartRuleKind::NeverPlayed => Some(SmartRule::NeverPlayed),
+            SmartRuleKind::PlayedWithinDays => number().map(SmartRule::PlayedWithinDays),
+            SmartRuleKind::ArtistIs => text().map(SmartRule::ArtistIs),
+            SmartRuleKind::TitleContains => text().map(SmartRule::TitleContains),
+        }
+    }
+
+    pub fn kind(&self) -> SmartRuleKind {
+        match self {
+            SmartRule::LikedWithinDays(_) => SmartRuleKind::LikedWithinDays,
+            SmartRule::IsLiked => SmartRuleKind::IsLiked,
+            SmartRule::PlayedMoreThan(_) => SmartRuleKind::PlayedMoreThan,
+            SmartRule::NeverPlayed => SmartRuleKind::NeverPlayed,
+            SmartRule::PlayedWithinDays(_) => SmartRuleKind::PlayedWithinDays,
+            SmartRule::ArtistIs(_) => SmartRuleKind::ArtistIs,
+            SmartRule::TitleContains(_) => SmartRuleKind::TitleContains,
+        }
+    }
+
+    /// Value as shown in the rule editor
+    pub fn value_text(&self) -> String {
+        match self {
+            SmartRule::LikedWithinDays(number)
+            | SmartRule::PlayedMoreThan(number)
+            | SmartRule::PlayedWithinDays(number) => number.to_string(),
+            SmartRule::ArtistIs(text) | SmartRule::TitleContains(text) => text.clone(),
+            SmartRule::IsLiked | SmartRule::NeverPlayed => String::new(),
+        }
+    }
+
+    /// SQL condition over the `t` (track), `l` (liked) and `s` (play stats) aliases
+    pub(crate) fn to_sql(&self, now: i64) -> (&'static str, Option<SqlValue>) {
+        match self {
+            SmartRule::LikedWithinDays(days) => (
+                "l.liked_at >= ?",
+                Some(SqlValue::Integer(days_before(now, *days))),
+            ),
+            SmartRule::IsLiked => ("l.video_id IS NOT NULL", None),
+            SmartRule::PlayedMoreThan(count) => (
+                "COALESCE(s.play_count, 0) > ?",
+                Some(SqlValue::Integer(*count)),
+            ),
+            SmartRule::NeverPlayed => ("COALESCE(s.play_count, 0) = 0", None),
+            SmartRule::PlayedWithinDays(days) => (
+                "s.last_played_at >= ?",
+                Some(SqlValue::Integer(days_before(now, *days))),
+            ),
+            SmartRule::ArtistIs(artist) => (
+                "t.artist = ? COLLATE NOCASE",
+                Some(SqlValue::Text(artist.clone())),
+            ),
+            SmartRule::TitleContains(text) => (
+                "instr(lower(t.title), lower(?)) > 0",
+                Some(SqlValue::Text(text.clone())),
+            ),
+        }
+    }
+}
+
+/// Timestamp `days` days before `now`; the day count is typed by the user, so it may be huge
+fn days_before(now: i64, days: i64) -> i64 {
+    now.saturating_sub(days.saturating_mul(SECONDS_PER_DAY))
+}
+
+/// Serialize rules for the `smart_playlists.rules` column
+pub(crate) fn rules_to_json(rules: &[SmartRule]) -> String {
+    let values: Vec<Value> = rules
+        .iter()
+        .map(|rule| {
+            let value = match rule {
+                SmartRule::LikedWithinDays(number)
+                | SmartRule::PlayedMoreThan(number)
+                | SmartRule::PlayedWithinDays(number) => json!(number),
+                SmartRule::ArtistIs(text) | SmartRule::TitleContains(text) => json!(text),
+                SmartRule::IsLiked | SmartRule::NeverPlayed => Value::Null,
+            };
+            json!({ "kind": rule.kind().key(), "value": value })
+        })
+        .collect();
+    Value::Array(values).to_string()
+}
+
+/// Parse the `smart_playlists.rules` column, skipping rules this version does not know
+pub(crate) fn rules_from_json(data: &str) -> Vec<SmartRule> {
+    let Ok(Value::Array(values)) = serde_json::from_str::<Value>(data) else {
+        return Vec::new();
+    };
+
+    values
+        .iter()
+        .filter_map(|value| {
+            let kind = SmartRuleKind::from_key(value.get("kind")?.as_str()?)?;
+            let text = match value.get("value") {
+                Some(Value::Number(number)) => number.to_string(),
+                Some(Value::String(text)) => text.clone(),
+                _ => String::new(),
+            };
+            SmartRule::new(kind, &text)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+
+    fn cutoff(rule: &SmartRule) -> i64 {
+        match rule.to_sql(NOW).1 {
+            Some(SqlValue::Integer(cutoff)) => cutoff,
+            other => panic!("expected an integer bound, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn days_rules_count_back_from_now() {
+        let rule = SmartRule::new(SmartRuleKind::PlayedWithinDays, "7").unwrap();
+        assert_eq!(cutoff(&rule), NOW - 7 * SECONDS_PER_DAY);
+    }
+
+    #[test]
+    fn huge_day_counts_do_not_overflow() {
+        for kind in [SmartRuleKind::LikedWithinDays, SmartRuleKind::PlayedWithinDays] {
+            let rule = SmartRule::new(kind, &i64::MAX.to_string()).unwrap();
+            assert!(cutoff(&rule) < 0);
+        }
+    }
+
+    #[test]
+    fn rules_survive_a_json_round_trip() {
+        let rules = vec![
+            SmartRule::LikedWithinDays(30),
+            SmartRule::IsLiked,
+            SmartRule::ArtistIs("Björk".to_string()),
+        ];
+        assert_eq!(rules_from_json(&rules_to_json(&rules)), rules);
+    }
+}
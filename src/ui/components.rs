@@ -16,11 +16,14 @@ use libadwaita::prelude::*;
 use crate::config::{
     COVER_SIZE_MINI, DEFAULT_COVER_PATH, ICON_MUSIC, ICON_PLAYLIST, MARGIN_TINY, POLL_INTERVAL_MS,
 };
+use crate::sources::{SUBSONIC_COVER_PREFIX, Sources};
 
 const IMAGE_CACHE_LIMIT: usize = 200;
 const IMAGE_CACHE_PREFIX: &str = "cloudmusic-image-cache";
 const IMAGE_CACHE_MAX_FILES: usize = 500;
 
+static UNDO_TOAST_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
 struct ImageCache {
     map: HashMap<String, Vec<u8>>,
     order: VecDeque<String>,
@@ -69,6 +72,13 @@ impl ImageCache {
 static IMAGE_CACHE: OnceLock<Mutex<ImageCache>> = OnceLock::new();
 static IMAGE_CACHE_DIR: OnceLock<PathBuf> = OnceLock::new();
 static IMAGE_CACHE_FILE_COUNT: AtomicUsize = AtomicUsize::new(0);
+// Signs Subsonic cover URLs when they are fetched
+static IMAGE_SOURCES: OnceLock<Sources> = OnceLock::new();
+
+/// Let the image loader fetch covers from the music sources; called once at startup
+pub fn set_image_sources(sources: Sources) {
+    let _ = IMAGE_SOURCES.set(sources);
+}
 
 fn image_cache_get(url: &str) -> Option<Vec<u8>> {
     let cache = IMAGE_CACHE.get_or_init(|| Mutex::new(ImageCache::new()));
@@ -159,6 +169,28 @@ pub fn song_card_row_with_like(
     row
 }
 
+/// Whether a cover URL points at something `load_image_async` can load:
+/// a web image, a local file or a Subsonic cover
+pub fn is_image_url(url: &str) -> bool {
+    url.starts_with("http://")
+        || url.starts_with("https://")
+        || url.starts_with("file://")
+        || url.starts_with(SUBSONIC_COVER_PREFIX)
+}
+
+/// A URL other programs can load a cover from, without sign-in details.
+/// Subsonic covers are offered as their cached file, once they have been fetched.
+pub fn shareable_image_url(url: &str) -> Option<String> {
+    if !url.starts_with(SUBSONIC_COVER_PREFIX) {
+        return Some(url.to_string());
+    }
+    let path = image_cache_path(url);
+    path.is_file()
+        .then(|| glib::filename_to_uri(&path, None).ok())
+        .flatten()
+        .map(String::from)
+}
+
 /// Creates a cover image widget with optional async loading
 pub fn cover_widget(thumbnail_url: Option<&str>, size: i32) -> gtk4::Widget {
     let image = gtk4::Image::from_icon_name(ICON_MUSIC);
@@ -180,7 +212,7 @@ pub fn cover_widget(thumbnail_url: Option<&str>, size: i32) -> gtk4::Widget {
     }
 
     if let Some(url) = thumbnail_url {
-        if url.starts_with("http://") || url.starts_with("https://") {
+        if is_image_url(url) {
             load_image_async(image.clone(), url.to_string(), None);
         }
     }
@@ -189,6 +221,58 @@ pub fn cover_widget(thumbnail_url: Option<&str>, size: i32) -> gtk4::Widget {
     frame.upcast()
 }
 
+/// Creates a playlist cover: the custom image if set, otherwise a 2×2 collage of song covers
+pub fn playlist_cover_widget(cover_path: Option<&str>, thumbnails: &[String], size: i32) -> gtk4::Widget {
+    if let Some(path) = cover_path {
+        if let Ok(pixbuf) = gtk4::gdk_pixbuf::Pixbuf::from_file_at_scale(path, size, size, true) {
+            let image = gtk4::Image::from_paintable(Some(&gtk4::gdk::Texture::for_pixbuf(&pixbuf)));
+            image.set_pixel_size(size);
+            image.add_css_class("album-cover-image");
+
+            let frame = gtk4::Frame::new(None);
+            frame.set_size_request(size, size);
+            frame.set_overflow(gtk4::Overflow::Hidden);
+            frame.add_css_class("album-cover");
+            frame.add_css_class("album-cover-frame");
+            if size <= COVER_SIZE_MINI {
+                frame.add_css_class("album-cover-small");
+            }
+            frame.set_child(Some(&image));
+            return frame.upcast();
+        }
+    }
+
+    if thumbnails.len() < 4 {
+        return cover_widget(thumbnails.first().map(String::as_str), size);
+    }
+
+    let tile_size = size / 2;
+    let grid = gtk4::Grid::new();
+    grid.set_row_homogeneous(true);
+    grid.set_column_homogeneous(true);
+    for (index, url) in thumbnails.iter().take(4).enumerate() {
+        let tile = gtk4::Image::from_icon_name(ICON_MUSIC);
+        tile.set_pixel_size(tile_size);
+        tile.set_size_request(tile_size, tile_size);
+        tile.set_overflow(gtk4::Overflow::Hidden);
+        if is_image_url(url) {
+            load_image_async(tile.clone(), url.clone(), None);
+        }
+        grid.attach(&tile, (index % 2) as i32, (index / 2) as i32, 1, 1);
+    }
+
+    let frame = gtk4::Frame::new(None);
+    frame.set_size_request(size, size);
+    frame.set_overflow(gtk4::Overflow::Hidden);
+    frame.add_css_class("album-cover");
+    frame.add_css_class("album-cover-frame");
+    if size <= COVER_SIZE_MINI {
+        frame.add_css_class("album-cover-small");
+    }
+    frame.set_child(Some(&grid));
+    frame.upcast()
+}
+
 /// RGB color representation
 #[derive(Clone, Copy, Debug)]
 pub struct RgbColor {
@@ -340,6 +424,60 @@ pub fn load_image_async(
     load_image_async_with_callback(image, url, token_guard, None::<fn(RgbColor)>);
 }
 
+/// Image bytes for a URL, from the disk cache, a local file or the network; run off the main thread
+fn fetch_image(url: &str) -> Option<Vec<u8>> {
+    // Local covers are read straight from disk, without the disk cache
+    if url.starts_with("file://") {
+        let payload = glib::filename_from_uri(url)
+            .ok()
+            .and_then(|(path, _)| std::fs::read(path).ok())?;
+        image_cache_put(url, payload.clone());
+        return Some(payload);
+    }
+    if let Some(payload) = image_cache_read_disk(url) {
+        image_cache_put(url, payload.clone());
+        return Some(payload);
+    }
+    // Subsonic covers are cached by their reference; the signed URL is never stored
+    let fetch_url = if url.starts_with(SUBSONIC_COVER_PREFIX) {
+        IMAGE_SOURCES.get()?.cover_url(url)?
+    } else {
+        url.to_string()
+    };
+    let bytes = reqwest::blocking::get(fetch_url).and_then(|resp| resp.bytes()).ok()?;
+    let payload = bytes.to_vec();
+    image_cache_put(url, payload.clone());
+    image_cache_write_disk(url, &payload);
+    Some(payload)
+}
+
+/// Loads the raw bytes of an image, calling `on_loaded` with `None` if it can't be fetched
+pub fn load_image_bytes_async(url: String, on_loaded: impl FnOnce(Option<Vec<u8>>) + 'static) {
+    if let Some(payload) = image_cache_get(&url) {
+        on_loaded(Some(payload));
+        return;
+    }
+
+    let (sender, receiver) = mpsc::channel::<Option<Vec<u8>>>();
+    std::thread::spawn(move || {
+        let _ = sender.send(fetch_image(&url));
+    });
+
+    let mut on_loaded = Some(on_loaded);
+    glib::timeout_add_local(Duration::from_millis(POLL_INTERVAL_MS), move || {
+        match receiver.try_recv() {
+            Ok(payload) => {
+                if let Some(on_loaded) = on_loaded.take() {
+                    on_loaded(payload);
+                }
+                ControlFlow::Break
+            }
+            Err(mpsc::TryRecvError::Empty) => ControlFlow::Continue,
+            Err(mpsc::TryRecvError::Disconnected) => ControlFlow::Break,
+        }
+    });
+}
+
 /// Loads an image asynchronously from a URL with optional color callback
 pub fn load_image_async_with_callback<F>(
     image: gtk4::Image,
@@ -355,19 +493,9 @@ where
         let _ = sender.send(payload);
     } else {
         std::thread::spawn(move || {
-            if let Some(payload) = image_cache_read_disk(&url) {
-                image_cache_put(&url, payload.clone());
+            if let Some(payload) = fetch_image(&url) {
                 let _ = sender.send(payload);
-                return;
             }
-            let bytes = match reqwest::blocking::get(&url).and_then(|resp| resp.bytes()) {
-                Ok(bytes) => bytes,
-                Err(_) => return,
-            };
-            let payload = bytes.to_vec();
-            image_cache_put(&url, payload.clone());
-            image_cache_write_disk(&url, &payload);
-            let _ = sender.send(payload);
         });
     }
 
@@ -506,6 +634,172 @@ pub fn loading_row(message: &str) -> gtk4::ListBoxRow {
     row
 }
 
+/// Opens a native file chooser and calls `on_pick` with the chosen path
+pub fn choose_file(
+    parent: &impl IsA<gtk4::Widget>,
+    title: &str,
+    action: gtk4::FileChooserAction,
+    suggested_name: Option<&str>,
+    filter: Option<(&str, &[&str])>,
+    on_pick: impl Fn(PathBuf) + 'static,
+) {
+    let parent_window = parent.root().and_downcast::<gtk4::Window>();
+    let accept_label = match action {
+        gtk4::FileChooserAction::Save => "Save",
+        gtk4::FileChooserAction::SelectFolder => "Select",
+        _ => "Open",
+    };
+    let chooser = gtk4::FileChooserNative::new(
+        Some(title),
+        parent_window.as_ref(),
+        action,
+        Some(accept_label),
+        Some("Cancel"),
+    );
+    chooser.set_modal(true);
+
+    if let Some(name) = suggested_name {
+        chooser.set_current_name(name);
+    }
+
+    if let Some((name, patterns)) = filter {
+        let file_filter = gtk4::FileFilter::new();
+        file_filter.set_name(Some(name));
+        for pattern in patterns {
+            file_filter.add_pattern(pattern);
+        }
+        chooser.add_filter(&file_filter);
+    }
+
+    // Native dialogs are not widgets, so keep a reference until they respond
+    let keep_alive = Rc::new(RefCell::new(Some(chooser.clone())));
+    chooser.connect_response(move |chooser, response| {
+        if response == gtk4::ResponseType::Accept {
+            if let Some(path) = chooser.file().and_then(|file| file.path()) {
+                on_pick(path);
+            }
+        }
+        keep_alive.borrow_mut().take();
+    });
+
+    chooser.show();
+}
+
+/// Shows a modal dialog with a message and a single OK button
+pub fn show_message_dialog(parent: &impl IsA<gtk4::Widget>, title: &str, body: &str) {
+    let parent_window = parent.root().and_downcast::<gtk4::Window>();
+    let dialog = gtk4::Dialog::with_buttons(
+        Some(title),
+        parent_window.as_ref(),
+        gtk4::DialogFlags::MODAL,
+        &[("OK", gtk4::ResponseType::Ok)],
+    );
+    dialog.set_default_response(gtk4::ResponseType::Ok);
+
+    let content = dialog.content_area();
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    let label = gtk4::Label::new(Some(body));
+    label.set_wrap(true);
+    label.set_xalign(0.0);
+    content.append(&label);
+
+    dialog.connect_response(|dialog: &gtk4::Dialog, _| dialog.close());
+    dialog.present();
+}
+
+/// Shows a modal dialog asking for a name, calling `on_accept` with it trimmed unless it is empty
+pub fn show_name_dialog(
+    parent: &impl IsA<gtk4::Widget>,
+    heading: &str,
+    placeholder: &str,
+    initial: &str,
+    on_accept: impl Fn(String) + 'static,
+) {
+    let parent_window = parent.root().and_downcast::<gtk4::Window>();
+    let dialog = gtk4::Dialog::with_buttons(
+        Some(heading),
+        parent_window.as_ref(),
+        gtk4::DialogFlags::MODAL,
+        &[("Cancel", gtk4::ResponseType::Cancel), ("Save", gtk4::ResponseType::Ok)],
+    );
+    dialog.set_default_response(gtk4::ResponseType::Ok);
+
+    let content = dialog.content_area();
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+
+    let entry = gtk4::Entry::new();
+    entry.set_placeholder_text(Some(placeholder));
+    entry.set_text(initial);
+    content.append(&entry);
+
+    dialog.connect_response(move |dialog: &gtk4::Dialog, response| {
+        if response == gtk4::ResponseType::Ok {
+            let name = entry.text().to_string();
+            let trimmed = name.trim();
+            if !trimmed.is_empty() {
+                on_accept(trimmed.to_string());
+            }
+        }
+        dialog.close();
+    });
+
+    dialog.present();
+}
+
+/// Shows a plain toast on the nearest toast overlay above `widget`
+pub fn show_toast(widget: &impl IsA<gtk4::Widget>, title: &str) {
+    if let Some(overlay) = widget
+        .ancestor(adw::ToastOverlay::static_type())
+        .and_downcast::<adw::ToastOverlay>()
+    {
+        overlay.add_toast(adw::Toast::new(&glib::markup_escape_text(title)));
+    }
+}
+
+/// Shows a toast with an Undo button on the nearest toast overlay above `widget`
+pub fn show_undo_toast(widget: &impl IsA<gtk4::Widget>, title: &str, on_undo: impl Fn() + 'static) {
+    let Some(overlay) = widget
+        .ancestor(adw::ToastOverlay::static_type())
+        .and_downcast::<adw::ToastOverlay>()
+    else {
+        return;
+    };
+
+    // Each toast gets its own action group so queued toasts undo their own change
+    let prefix = format!("undo-{}", UNDO_TOAST_COUNTER.fetch_add(1, Ordering::Relaxed));
+    let action = gtk4::gio::SimpleAction::new("undo", None);
+    action.connect_activate(move |action, _| {
+        action.set_enabled(false);
+        on_undo();
+    });
+    let group = gtk4::gio::SimpleActionGroup::new();
+    group.add_action(&action);
+    overlay.insert_action_group(&prefix, Some(&group));
+
+    let toast = adw::Toast::new(&glib::markup_escape_text(title));
+    toast.set_button_label(Some("Undo"));
+    toast.set_action_name(Some(&format!("{}.undo", prefix)));
+    toast.connect_dismissed(glib::clone!(
+        #[weak]
+        overlay,
+        move |_| {
+            // Remove the group after any activation triggered by the same click has run
+            let overlay = overlay.clone();
+            let prefix = prefix.clone();
+            glib::idle_add_local_once(move || {
+                overlay.insert_action_group(&prefix, None::<&gtk4::gio::ActionGroup>);
+            });
+        }
+    ));
+    overlay.add_toast(toast);
+}
+
 /// Creates a large cover widget for now playing view
 pub fn large_cover_widget(size: i32) -> gtk4::Image {
     let image = gtk4::Image::from_file(DEFAULT_COVER_PATH);
@@ -0,0 +1,116 @@
+use gtk4::prelude::*;
+
+use crate::storage::Song;
+use crate::ui::components::show_message_dialog;
+
+const DIALOG_LIST_HEIGHT: i32 = 360;
+
+/// Lists groups of duplicate songs and asks which version of each to keep.
+/// `on_resolve` gets every group reordered so the kept entry comes first, and whether to merge.
+pub fn show_duplicates_dialog(
+    parent: &impl IsA<gtk4::Widget>,
+    songs: &[Song],
+    groups: Vec<Vec<usize>>,
+    on_resolve: impl Fn(Vec<Vec<usize>>, bool) + 'static,
+) {
+    if groups.is_empty() {
+        show_message_dialog(parent, "No duplicates found", "Every song here is a different track.");
+        return;
+    }
+
+    let parent_window = parent.root().and_downcast::<gtk4::Window>();
+    let dialog = gtk4::Dialog::with_buttons(
+        Some("Duplicates"),
+        parent_window.as_ref(),
+        gtk4::DialogFlags::MODAL,
+        &[
+            ("Cancel", gtk4::ResponseType::Cancel),
+            ("Remove extras", gtk4::ResponseType::Apply),
+            ("Merge", gtk4::ResponseType::Ok),
+        ],
+    );
+    dialog.set_default_response(gtk4::ResponseType::Ok);
+    dialog.set_default_size(480, -1);
+
+    let content = dialog.content_area();
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    content.set_spacing(12);
+
+    let intro = gtk4::Label::new(Some(
+        "Pick the version to keep of each song. Remove extras leaves it where it is; \
+         Merge also moves it to where the first copy was.",
+    ));
+    intro.set_wrap(true);
+    intro.set_xalign(0.0);
+    content.append(&intro);
+
+    let groups_box = gtk4::Box::new(gtk4::Orientation::Vertical, 12);
+    let mut choices: Vec<Vec<gtk4::CheckButton>> = Vec::new();
+    for group in &groups {
+        let group_box = gtk4::Box::new(gtk4::Orientation::Vertical, 4);
+        let mut buttons: Vec<gtk4::CheckButton> = Vec::new();
+        for index in group {
+            let song = &songs[*index];
+            let duration = if song.duration.trim().is_empty() {
+                "--:--"
+            } else {
+                &song.duration
+            };
+            let button = gtk4::CheckButton::with_label(&format!(
+                "{} — {} · {}",
+                song.title, song.artist, duration
+            ));
+            if let Some(first) = buttons.first() {
+                button.set_group(Some(first));
+            } else {
+                button.set_active(true);
+            }
+            group_box.append(&button);
+            buttons.push(button);
+        }
+        groups_box.append(&group_box);
+        choices.push(buttons);
+    }
+
+    let scroller = gtk4::ScrolledWindow::new();
+    scroller.set_hscrollbar_policy(gtk4::PolicyType::Never);
+    scroller.set_max_content_height(DIALOG_LIST_HEIGHT);
+    scroller.set_propagate_natural_height(true);
+    scroller.set_child(Some(&groups_box));
+    content.append(&scroller);
+
+    dialog.connect_response(move |dialog: &gtk4::Dialog, response| {
+        let merge = match response {
+            gtk4::ResponseType::Ok => true,
+            gtk4::ResponseType::Apply => false,
+            _ => {
+                dialog.close();
+                return;
+            }
+        };
+
+        let resolved = groups
+            .iter()
+            .zip(&choices)
+            .map(|(group, buttons)| {
+                let kept = buttons.iter().position(|button| button.is_active()).unwrap_or(0);
+                let mut ordered = vec![group[kept]];
+                ordered.extend(
+                    group
+                        .iter()
+                        .enumerate()
+                        .filter(|(position, _)| *position != kept)
+                        .map(|(_, index)| *index),
+                );
+                ordered
+            })
+            .collect();
+        on_resolve(resolved, merge);
+        dialog.close();
+    });
+
+    dialog.present();
+}
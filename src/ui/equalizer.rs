@@ -0,0 +1,321 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use gtk4::prelude::*;
+
+use crate::config::{EQ_MAX_GAIN, EQ_MIN_GAIN, MARGIN_MEDIUM};
+use crate::playback::{
+    CUSTOM_PRESET, EQ_BAND_LABELS, EqPreset, PlaybackController, PlayerChange, equalizer_presets,
+    is_builtin_preset, save_equalizer,
+};
+use crate::storage::Database;
+use crate::ui::components::{show_message_dialog, show_name_dialog};
+
+const BAND_HEIGHT: i32 = 200;
+
+/// Widgets showing the preset in use, kept in step with the controller
+#[derive(Clone)]
+struct EqualizerControls {
+    presets: Rc<RefCell<Vec<EqPreset>>>,
+    preset_names: gtk4::StringList,
+    preset_dropdown: gtk4::DropDown,
+    delete_button: gtk4::Button,
+    bands: Rc<Vec<gtk4::Scale>>,
+    override_notice: gtk4::Label,
+    // Set while the widgets are updated, so their handlers don't treat it as a choice
+    updating: Rc<Cell<bool>>,
+}
+
+impl EqualizerControls {
+    /// The preset being heard: the playlist's own, or the one chosen here
+    fn shown_preset(controller: &PlaybackController) -> EqPreset {
+        controller
+            .playlist_equalizer()
+            .unwrap_or_else(|| controller.equalizer())
+    }
+
+    fn refresh(&self, controller: &PlaybackController) {
+        self.updating.set(true);
+        let shown = Self::shown_preset(controller);
+
+        // Gains moved by hand, or from a preset since deleted, are listed as Custom
+        let presets = self.presets.borrow();
+        let mut names: Vec<&str> = presets.iter().map(|preset| preset.name.as_str()).collect();
+        let found = presets.iter().position(|preset| preset.name == shown.name);
+        let selected = found.unwrap_or_else(|| {
+            names.push(CUSTOM_PRESET);
+            names.len() - 1
+        });
+        self.preset_names
+            .splice(0, self.preset_names.n_items(), &names);
+        self.preset_dropdown.set_selected(selected as u32);
+        self.delete_button
+            .set_sensitive(found.is_some_and(|index| !presets[index].builtin));
+
+        for (scale, gain) in self.bands.iter().zip(shown.gains) {
+            scale.set_value(gain);
+        }
+
+        match controller.playlist_equalizer() {
+            Some(preset) => {
+                self.override_notice.set_text(&format!(
+                    "This playlist plays with its own preset, “{}”. Choosing one here switches back.",
+                    preset.name
+                ));
+                self.override_notice.set_visible(true);
+            }
+            None => self.override_notice.set_visible(false),
+        }
+        self.updating.set(false);
+    }
+
+    fn reload_presets(&self, database: &Database, controller: &PlaybackController) {
+        *self.presets.borrow_mut() = equalizer_presets(database);
+        self.refresh(controller);
+    }
+}
+
+/// Make `preset` the one chosen on the equalizer page, and remember it
+fn choose_preset(
+    controller: &PlaybackController,
+    database: &Database,
+    parent: &impl IsA<gtk4::Widget>,
+    preset: EqPreset,
+) {
+    if let Err(error) = save_equalizer(database, &preset) {
+        show_message_dialog(parent, "Could not save equalizer", &error);
+    }
+    controller.set_equalizer(preset);
+}
+
+/// Builds the equalizer page, opened from the now playing view
+pub fn build_equalizer_view(controller: PlaybackController, database: Database) -> gtk4::Box {
+    let container = gtk4::Box::new(gtk4::Orientation::Vertical, 24);
+    container.set_margin_top(MARGIN_MEDIUM);
+    container.set_margin_bottom(MARGIN_MEDIUM);
+    container.set_margin_start(MARGIN_MEDIUM);
+    container.set_margin_end(MARGIN_MEDIUM);
+    container.add_css_class("equalizer-view");
+
+    let header = gtk4::Box::new(gtk4::Orientation::Horizontal, 12);
+    let back_button = gtk4::Button::from_icon_name("go-previous-symbolic");
+    back_button.add_css_class("flat");
+    back_button.set_tooltip_text(Some("Back to Now Playing"));
+    back_button.set_action_name(Some("app.show-now-playing"));
+    let title = gtk4::Label::new(Some("Equalizer"));
+    title.add_css_class("title-1");
+    title.set_xalign(0.0);
+    header.append(&back_button);
+    header.append(&title);
+
+    let unavailable = gtk4::Label::new(Some(
+        "The GStreamer equalizer plugin is not installed, so these settings have no effect.",
+    ));
+    unavailable.add_css_class("dim-label");
+    unavailable.set_wrap(true);
+    unavailable.set_xalign(0.0);
+    unavailable.set_visible(!controller.can_equalize());
+
+    let override_notice = gtk4::Label::new(None);
+    override_notice.add_css_class("dim-label");
+    override_notice.set_wrap(true);
+    override_notice.set_xalign(0.0);
+    override_notice.set_visible(false);
+
+    // Presets
+    let preset_row = gtk4::Box::new(gtk4::Orientation::Horizontal, 8);
+    let preset_names = gtk4::StringList::new(&[]);
+    let preset_dropdown = gtk4::DropDown::builder().model(&preset_names).build();
+    preset_dropdown.set_hexpand(true);
+    let save_button = gtk4::Button::with_label("Save as…");
+    let delete_button = gtk4::Button::from_icon_name("user-trash-symbolic");
+    delete_button.set_tooltip_text(Some("Delete preset"));
+    preset_row.append(&preset_dropdown);
+    preset_row.append(&save_button);
+    preset_row.append(&delete_button);
+
+    // One slider per band, boosting upwards
+    let bands_row = gtk4::Box::new(gtk4::Orientation::Horizontal, 4);
+    bands_row.set_halign(gtk4::Align::Center);
+    bands_row.add_css_class("equalizer-bands");
+    let mut bands = Vec::new();
+    for label in EQ_BAND_LABELS {
+        let band = gtk4::Box::new(gtk4::Orientation::Vertical, 4);
+        let scale =
+            gtk4::Scale::with_range(gtk4::Orientation::Vertical, EQ_MIN_GAIN, EQ_MAX_GAIN, 0.5);
+        scale.set_inverted(true);
+        scale.set_draw_value(false);
+        scale.add_mark(0.0, gtk4::PositionType::Right, None);
+        scale.set_size_request(-1, BAND_HEIGHT);
+        let label = gtk4::Label::new(Some(label));
+        label.add_css_class("caption");
+        label.add_css_class("dim-label");
+        band.append(&scale);
+        band.append(&label);
+        bands_row.append(&band);
+        bands.push(scale);
+    }
+
+    let hint = gtk4::Label::new(Some(&format!(
+        "Band frequencies in Hz; each slider boosts or cuts by up to {} dB",
+        EQ_MAX_GAIN
+    )));
+    hint.add_css_class("caption");
+    hint.add_css_class("dim-label");
+
+    container.append(&header);
+    container.append(&unavailable);
+    container.append(&preset_row);
+    container.append(&override_notice);
+    container.append(&bands_row);
+    container.append(&hint);
+
+    let controls = EqualizerControls {
+        presets: Rc::new(RefCell::new(Vec::new())),
+        preset_names,
+        preset_dropdown: preset_dropdown.clone(),
+        delete_button: delete_button.clone(),
+        bands: Rc::new(bands),
+        override_notice,
+        updating: Rc::new(Cell::new(false)),
+    };
+    controls.reload_presets(&database, &controller);
+
+    preset_dropdown.connect_selected_notify(glib::clone!(
+        #[strong]
+        controls,
+        #[strong]
+        controller,
+        #[strong]
+        database,
+        move |dropdown| {
+            if controls.updating.get() {
+                return;
+            }
+            let preset = controls
+                .presets
+                .borrow()
+                .get(dropdown.selected() as usize)
+                .cloned();
+            if let Some(preset) = preset {
+                choose_preset(&controller, &database, dropdown, preset);
+            }
+        }
+    ));
+
+    // Moving a slider turns the gains heard into a custom preset
+    for (index, scale) in controls.bands.iter().enumerate() {
+        scale.connect_value_changed(glib::clone!(
+            #[strong]
+            controls,
+            #[strong]
+            controller,
+            #[strong]
+            database,
+            move |scale| {
+                if controls.updating.get() {
+                    return;
+                }
+                let mut gains = EqualizerControls::shown_preset(&controller).gains;
+                gains[index] = scale.value();
+                choose_preset(&controller, &database, scale, EqPreset::custom(gains));
+            }
+        ));
+    }
+
+    save_button.connect_clicked(glib::clone!(
+        #[strong]
+        controls,
+        #[strong]
+        controller,
+        #[strong]
+        database,
+        move |button| {
+            let shown = EqualizerControls::shown_preset(&controller);
+            let initial = if shown.builtin || shown.name == CUSTOM_PRESET {
+                ""
+            } else {
+                shown.name.as_str()
+            };
+            show_name_dialog(
+                button,
+                "Save preset",
+                "Preset name",
+                initial,
+                glib::clone!(
+                    #[strong]
+                    controls,
+                    #[strong]
+                    controller,
+                    #[strong]
+                    database,
+                    #[weak]
+                    button,
+                    move |name| {
+                        if is_builtin_preset(&name) || name.eq_ignore_ascii_case(CUSTOM_PRESET) {
+                            show_message_dialog(
+                                &button,
+                                "Could not save preset",
+                                &format!("“{}” is a built-in name; choose another.", name),
+                            );
+                            return;
+                        }
+                        let gains = EqualizerControls::shown_preset(&controller).gains;
+                        if let Err(error) = database.save_eq_preset(&name, &gains) {
+                            show_message_dialog(&button, "Could not save preset", &error);
+                            return;
+                        }
+                        *controls.presets.borrow_mut() = equalizer_presets(&database);
+                        let preset = EqPreset {
+                            name,
+                            gains,
+                            builtin: false,
+                        };
+                        choose_preset(&controller, &database, &button, preset);
+                    }
+                ),
+            );
+        }
+    ));
+
+    delete_button.connect_clicked(glib::clone!(
+        #[strong]
+        controls,
+        #[strong]
+        controller,
+        #[strong]
+        database,
+        move |button| {
+            let shown = EqualizerControls::shown_preset(&controller);
+            if shown.builtin {
+                return;
+            }
+            if let Err(error) = database.delete_eq_preset(&shown.name) {
+                show_message_dialog(button, "Could not delete preset", &error);
+                return;
+            }
+            *controls.presets.borrow_mut() = equalizer_presets(&database);
+            // The sound stays as it was until another preset is chosen
+            choose_preset(
+                &controller,
+                &database,
+                button,
+                EqPreset::custom(shown.gains),
+            );
+        }
+    ));
+
+    controller.connect_changed(glib::clone!(
+        #[strong]
+        controls,
+        #[strong]
+        controller,
+        move |change| {
+            if change == PlayerChange::Equalizer {
+                controls.refresh(&controller);
+            }
+        }
+    ));
+
+    container
+}
@@ -1,7 +1,8 @@
 use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::sync::mpsc;
-use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use glib::ControlFlow;
@@ -9,16 +10,88 @@ use gtk4::prelude::*;
 use libadwaita as adw;
 use libadwaita::prelude::*;
 
-use crate::api::{InnertubeClient, SearchResult, StreamInfo};
+use crate::api::{SearchResult, StreamInfo};
 use crate::config::{
-    ICON_HEART_FILLED, ICON_PLAYLIST, ICON_RECENT, MARGIN_MEDIUM, MARGIN_TINY, POLL_INTERVAL_MS,
+    ICON_HEART, ICON_HEART_FILLED, ICON_LOCAL_FILES, ICON_PLAYLIST, ICON_RECENT, MARGIN_MEDIUM,
+    MARGIN_TINY, POLL_INTERVAL_MS,
 };
-use crate::playback::PlaybackController;
-use crate::storage::{Database, Song};
-use crate::ui::components::{clear_listbox, cover_widget, section};
+use crate::formats::{PlaylistFormat, find_duplicates};
+use crate::local::LocalLibrary;
+use crate::sources::Sources;
+use crate::playback::{DownloadManager, OfflineMode, PlaybackController, equalizer_presets, find_preset};
+use crate::storage::{Database, Playlist, PlaylistFolder, PlaylistSort, SmartPlaylist, Song};
+use crate::ui::components::{
+    choose_file, clear_listbox, cover_widget, playlist_cover_widget, section, show_message_dialog,
+    show_name_dialog, show_toast, show_undo_toast,
+};
+use crate::ui::duplicates::show_duplicates_dialog;
+use crate::ui::playlist_files::{export_playlist, import_playlist};
+use crate::ui::selection::{
+    SongSelection, download_songs, queue_items, selected_songs, selection_player,
+    show_add_to_playlist_dialog, song_count_label,
+};
+use crate::ui::smart_playlists::show_smart_playlist_editor;
+
+/// Number of song covers in a playlist collage
+const COLLAGE_SIZE: usize = 4;
+const DETAIL_COVER_SIZE: i32 = 96;
+const COVER_IMAGE_PATTERNS: &[&str] = &["*.png", "*.jpg", "*.jpeg", "*.webp"];
+
+/// How the cover should change when playlist details are saved
+enum CoverChoice {
+    Keep,
+    Custom(PathBuf),
+    Collage,
+}
+
+/// Values entered in the edit playlist dialog
+struct PlaylistDetails {
+    name: String,
+    description: String,
+    cover: CoverChoice,
+    // Equalizer preset the playlist plays with, or `None` for the one chosen on the equalizer page
+    eq_preset: Option<String>,
+}
+
+/// Kind and ID of a row in the playlists list, stored as the row's widget name
+#[derive(Clone, Copy)]
+enum PlaylistRow {
+    Playlist(i64),
+    Smart(i64),
+    Folder(i64),
+}
+
+impl PlaylistRow {
+    fn name(self) -> String {
+        match self {
+            PlaylistRow::Playlist(id) => format!("playlist:{}", id),
+            PlaylistRow::Smart(id) => format!("smart:{}", id),
+            PlaylistRow::Folder(id) => format!("folder:{}", id),
+        }
+    }
+
+    fn from_row(row: &gtk4::ListBoxRow) -> Option<Self> {
+        let name = row.widget_name();
+        let (kind, id) = name.split_once(':')?;
+        let id = id.parse().ok()?;
+        match kind {
+            "playlist" => Some(PlaylistRow::Playlist(id)),
+            "smart" => Some(PlaylistRow::Smart(id)),
+            "folder" => Some(PlaylistRow::Folder(id)),
+            _ => None,
+        }
+    }
+}
 
 /// Builds the library view
-pub fn build_library_view(playback: PlaybackController, database: Database) -> gtk4::Box {
+pub fn build_library_view(
+    playback: PlaybackController,
+    database: Database,
+    downloads: DownloadManager,
+    offline: OfflineMode,
+    local_library: LocalLibrary,
+    sources: Sources,
+) -> gtk4::Box {
     let container = gtk4::Box::new(gtk4::Orientation::Vertical, 0);
 
     let scroller = gtk4::ScrolledWindow::new();
@@ -46,12 +119,47 @@ pub fn build_library_view(playback: PlaybackController, database: Database) -> g
     let liked_count = database.get_liked_songs_count();
     let liked_header = format!("Liked Songs ({})", liked_count);
 
+    let liked_filter = gtk4::SearchEntry::new();
+    liked_filter.set_placeholder_text(Some("Filter liked songs"));
+    liked_filter.set_hexpand(true);
+    let liked_selection = SongSelection::new();
+    let liked_toolbar = gtk4::Box::new(gtk4::Orientation::Horizontal, MARGIN_TINY);
+    let liked_duplicates_btn = gtk4::Button::from_icon_name("edit-copy-symbolic");
+    liked_duplicates_btn.set_tooltip_text(Some("Find duplicates"));
+    liked_duplicates_btn.add_css_class("flat");
+    liked_duplicates_btn.add_css_class("circular");
+    liked_toolbar.append(&liked_filter);
+    liked_toolbar.append(&liked_duplicates_btn);
+    liked_toolbar.append(liked_selection.toggle_button());
+    let liked_content = gtk4::Box::new(gtk4::Orientation::Vertical, MARGIN_TINY);
+    liked_content.append(&liked_toolbar);
+    liked_content.append(liked_selection.bar());
+    liked_content.append(&liked_list);
+
     // Recent plays section
     let recent_list = gtk4::ListBox::new();
     recent_list.set_selection_mode(gtk4::SelectionMode::None);
     recent_list.add_css_class("boxed-list");
     recent_list.set_activate_on_single_click(true);
 
+    // Local files section
+    let local_list = gtk4::ListBox::new();
+    local_list.set_selection_mode(gtk4::SelectionMode::None);
+    local_list.add_css_class("boxed-list");
+    local_list.set_activate_on_single_click(true);
+
+    let local_filter = gtk4::SearchEntry::new();
+    local_filter.set_placeholder_text(Some("Filter local files"));
+    local_filter.set_hexpand(true);
+    let local_selection = SongSelection::new();
+    let local_toolbar = gtk4::Box::new(gtk4::Orientation::Horizontal, MARGIN_TINY);
+    local_toolbar.append(&local_filter);
+    local_toolbar.append(local_selection.toggle_button());
+    let local_content = gtk4::Box::new(gtk4::Orientation::Vertical, MARGIN_TINY);
+    local_content.append(&local_toolbar);
+    local_content.append(local_selection.bar());
+    local_content.append(&local_list);
+
     // Playlists section
     let playlists_list = gtk4::ListBox::new();
     playlists_list.set_selection_mode(gtk4::SelectionMode::None);
@@ -72,8 +180,52 @@ pub fn build_library_view(playback: PlaybackController, database: Database) -> g
     let edit_playlist_btn = gtk4::Button::from_icon_name("document-edit-symbolic");
     edit_playlist_btn.add_css_class("flat");
     edit_playlist_btn.add_css_class("circular");
+
+    // Export menu (M3U / XSPF)
+    let export_m3u_btn = gtk4::Button::with_label("Export as M3U…");
+    export_m3u_btn.add_css_class("flat");
+    let export_xspf_btn = gtk4::Button::with_label("Export as XSPF…");
+    export_xspf_btn.add_css_class("flat");
+    let export_menu = gtk4::Box::new(gtk4::Orientation::Vertical, 0);
+    export_menu.append(&export_m3u_btn);
+    export_menu.append(&export_xspf_btn);
+    let export_popover = gtk4::Popover::new();
+    export_popover.set_child(Some(&export_menu));
+    let export_playlist_btn = gtk4::MenuButton::new();
+    export_playlist_btn.set_icon_name("document-save-symbolic");
+    export_playlist_btn.set_tooltip_text(Some("Export playlist"));
+    export_playlist_btn.set_popover(Some(&export_popover));
+    export_playlist_btn.add_css_class("flat");
+    export_playlist_btn.add_css_class("circular");
+
+    let playlist_duplicates_btn = gtk4::Button::from_icon_name("edit-copy-symbolic");
+    playlist_duplicates_btn.set_tooltip_text(Some("Find duplicates"));
+    playlist_duplicates_btn.add_css_class("flat");
+    playlist_duplicates_btn.add_css_class("circular");
+
+    let download_playlist_btn = gtk4::Button::from_icon_name("folder-download-symbolic");
+    download_playlist_btn.set_tooltip_text(Some("Download for offline playback"));
+    download_playlist_btn.add_css_class("flat");
+    download_playlist_btn.add_css_class("circular");
+
+    let play_playlist_btn = gtk4::Button::from_icon_name("media-playback-start-symbolic");
+    play_playlist_btn.set_tooltip_text(Some("Play"));
+    play_playlist_btn.add_css_class("flat");
+    play_playlist_btn.add_css_class("circular");
+    let shuffle_playlist_btn = gtk4::Button::from_icon_name("media-playlist-shuffle-symbolic");
+    shuffle_playlist_btn.set_tooltip_text(Some("Shuffle"));
+    shuffle_playlist_btn.add_css_class("flat");
+    shuffle_playlist_btn.add_css_class("circular");
+
     playlist_header.append(&back_btn);
     playlist_header.append(&playlist_title);
+    playlist_header.append(&play_playlist_btn);
+    playlist_header.append(&shuffle_playlist_btn);
+    let playlist_selection = SongSelection::new();
+    playlist_header.append(playlist_selection.toggle_button());
+    playlist_header.append(&playlist_duplicates_btn);
+    playlist_header.append(&download_playlist_btn);
+    playlist_header.append(&export_playlist_btn);
     playlist_header.append(&edit_playlist_btn);
 
     let playlist_songs_list = gtk4::ListBox::new();
@@ -81,38 +233,134 @@ pub fn build_library_view(playback: PlaybackController, database: Database) -> g
     playlist_songs_list.add_css_class("boxed-list");
     playlist_songs_list.set_activate_on_single_click(true);
 
+    // Cover and description under the header
+    let playlist_info = gtk4::Box::new(gtk4::Orientation::Horizontal, 16);
+    let playlist_cover_slot = gtk4::Box::new(gtk4::Orientation::Vertical, 0);
+    let playlist_description = gtk4::Label::new(None);
+    playlist_description.set_wrap(true);
+    playlist_description.set_xalign(0.0);
+    playlist_description.set_valign(gtk4::Align::Center);
+    playlist_description.set_hexpand(true);
+    playlist_description.add_css_class("dim-label");
+    playlist_info.append(&playlist_cover_slot);
+    playlist_info.append(&playlist_description);
+
+    let playlist_filter = gtk4::SearchEntry::new();
+    playlist_filter.set_placeholder_text(Some("Filter songs"));
+
     playlist_detail.append(&playlist_header);
+    playlist_detail.append(&playlist_info);
+    playlist_detail.append(&playlist_filter);
+    playlist_detail.append(playlist_selection.bar());
     playlist_detail.append(&playlist_songs_list);
 
     // Main content (will be toggled with playlist detail)
     let main_content = gtk4::Box::new(gtk4::Orientation::Vertical, 24);
     main_content.append(&title);
-    main_content.append(&section(&liked_header, Some(ICON_HEART_FILLED), &liked_list));
+    main_content.append(&section(&liked_header, Some(ICON_HEART_FILLED), &liked_content));
     main_content.append(&section("Recent Plays", Some(ICON_RECENT), &recent_list));
     main_content.append(&section("Playlists", Some(ICON_PLAYLIST), &playlists_list));
 
-    // Create playlist button
+    // Create and import playlist buttons
+    let playlist_actions = gtk4::Box::new(gtk4::Orientation::Horizontal, MARGIN_TINY);
     let add_playlist = gtk4::Button::with_label("Create playlist");
-    add_playlist.set_halign(gtk4::Align::Start);
     add_playlist.add_css_class("suggested-action");
-    main_content.append(&add_playlist);
+    let add_smart_playlist = gtk4::Button::with_label("New smart playlist");
+    add_smart_playlist.set_tooltip_text(Some("Create a playlist that fills itself from rules"));
+    let import_playlist_btn = gtk4::Button::with_label("Import playlist");
+    import_playlist_btn.set_tooltip_text(Some("Import an M3U, XSPF or CSV file"));
+    // Imports match songs on YouTube, which needs the network
+    import_playlist_btn.set_sensitive(!offline.is_offline());
+    offline.connect_changed(glib::clone!(
+        #[weak]
+        import_playlist_btn,
+        move |is_offline| import_playlist_btn.set_sensitive(!is_offline)
+    ));
+    let add_folder = gtk4::Button::from_icon_name("folder-new-symbolic");
+    add_folder.set_tooltip_text(Some("New folder"));
+
+    // Sort order for the playlists list, remembered in the database
+    let sort_labels: Vec<&str> = PlaylistSort::ALL.iter().map(|sort| sort.label()).collect();
+    let sort_dropdown = gtk4::DropDown::from_strings(&sort_labels);
+    sort_dropdown.set_tooltip_text(Some("Sort playlists"));
+    sort_dropdown.set_hexpand(true);
+    sort_dropdown.set_halign(gtk4::Align::End);
+    let current_sort = database.get_playlist_sort();
+    if let Some(index) = PlaylistSort::ALL.iter().position(|sort| *sort == current_sort) {
+        sort_dropdown.set_selected(index as u32);
+    }
+
+    playlist_actions.append(&add_playlist);
+    playlist_actions.append(&add_smart_playlist);
+    playlist_actions.append(&import_playlist_btn);
+    playlist_actions.append(&add_folder);
+    playlist_actions.append(&sort_dropdown);
+    main_content.append(&playlist_actions);
+
+    main_content.append(&section("Local Files", Some(ICON_LOCAL_FILES), &local_content));
 
     content.append(&main_content);
     content.append(&playlist_detail);
 
-    // Store current playlist ID for detail view
+    // Store current playlist ID for detail view; smart playlists set the second cell instead
     let current_playlist_id: Rc<Cell<i64>> = Rc::new(Cell::new(0));
+    let current_smart_id: Rc<Cell<Option<i64>>> = Rc::new(Cell::new(None));
 
     // Load initial data
-    load_liked_songs(&liked_list, &database, &playback);
+    load_liked_songs(&liked_list, &database, &playback, &liked_selection);
     load_recent_plays(&recent_list, &database, &playback);
     load_playlists(&playlists_list, &database);
+    load_local_tracks(&local_list, &database, &local_library, &local_selection);
+
+    // Rescans add, change and remove local files
+    local_library.connect_changed(glib::clone!(
+        #[weak]
+        local_list,
+        #[strong]
+        database,
+        #[strong]
+        local_library,
+        #[strong]
+        local_selection,
+        move || load_local_tracks(&local_list, &database, &local_library, &local_selection)
+    ));
+
+    // Filter entries narrow the lists through the library search index
+    wire_song_filter(&liked_filter, &liked_list, &database);
+    wire_song_filter(&playlist_filter, &playlist_songs_list, &database);
+    wire_song_filter(&local_filter, &local_list, &database);
+
+    // Bulk actions on the songs picked in selection mode
+    let play_selection = selection_player(&playback, &sources);
+    wire_liked_selection(
+        &liked_selection,
+        &liked_list,
+        &play_selection,
+        &database,
+        &playback,
+        &downloads,
+    );
+    let remove_selected_btn = wire_playlist_selection(
+        &playlist_selection,
+        &playlist_songs_list,
+        &current_playlist_id,
+        &current_smart_id,
+        &play_selection,
+        &database,
+        &playback,
+        &downloads,
+    );
+
+    wire_local_selection(&local_selection, &play_selection, &database, &playback);
 
     // Handle liked songs row activation (play song)
-    wire_liked_songs_playback(&liked_list, &database, &playback);
+    wire_liked_songs_playback(&liked_list, &liked_selection, &database, &playback, &sources);
+
+    // Local files play straight from disk
+    wire_local_tracks_playback(&local_list, &local_selection, &database, &playback);
 
     // Handle recent plays row activation (play song)
-    wire_recent_plays_playback(&recent_list, &database, &playback);
+    wire_recent_plays_playback(&recent_list, &database, &playback, &sources);
 
     // Handle playlist row activation (show playlist detail)
     playlists_list.connect_row_activated(glib::clone!(
@@ -128,25 +376,101 @@ pub fn build_library_view(playback: PlaybackController, database: Database) -> g
         playlist_title,
         #[weak]
         playlist_songs_list,
+        #[weak]
+        export_playlist_btn,
+        #[weak]
+        playlist_duplicates_btn,
+        #[weak]
+        playlist_filter,
+        #[weak]
+        playlist_cover_slot,
+        #[weak]
+        playlist_description,
         #[strong]
         current_playlist_id,
-        move |_, row| {
-            let index = row.index();
-            if index < 0 {
+        #[strong]
+        current_smart_id,
+        #[strong]
+        playlist_selection,
+        #[weak]
+        remove_selected_btn,
+        move |list, row| {
+            let Some(entry) = PlaylistRow::from_row(row) else {
                 return;
-            }
-
-            if let Ok(playlists) = database.get_playlists() {
-                if let Some(playlist) = playlists.get(index as usize) {
+            };
+            match entry {
+                PlaylistRow::Folder(folder_id) => {
+                    // Folder rows expand or collapse in place
+                    let expanded = database
+                        .get_folders()
+                        .unwrap_or_default()
+                        .iter()
+                        .any(|folder| folder.id == folder_id && folder.expanded);
+                    if database.set_folder_expanded(folder_id, !expanded).is_ok() {
+                        load_playlists(list, &database);
+                    }
+                    return;
+                }
+                PlaylistRow::Playlist(playlist_id) => {
+                    let Ok(Some(playlist)) = database.get_playlist(playlist_id) else {
+                        return;
+                    };
                     current_playlist_id.set(playlist.id);
+                    current_smart_id.set(None);
                     playlist_title.set_text(&playlist.name);
-
-                    load_playlist_songs(&playlist_songs_list, playlist.id, &database, &playback);
-
-                    main_content.set_visible(false);
-                    playlist_detail.set_visible(true);
+                    export_playlist_btn.set_visible(true);
+                    playlist_duplicates_btn.set_visible(true);
+                    remove_selected_btn.set_visible(true);
+                    show_playlist_info(
+                        &playlist_cover_slot,
+                        &playlist_description,
+                        playlist_cover_widget(
+                            playlist.cover_path.as_deref(),
+                            &database.get_playlist_thumbnails(playlist.id, COLLAGE_SIZE),
+                            DETAIL_COVER_SIZE,
+                        ),
+                        &playlist.description,
+                    );
+
+                    load_playlist_songs(
+                        &playlist_songs_list,
+                        playlist.id,
+                        &database,
+                        &playback,
+                        &playlist_selection,
+                    );
+                }
+                PlaylistRow::Smart(smart_id) => {
+                    let Ok(Some(smart_playlist)) = database.get_smart_playlist(smart_id) else {
+                        return;
+                    };
+                    current_playlist_id.set(0);
+                    current_smart_id.set(Some(smart_playlist.id));
+                    playlist_title.set_text(&smart_playlist.name);
+                    export_playlist_btn.set_visible(false);
+                    playlist_duplicates_btn.set_visible(false);
+                    remove_selected_btn.set_visible(false);
+                    let songs = database.get_smart_playlist_songs(&smart_playlist).unwrap_or_default();
+                    show_playlist_info(
+                        &playlist_cover_slot,
+                        &playlist_description,
+                        playlist_cover_widget(None, &smart_thumbnails(&songs), DETAIL_COVER_SIZE),
+                        "",
+                    );
+
+                    load_smart_playlist_songs(
+                        &playlist_songs_list,
+                        &smart_playlist,
+                        &database,
+                        &playlist_selection,
+                    );
                 }
             }
+            playlist_filter.set_text("");
+            playlist_selection.toggle_button().set_active(false);
+
+            main_content.set_visible(false);
+            playlist_detail.set_visible(true);
         }
     ));
 
@@ -164,11 +488,13 @@ pub fn build_library_view(playback: PlaybackController, database: Database) -> g
         database,
         #[strong]
         playback,
+        #[strong]
+        liked_selection,
         move |_| {
             main_content.set_visible(true);
             playlist_detail.set_visible(false);
             // Refresh lists when returning
-            load_liked_songs(&liked_list, &database, &playback);
+            load_liked_songs(&liked_list, &database, &playback, &liked_selection);
             load_playlists(&playlists_list, &database);
         }
     ));
@@ -182,9 +508,10 @@ pub fn build_library_view(playback: PlaybackController, database: Database) -> g
         #[weak]
         add_playlist,
         move |_| {
-            show_playlist_name_dialog(
+            show_name_dialog(
                 &add_playlist,
                 "Create playlist",
+                "Playlist name",
                 "",
                 glib::clone!(
                     #[strong]
@@ -201,35 +528,286 @@ pub fn build_library_view(playback: PlaybackController, database: Database) -> g
         }
     ));
 
-    // Handle rename playlist button
+    // Handle new folder button
+    add_folder.connect_clicked(glib::clone!(
+        #[strong]
+        database,
+        #[weak]
+        playlists_list,
+        move |button| {
+            show_name_dialog(
+                button,
+                "New folder",
+                "Folder name",
+                "",
+                glib::clone!(
+                    #[strong]
+                    database,
+                    #[weak]
+                    playlists_list,
+                    move |name| {
+                        if database.create_folder(&name).is_ok() {
+                            load_playlists(&playlists_list, &database);
+                        }
+                    }
+                ),
+            );
+        }
+    ));
+
+    // Handle playlist sort order
+    sort_dropdown.connect_selected_notify(glib::clone!(
+        #[strong]
+        database,
+        #[weak]
+        playlists_list,
+        move |dropdown| {
+            let Some(sort) = PlaylistSort::ALL.get(dropdown.selected() as usize) else {
+                return;
+            };
+            if database.set_playlist_sort(*sort).is_ok() {
+                load_playlists(&playlists_list, &database);
+            }
+        }
+    ));
+
+    // Handle create smart playlist button
+    add_smart_playlist.connect_clicked(glib::clone!(
+        #[strong]
+        database,
+        #[weak]
+        playlists_list,
+        move |button| {
+            show_smart_playlist_editor(
+                button,
+                None,
+                glib::clone!(
+                    #[strong]
+                    database,
+                    #[weak]
+                    playlists_list,
+                    move |name, rules, match_all| {
+                        if database.create_smart_playlist(&name, &rules, match_all).is_ok() {
+                            load_playlists(&playlists_list, &database);
+                        }
+                    }
+                ),
+            );
+        }
+    ));
+
+    // Handle import playlist button
+    import_playlist_btn.connect_clicked(glib::clone!(
+        #[strong]
+        database,
+        #[strong]
+        sources,
+        #[weak]
+        playlists_list,
+        move |button| {
+            import_playlist(
+                button,
+                &database,
+                &sources,
+                glib::clone!(
+                    #[strong]
+                    database,
+                    #[weak]
+                    playlists_list,
+                    move || load_playlists(&playlists_list, &database)
+                ),
+            );
+        }
+    ));
+
+    // Handle duplicate finders
+    liked_duplicates_btn.connect_clicked(glib::clone!(
+        #[weak]
+        liked_list,
+        #[strong]
+        database,
+        #[strong]
+        playback,
+        #[strong]
+        liked_selection,
+        move |button| find_liked_duplicates(button, &liked_list, &database, &playback, &liked_selection)
+    ));
+
+    playlist_duplicates_btn.connect_clicked(glib::clone!(
+        #[weak]
+        playlist_songs_list,
+        #[strong]
+        database,
+        #[strong]
+        playback,
+        #[strong]
+        playlist_selection,
+        #[strong]
+        current_playlist_id,
+        move |button| {
+            find_playlist_duplicates(
+                button,
+                &playlist_songs_list,
+                current_playlist_id.get(),
+                &database,
+                &playback,
+                &playlist_selection,
+            )
+        }
+    ));
+
+    // Handle downloading the open playlist
+    download_playlist_btn.connect_clicked(glib::clone!(
+        #[strong]
+        database,
+        #[strong]
+        downloads,
+        #[strong]
+        current_playlist_id,
+        #[strong]
+        current_smart_id,
+        move |button| {
+            let songs = open_playlist_songs(&database, current_playlist_id.get(), current_smart_id.get());
+            download_songs(button, &downloads, &songs);
+        }
+    ));
+
+    // Handle playlist export menu
+    for (button, format) in [
+        (export_m3u_btn.clone(), PlaylistFormat::M3u),
+        (export_xspf_btn.clone(), PlaylistFormat::Xspf),
+    ] {
+        button.connect_clicked(glib::clone!(
+            #[strong]
+            database,
+            #[strong]
+            current_playlist_id,
+            #[weak]
+            export_popover,
+            #[weak]
+            export_playlist_btn,
+            move |_| {
+                export_popover.popdown();
+                let playlist_id = current_playlist_id.get();
+                if playlist_id > 0 {
+                    export_playlist(&export_playlist_btn, &database, playlist_id, format);
+                }
+            }
+        ));
+    }
+
+    // Handle edit playlist button (rule editor for smart playlists)
     edit_playlist_btn.connect_clicked(glib::clone!(
         #[strong]
         database,
         #[weak]
         playlist_title,
+        #[weak]
+        playlist_songs_list,
+        #[weak]
+        playlist_cover_slot,
+        #[weak]
+        playlist_description,
         #[strong]
         current_playlist_id,
+        #[strong]
+        current_smart_id,
+        #[strong]
+        playlist_selection,
         #[weak]
         edit_playlist_btn,
         move |_| {
-            let playlist_id = current_playlist_id.get();
-            if playlist_id <= 0 {
+            if let Some(smart_id) = current_smart_id.get() {
+                let Ok(Some(smart_playlist)) = database.get_smart_playlist(smart_id) else {
+                    return;
+                };
+                show_smart_playlist_editor(
+                    &edit_playlist_btn,
+                    Some(&smart_playlist),
+                    glib::clone!(
+                        #[strong]
+                        database,
+                        #[weak]
+                        playlist_title,
+                        #[weak]
+                        playlist_songs_list,
+                        #[weak]
+                        playlist_cover_slot,
+                        #[weak]
+                        playlist_description,
+                        #[strong]
+                        playlist_selection,
+                        move |name, rules, match_all| {
+                            if database
+                                .update_smart_playlist(smart_id, &name, &rules, match_all)
+                                .is_err()
+                            {
+                                return;
+                            }
+                            playlist_title.set_text(&name);
+                            if let Ok(Some(updated)) = database.get_smart_playlist(smart_id) {
+                                load_smart_playlist_songs(
+                                    &playlist_songs_list,
+                                    &updated,
+                                    &database,
+                                    &playlist_selection,
+                                );
+                                let songs = database.get_smart_playlist_songs(&updated).unwrap_or_default();
+                                show_playlist_info(
+                                    &playlist_cover_slot,
+                                    &playlist_description,
+                                    playlist_cover_widget(None, &smart_thumbnails(&songs), DETAIL_COVER_SIZE),
+                                    "",
+                                );
+                            }
+                        }
+                    ),
+                );
                 return;
             }
-            let current_name = playlist_title.text().to_string();
-            show_playlist_name_dialog(
+
+            let playlist_id = current_playlist_id.get();
+            let Ok(Some(playlist)) = database.get_playlist(playlist_id) else {
+                return;
+            };
+            let eq_presets: Vec<String> = equalizer_presets(&database)
+                .into_iter()
+                .map(|preset| preset.name)
+                .collect();
+            show_playlist_details_dialog(
                 &edit_playlist_btn,
-                "Rename playlist",
-                &current_name,
+                &playlist,
+                &eq_presets,
+                database.get_playlist_eq_preset(playlist_id).as_deref(),
                 glib::clone!(
                     #[strong]
                     database,
                     #[weak]
                     playlist_title,
-                    move |name| {
-                        if database.rename_playlist(playlist_id, &name).is_ok() {
-                            playlist_title.set_text(&name);
+                    #[weak]
+                    playlist_cover_slot,
+                    #[weak]
+                    playlist_description,
+                    #[weak]
+                    edit_playlist_btn,
+                    move |details| {
+                        if let Err(error) = save_playlist_details(&database, playlist_id, details) {
+                            show_message_dialog(&edit_playlist_btn, "Could not save playlist", &error);
                         }
+                        let Ok(Some(playlist)) = database.get_playlist(playlist_id) else {
+                            return;
+                        };
+                        playlist_title.set_text(&playlist.name);
+                        show_playlist_info(
+                            &playlist_cover_slot,
+                            &playlist_description,
+                            playlist_cover_widget(
+                                playlist.cover_path.as_deref(),
+                                &database.get_playlist_thumbnails(playlist_id, COLLAGE_SIZE),
+                                DETAIL_COVER_SIZE,
+                            ),
+                            &playlist.description,
+                        );
                     }
                 ),
             );
@@ -237,7 +815,17 @@ pub fn build_library_view(playback: PlaybackController, database: Database) -> g
     ));
 
     // Wire playlist songs playback
-    wire_playlist_songs_playback(&playlist_songs_list, &current_playlist_id, &database, &playback);
+    wire_playlist_songs_playback(
+        &playlist_songs_list,
+        &playlist_selection,
+        &play_playlist_btn,
+        &shuffle_playlist_btn,
+        &current_playlist_id,
+        &current_smart_id,
+        &database,
+        &playback,
+        &sources,
+    );
 
     // Set up periodic refresh of recent plays and liked songs
     setup_refresh_polling(&liked_list, &recent_list, &database, &playback, &main_content);
@@ -247,7 +835,12 @@ pub fn build_library_view(playback: PlaybackController, database: Database) -> g
     container
 }
 
-fn load_liked_songs(list: &gtk4::ListBox, database: &Rc<Database>, playback: &Rc<PlaybackController>) {
+fn load_liked_songs(
+    list: &gtk4::ListBox,
+    database: &Rc<Database>,
+    playback: &Rc<PlaybackController>,
+    selection: &SongSelection,
+) {
     clear_listbox(list);
 
     match database.get_liked_songs() {
@@ -258,7 +851,9 @@ fn load_liked_songs(list: &gtk4::ListBox, database: &Rc<Database>, playback: &Rc
                     database.clone(),
                     list.clone(),
                     playback.clone(),
+                    selection.clone(),
                 );
+                selection.attach(&row);
                 list.append(&row);
             }
         }
@@ -268,6 +863,40 @@ fn load_liked_songs(list: &gtk4::ListBox, database: &Rc<Database>, playback: &Rc
     }
 }
 
+fn load_local_tracks(
+    list: &gtk4::ListBox,
+    database: &Rc<Database>,
+    local_library: &LocalLibrary,
+    selection: &SongSelection,
+) {
+    clear_listbox(list);
+
+    match database.get_local_tracks() {
+        Ok(tracks) if !tracks.is_empty() => {
+            for track in tracks.iter() {
+                let row = create_song_row(&track.song);
+                if let (Some(album), Some(action)) = (
+                    track.album.as_deref(),
+                    row.child().and_downcast::<adw::ActionRow>(),
+                ) {
+                    action.set_subtitle(&format!("{} · {}", track.song.artist, album));
+                }
+                selection.attach(&row);
+                list.append(&row);
+            }
+        }
+        _ if local_library.is_scanning() => {
+            list.append(&create_empty_state("Scanning music folders…", "Found songs will show up here"));
+        }
+        _ if local_library.folders().is_empty() => {
+            list.append(&create_empty_state("No music folders", "Add a folder in Settings to play your own files"));
+        }
+        _ => {
+            list.append(&create_empty_state("No local files", "No FLAC, MP3, Opus or M4A files were found"));
+        }
+    }
+}
+
 fn load_recent_plays(list: &gtk4::ListBox, database: &Rc<Database>, _playback: &Rc<PlaybackController>) {
     clear_listbox(list);
 
@@ -287,18 +916,53 @@ fn load_recent_plays(list: &gtk4::ListBox, database: &Rc<Database>, _playback: &
 fn load_playlists(list: &gtk4::ListBox, database: &Rc<Database>) {
     clear_listbox(list);
 
-    match database.get_playlists() {
-        Ok(playlists) if !playlists.is_empty() => {
-            for playlist in playlists.iter() {
-                let count = database.get_playlist_song_count(playlist.id);
-                let row = create_playlist_row(&playlist.name, count, playlist.id, database.clone(), list.clone());
-                list.append(&row);
+    let playlists = database.get_playlists().unwrap_or_default();
+    let folders = database.get_folders().unwrap_or_default();
+    let smart_playlists = database.get_smart_playlists().unwrap_or_default();
+    if playlists.is_empty() && folders.is_empty() && smart_playlists.is_empty() {
+        list.append(&create_empty_state("No playlists", "Create a playlist to get started"));
+        return;
+    }
+
+    let append_playlist = |playlist: &Playlist, indented: bool| {
+        let count = database.get_playlist_song_count(playlist.id);
+        let row = create_playlist_row(playlist, count, &folders, database.clone(), list.clone());
+        if indented {
+            if let Some(child) = row.child() {
+                child.set_margin_start(MARGIN_MEDIUM);
             }
         }
-        _ => {
-            list.append(&create_empty_state("No playlists", "Create a playlist to get started"));
+        list.append(&row);
+    };
+
+    // Pinned playlists come first wherever they are filed, then folders, then the rest
+    for playlist in playlists.iter().filter(|playlist| playlist.pinned) {
+        append_playlist(playlist, false);
+    }
+    for folder in folders.iter() {
+        let contents: Vec<&Playlist> = playlists
+            .iter()
+            .filter(|playlist| !playlist.pinned && playlist.folder_id == Some(folder.id))
+            .collect();
+        list.append(&create_folder_row(folder, contents.len(), database.clone(), list.clone()));
+        if folder.expanded {
+            for playlist in contents {
+                append_playlist(playlist, true);
+            }
         }
     }
+    for playlist in playlists.iter().filter(|playlist| {
+        !playlist.pinned
+            && playlist
+                .folder_id
+                .is_none_or(|folder_id| !folders.iter().any(|folder| folder.id == folder_id))
+    }) {
+        append_playlist(playlist, false);
+    }
+    for smart_playlist in smart_playlists.iter() {
+        let row = create_smart_playlist_row(smart_playlist, database.clone(), list.clone());
+        list.append(&row);
+    }
 }
 
 fn load_playlist_songs(
@@ -306,6 +970,7 @@ fn load_playlist_songs(
     playlist_id: i64,
     database: &Rc<Database>,
     playback: &Rc<PlaybackController>,
+    selection: &SongSelection,
 ) {
     clear_listbox(list);
 
@@ -313,26 +978,18 @@ fn load_playlist_songs(
         Ok(songs) if !songs.is_empty() => {
             let total = songs.len();
             for (index, playlist_song) in songs.iter().enumerate() {
-                let prev_id = if index > 0 {
-                    Some(songs[index - 1].id)
-                } else {
-                    None
-                };
-                let next_id = if index + 1 < total {
-                    Some(songs[index + 1].id)
-                } else {
-                    None
-                };
                 let row = create_playlist_song_row(
                     &playlist_song.song,
                     playlist_song.id,
                     playlist_id,
-                    prev_id,
-                    next_id,
+                    index,
+                    total,
                     database.clone(),
                     list.clone(),
                     playback.clone(),
+                    selection.clone(),
                 );
+                selection.attach(&row);
                 list.append(&row);
             }
         }
@@ -342,8 +999,31 @@ fn load_playlist_songs(
     }
 }
 
+fn load_smart_playlist_songs(
+    list: &gtk4::ListBox,
+    smart_playlist: &SmartPlaylist,
+    database: &Rc<Database>,
+    selection: &SongSelection,
+) {
+    clear_listbox(list);
+
+    match database.get_smart_playlist_songs(smart_playlist) {
+        Ok(songs) if !songs.is_empty() => {
+            for song in songs.iter() {
+                let row = create_song_row(song);
+                selection.attach(&row);
+                list.append(&row);
+            }
+        }
+        _ => {
+            list.append(&create_empty_state("No matching songs", "Songs that match the rules show up here"));
+        }
+    }
+}
+
 fn create_song_row(song: &Song) -> gtk4::ListBoxRow {
     let row = gtk4::ListBoxRow::new();
+    row.set_widget_name(&song.video_id);
     let action = adw::ActionRow::new();
 
     let duration_text = if song.duration.trim().is_empty() {
@@ -369,9 +1049,11 @@ fn create_song_row_with_unlike(
     song: &Song,
     database: Rc<Database>,
     list: gtk4::ListBox,
-    _playback: Rc<PlaybackController>,
+    playback: Rc<PlaybackController>,
+    selection: SongSelection,
 ) -> gtk4::ListBoxRow {
     let row = gtk4::ListBoxRow::new();
+    row.set_widget_name(&song.video_id);
     let action = adw::ActionRow::new();
 
     let duration_text = if song.duration.trim().is_empty() {
@@ -389,26 +1071,38 @@ fn create_song_row_with_unlike(
     unlike_btn.add_css_class("liked");
 
     let video_id = song.video_id.clone();
+    let title = song.title.clone();
     let db = database.clone();
     unlike_btn.connect_clicked(glib::clone!(
         #[weak]
         list,
-        move |_| {
-            if db.unlike_song(&video_id).is_ok() {
-                // Refresh the list
-                clear_listbox(&list);
-                if let Ok(songs) = db.get_liked_songs() {
-                    if songs.is_empty() {
-                        list.append(&create_empty_state("No liked songs yet", "Like songs to see them here"));
-                    } else {
-                        for liked in songs.iter() {
-                            // We need to recreate rows without the unlike functionality to avoid infinite loop
-                            let row = create_song_row(&liked.song);
-                            list.append(&row);
-                        }
-                    }
+        move |button| {
+            let undo_id = match db.unlike_song(&video_id) {
+                Ok(undo_id) => undo_id,
+                Err(error) => {
+                    show_message_dialog(button, "Could not unlike song", &error);
+                    return;
                 }
-            }
+            };
+            show_undo_toast(
+                button,
+                &format!("Removed \"{}\" from liked songs", title),
+                glib::clone!(
+                    #[weak]
+                    list,
+                    #[strong]
+                    db,
+                    #[strong]
+                    playback,
+                    #[strong]
+                    selection,
+                    move || match db.undo(undo_id) {
+                        Ok(()) => load_liked_songs(&list, &db, &playback, &selection),
+                        Err(error) => show_message_dialog(&list, "Could not undo", &error),
+                    }
+                ),
+            );
+            load_liked_songs(&list, &db, &playback, &selection);
         }
     ));
 
@@ -425,48 +1119,328 @@ fn create_song_row_with_unlike(
 }
 
 fn create_playlist_row(
-    name: &str,
+    playlist: &Playlist,
     song_count: i64,
-    playlist_id: i64,
+    folders: &[PlaylistFolder],
     database: Rc<Database>,
     list: gtk4::ListBox,
 ) -> gtk4::ListBoxRow {
     let row = gtk4::ListBoxRow::new();
+    row.set_widget_name(&PlaylistRow::Playlist(playlist.id).name());
     let container = gtk4::Box::new(gtk4::Orientation::Horizontal, MARGIN_TINY);
+    let playlist_id = playlist.id;
 
-    let cover_url = database
-        .get_playlist_songs(playlist_id)
-        .ok()
-        .and_then(|songs| songs.first().and_then(|playlist_song| playlist_song.song.thumbnail_url.clone()));
-    let cover = cover_widget(cover_url.as_deref(), 40);
-    let label = gtk4::Label::new(Some(name));
-    label.set_xalign(0.0);
-    label.set_hexpand(true);
+    let cover = playlist_cover_widget(
+        playlist.cover_path.as_deref(),
+        &database.get_playlist_thumbnails(playlist_id, COLLAGE_SIZE),
+        40,
+    );
+
+    let labels = gtk4::Box::new(gtk4::Orientation::Vertical, 2);
+    labels.set_hexpand(true);
+    labels.set_valign(gtk4::Align::Center);
+    let title_box = gtk4::Box::new(gtk4::Orientation::Horizontal, 4);
+    if playlist.pinned {
+        let pin_icon = gtk4::Image::from_icon_name("view-pin-symbolic");
+        pin_icon.add_css_class("dim-label");
+        title_box.append(&pin_icon);
+    }
+    let label = gtk4::Label::new(Some(&playlist.name));
+    label.set_xalign(0.0);
+    title_box.append(&label);
+    labels.append(&title_box);
+    if !playlist.description.is_empty() {
+        let description_label = gtk4::Label::new(Some(&playlist.description));
+        description_label.set_xalign(0.0);
+        description_label.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+        description_label.add_css_class("dim-label");
+        description_label.add_css_class("caption");
+        labels.append(&description_label);
+    }
 
     let count_label = gtk4::Label::new(Some(&format!("{} songs", song_count)));
     count_label.add_css_class("dim-label");
 
+    let options_btn = create_playlist_options_button(playlist, folders, &database, &list);
+
     // Delete button
     let delete_btn = gtk4::Button::from_icon_name("user-trash-symbolic");
     delete_btn.add_css_class("flat");
     delete_btn.add_css_class("circular");
 
     let db = database.clone();
+    let name = playlist.name.clone();
+    delete_btn.connect_clicked(glib::clone!(
+        #[weak]
+        list,
+        move |button| {
+            let undo_id = match db.delete_playlist(playlist_id) {
+                Ok(undo_id) => undo_id,
+                Err(error) => {
+                    show_message_dialog(button, "Could not delete playlist", &error);
+                    return;
+                }
+            };
+            show_undo_toast(
+                button,
+                &format!("Deleted \"{}\"", name),
+                glib::clone!(
+                    #[weak]
+                    list,
+                    #[strong]
+                    db,
+                    move || match db.undo(undo_id) {
+                        Ok(()) => load_playlists(&list, &db),
+                        Err(error) => show_message_dialog(&list, "Could not undo", &error),
+                    }
+                ),
+            );
+            load_playlists(&list, &db);
+        }
+    ));
+
+    let arrow = gtk4::Image::from_icon_name("go-next-symbolic");
+    arrow.add_css_class("dim-label");
+
+    container.append(&cover);
+    container.append(&labels);
+    container.append(&count_label);
+    container.append(&options_btn);
+    container.append(&delete_btn);
+    container.append(&arrow);
+    container.set_margin_top(8);
+    container.set_margin_bottom(8);
+    container.set_margin_start(MARGIN_TINY);
+    container.set_margin_end(MARGIN_TINY);
+
+    row.set_child(Some(&container));
+    row
+}
+
+/// Menu with pin and move-to-folder actions for a playlist row
+fn create_playlist_options_button(
+    playlist: &Playlist,
+    folders: &[PlaylistFolder],
+    database: &Rc<Database>,
+    list: &gtk4::ListBox,
+) -> gtk4::MenuButton {
+    let playlist_id = playlist.id;
+    let menu = gtk4::Box::new(gtk4::Orientation::Vertical, 0);
+    let popover = gtk4::Popover::new();
+
+    let pin_btn = gtk4::Button::with_label(if playlist.pinned { "Unpin" } else { "Pin to top" });
+    pin_btn.add_css_class("flat");
+    let pinned = playlist.pinned;
+    pin_btn.connect_clicked(glib::clone!(
+        #[strong]
+        database,
+        #[weak]
+        list,
+        #[weak]
+        popover,
+        move |_| {
+            popover.popdown();
+            if database.set_playlist_pinned(playlist_id, !pinned).is_ok() {
+                load_playlists(&list, &database);
+            }
+        }
+    ));
+    menu.append(&pin_btn);
+
+    if !folders.is_empty() {
+        menu.append(&gtk4::Separator::new(gtk4::Orientation::Horizontal));
+        let move_label = gtk4::Label::new(Some("Move to"));
+        move_label.set_xalign(0.0);
+        move_label.set_margin_start(MARGIN_TINY);
+        move_label.set_margin_top(4);
+        move_label.add_css_class("dim-label");
+        move_label.add_css_class("caption");
+        menu.append(&move_label);
+
+        let targets = std::iter::once((None, "No folder".to_string()))
+            .chain(folders.iter().map(|folder| (Some(folder.id), folder.name.clone())));
+        for (folder_id, name) in targets {
+            let move_btn = gtk4::Button::with_label(&name);
+            move_btn.add_css_class("flat");
+            move_btn.set_sensitive(folder_id != playlist.folder_id);
+            move_btn.connect_clicked(glib::clone!(
+                #[strong]
+                database,
+                #[weak]
+                list,
+                #[weak]
+                popover,
+                move |_| {
+                    popover.popdown();
+                    if database.set_playlist_folder(playlist_id, folder_id).is_ok() {
+                        load_playlists(&list, &database);
+                    }
+                }
+            ));
+            menu.append(&move_btn);
+        }
+    }
+
+    popover.set_child(Some(&menu));
+    let button = gtk4::MenuButton::new();
+    button.set_icon_name("view-more-symbolic");
+    button.set_tooltip_text(Some("Playlist options"));
+    button.set_popover(Some(&popover));
+    button.add_css_class("flat");
+    button.add_css_class("circular");
+    button
+}
+
+fn create_folder_row(
+    folder: &PlaylistFolder,
+    playlist_count: usize,
+    database: Rc<Database>,
+    list: gtk4::ListBox,
+) -> gtk4::ListBoxRow {
+    let row = gtk4::ListBoxRow::new();
+    row.set_widget_name(&PlaylistRow::Folder(folder.id).name());
+    let container = gtk4::Box::new(gtk4::Orientation::Horizontal, MARGIN_TINY);
+    let folder_id = folder.id;
+
+    let expander = gtk4::Image::from_icon_name(if folder.expanded {
+        "pan-down-symbolic"
+    } else {
+        "pan-end-symbolic"
+    });
+    expander.add_css_class("dim-label");
+    let icon = gtk4::Image::from_icon_name("folder-symbolic");
+    let label = gtk4::Label::new(Some(&folder.name));
+    label.set_xalign(0.0);
+    label.set_hexpand(true);
+    label.add_css_class("heading");
+
+    let count_label = gtk4::Label::new(Some(&format!("{} playlists", playlist_count)));
+    count_label.add_css_class("dim-label");
+
+    let rename_btn = gtk4::Button::from_icon_name("document-edit-symbolic");
+    rename_btn.add_css_class("flat");
+    rename_btn.add_css_class("circular");
+    rename_btn.set_tooltip_text(Some("Rename folder"));
+    let folder_name = folder.name.clone();
+    rename_btn.connect_clicked(glib::clone!(
+        #[strong]
+        database,
+        #[weak]
+        list,
+        move |button| {
+            show_name_dialog(
+                button,
+                "Rename folder",
+                "Folder name",
+                &folder_name,
+                glib::clone!(
+                    #[strong]
+                    database,
+                    #[weak]
+                    list,
+                    move |name| {
+                        if database.rename_folder(folder_id, &name).is_ok() {
+                            load_playlists(&list, &database);
+                        }
+                    }
+                ),
+            );
+        }
+    ));
+
+    let delete_btn = gtk4::Button::from_icon_name("user-trash-symbolic");
+    delete_btn.add_css_class("flat");
+    delete_btn.add_css_class("circular");
+    delete_btn.set_tooltip_text(Some("Delete folder"));
     delete_btn.connect_clicked(glib::clone!(
+        #[strong]
+        database,
         #[weak]
         list,
         move |button| {
             show_confirm_dialog(
                 button,
-                "Delete playlist",
-                "This will remove the playlist and its songs.",
+                "Delete folder",
+                "The folder is removed. Its playlists are kept.",
+                glib::clone!(
+                    #[strong]
+                    database,
+                    #[weak]
+                    list,
+                    move || {
+                        if database.delete_folder(folder_id).is_ok() {
+                            load_playlists(&list, &database);
+                        }
+                    }
+                ),
+            );
+        }
+    ));
+
+    container.append(&expander);
+    container.append(&icon);
+    container.append(&label);
+    container.append(&count_label);
+    container.append(&rename_btn);
+    container.append(&delete_btn);
+    container.set_margin_top(8);
+    container.set_margin_bottom(8);
+    container.set_margin_start(MARGIN_TINY);
+    container.set_margin_end(MARGIN_TINY);
+
+    row.set_child(Some(&container));
+    row
+}
+
+fn create_smart_playlist_row(
+    smart_playlist: &SmartPlaylist,
+    database: Rc<Database>,
+    list: gtk4::ListBox,
+) -> gtk4::ListBoxRow {
+    let row = gtk4::ListBoxRow::new();
+    row.set_widget_name(&PlaylistRow::Smart(smart_playlist.id).name());
+    let container = gtk4::Box::new(gtk4::Orientation::Horizontal, MARGIN_TINY);
+
+    let songs = database.get_smart_playlist_songs(smart_playlist).unwrap_or_default();
+    let cover = playlist_cover_widget(None, &smart_thumbnails(&songs), 40);
+
+    let labels = gtk4::Box::new(gtk4::Orientation::Vertical, 2);
+    labels.set_hexpand(true);
+    labels.set_valign(gtk4::Align::Center);
+    let label = gtk4::Label::new(Some(&smart_playlist.name));
+    label.set_xalign(0.0);
+    let kind_label = gtk4::Label::new(Some("Smart playlist"));
+    kind_label.set_xalign(0.0);
+    kind_label.add_css_class("dim-label");
+    kind_label.add_css_class("caption");
+    labels.append(&label);
+    labels.append(&kind_label);
+
+    let count_label = gtk4::Label::new(Some(&format!("{} songs", songs.len())));
+    count_label.add_css_class("dim-label");
+
+    // Delete button
+    let delete_btn = gtk4::Button::from_icon_name("user-trash-symbolic");
+    delete_btn.add_css_class("flat");
+    delete_btn.add_css_class("circular");
+
+    let smart_playlist_id = smart_playlist.id;
+    let db = database.clone();
+    delete_btn.connect_clicked(glib::clone!(
+        #[weak]
+        list,
+        move |button| {
+            show_confirm_dialog(
+                button,
+                "Delete smart playlist",
+                "This will remove the smart playlist. Your songs are not affected.",
                 glib::clone!(
                     #[weak]
                     list,
                     #[strong]
                     db,
                     move || {
-                        if db.delete_playlist(playlist_id).is_ok() {
+                        if db.delete_smart_playlist(smart_playlist_id).is_ok() {
                             load_playlists(&list, &db);
                         }
                     }
@@ -479,7 +1453,7 @@ fn create_playlist_row(
     arrow.add_css_class("dim-label");
 
     container.append(&cover);
-    container.append(&label);
+    container.append(&labels);
     container.append(&count_label);
     container.append(&delete_btn);
     container.append(&arrow);
@@ -496,13 +1470,15 @@ fn create_playlist_song_row(
     song: &Song,
     song_id: i64,
     playlist_id: i64,
-    prev_song_id: Option<i64>,
-    next_song_id: Option<i64>,
+    index: usize,
+    total: usize,
     database: Rc<Database>,
     list: gtk4::ListBox,
     playback: Rc<PlaybackController>,
+    selection: SongSelection,
 ) -> gtk4::ListBoxRow {
     let row = gtk4::ListBoxRow::new();
+    row.set_widget_name(&song.video_id);
     let action = adw::ActionRow::new();
 
     let duration_text = if song.duration.trim().is_empty() {
@@ -513,98 +1489,732 @@ fn create_playlist_song_row(
     let duration_label = gtk4::Label::new(Some(duration_text));
     duration_label.add_css_class("dim-label");
 
-    // Reorder buttons
-    let move_up_btn = gtk4::Button::from_icon_name("go-up-symbolic");
-    move_up_btn.add_css_class("flat");
-    move_up_btn.add_css_class("circular");
-    move_up_btn.set_sensitive(prev_song_id.is_some());
+    // Reorder buttons, also reachable with Alt+Up/Down and Alt+Home/End on the row
+    let move_up_btn = gtk4::Button::from_icon_name("go-up-symbolic");
+    move_up_btn.add_css_class("flat");
+    move_up_btn.add_css_class("circular");
+    move_up_btn.set_tooltip_text(Some("Move up (Alt+Up)"));
+    move_up_btn.set_sensitive(index > 0);
+
+    let move_down_btn = gtk4::Button::from_icon_name("go-down-symbolic");
+    move_down_btn.add_css_class("flat");
+    move_down_btn.add_css_class("circular");
+    move_down_btn.set_tooltip_text(Some("Move down (Alt+Down)"));
+    move_down_btn.set_sensitive(index + 1 < total);
+
+    let db = database.clone();
+    let playback_clone = playback.clone();
+    let selection_clone = selection.clone();
+    move_up_btn.connect_clicked(glib::clone!(
+        #[weak]
+        list,
+        move |_| {
+            if index > 0 {
+                move_playlist_song(&list, playlist_id, song_id, index - 1, &db, &playback_clone, &selection_clone);
+            }
+        }
+    ));
+
+    let db = database.clone();
+    let playback_clone = playback.clone();
+    let selection_clone = selection.clone();
+    move_down_btn.connect_clicked(glib::clone!(
+        #[weak]
+        list,
+        move |_| {
+            if index + 1 < total {
+                move_playlist_song(&list, playlist_id, song_id, index + 1, &db, &playback_clone, &selection_clone);
+            }
+        }
+    ));
+
+    let db = database.clone();
+    let playback_clone = playback.clone();
+    let selection_clone = selection.clone();
+    let key_controller = gtk4::EventControllerKey::new();
+    key_controller.connect_key_pressed(glib::clone!(
+        #[weak]
+        list,
+        #[upgrade_or]
+        glib::Propagation::Proceed,
+        move |_, key, _, modifiers| {
+            if !modifiers.contains(gtk4::gdk::ModifierType::ALT_MASK) {
+                return glib::Propagation::Proceed;
+            }
+            let new_index = match key {
+                gtk4::gdk::Key::Up if index > 0 => index - 1,
+                gtk4::gdk::Key::Down if index + 1 < total => index + 1,
+                gtk4::gdk::Key::Home => 0,
+                gtk4::gdk::Key::End => total - 1,
+                _ => return glib::Propagation::Proceed,
+            };
+            move_playlist_song(&list, playlist_id, song_id, new_index, &db, &playback_clone, &selection_clone);
+            glib::Propagation::Stop
+        }
+    ));
+    row.add_controller(key_controller);
+
+    // Drag a row by its song ID and drop it onto the row whose place it should take
+    let drag_source = gtk4::DragSource::new();
+    drag_source.set_actions(gtk4::gdk::DragAction::MOVE);
+    drag_source.connect_prepare(move |_, _, _| {
+        Some(gtk4::gdk::ContentProvider::for_value(&song_id.to_value()))
+    });
+    drag_source.connect_drag_begin(glib::clone!(
+        #[weak]
+        row,
+        move |source, _| {
+            let paintable = gtk4::WidgetPaintable::new(Some(&row));
+            source.set_icon(Some(&paintable), 0, 0);
+        }
+    ));
+    row.add_controller(drag_source);
+
+    let drop_target = gtk4::DropTarget::new(glib::Type::I64, gtk4::gdk::DragAction::MOVE);
+    let db = database.clone();
+    let playback_clone = playback.clone();
+    let selection_clone = selection.clone();
+    drop_target.connect_drop(glib::clone!(
+        #[weak]
+        list,
+        #[upgrade_or]
+        false,
+        move |_, value, _, _| {
+            let Ok(dropped_song_id) = value.get::<i64>() else {
+                return false;
+            };
+            if dropped_song_id == song_id {
+                return false;
+            }
+            // Rebuild the list once the drop has finished rather than inside its handler
+            let db = db.clone();
+            let playback = playback_clone.clone();
+            let selection = selection_clone.clone();
+            glib::idle_add_local_once(move || {
+                move_playlist_song(&list, playlist_id, dropped_song_id, index, &db, &playback, &selection);
+            });
+            true
+        }
+    ));
+    row.add_controller(drop_target);
+
+    // Remove from playlist button
+    let remove_btn = gtk4::Button::from_icon_name("list-remove-symbolic");
+    remove_btn.add_css_class("flat");
+    remove_btn.add_css_class("circular");
+
+    let db = database.clone();
+    remove_btn.connect_clicked(glib::clone!(
+        #[weak]
+        list,
+        #[strong]
+        playback,
+        move |button| {
+            let undo_id = match db.remove_song_from_playlist(playlist_id, song_id) {
+                Ok(undo_id) => undo_id,
+                Err(error) => {
+                    show_message_dialog(button, "Could not remove song", &error);
+                    return;
+                }
+            };
+            show_undo_toast(
+                button,
+                "Removed from playlist",
+                glib::clone!(
+                    #[weak]
+                    list,
+                    #[strong]
+                    playback,
+                    #[strong]
+                    db,
+                    #[strong]
+                    selection,
+                    move || match db.undo(undo_id) {
+                        Ok(()) => load_playlist_songs(&list, playlist_id, &db, &playback, &selection),
+                        Err(error) => show_message_dialog(&list, "Could not undo", &error),
+                    }
+                ),
+            );
+            load_playlist_songs(&list, playlist_id, &db, &playback, &selection);
+        }
+    ));
+
+    action.set_title(&song.title);
+    action.set_subtitle(&song.artist);
+    action.add_prefix(&cover_widget(song.thumbnail_url.as_deref(), 40));
+    action.add_suffix(&move_up_btn);
+    action.add_suffix(&move_down_btn);
+    action.add_suffix(&remove_btn);
+    action.add_suffix(&duration_label);
+    action.set_activatable(true);
+    action.add_css_class("song-card");
+
+    row.set_child(Some(&action));
+    row
+}
+
+/// Moves a song and reloads the playlist, keeping keyboard focus on the moved row
+fn move_playlist_song(
+    list: &gtk4::ListBox,
+    playlist_id: i64,
+    song_id: i64,
+    new_index: usize,
+    database: &Rc<Database>,
+    playback: &Rc<PlaybackController>,
+    selection: &SongSelection,
+) {
+    if database.move_playlist_song(playlist_id, song_id, new_index).is_err() {
+        return;
+    }
+    load_playlist_songs(list, playlist_id, database, playback, selection);
+    if let Some(row) = list.row_at_index(new_index as i32) {
+        row.grab_focus();
+    }
+}
+
+/// Wires the bulk actions for songs selected in the liked songs list
+fn wire_liked_selection(
+    selection: &SongSelection,
+    list: &gtk4::ListBox,
+    play_selection: &Rc<dyn Fn(Vec<Song>)>,
+    database: &Rc<Database>,
+    playback: &Rc<PlaybackController>,
+    downloads: &DownloadManager,
+) {
+    let liked_songs = |database: &Database| -> Vec<Song> {
+        database
+            .get_liked_songs()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|liked| liked.song)
+            .collect()
+    };
+
+    selection.add_action(
+        "media-playback-start-symbolic",
+        "Play selection",
+        glib::clone!(
+            #[strong]
+            database,
+            #[strong]
+            play_selection,
+            move |_: &gtk4::Button, keys: Vec<String>| {
+                play_selection(selected_songs(&liked_songs(&database), &keys));
+            }
+        ),
+    );
+
+    selection.add_action(
+        "media-playlist-consecutive-symbolic",
+        "Add to queue",
+        glib::clone!(
+            #[strong]
+            database,
+            #[strong]
+            playback,
+            move |_: &gtk4::Button, keys: Vec<String>| {
+                playback.append_to_queue(queue_items(&selected_songs(&liked_songs(&database), &keys)));
+            }
+        ),
+    );
+
+    selection.add_action(
+        "list-add-symbolic",
+        "Add to playlist",
+        glib::clone!(
+            #[strong]
+            database,
+            move |button: &gtk4::Button, keys: Vec<String>| {
+                let songs = selected_songs(&liked_songs(&database), &keys);
+                show_add_to_playlist_dialog(button, &database, songs);
+            }
+        ),
+    );
+
+    selection.add_action(
+        "folder-download-symbolic",
+        "Download",
+        glib::clone!(
+            #[strong]
+            database,
+            #[strong]
+            downloads,
+            move |button: &gtk4::Button, keys: Vec<String>| {
+                download_songs(button, &downloads, &selected_songs(&liked_songs(&database), &keys));
+            }
+        ),
+    );
+
+    selection.add_action(
+        ICON_HEART,
+        "Unlike",
+        glib::clone!(
+            #[strong]
+            database,
+            #[strong]
+            playback,
+            #[strong]
+            selection,
+            #[weak]
+            list,
+            move |button: &gtk4::Button, keys: Vec<String>| {
+                let undo_id = match database.unlike_songs(&keys) {
+                    Ok(undo_id) => undo_id,
+                    Err(error) => {
+                        show_message_dialog(button, "Could not unlike songs", &error);
+                        return;
+                    }
+                };
+                show_undo_toast(
+                    button,
+                    &format!("Removed {} from liked songs", song_count_label(keys.len())),
+                    glib::clone!(
+                        #[weak]
+                        list,
+                        #[strong]
+                        database,
+                        #[strong]
+                        playback,
+                        #[strong]
+                        selection,
+                        move || match database.undo(undo_id) {
+                            Ok(()) => load_liked_songs(&list, &database, &playback, &selection),
+                            Err(error) => show_message_dialog(&list, "Could not undo", &error),
+                        }
+                    ),
+                );
+                load_liked_songs(&list, &database, &playback, &selection);
+            }
+        ),
+    );
+}
+
+fn wire_local_selection(
+    selection: &SongSelection,
+    play_selection: &Rc<dyn Fn(Vec<Song>)>,
+    database: &Rc<Database>,
+    playback: &Rc<PlaybackController>,
+) {
+    let local_songs = |database: &Database| -> Vec<Song> {
+        database
+            .get_local_tracks()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|track| track.song)
+            .collect()
+    };
+
+    selection.add_action(
+        "media-playback-start-symbolic",
+        "Play selection",
+        glib::clone!(
+            #[strong]
+            database,
+            #[strong]
+            play_selection,
+            move |_: &gtk4::Button, keys: Vec<String>| {
+                play_selection(selected_songs(&local_songs(&database), &keys));
+            }
+        ),
+    );
+
+    selection.add_action(
+        "media-playlist-consecutive-symbolic",
+        "Add to queue",
+        glib::clone!(
+            #[strong]
+            database,
+            #[strong]
+            playback,
+            move |_: &gtk4::Button, keys: Vec<String>| {
+                playback.append_to_queue(queue_items(&selected_songs(&local_songs(&database), &keys)));
+            }
+        ),
+    );
+
+    selection.add_action(
+        "list-add-symbolic",
+        "Add to playlist",
+        glib::clone!(
+            #[strong]
+            database,
+            move |button: &gtk4::Button, keys: Vec<String>| {
+                let songs = selected_songs(&local_songs(&database), &keys);
+                show_add_to_playlist_dialog(button, &database, songs);
+            }
+        ),
+    );
+
+    selection.add_action(
+        ICON_HEART_FILLED,
+        "Like",
+        glib::clone!(
+            #[strong]
+            database,
+            move |button: &gtk4::Button, keys: Vec<String>| {
+                let songs = selected_songs(&local_songs(&database), &keys);
+                match database.like_songs(&songs) {
+                    Ok(()) => show_toast(button, &format!("Liked {}", song_count_label(songs.len()))),
+                    Err(error) => show_message_dialog(button, "Could not like songs", &error),
+                }
+            }
+        ),
+    );
+}
+
+/// Wires the bulk actions for songs selected in the open playlist.
+/// Returns the remove button so it can be hidden for smart playlists.
+fn wire_playlist_selection(
+    selection: &SongSelection,
+    list: &gtk4::ListBox,
+    current_playlist_id: &Rc<Cell<i64>>,
+    current_smart_id: &Rc<Cell<Option<i64>>>,
+    play_selection: &Rc<dyn Fn(Vec<Song>)>,
+    database: &Rc<Database>,
+    playback: &Rc<PlaybackController>,
+    downloads: &DownloadManager,
+) -> gtk4::Button {
+    let selected_playlist_songs: Rc<dyn Fn(&[String]) -> Vec<Song>> = Rc::new(glib::clone!(
+        #[strong]
+        database,
+        #[strong]
+        current_playlist_id,
+        #[strong]
+        current_smart_id,
+        move |keys: &[String]| {
+            let songs = open_playlist_songs(&database, current_playlist_id.get(), current_smart_id.get());
+            selected_songs(&songs, keys)
+        }
+    ));
+
+    selection.add_action(
+        "media-playback-start-symbolic",
+        "Play selection",
+        glib::clone!(
+            #[strong]
+            selected_playlist_songs,
+            #[strong]
+            play_selection,
+            move |_: &gtk4::Button, keys: Vec<String>| {
+                play_selection(selected_playlist_songs(&keys));
+            }
+        ),
+    );
+
+    selection.add_action(
+        "media-playlist-consecutive-symbolic",
+        "Add to queue",
+        glib::clone!(
+            #[strong]
+            selected_playlist_songs,
+            #[strong]
+            playback,
+            move |_: &gtk4::Button, keys: Vec<String>| {
+                playback.append_to_queue(queue_items(&selected_playlist_songs(&keys)));
+            }
+        ),
+    );
+
+    selection.add_action(
+        "list-add-symbolic",
+        "Add to playlist",
+        glib::clone!(
+            #[strong]
+            selected_playlist_songs,
+            #[strong]
+            database,
+            move |button: &gtk4::Button, keys: Vec<String>| {
+                show_add_to_playlist_dialog(button, &database, selected_playlist_songs(&keys));
+            }
+        ),
+    );
+
+    selection.add_action(
+        "folder-download-symbolic",
+        "Download",
+        glib::clone!(
+            #[strong]
+            selected_playlist_songs,
+            #[strong]
+            downloads,
+            move |button: &gtk4::Button, keys: Vec<String>| {
+                download_songs(button, &downloads, &selected_playlist_songs(&keys));
+            }
+        ),
+    );
+
+    selection.add_action(
+        ICON_HEART_FILLED,
+        "Like",
+        glib::clone!(
+            #[strong]
+            selected_playlist_songs,
+            #[strong]
+            database,
+            move |button: &gtk4::Button, keys: Vec<String>| {
+                if let Err(error) = database.like_songs(&selected_playlist_songs(&keys)) {
+                    show_message_dialog(button, "Could not like songs", &error);
+                }
+            }
+        ),
+    );
+
+    selection.add_action(
+        ICON_HEART,
+        "Unlike",
+        glib::clone!(
+            #[strong]
+            database,
+            move |button: &gtk4::Button, keys: Vec<String>| {
+                let undo_id = match database.unlike_songs(&keys) {
+                    Ok(undo_id) => undo_id,
+                    Err(error) => {
+                        show_message_dialog(button, "Could not unlike songs", &error);
+                        return;
+                    }
+                };
+                show_undo_toast(
+                    button,
+                    &format!("Removed {} from liked songs", song_count_label(keys.len())),
+                    glib::clone!(
+                        #[weak]
+                        button,
+                        #[strong]
+                        database,
+                        move || {
+                            if let Err(error) = database.undo(undo_id) {
+                                show_message_dialog(&button, "Could not undo", &error);
+                            }
+                        }
+                    ),
+                );
+            }
+        ),
+    );
 
-    let move_down_btn = gtk4::Button::from_icon_name("go-down-symbolic");
-    move_down_btn.add_css_class("flat");
-    move_down_btn.add_css_class("circular");
-    move_down_btn.set_sensitive(next_song_id.is_some());
+    selection.add_action(
+        "list-remove-symbolic",
+        "Remove from playlist",
+        glib::clone!(
+            #[strong]
+            database,
+            #[strong]
+            playback,
+            #[strong]
+            selection,
+            #[strong]
+            current_playlist_id,
+            #[weak]
+            list,
+            move |button: &gtk4::Button, keys: Vec<String>| {
+                let playlist_id = current_playlist_id.get();
+                let song_ids: Vec<i64> = database
+                    .get_playlist_songs(playlist_id)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|playlist_song| keys.contains(&playlist_song.song.video_id))
+                    .map(|playlist_song| playlist_song.id)
+                    .collect();
+                let undo_id = match database.remove_songs_from_playlist(playlist_id, &song_ids) {
+                    Ok(undo_id) => undo_id,
+                    Err(error) => {
+                        show_message_dialog(button, "Could not remove songs", &error);
+                        return;
+                    }
+                };
+                show_undo_toast(
+                    button,
+                    &format!("Removed {} from playlist", song_count_label(song_ids.len())),
+                    glib::clone!(
+                        #[weak]
+                        list,
+                        #[strong]
+                        database,
+                        #[strong]
+                        playback,
+                        #[strong]
+                        selection,
+                        #[strong]
+                        current_playlist_id,
+                        move || match database.undo(undo_id) {
+                            // Only reload if the same playlist is still open
+                            Ok(()) if current_playlist_id.get() == playlist_id => {
+                                load_playlist_songs(&list, playlist_id, &database, &playback, &selection)
+                            }
+                            Ok(()) => {}
+                            Err(error) => show_message_dialog(&list, "Could not undo", &error),
+                        }
+                    ),
+                );
+                load_playlist_songs(&list, playlist_id, &database, &playback, &selection);
+            }
+        ),
+    )
+}
 
-    let db = database.clone();
-    let playback_clone = playback.clone();
-    move_up_btn.connect_clicked(glib::clone!(
-        #[weak]
-        list,
-        move |_| {
-            let Some(prev_id) = prev_song_id else {
-                return;
-            };
-            if db
-                .swap_playlist_song_positions(playlist_id, song_id, prev_id)
-                .is_ok()
-            {
-                load_playlist_songs(&list, playlist_id, &db, &playback_clone);
+/// Offers to merge or remove liked songs that are other uploads of the same track
+fn find_liked_duplicates(
+    button: &gtk4::Button,
+    list: &gtk4::ListBox,
+    database: &Rc<Database>,
+    playback: &Rc<PlaybackController>,
+    selection: &SongSelection,
+) {
+    let songs: Vec<Song> = database
+        .get_liked_songs()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|liked| liked.song)
+        .collect();
+    let groups = find_duplicates(&songs);
+
+    show_duplicates_dialog(
+        button,
+        &songs,
+        groups,
+        glib::clone!(
+            #[weak]
+            list,
+            #[strong]
+            database,
+            #[strong]
+            playback,
+            #[strong]
+            selection,
+            #[strong]
+            songs,
+            move |groups, merge| {
+                let groups: Vec<Vec<String>> = groups
+                    .iter()
+                    .map(|group| group.iter().map(|index| songs[*index].video_id.clone()).collect())
+                    .collect();
+                let removed: usize = groups.iter().map(|group| group.len() - 1).sum();
+                let undo_id = match database.resolve_liked_duplicates(&groups, merge) {
+                    Ok(undo_id) => undo_id,
+                    Err(error) => {
+                        show_message_dialog(&list, "Could not remove duplicates", &error);
+                        return;
+                    }
+                };
+                show_undo_toast(
+                    &list,
+                    &format!("Removed {} from liked songs", song_count_label(removed)),
+                    glib::clone!(
+                        #[weak]
+                        list,
+                        #[strong]
+                        database,
+                        #[strong]
+                        playback,
+                        #[strong]
+                        selection,
+                        move || match database.undo(undo_id) {
+                            Ok(()) => load_liked_songs(&list, &database, &playback, &selection),
+                            Err(error) => show_message_dialog(&list, "Could not undo", &error),
+                        }
+                    ),
+                );
+                load_liked_songs(&list, &database, &playback, &selection);
             }
-        }
-    ));
+        ),
+    );
+}
 
-    let db = database.clone();
-    let playback_clone = playback.clone();
-    move_down_btn.connect_clicked(glib::clone!(
-        #[weak]
-        list,
-        move |_| {
-            let Some(next_id) = next_song_id else {
-                return;
-            };
-            if db
-                .swap_playlist_song_positions(playlist_id, song_id, next_id)
-                .is_ok()
-            {
-                load_playlist_songs(&list, playlist_id, &db, &playback_clone);
+/// Offers to merge or remove playlist entries that are other uploads of the same track
+fn find_playlist_duplicates(
+    button: &gtk4::Button,
+    list: &gtk4::ListBox,
+    playlist_id: i64,
+    database: &Rc<Database>,
+    playback: &Rc<PlaybackController>,
+    selection: &SongSelection,
+) {
+    let playlist_songs = database.get_playlist_songs(playlist_id).unwrap_or_default();
+    let songs: Vec<Song> = playlist_songs.iter().map(|playlist_song| playlist_song.song.clone()).collect();
+    let groups = find_duplicates(&songs);
+
+    show_duplicates_dialog(
+        button,
+        &songs,
+        groups,
+        glib::clone!(
+            #[weak]
+            list,
+            #[strong]
+            database,
+            #[strong]
+            playback,
+            #[strong]
+            selection,
+            move |groups, merge| {
+                let groups: Vec<Vec<i64>> = groups
+                    .iter()
+                    .map(|group| group.iter().map(|index| playlist_songs[*index].id).collect())
+                    .collect();
+                let removed: usize = groups.iter().map(|group| group.len() - 1).sum();
+                let undo_id = match database.resolve_playlist_duplicates(playlist_id, &groups, merge) {
+                    Ok(undo_id) => undo_id,
+                    Err(error) => {
+                        show_message_dialog(&list, "Could not remove duplicates", &error);
+                        return;
+                    }
+                };
+                show_undo_toast(
+                    &list,
+                    &format!("Removed {} from playlist", song_count_label(removed)),
+                    glib::clone!(
+                        #[weak]
+                        list,
+                        #[strong]
+                        database,
+                        #[strong]
+                        playback,
+                        #[strong]
+                        selection,
+                        move || match database.undo(undo_id) {
+                            Ok(()) => load_playlist_songs(&list, playlist_id, &database, &playback, &selection),
+                            Err(error) => show_message_dialog(&list, "Could not undo", &error),
+                        }
+                    ),
+                );
+                load_playlist_songs(&list, playlist_id, &database, &playback, &selection);
             }
+        ),
+    );
+}
+
+/// Hides song rows that do not match the entry's text; rows are named after their video ID
+fn wire_song_filter(entry: &gtk4::SearchEntry, list: &gtk4::ListBox, database: &Rc<Database>) {
+    let matches: Rc<RefCell<Option<HashSet<String>>>> = Rc::new(RefCell::new(None));
+
+    list.set_filter_func(glib::clone!(
+        #[strong]
+        matches,
+        move |row| {
+            // Empty states are not activatable and stay visible
+            !row.is_activatable()
+                || matches
+                    .borrow()
+                    .as_ref()
+                    .is_none_or(|ids| ids.contains(row.widget_name().as_str()))
         }
     ));
 
-    // Remove from playlist button
-    let remove_btn = gtk4::Button::from_icon_name("list-remove-symbolic");
-    remove_btn.add_css_class("flat");
-    remove_btn.add_css_class("circular");
-
-    let db = database.clone();
-    remove_btn.connect_clicked(glib::clone!(
+    entry.connect_search_changed(glib::clone!(
         #[weak]
         list,
         #[strong]
-        playback,
-        move |button| {
-            show_confirm_dialog(
-                button,
-                "Remove from playlist",
-                "This song will be removed from the playlist.",
-                glib::clone!(
-                    #[weak]
-                    list,
-                    #[strong]
-                    playback,
-                    #[strong]
-                    db,
-                    move || {
-                        if db.remove_song_from_playlist(playlist_id, song_id).is_ok() {
-                            load_playlist_songs(&list, playlist_id, &db, &playback);
-                        }
-                    }
-                ),
-            );
+        database,
+        move |entry| {
+            let query = entry.text();
+            *matches.borrow_mut() = if query.trim().is_empty() {
+                None
+            } else {
+                Some(database.search_library_ids(&query).unwrap_or_default())
+            };
+            list.invalidate_filter();
         }
     ));
-
-    action.set_title(&song.title);
-    action.set_subtitle(&song.artist);
-    action.add_prefix(&cover_widget(song.thumbnail_url.as_deref(), 40));
-    action.add_suffix(&move_up_btn);
-    action.add_suffix(&move_down_btn);
-    action.add_suffix(&remove_btn);
-    action.add_suffix(&duration_label);
-    action.set_activatable(true);
-    action.add_css_class("song-card");
-
-    row.set_child(Some(&action));
-    row
 }
 
 fn create_empty_state(title: &str, subtitle: &str) -> gtk4::ListBoxRow {
@@ -631,15 +2241,16 @@ fn create_empty_state(title: &str, subtitle: &str) -> gtk4::ListBoxRow {
     row
 }
 
-fn show_playlist_name_dialog(
+fn show_playlist_details_dialog(
     parent: &impl IsA<gtk4::Widget>,
-    heading: &str,
-    initial: &str,
-    on_accept: impl Fn(String) + 'static,
+    playlist: &Playlist,
+    eq_presets: &[String],
+    eq_preset: Option<&str>,
+    on_accept: impl Fn(PlaylistDetails) + 'static,
 ) {
     let parent_window = parent.root().and_downcast::<gtk4::Window>();
     let dialog = gtk4::Dialog::with_buttons(
-        Some(heading),
+        Some("Edit playlist"),
         parent_window.as_ref(),
         gtk4::DialogFlags::MODAL,
         &[("Cancel", gtk4::ResponseType::Cancel), ("Save", gtk4::ResponseType::Ok)],
@@ -651,18 +2262,107 @@ fn show_playlist_name_dialog(
     content.set_margin_bottom(12);
     content.set_margin_start(12);
     content.set_margin_end(12);
+    content.set_spacing(12);
+
+    let name_entry = gtk4::Entry::new();
+    name_entry.set_placeholder_text(Some("Playlist name"));
+    name_entry.set_text(&playlist.name);
+    content.append(&name_entry);
+
+    let description_entry = gtk4::Entry::new();
+    description_entry.set_placeholder_text(Some("Description"));
+    description_entry.set_text(&playlist.description);
+    content.append(&description_entry);
+
+    // Cover: a local image, or the collage of song covers
+    let cover_choice = Rc::new(RefCell::new(CoverChoice::Keep));
+    let cover_row = gtk4::Box::new(gtk4::Orientation::Horizontal, MARGIN_TINY);
+    let cover_label = gtk4::Label::new(Some(if playlist.cover_path.is_some() {
+        "Custom image"
+    } else {
+        "Song collage"
+    }));
+    cover_label.set_xalign(0.0);
+    cover_label.set_hexpand(true);
+    cover_label.add_css_class("dim-label");
+    let choose_cover_btn = gtk4::Button::with_label("Choose image…");
+    let collage_btn = gtk4::Button::with_label("Use collage");
+    cover_row.append(&cover_label);
+    cover_row.append(&choose_cover_btn);
+    cover_row.append(&collage_btn);
+    content.append(&cover_row);
+
+    choose_cover_btn.connect_clicked(glib::clone!(
+        #[strong]
+        cover_choice,
+        #[weak]
+        cover_label,
+        move |button| {
+            choose_file(
+                button,
+                "Choose cover image",
+                gtk4::FileChooserAction::Open,
+                None,
+                Some(("Images", COVER_IMAGE_PATTERNS)),
+                glib::clone!(
+                    #[strong]
+                    cover_choice,
+                    #[weak]
+                    cover_label,
+                    move |path| {
+                        let name = path
+                            .file_name()
+                            .map(|name| name.to_string_lossy().to_string())
+                            .unwrap_or_default();
+                        cover_label.set_text(&name);
+                        *cover_choice.borrow_mut() = CoverChoice::Custom(path);
+                    }
+                ),
+            );
+        }
+    ));
+
+    collage_btn.connect_clicked(glib::clone!(
+        #[strong]
+        cover_choice,
+        #[weak]
+        cover_label,
+        move |_| {
+            cover_label.set_text("Song collage");
+            *cover_choice.borrow_mut() = CoverChoice::Collage;
+        }
+    ));
 
-    let entry = gtk4::Entry::new();
-    entry.set_placeholder_text(Some("Playlist name"));
-    entry.set_text(initial);
-    content.append(&entry);
+    // Equalizer: the one chosen on the equalizer page, or a preset of the playlist's own
+    let eq_row = gtk4::Box::new(gtk4::Orientation::Horizontal, MARGIN_TINY);
+    let eq_label = gtk4::Label::new(Some("Equalizer"));
+    eq_label.set_xalign(0.0);
+    eq_label.set_hexpand(true);
+    eq_label.add_css_class("dim-label");
+    let mut eq_labels = vec!["Default"];
+    eq_labels.extend(eq_presets.iter().map(String::as_str));
+    let eq_dropdown = gtk4::DropDown::from_strings(&eq_labels);
+    let eq_selected = eq_preset
+        .and_then(|name| eq_presets.iter().position(|preset| preset == name))
+        .map_or(0, |index| index + 1);
+    eq_dropdown.set_selected(eq_selected as u32);
+    eq_row.append(&eq_label);
+    eq_row.append(&eq_dropdown);
+    content.append(&eq_row);
+    let eq_presets = eq_presets.to_vec();
 
     dialog.connect_response(move |dialog: &gtk4::Dialog, response| {
         if response == gtk4::ResponseType::Ok {
-            let name = entry.text().to_string();
-            let trimmed = name.trim();
-            if !trimmed.is_empty() {
-                on_accept(trimmed.to_string());
+            let name = name_entry.text().trim().to_string();
+            if !name.is_empty() {
+                on_accept(PlaylistDetails {
+                    name,
+                    description: description_entry.text().trim().to_string(),
+                    cover: cover_choice.replace(CoverChoice::Keep),
+                    eq_preset: (eq_dropdown.selected() as usize)
+                        .checked_sub(1)
+                        .and_then(|index| eq_presets.get(index).cloned()),
+                });
             }
         }
         dialog.close();
@@ -671,6 +2371,56 @@ fn show_playlist_name_dialog(
     dialog.present();
 }
 
+/// Saves edited playlist details, copying a chosen cover into the data directory
+fn save_playlist_details(database: &Database, playlist_id: i64, details: PlaylistDetails) -> Result<(), String> {
+    database.rename_playlist(playlist_id, &details.name)?;
+    database.set_playlist_description(playlist_id, &details.description)?;
+    database.set_playlist_eq_preset(playlist_id, details.eq_preset.as_deref())?;
+
+    let previous_cover = database
+        .get_playlist(playlist_id)?
+        .and_then(|playlist| playlist.cover_path);
+    let new_cover = match details.cover {
+        CoverChoice::Keep => return Ok(()),
+        CoverChoice::Collage => None,
+        CoverChoice::Custom(source) => Some(store_playlist_cover(database, playlist_id, &source)?),
+    };
+
+    database.set_playlist_cover(playlist_id, new_cover.as_deref())?;
+    if let Some(previous) = previous_cover.filter(|previous| Some(previous) != new_cover.as_ref()) {
+        let _ = std::fs::remove_file(previous);
+    }
+    Ok(())
+}
+
+fn store_playlist_cover(database: &Database, playlist_id: i64, source: &Path) -> Result<String, String> {
+    let image = std::fs::read(source)
+        .map_err(|e| format!("Failed to read {}: {}", source.display(), e))?;
+    let extension = source
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("img");
+    database.store_playlist_cover(playlist_id, &image, extension)
+}
+
+/// Replaces the cover in the detail header and shows the description when there is one
+fn show_playlist_info(cover_slot: &gtk4::Box, description_label: &gtk4::Label, cover: gtk4::Widget, description: &str) {
+    while let Some(child) = cover_slot.first_child() {
+        cover_slot.remove(&child);
+    }
+    cover_slot.append(&cover);
+    description_label.set_text(description);
+    description_label.set_visible(!description.is_empty());
+}
+
+fn smart_thumbnails(songs: &[Song]) -> Vec<String> {
+    songs
+        .iter()
+        .filter_map(|song| song.thumbnail_url.clone())
+        .take(COLLAGE_SIZE)
+        .collect()
+}
+
 fn show_confirm_dialog(
     parent: &impl IsA<gtk4::Widget>,
     title: &str,
@@ -716,10 +2466,11 @@ struct LibraryPlaybackMessage {
 
 fn wire_liked_songs_playback(
     list: &gtk4::ListBox,
+    selection: &SongSelection,
     database: &Rc<Database>,
     playback: &Rc<PlaybackController>,
+    sources: &Sources,
 ) {
-    let client = Arc::new(Mutex::new(InnertubeClient::new()));
     let (sender, receiver) = mpsc::channel::<LibraryPlaybackMessage>();
     let receiver = Rc::new(RefCell::new(receiver));
     let playback_token = Rc::new(Cell::new(0u64));
@@ -765,10 +2516,15 @@ fn wire_liked_songs_playback(
         #[strong]
         playback_token,
         #[strong]
-        client,
+        sources,
         #[strong]
         sender,
+        #[strong]
+        selection,
         move |_, row| {
+            if selection.toggle_row(row) {
+                return;
+            }
             let index = row.index();
             if index < 0 {
                 return;
@@ -794,12 +2550,14 @@ fn wire_liked_songs_playback(
                     playback.show_loading("Loading stream...");
                     let token = playback_token.get().saturating_add(1);
                     playback_token.set(token);
+                    if playback.current_item().is_some_and(|item| playback.play_downloaded(&item)) {
+                        return;
+                    }
 
-                    let client = Arc::clone(&client);
+                    let sources = sources.clone();
                     let sender = sender.clone();
                     std::thread::spawn(move || {
-                        let mut locked = client.lock().expect("innertube client lock");
-                        let result = locked.stream_info(&video_id);
+                        let result = sources.resolve_stream(&video_id);
                         let _ = sender.send(LibraryPlaybackMessage {
                             token,
                             result,
@@ -812,12 +2570,52 @@ fn wire_liked_songs_playback(
     ));
 }
 
+fn wire_local_tracks_playback(
+    list: &gtk4::ListBox,
+    selection: &SongSelection,
+    database: &Rc<Database>,
+    playback: &Rc<PlaybackController>,
+) {
+    list.connect_row_activated(glib::clone!(
+        #[strong]
+        database,
+        #[strong]
+        playback,
+        #[strong]
+        selection,
+        move |_, row| {
+            if selection.toggle_row(row) {
+                return;
+            }
+            let Ok(index) = usize::try_from(row.index()) else {
+                return;
+            };
+            let songs: Vec<Song> = database
+                .get_local_tracks()
+                .unwrap_or_default()
+                .into_iter()
+                .map(|track| track.song)
+                .collect();
+            if index >= songs.len() {
+                return;
+            }
+
+            // Set up queue with all local files; no stream has to be fetched
+            playback.set_queue(queue_items(&songs));
+            playback.set_current_index(index);
+            if let Some(item) = playback.current_item() {
+                playback.play_downloaded(&item);
+            }
+        }
+    ));
+}
+
 fn wire_recent_plays_playback(
     list: &gtk4::ListBox,
     database: &Rc<Database>,
     playback: &Rc<PlaybackController>,
+    sources: &Sources,
 ) {
-    let client = Arc::new(Mutex::new(InnertubeClient::new()));
     let (sender, receiver) = mpsc::channel::<LibraryPlaybackMessage>();
     let receiver = Rc::new(RefCell::new(receiver));
     let playback_token = Rc::new(Cell::new(0u64));
@@ -863,7 +2661,7 @@ fn wire_recent_plays_playback(
         #[strong]
         playback_token,
         #[strong]
-        client,
+        sources,
         #[strong]
         sender,
         move |_, row| {
@@ -892,12 +2690,14 @@ fn wire_recent_plays_playback(
                     playback.show_loading("Loading stream...");
                     let token = playback_token.get().saturating_add(1);
                     playback_token.set(token);
+                    if playback.current_item().is_some_and(|item| playback.play_downloaded(&item)) {
+                        return;
+                    }
 
-                    let client = Arc::clone(&client);
+                    let sources = sources.clone();
                     let sender = sender.clone();
                     std::thread::spawn(move || {
-                        let mut locked = client.lock().expect("innertube client lock");
-                        let result = locked.stream_info(&video_id);
+                        let result = sources.resolve_stream(&video_id);
                         let _ = sender.send(LibraryPlaybackMessage {
                             token,
                             result,
@@ -912,11 +2712,15 @@ fn wire_recent_plays_playback(
 
 fn wire_playlist_songs_playback(
     list: &gtk4::ListBox,
+    selection: &SongSelection,
+    play_btn: &gtk4::Button,
+    shuffle_btn: &gtk4::Button,
     current_playlist_id: &Rc<Cell<i64>>,
+    current_smart_id: &Rc<Cell<Option<i64>>>,
     database: &Rc<Database>,
     playback: &Rc<PlaybackController>,
+    sources: &Sources,
 ) {
-    let client = Arc::new(Mutex::new(InnertubeClient::new()));
     let (sender, receiver) = mpsc::channel::<LibraryPlaybackMessage>();
     let receiver = Rc::new(RefCell::new(receiver));
     let playback_token = Rc::new(Cell::new(0u64));
@@ -954,62 +2758,135 @@ fn wire_playlist_songs_playback(
         ),
     );
 
-    list.connect_row_activated(glib::clone!(
-        #[strong]
-        database,
+    // Queue the open playlist's songs and start playing at `index`
+    let play_songs: Rc<dyn Fn(&[Song], usize)> = Rc::new(glib::clone!(
         #[strong]
         playback,
         #[strong]
         playback_token,
         #[strong]
-        client,
+        database,
         #[strong]
-        sender,
+        current_playlist_id,
+        #[strong]
+        current_smart_id,
+        #[strong]
+        sources,
+        move |songs: &[Song], index: usize| {
+            let Some(song) = songs.get(index) else {
+                return;
+            };
+            let playlist_id = current_playlist_id.get();
+            if current_smart_id.get().is_none() && playlist_id > 0 {
+                let _ = database.mark_playlist_played(playlist_id);
+            }
+            let video_id = song.video_id.clone();
+            let thumbnail = song.thumbnail_url.clone();
+
+            // Set up queue with playlist songs
+            let queue: Vec<SearchResult> = songs.iter().map(|song| SearchResult {
+                video_id: song.video_id.clone(),
+                title: song.title.clone(),
+                artist: song.artist.clone(),
+                duration: song.duration.clone(),
+                thumbnail_url: song.thumbnail_url.clone(),
+            }).collect();
+            playback.set_queue(queue);
+            if current_smart_id.get().is_none() && playlist_id > 0 {
+                playback.set_playlist_equalizer(
+                    database
+                        .get_playlist_eq_preset(playlist_id)
+                        .and_then(|name| find_preset(&database, &name)),
+                );
+            }
+            playback.set_current_index(index);
+
+            playback.show_loading("Loading stream...");
+            let token = playback_token.get().saturating_add(1);
+            playback_token.set(token);
+            if playback.current_item().is_some_and(|item| playback.play_downloaded(&item)) {
+                return;
+            }
+
+            let sources = sources.clone();
+            let sender = sender.clone();
+            std::thread::spawn(move || {
+                let result = sources.resolve_stream(&video_id);
+                let _ = sender.send(LibraryPlaybackMessage {
+                    token,
+                    result,
+                    fallback_thumbnail: thumbnail,
+                });
+            });
+        }
+    ));
+
+    let open_playlist_songs: Rc<dyn Fn() -> Vec<Song>> = Rc::new(glib::clone!(
+        #[strong]
+        database,
         #[strong]
         current_playlist_id,
+        #[strong]
+        current_smart_id,
+        move || open_playlist_songs(&database, current_playlist_id.get(), current_smart_id.get())
+    ));
+
+    list.connect_row_activated(glib::clone!(
+        #[strong]
+        play_songs,
+        #[strong]
+        open_playlist_songs,
+        #[strong]
+        selection,
         move |_, row| {
+            if selection.toggle_row(row) {
+                return;
+            }
             let index = row.index();
             if index < 0 {
                 return;
             }
+            play_songs(&open_playlist_songs(), index as usize);
+        }
+    ));
 
-            let playlist_id = current_playlist_id.get();
-            if let Ok(songs) = database.get_playlist_songs(playlist_id) {
-                if let Some(playlist_song) = songs.get(index as usize) {
-                    let song = &playlist_song.song;
-                    let video_id = song.video_id.clone();
-                    let thumbnail = song.thumbnail_url.clone();
+    play_btn.connect_clicked(glib::clone!(
+        #[strong]
+        play_songs,
+        #[strong]
+        open_playlist_songs,
+        move |_| play_songs(&open_playlist_songs(), 0)
+    ));
 
-                    // Set up queue with playlist songs
-                    let queue: Vec<SearchResult> = songs.iter().map(|ps| SearchResult {
-                        video_id: ps.song.video_id.clone(),
-                        title: ps.song.title.clone(),
-                        artist: ps.song.artist.clone(),
-                        duration: ps.song.duration.clone(),
-                        thumbnail_url: ps.song.thumbnail_url.clone(),
-                    }).collect();
-                    playback.set_queue(queue);
-                    playback.set_current_index(index as usize);
+    shuffle_btn.connect_clicked(move |_| {
+        let mut songs = open_playlist_songs();
+        shuffle_songs(&mut songs);
+        play_songs(&songs, 0);
+    });
+}
 
-                    playback.show_loading("Loading stream...");
-                    let token = playback_token.get().saturating_add(1);
-                    playback_token.set(token);
+/// Songs of whichever playlist is open, regular or smart
+fn open_playlist_songs(database: &Database, playlist_id: i64, smart_id: Option<i64>) -> Vec<Song> {
+    match smart_id {
+        Some(smart_id) => database
+            .get_smart_playlist(smart_id)
+            .ok()
+            .flatten()
+            .and_then(|smart_playlist| database.get_smart_playlist_songs(&smart_playlist).ok())
+            .unwrap_or_default(),
+        None => database
+            .get_playlist_songs(playlist_id)
+            .map(|songs| songs.into_iter().map(|playlist_song| playlist_song.song).collect())
+            .unwrap_or_default(),
+    }
+}
 
-                    let client = Arc::clone(&client);
-                    let sender = sender.clone();
-                    std::thread::spawn(move || {
-                        let mut locked = client.lock().expect("innertube client lock");
-                        let result = locked.stream_info(&video_id);
-                        let _ = sender.send(LibraryPlaybackMessage {
-                            token,
-                            result,
-                            fallback_thumbnail: thumbnail,
-                        });
-                    });
-                }
-            }
-        }
-    ));
+/// Fisher-Yates shuffle using GLib's random number generator
+fn shuffle_songs(songs: &mut [Song]) {
+    for index in (1..songs.len()).rev() {
+        let other = glib::random_int_range(0, index as i32 + 1) as usize;
+        songs.swap(index, other);
+    }
 }
 
 fn setup_refresh_polling(
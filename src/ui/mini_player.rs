@@ -4,6 +4,8 @@ use libadwaita as adw;
 use crate::config::{COVER_SIZE_SMALL, DEFAULT_COVER_PATH, MARGIN_SMALL};
 use crate::playback::PlaybackController;
 
+use super::sleep_timer::build_sleep_timer_button;
+
 /// Builds the mini player widget shown at the bottom of the window
 pub fn build_mini_player(controller: PlaybackController, stack: adw::ViewStack) -> gtk4::Box {
     let container = gtk4::Box::new(gtk4::Orientation::Vertical, 0);
@@ -16,6 +18,7 @@ pub fn build_mini_player(controller: PlaybackController, stack: adw::ViewStack)
     progress.add_css_class("mini-progress");
 
     // Wire up seeking
+    controller.connect_scroll_seek(&progress);
     progress.connect_change_value(glib::clone!(
         #[strong]
         controller,
@@ -106,7 +109,9 @@ pub fn build_mini_player(controller: PlaybackController, stack: adw::ViewStack)
     center_controls.append(&play);
     center_controls.append(&next);
 
-    // Right section: expand button
+    // Right section: sleep timer and expand button
+    let sleep_timer = build_sleep_timer_button(&controller, &["flat"]);
+
     let expand_btn = gtk4::Button::from_icon_name("go-up-symbolic");
     expand_btn.add_css_class("flat");
     expand_btn.set_tooltip_text(Some("View Now Playing"));
@@ -121,6 +126,7 @@ pub fn build_mini_player(controller: PlaybackController, stack: adw::ViewStack)
 
     controls_row.append(&left_box);
     controls_row.append(&center_controls);
+    controls_row.append(&sleep_timer);
     controls_row.append(&expand_btn);
 
     container.append(&progress);
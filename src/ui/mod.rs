@@ -1,14 +1,27 @@
 pub mod components;
+pub mod duplicates;
+pub mod equalizer;
 pub mod header;
 pub mod library;
 pub mod mini_player;
 pub mod now_playing;
+pub mod offline_banner;
+pub mod playlist_files;
 pub mod search;
+pub mod seek_indicator;
+pub mod selection;
 pub mod settings;
+pub mod shortcuts;
+pub mod sleep_timer;
+pub mod smart_playlists;
 
+pub use equalizer::build_equalizer_view;
 pub use header::build_header;
 pub use library::build_library_view;
 pub use mini_player::build_mini_player;
 pub use now_playing::build_now_playing_view;
+pub use offline_banner::build_offline_banner;
 pub use search::build_search_view;
+pub use seek_indicator::build_seek_indicator;
 pub use settings::build_settings_view;
+pub use shortcuts::{build_shortcuts_dialog, set_shortcut_accels};
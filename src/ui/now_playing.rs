@@ -1,13 +1,19 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
+use gtk4::gio;
 use gtk4::prelude::*;
 
-use crate::config::MARGIN_MEDIUM;
-use crate::playback::PlaybackController;
+use crate::config::{ICON_EQUALIZER, MARGIN_MEDIUM, PLAYBACK_RATES};
+use crate::playback::{PlaybackController, PlayerChange, RepeatMode};
+
+use super::sleep_timer::build_sleep_timer_button;
 
 const COVER_SIZE: i32 = 320;
 
+/// Stateful app action the speed menu sets, taking the speed as a double
+pub const PLAYBACK_RATE_ACTION: &str = "playback-rate";
+
 /// Builds the now playing view with a clean, minimalist design
 pub fn build_now_playing_view(controller: PlaybackController) -> gtk4::Box {
     let container = gtk4::Box::new(gtk4::Orientation::Vertical, 0);
@@ -85,8 +91,15 @@ pub fn build_now_playing_view(controller: PlaybackController) -> gtk4::Box {
     lyrics_toggle.add_css_class("flat");
     lyrics_toggle.set_tooltip_text(Some("Lyrics"));
 
+    let equalizer_button = gtk4::Button::from_icon_name(ICON_EQUALIZER);
+    equalizer_button.add_css_class("toggle-button");
+    equalizer_button.add_css_class("flat");
+    equalizer_button.set_tooltip_text(Some("Equalizer"));
+    equalizer_button.set_action_name(Some("app.show-equalizer"));
+
     toggles_row.append(&queue_toggle);
     toggles_row.append(&lyrics_toggle);
+    toggles_row.append(&equalizer_button);
 
     // Album art - clean rounded rectangle
     let art_container = gtk4::Box::new(gtk4::Orientation::Vertical, 0);
@@ -159,6 +172,9 @@ pub fn build_now_playing_view(controller: PlaybackController) -> gtk4::Box {
     repeat.add_css_class("control-button-small");
     repeat.set_tooltip_text(Some("Repeat"));
 
+    let sleep_timer =
+        build_sleep_timer_button(&controller, &["control-button", "control-button-small"]);
+
     // Wire up playback controls
     play.connect_clicked(glib::clone!(
         #[strong]
@@ -184,11 +200,47 @@ pub fn build_now_playing_view(controller: PlaybackController) -> gtk4::Box {
         }
     ));
 
+    shuffle.connect_toggled(glib::clone!(
+        #[strong]
+        controller,
+        move |button| {
+            controller.set_shuffle(button.is_active());
+        }
+    ));
+
+    // The button repeats the queue; repeating one song is set from desktop media controls
+    repeat.connect_toggled(glib::clone!(
+        #[strong]
+        controller,
+        move |button| {
+            if button.is_active() != (controller.repeat() != RepeatMode::Off) {
+                let mode = if button.is_active() { RepeatMode::Queue } else { RepeatMode::Off };
+                controller.set_repeat(mode);
+            }
+        }
+    ));
+
+    controller.connect_changed(glib::clone!(
+        #[weak]
+        shuffle,
+        #[weak]
+        repeat,
+        #[strong]
+        controller,
+        move |change| {
+            if change == PlayerChange::Options {
+                shuffle.set_active(controller.shuffle());
+                repeat.set_active(controller.repeat() != RepeatMode::Off);
+            }
+        }
+    ));
+
     controls.append(&shuffle);
     controls.append(&prev);
     controls.append(&play);
     controls.append(&next);
     controls.append(&repeat);
+    controls.append(&sleep_timer);
 
     // Progress section
     let progress_box = gtk4::Box::new(gtk4::Orientation::Vertical, 6);
@@ -203,6 +255,7 @@ pub fn build_now_playing_view(controller: PlaybackController) -> gtk4::Box {
     progress.add_css_class("now-playing-progress");
 
     // Wire up seeking
+    controller.connect_scroll_seek(&progress);
     progress.connect_change_value(glib::clone!(
         #[strong]
         controller,
@@ -215,12 +268,16 @@ pub fn build_now_playing_view(controller: PlaybackController) -> gtk4::Box {
     let time_labels = gtk4::Box::new(gtk4::Orientation::Horizontal, 0);
     let current_time = gtk4::Label::new(Some("0:00"));
     current_time.add_css_class("time-label");
+    current_time.set_hexpand(true);
+    current_time.set_xalign(0.0);
+    let rate = build_rate_button(&controller);
     let total_time = gtk4::Label::new(Some("0:00"));
     total_time.add_css_class("time-label");
     total_time.set_hexpand(true);
     total_time.set_xalign(1.0);
 
     time_labels.append(&current_time);
+    time_labels.append(&rate);
     time_labels.append(&total_time);
 
     progress_box.append(&progress);
@@ -357,3 +414,44 @@ pub fn build_now_playing_view(controller: PlaybackController) -> gtk4::Box {
 
     container
 }
+
+/// Speed menu, labelled with the speed of the song playing
+fn build_rate_button(controller: &PlaybackController) -> gtk4::MenuButton {
+    let menu = gio::Menu::new();
+    for rate in PLAYBACK_RATES {
+        let item = gio::MenuItem::new(Some(&format_rate(*rate)), None);
+        item.set_action_and_target_value(
+            Some(&format!("app.{}", PLAYBACK_RATE_ACTION)),
+            Some(&rate.to_variant()),
+        );
+        menu.append_item(&item);
+    }
+
+    let button = gtk4::MenuButton::new();
+    button.set_label(&format_rate(controller.rate()));
+    button.set_tooltip_text(Some("Playback speed"));
+    button.set_menu_model(Some(&menu));
+    button.set_valign(gtk4::Align::Center);
+    button.add_css_class("flat");
+    button.add_css_class("rate-button");
+    button.set_visible(controller.can_change_rate());
+
+    // Long tracks can have their own speed, so it changes with the song too
+    controller.connect_changed(glib::clone!(
+        #[weak]
+        button,
+        #[strong]
+        controller,
+        move |change| {
+            if matches!(change, PlayerChange::Options | PlayerChange::Track) {
+                button.set_label(&format_rate(controller.rate()));
+            }
+        }
+    ));
+
+    button
+}
+
+fn format_rate(rate: f64) -> String {
+    format!("{}×", rate)
+}
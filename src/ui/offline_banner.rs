@@ -0,0 +1,56 @@
+use gtk4::prelude::*;
+
+use crate::config::MARGIN_SMALL;
+use crate::playback::OfflineMode;
+use crate::ui::components::show_message_dialog;
+
+/// Builds the banner explaining what is unavailable while offline
+pub fn build_offline_banner(offline: &OfflineMode) -> gtk4::Revealer {
+    let label = gtk4::Label::new(Some(
+        "You're offline. Searching YouTube, streaming and importing playlists are unavailable, \
+         and downloads wait for the network. Downloaded songs, cached covers and lyrics still work.",
+    ));
+    label.set_wrap(true);
+    label.set_xalign(0.0);
+    label.set_hexpand(true);
+
+    // Only offered when offline mode was turned on by hand
+    let go_online_btn = gtk4::Button::with_label("Go online");
+    go_online_btn.set_valign(gtk4::Align::Center);
+
+    let content = gtk4::Box::new(gtk4::Orientation::Horizontal, MARGIN_SMALL);
+    content.add_css_class("offline-banner");
+    content.append(&label);
+    content.append(&go_online_btn);
+
+    let banner = gtk4::Revealer::new();
+    banner.set_transition_type(gtk4::RevealerTransitionType::SlideDown);
+    banner.set_child(Some(&content));
+
+    let update = glib::clone!(
+        #[weak]
+        banner,
+        #[weak]
+        go_online_btn,
+        #[strong]
+        offline,
+        move || {
+            banner.set_reveal_child(offline.is_offline());
+            go_online_btn.set_visible(offline.is_forced());
+        }
+    );
+    update();
+    offline.connect_changed(move |_| update());
+
+    go_online_btn.connect_clicked(glib::clone!(
+        #[strong]
+        offline,
+        move |button| {
+            if let Err(error) = offline.set_forced(false) {
+                show_message_dialog(button, "Could not go online", &error);
+            }
+        }
+    ));
+
+    banner
+}
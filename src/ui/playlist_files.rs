@@ -0,0 +1,488 @@
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use glib::ControlFlow;
+use gtk4::prelude::*;
+
+use crate::api::SearchResult;
+use crate::config::POLL_INTERVAL_MS;
+use crate::formats::{
+    PlaylistDocument, PlaylistFormat, ResolvedPlaylist, TrackMatch, match_track, parse_csv_tracks,
+};
+use crate::sources::Sources;
+use crate::storage::{Database, Song};
+use crate::ui::components::{choose_file, show_message_dialog};
+
+const PLAYLIST_FILE_PATTERNS: &[&str] = &["*.m3u", "*.m3u8", "*.xspf", "*.csv"];
+
+enum CsvImportMessage {
+    Progress(usize, usize),
+    Finished(Result<Vec<TrackMatch>, String>),
+}
+
+/// Exports a playlist as M3U or XSPF to a file chosen by the user
+pub fn export_playlist(
+    parent: &impl IsA<gtk4::Widget>,
+    database: &Rc<Database>,
+    playlist_id: i64,
+    format: PlaylistFormat,
+) {
+    let Ok(Some(playlist)) = database.get_playlist(playlist_id) else {
+        return;
+    };
+
+    let file_name = format!("{}.{}", sanitize_file_name(&playlist.name), format.extension());
+    let pattern = format!("*.{}", format.extension());
+    let parent_widget = parent.upcast_ref::<gtk4::Widget>().clone();
+    let database = database.clone();
+
+    choose_file(
+        parent,
+        "Export playlist",
+        gtk4::FileChooserAction::Save,
+        Some(&file_name),
+        Some(("Playlist files", &[pattern.as_str()])),
+        move |path| {
+            let result = database.get_playlist_songs(playlist_id).and_then(|songs| {
+                let songs: Vec<Song> = songs.into_iter().map(|playlist_song| playlist_song.song).collect();
+                let data = PlaylistDocument::from_songs(&playlist.name, &songs).write(format);
+                std::fs::write(&path, data)
+                    .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+            });
+
+            match result {
+                Ok(()) => show_message_dialog(
+                    &parent_widget,
+                    "Playlist exported",
+                    &format!("Saved to {}", path.display()),
+                ),
+                Err(error) => show_message_dialog(&parent_widget, "Export failed", &error),
+            }
+        },
+    );
+}
+
+/// Imports an M3U, XSPF or CSV file into a new playlist and calls `on_imported` once it is saved
+pub fn import_playlist(
+    button: &gtk4::Button,
+    database: &Rc<Database>,
+    sources: &Sources,
+    on_imported: impl Fn() + 'static,
+) {
+    let on_imported: Rc<dyn Fn()> = Rc::new(on_imported);
+
+    choose_file(
+        button,
+        "Import playlist",
+        gtk4::FileChooserAction::Open,
+        None,
+        Some(("Playlist files", PLAYLIST_FILE_PATTERNS)),
+        glib::clone!(
+            #[weak]
+            button,
+            #[strong]
+            database,
+            #[strong]
+            sources,
+            move |path| {
+                start_import(&button, &database, &sources, path, on_imported.clone());
+            }
+        ),
+    );
+}
+
+fn start_import(
+    button: &gtk4::Button,
+    database: &Rc<Database>,
+    sources: &Sources,
+    path: PathBuf,
+    on_imported: Rc<dyn Fn()>,
+) {
+    let is_csv = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("csv"));
+    if is_csv {
+        start_csv_import(button, database, sources, path, on_imported);
+        return;
+    }
+
+    let Some(format) = PlaylistFormat::from_path(&path) else {
+        show_message_dialog(button, "Import failed", "Unsupported playlist file type.");
+        return;
+    };
+
+    let fallback_name = playlist_name_from_path(&path);
+
+    let (sender, receiver) = mpsc::channel::<Result<(String, ResolvedPlaylist), String>>();
+    let sources = sources.clone();
+    std::thread::spawn(move || {
+        let result = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+            .map(|data| {
+                let mut document = PlaylistDocument::parse(format, &data);
+                if let Some(folder) = path.parent() {
+                    document.resolve_relative_locations(folder);
+                }
+                let name = document.name.clone().unwrap_or(fallback_name);
+                (name, document.resolve(&sources))
+            });
+        let _ = sender.send(result);
+    });
+
+    let idle_label = button.label().map(|label| label.to_string()).unwrap_or_default();
+    button.set_sensitive(false);
+    button.set_label("Importing…");
+
+    glib::timeout_add_local(
+        Duration::from_millis(POLL_INTERVAL_MS),
+        glib::clone!(
+            #[weak_allow_none]
+            button,
+            #[strong]
+            database,
+            move || {
+                let result = match receiver.try_recv() {
+                    Ok(result) => result,
+                    Err(mpsc::TryRecvError::Empty) => return ControlFlow::Continue,
+                    Err(mpsc::TryRecvError::Disconnected) => return ControlFlow::Break,
+                };
+                let Some(button) = button else {
+                    return ControlFlow::Break;
+                };
+
+                button.set_sensitive(true);
+                button.set_label(&idle_label);
+
+                match result.and_then(|(name, resolved)| save_imported_playlist(&database, &name, &resolved)) {
+                    Ok(message) => {
+                        on_imported();
+                        show_message_dialog(&button, "Playlist imported", &message);
+                    }
+                    Err(error) => show_message_dialog(&button, "Import failed", &error),
+                }
+                ControlFlow::Break
+            }
+        ),
+    );
+}
+
+fn start_csv_import(
+    button: &gtk4::Button,
+    database: &Rc<Database>,
+    sources: &Sources,
+    path: PathBuf,
+    on_imported: Rc<dyn Fn()>,
+) {
+    let name = playlist_name_from_path(&path);
+
+    let (sender, receiver) = mpsc::channel::<CsvImportMessage>();
+    let sources = sources.clone();
+    std::thread::spawn(move || {
+        let tracks = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+            .and_then(|data| parse_csv_tracks(&data));
+        let tracks = match tracks {
+            Ok(tracks) => tracks,
+            Err(error) => {
+                let _ = sender.send(CsvImportMessage::Finished(Err(error)));
+                return;
+            }
+        };
+
+        let mut matches = Vec::with_capacity(tracks.len());
+        for (index, track) in tracks.iter().enumerate() {
+            if sender.send(CsvImportMessage::Progress(index + 1, tracks.len())).is_err() {
+                return;
+            }
+            matches.push(match_track(&sources, track));
+        }
+        let _ = sender.send(CsvImportMessage::Finished(Ok(matches)));
+    });
+
+    let idle_label = button.label().map(|label| label.to_string()).unwrap_or_default();
+    button.set_sensitive(false);
+    button.set_label("Importing…");
+
+    glib::timeout_add_local(
+        Duration::from_millis(POLL_INTERVAL_MS),
+        glib::clone!(
+            #[weak_allow_none]
+            button,
+            #[strong]
+            database,
+            move || {
+                let Some(button) = button.as_ref() else {
+                    return ControlFlow::Break;
+                };
+
+                loop {
+                    match receiver.try_recv() {
+                        Ok(CsvImportMessage::Progress(current, total)) => {
+                            button.set_label(&format!("Matching {}/{}…", current, total));
+                        }
+                        Ok(CsvImportMessage::Finished(result)) => {
+                            button.set_sensitive(true);
+                            button.set_label(&idle_label);
+                            match result {
+                                Ok(matches) => show_match_review(
+                                    button,
+                                    &database,
+                                    &name,
+                                    matches,
+                                    on_imported.clone(),
+                                ),
+                                Err(error) => show_message_dialog(button, "Import failed", &error),
+                            }
+                            return ControlFlow::Break;
+                        }
+                        Err(mpsc::TryRecvError::Empty) => return ControlFlow::Continue,
+                        Err(mpsc::TryRecvError::Disconnected) => {
+                            button.set_sensitive(true);
+                            button.set_label(&idle_label);
+                            return ControlFlow::Break;
+                        }
+                    }
+                }
+            }
+        ),
+    );
+}
+
+/// Lets the user confirm low-confidence matches before the playlist is created
+fn show_match_review(
+    parent: &gtk4::Button,
+    database: &Rc<Database>,
+    name: &str,
+    matches: Vec<TrackMatch>,
+    on_imported: Rc<dyn Fn()>,
+) {
+    if matches.is_empty() {
+        show_message_dialog(parent, "Import failed", "The file does not contain any tracks.");
+        return;
+    }
+
+    let parent_window = parent.root().and_downcast::<gtk4::Window>();
+    let dialog = gtk4::Dialog::with_buttons(
+        Some("Review matches"),
+        parent_window.as_ref(),
+        gtk4::DialogFlags::MODAL,
+        &[
+            ("Cancel", gtk4::ResponseType::Cancel),
+            ("Create playlist", gtk4::ResponseType::Accept),
+        ],
+    );
+    dialog.set_default_response(gtk4::ResponseType::Accept);
+    dialog.set_default_size(560, 520);
+
+    let content = dialog.content_area();
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    content.set_spacing(12);
+
+    let name_entry = gtk4::Entry::new();
+    name_entry.set_text(name);
+    name_entry.set_placeholder_text(Some("Playlist name"));
+    content.append(&name_entry);
+
+    let confident = matches.iter().filter(|track_match| track_match.is_confident()).count();
+    let summary = if confident == matches.len() {
+        format!("All {} tracks were matched.", matches.len())
+    } else {
+        format!(
+            "{} of {} tracks were matched automatically. \
+             Check the uncertain matches below to include them.",
+            confident,
+            matches.len()
+        )
+    };
+    let summary_label = gtk4::Label::new(Some(&summary));
+    summary_label.set_wrap(true);
+    summary_label.set_xalign(0.0);
+    content.append(&summary_label);
+
+    // Checkbox and candidate picker for every track that needs review, by track index
+    let mut review_rows: Vec<(usize, gtk4::CheckButton, gtk4::DropDown)> = Vec::new();
+    let review_list = gtk4::ListBox::new();
+    review_list.set_selection_mode(gtk4::SelectionMode::None);
+    review_list.add_css_class("boxed-list");
+
+    for (index, track_match) in matches.iter().enumerate() {
+        if track_match.is_confident() {
+            continue;
+        }
+
+        let row_box = gtk4::Box::new(gtk4::Orientation::Vertical, 6);
+        row_box.set_margin_top(8);
+        row_box.set_margin_bottom(8);
+        row_box.set_margin_start(8);
+        row_box.set_margin_end(8);
+
+        let track = &track_match.track;
+        let mut description = track.title.clone();
+        if !track.artist.is_empty() {
+            description.push_str(&format!(" — {}", track.artist));
+        }
+        if !track.album.is_empty() {
+            description.push_str(&format!(" · {}", track.album));
+        }
+        let track_label = gtk4::Label::new(Some(&description));
+        track_label.set_xalign(0.0);
+        track_label.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+        track_label.add_css_class("heading");
+        row_box.append(&track_label);
+
+        let picker_box = gtk4::Box::new(gtk4::Orientation::Horizontal, 8);
+        let check = gtk4::CheckButton::new();
+        check.set_tooltip_text(Some("Include this track"));
+
+        let candidate_labels: Vec<String> = if track_match.candidates.is_empty() {
+            vec!["No match found".to_string()]
+        } else {
+            track_match
+                .candidates
+                .iter()
+                .map(|(result, confidence)| {
+                    format!(
+                        "{} — {} ({:.0}%)",
+                        result.title,
+                        result.artist,
+                        confidence * 100.0
+                    )
+                })
+                .collect()
+        };
+        let label_refs: Vec<&str> = candidate_labels.iter().map(|label| label.as_str()).collect();
+        let dropdown = gtk4::DropDown::from_strings(&label_refs);
+        dropdown.set_hexpand(true);
+
+        if track_match.candidates.is_empty() {
+            check.set_sensitive(false);
+            dropdown.set_sensitive(false);
+        }
+
+        picker_box.append(&check);
+        picker_box.append(&dropdown);
+        row_box.append(&picker_box);
+        review_list.append(&row_box);
+        review_rows.push((index, check, dropdown));
+    }
+
+    if !review_rows.is_empty() {
+        let scrolled = gtk4::ScrolledWindow::new();
+        scrolled.set_vexpand(true);
+        scrolled.set_hscrollbar_policy(gtk4::PolicyType::Never);
+        scrolled.set_child(Some(&review_list));
+        content.append(&scrolled);
+    }
+
+    let database = database.clone();
+    let parent = parent.clone();
+    let fallback_name = name.to_string();
+    dialog.connect_response(move |dialog: &gtk4::Dialog, response| {
+        if response != gtk4::ResponseType::Accept {
+            dialog.close();
+            return;
+        }
+
+        let songs: Vec<Song> = matches
+            .iter()
+            .enumerate()
+            .filter_map(|(index, track_match)| {
+                if track_match.is_confident() {
+                    return track_match.best().map(|(result, _)| song_from_result(result));
+                }
+                let (_, check, dropdown) = review_rows.iter().find(|(row, _, _)| *row == index)?;
+                if !check.is_active() {
+                    return None;
+                }
+                track_match
+                    .candidates
+                    .get(dropdown.selected() as usize)
+                    .map(|(result, _)| song_from_result(result))
+            })
+            .collect();
+
+        let entered_name = name_entry.text();
+        let playlist_name = if entered_name.trim().is_empty() {
+            fallback_name.as_str()
+        } else {
+            entered_name.trim()
+        };
+        let resolved = ResolvedPlaylist {
+            unresolved: Vec::new(),
+            songs,
+        };
+        let skipped = matches.len() - resolved.songs.len();
+
+        dialog.close();
+        match save_imported_playlist(&database, playlist_name, &resolved) {
+            Ok(mut message) => {
+                if skipped > 0 {
+                    message.push_str(&format!(" {} tracks were skipped.", skipped));
+                }
+                on_imported();
+                show_message_dialog(&parent, "Playlist imported", &message);
+            }
+            Err(error) => show_message_dialog(&parent, "Import failed", &error),
+        }
+    });
+
+    dialog.present();
+}
+
+fn song_from_result(result: &SearchResult) -> Song {
+    Song {
+        video_id: result.video_id.clone(),
+        title: result.title.clone(),
+        artist: result.artist.clone(),
+        duration: result.duration.clone(),
+        thumbnail_url: result.thumbnail_url.clone(),
+    }
+}
+
+fn playlist_name_from_path(path: &std::path::Path) -> String {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("Imported playlist")
+        .to_string()
+}
+
+fn save_imported_playlist(
+    database: &Database,
+    name: &str,
+    resolved: &ResolvedPlaylist,
+) -> Result<String, String> {
+    if resolved.songs.is_empty() {
+        return Err("None of the playlist entries could be found.".to_string());
+    }
+
+    let playlist = database.create_playlist(name)?;
+    let added = database.add_songs_to_playlist(playlist.id, &resolved.songs)?;
+
+    let mut message = format!("Added {} songs to \"{}\".", added, playlist.name);
+    if !resolved.unresolved.is_empty() {
+        message.push_str(&format!(
+            " {} entries could not be found.",
+            resolved.unresolved.len()
+        ));
+    }
+    Ok(message)
+}
+
+fn sanitize_file_name(name: &str) -> String {
+    let sanitized: String = name
+        .chars()
+        .map(|c| if matches!(c, '/' | '\\' | ':' | '\0') { '-' } else { c })
+        .collect();
+    let trimmed = sanitized.trim();
+    if trimmed.is_empty() {
+        "playlist".to_string()
+    } else {
+        trimmed.to_string()
+    }
+}
@@ -1,7 +1,6 @@
 use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 use std::sync::mpsc;
-use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use glib::ControlFlow;
@@ -9,16 +8,34 @@ use gtk4::prelude::*;
 use libadwaita as adw;
 use libadwaita::prelude::*;
 
-use crate::api::{InnertubeClient, SearchResult, StreamInfo};
-use crate::config::{DEBOUNCE_MS, ICON_HEART, ICON_HEART_FILLED, MARGIN_MEDIUM, MARGIN_SMALL, POLL_INTERVAL_MS};
-use crate::playback::PlaybackController;
+use crate::api::{SearchResult, StreamInfo};
+use crate::config::{
+    DEBOUNCE_MS, ICON_HEART, ICON_HEART_FILLED, ICON_LIBRARY, MARGIN_MEDIUM, MARGIN_SMALL,
+    POLL_INTERVAL_MS,
+};
+use crate::playback::{DownloadManager, OfflineMode, PlaybackController};
+use crate::sources::Sources;
 use crate::storage::{Database, Song};
-use crate::ui::components::{clear_listbox, cover_widget, loading_row, placeholder_row};
+use crate::ui::components::{
+    clear_listbox, cover_widget, loading_row, placeholder_row, section, show_message_dialog,
+    show_undo_toast,
+};
+use crate::ui::selection::{
+    SongSelection, download_songs, queue_items, selected_songs, show_add_to_playlist_dialog,
+    song_count_label,
+};
 
 const LOAD_MORE_THRESHOLD: f64 = 200.0;
+const LIBRARY_RESULTS_LIMIT: usize = 5;
 
 /// Builds the search view
-pub fn build_search_view(playback: PlaybackController, database: Database) -> gtk4::Box {
+pub fn build_search_view(
+    playback: PlaybackController,
+    database: Database,
+    downloads: DownloadManager,
+    offline: OfflineMode,
+    sources: Sources,
+) -> gtk4::Box {
     let container = gtk4::Box::new(gtk4::Orientation::Vertical, 0);
 
     let search_header = gtk4::Box::new(gtk4::Orientation::Vertical, 12);
@@ -36,8 +53,14 @@ pub fn build_search_view(playback: PlaybackController, database: Database) -> gt
     search_entry.set_hexpand(true);
     search_entry.add_css_class("search-entry");
 
+    let selection = SongSelection::new();
+    let search_bar = gtk4::Box::new(gtk4::Orientation::Horizontal, MARGIN_SMALL);
+    search_bar.append(&search_entry);
+    search_bar.append(selection.toggle_button());
+
     search_header.append(&title);
-    search_header.append(&search_entry);
+    search_header.append(&search_bar);
+    search_header.append(selection.bar());
 
     let results_list = gtk4::ListBox::new();
     results_list.set_selection_mode(gtk4::SelectionMode::None);
@@ -45,17 +68,101 @@ pub fn build_search_view(playback: PlaybackController, database: Database) -> gt
     results_list.set_activate_on_single_click(true);
     results_list.append(&placeholder_row("Type to search on YouTube"));
 
+    // Matches from the local library, shown above the network results
+    let library_list = gtk4::ListBox::new();
+    library_list.set_selection_mode(gtk4::SelectionMode::None);
+    library_list.add_css_class("boxed-list");
+    library_list.set_activate_on_single_click(true);
+
+    let library_section = section("In your library", Some(ICON_LIBRARY), &library_list);
+    library_section.set_visible(false);
+
+    // Offline, only the library is searched
+    let offline_hint = gtk4::Label::new(Some("Offline — showing matches from your library only"));
+    offline_hint.add_css_class("dim-label");
+    offline_hint.set_xalign(0.0);
+
+    // Sources that failed while others returned results
+    let source_failures = gtk4::Label::new(None);
+    source_failures.add_css_class("dim-label");
+    source_failures.set_xalign(0.0);
+    source_failures.set_wrap(true);
+    source_failures.set_visible(false);
+
+    let results_content = gtk4::Box::new(gtk4::Orientation::Vertical, 24);
+    results_content.append(&offline_hint);
+    results_content.append(&library_section);
+    results_content.append(&source_failures);
+    results_content.append(&results_list);
+
+    let show_offline = glib::clone!(
+        #[weak]
+        offline_hint,
+        #[weak]
+        results_list,
+        move |is_offline: bool| {
+            offline_hint.set_visible(is_offline);
+            results_list.set_visible(!is_offline);
+        }
+    );
+    show_offline(offline.is_offline());
+    offline.connect_changed(glib::clone!(
+        #[weak]
+        search_entry,
+        move |is_offline| {
+            show_offline(is_offline);
+            // Run the pending query against YouTube now that it is reachable
+            if !is_offline {
+                search_entry.emit_by_name::<()>("changed", &[]);
+            }
+        }
+    ));
+
     let results_scroller = gtk4::ScrolledWindow::new();
-    results_scroller.set_child(Some(&results_list));
+    results_scroller.set_child(Some(&results_content));
     results_scroller.set_vexpand(true);
     results_scroller.set_margin_start(MARGIN_MEDIUM);
     results_scroller.set_margin_end(MARGIN_MEDIUM);
     results_scroller.set_margin_bottom(MARGIN_SMALL);
 
-    wire_search(&search_entry, &results_list, &results_scroller, playback, database);
+    let database = Rc::new(database);
+    wire_library_search(
+        &search_entry,
+        &library_section,
+        &library_list,
+        &playback,
+        &database,
+        &sources,
+    );
+    wire_search(
+        &search_entry,
+        &results_list,
+        &source_failures,
+        &results_scroller,
+        &selection,
+        playback,
+        database,
+        downloads,
+        offline,
+        sources,
+    );
 
     container.append(&search_header);
     container.append(&results_scroller);
+
+    // `search.focus` lets the app-wide shortcut reach the entry
+    let actions = gtk4::gio::SimpleActionGroup::new();
+    let focus = gtk4::gio::SimpleAction::new("focus", None);
+    focus.connect_activate(glib::clone!(
+        #[weak]
+        search_entry,
+        move |_, _| {
+            search_entry.grab_focus();
+        }
+    ));
+    actions.add_action(&focus);
+    container.insert_action_group("search", Some(&actions));
+
     container
 }
 
@@ -64,6 +171,7 @@ enum SearchMessage {
         token: u64,
         results: Vec<SearchResult>,
         continuation: Option<String>,
+        failures: Vec<String>,
         append: bool,
     },
     Error {
@@ -91,17 +199,20 @@ struct PlaybackMessage {
 fn wire_search(
     search_entry: &gtk4::SearchEntry,
     results_list: &gtk4::ListBox,
+    source_failures: &gtk4::Label,
     results_scroller: &gtk4::ScrolledWindow,
+    selection: &SongSelection,
     playback: PlaybackController,
-    database: Database,
+    database: Rc<Database>,
+    downloads: DownloadManager,
+    offline: OfflineMode,
+    sources: Sources,
 ) {
-    let client = Arc::new(Mutex::new(InnertubeClient::new()));
     let (sender, receiver) = mpsc::channel::<SearchMessage>();
     let receiver = Rc::new(RefCell::new(receiver));
     let debounce_id: Rc<RefCell<Option<glib::SourceId>>> = Rc::new(RefCell::new(None));
     let latest_token = Rc::new(Cell::new(0u64));
     let search_results: Rc<RefCell<Vec<SearchResult>>> = Rc::new(RefCell::new(Vec::new()));
-    let database = Rc::new(database);
     let continuation_token: Rc<RefCell<Option<String>>> = Rc::new(RefCell::new(None));
     let loading_more = Rc::new(Cell::new(false));
     let current_query: Rc<RefCell<String>> = Rc::new(RefCell::new(String::new()));
@@ -112,19 +223,19 @@ fn wire_search(
     let playback_token = Rc::new(Cell::new(0u64));
 
     let request_search: Rc<dyn Fn(String, Option<String>, bool, u64)> = {
-        let client = Arc::clone(&client);
+        let sources = sources.clone();
         let sender = sender.clone();
         Rc::new(move |query: String, continuation: Option<String>, append: bool, token: u64| {
-            let client = Arc::clone(&client);
+            let sources = sources.clone();
             let sender = sender.clone();
             std::thread::spawn(move || {
-                let mut locked = client.lock().expect("innertube client lock");
-                let result = locked.search_music_page(&query, continuation.as_deref());
+                let result = sources.search(&query, continuation.as_deref());
                 let message = match result {
                     Ok(page) => SearchMessage::Results {
                         token,
                         results: page.results,
                         continuation: page.continuation,
+                        failures: page.failures,
                         append,
                     },
                     Err(error) => SearchMessage::Error { token, error, append },
@@ -140,6 +251,8 @@ fn wire_search(
         glib::clone!(
             #[weak_allow_none]
             results_list,
+            #[weak_allow_none]
+            source_failures,
             #[strong]
             receiver,
             #[strong]
@@ -156,8 +269,10 @@ fn wire_search(
             loading_more,
             #[strong]
             loading_row_ref,
+            #[strong]
+            selection,
             move || {
-                let Some(results_list) = results_list else {
+                let (Some(results_list), Some(source_failures)) = (results_list, source_failures) else {
                     return ControlFlow::Break;
                 };
                 loop {
@@ -174,10 +289,15 @@ fn wire_search(
                             if !append {
                                 clear_listbox(&results_list);
                                 search_results.borrow_mut().clear();
+                                selection.clear();
                             }
                             match message {
-                                SearchMessage::Results { results, continuation, append, .. } => {
+                                SearchMessage::Results { results, continuation, failures, append, .. } => {
                                     *continuation_token.borrow_mut() = continuation;
+                                    if !failures.is_empty() {
+                                        source_failures.set_text(&format!("Some sources could not be searched\n{}", failures.join("\n")));
+                                    }
+                                    source_failures.set_visible(!failures.is_empty());
                                     let mut stored = search_results.borrow_mut();
                                     if !append {
                                         stored.clear();
@@ -194,11 +314,13 @@ fn wire_search(
                                                 item,
                                                 &parent_widget,
                                             );
+                                            selection.attach(&row);
                                             results_list.append(&row);
                                         }
                                     }
                                 }
                                 SearchMessage::Error { error, .. } => {
+                                    source_failures.set_visible(false);
                                     results_list.append(&placeholder_row(&error));
                                 }
                             }
@@ -246,14 +368,17 @@ fn wire_search(
     );
 
     let request_play: Rc<dyn Fn(SearchResult, u64)> = {
-        let client = Arc::clone(&client);
+        let sources = sources.clone();
         let sender = play_sender.clone();
+        let playback = playback.clone();
         Rc::new(move |item: SearchResult, token: u64| {
-            let client = Arc::clone(&client);
+            if playback.play_downloaded(&item) {
+                return;
+            }
+            let sources = sources.clone();
             let sender = sender.clone();
             std::thread::spawn(move || {
-                let mut locked = client.lock().expect("innertube client lock");
-                let result = locked.stream_info(&item.video_id);
+                let result = sources.resolve_stream(&item.video_id);
                 let message = PlaybackMessage {
                     token,
                     result,
@@ -274,7 +399,12 @@ fn wire_search(
         playback_token,
         #[strong]
         request_play,
+        #[strong]
+        selection,
         move |_, row| {
+            if selection.toggle_row(row) {
+                return;
+            }
             let index = row.index();
             if index < 0 {
                 return;
@@ -293,6 +423,171 @@ fn wire_search(
         }
     ));
 
+    // Rebuild the result rows so their like buttons show the current state
+    let refresh_rows: Rc<dyn Fn()> = Rc::new(glib::clone!(
+        #[weak]
+        results_list,
+        #[strong]
+        search_results,
+        #[strong]
+        database,
+        #[strong]
+        selection,
+        #[strong]
+        loading_row_ref,
+        move || {
+            let results = search_results.borrow();
+            if results.is_empty() {
+                return;
+            }
+            clear_listbox(&results_list);
+            let parent_widget = results_list.clone().upcast::<gtk4::Widget>();
+            for item in results.iter() {
+                let row = create_search_result_row(&database, item, &parent_widget);
+                selection.attach(&row);
+                results_list.append(&row);
+            }
+            if let Some(row) = loading_row_ref.borrow().as_ref() {
+                results_list.append(row);
+            }
+        }
+    ));
+
+    // Bulk actions on the results picked in selection mode
+    let selected_results: Rc<dyn Fn(&[String]) -> Vec<Song>> = Rc::new(glib::clone!(
+        #[strong]
+        search_results,
+        move |keys: &[String]| {
+            let songs: Vec<Song> = search_results.borrow().iter().map(result_song).collect();
+            selected_songs(&songs, keys)
+        }
+    ));
+
+    selection.add_action(
+        "media-playback-start-symbolic",
+        "Play selection",
+        glib::clone!(
+            #[strong]
+            selected_results,
+            #[strong]
+            playback,
+            #[strong]
+            playback_token,
+            #[strong]
+            request_play,
+            move |_: &gtk4::Button, keys: Vec<String>| {
+                let queue = queue_items(&selected_results(&keys));
+                let Some(item) = queue.first().cloned() else {
+                    return;
+                };
+                playback.set_queue(queue);
+                playback.set_current_index(0);
+                playback.show_loading("Loading stream...");
+                let token = playback_token.get().saturating_add(1);
+                playback_token.set(token);
+                request_play.as_ref()(item, token);
+            }
+        ),
+    );
+
+    selection.add_action(
+        "media-playlist-consecutive-symbolic",
+        "Add to queue",
+        glib::clone!(
+            #[strong]
+            selected_results,
+            #[strong]
+            playback,
+            move |_: &gtk4::Button, keys: Vec<String>| {
+                playback.append_to_queue(queue_items(&selected_results(&keys)));
+            }
+        ),
+    );
+
+    selection.add_action(
+        "list-add-symbolic",
+        "Add to playlist",
+        glib::clone!(
+            #[strong]
+            selected_results,
+            #[strong]
+            database,
+            move |button: &gtk4::Button, keys: Vec<String>| {
+                show_add_to_playlist_dialog(button, &database, selected_results(&keys));
+            }
+        ),
+    );
+
+    selection.add_action(
+        "folder-download-symbolic",
+        "Download",
+        glib::clone!(
+            #[strong]
+            selected_results,
+            #[strong]
+            downloads,
+            move |button: &gtk4::Button, keys: Vec<String>| {
+                download_songs(button, &downloads, &selected_results(&keys));
+            }
+        ),
+    );
+
+    selection.add_action(
+        ICON_HEART_FILLED,
+        "Like",
+        glib::clone!(
+            #[strong]
+            selected_results,
+            #[strong]
+            database,
+            #[strong]
+            refresh_rows,
+            move |button: &gtk4::Button, keys: Vec<String>| {
+                match database.like_songs(&selected_results(&keys)) {
+                    Ok(()) => refresh_rows(),
+                    Err(error) => show_message_dialog(button, "Could not like songs", &error),
+                }
+            }
+        ),
+    );
+
+    selection.add_action(
+        ICON_HEART,
+        "Unlike",
+        glib::clone!(
+            #[strong]
+            database,
+            #[strong]
+            refresh_rows,
+            move |button: &gtk4::Button, keys: Vec<String>| {
+                let undo_id = match database.unlike_songs(&keys) {
+                    Ok(undo_id) => undo_id,
+                    Err(error) => {
+                        show_message_dialog(button, "Could not unlike songs", &error);
+                        return;
+                    }
+                };
+                show_undo_toast(
+                    button,
+                    &format!("Removed {} from liked songs", song_count_label(keys.len())),
+                    glib::clone!(
+                        #[weak]
+                        button,
+                        #[strong]
+                        database,
+                        #[strong]
+                        refresh_rows,
+                        move || match database.undo(undo_id) {
+                            Ok(()) => refresh_rows(),
+                            Err(error) => show_message_dialog(&button, "Could not undo", &error),
+                        }
+                    ),
+                );
+                refresh_rows();
+            }
+        ),
+    );
+
     let vadjustment = results_scroller.vadjustment();
     vadjustment.connect_value_changed(glib::clone!(
         #[strong]
@@ -485,7 +780,12 @@ fn wire_search(
             let loading_more = loading_more.clone();
             let request_search = request_search.clone();
             let loading_row_ref = loading_row_ref.clone();
+            let offline = offline.clone();
             let id = glib::timeout_add_local(Duration::from_millis(DEBOUNCE_MS), move || {
+                // Results stay hidden offline, so there is nothing to fetch
+                if offline.is_offline() {
+                    return ControlFlow::Break;
+                }
                 let trimmed = query.trim().to_string();
                 if trimmed.is_empty() {
                     clear_listbox(&results_list);
@@ -518,6 +818,125 @@ fn wire_search(
     ));
 }
 
+/// Searches the local library on every keystroke, without waiting for the network
+fn wire_library_search(
+    search_entry: &gtk4::SearchEntry,
+    library_section: &gtk4::Box,
+    library_list: &gtk4::ListBox,
+    playback: &PlaybackController,
+    database: &Rc<Database>,
+    sources: &Sources,
+) {
+    let playback = playback.clone();
+    let library_results: Rc<RefCell<Vec<SearchResult>>> = Rc::new(RefCell::new(Vec::new()));
+    let (sender, receiver) = mpsc::channel::<PlaybackMessage>();
+    let playback_token = Rc::new(Cell::new(0u64));
+
+    // Poll for playback results
+    glib::timeout_add_local(
+        Duration::from_millis(POLL_INTERVAL_MS),
+        glib::clone!(
+            #[strong]
+            playback,
+            #[strong]
+            playback_token,
+            move || {
+                loop {
+                    match receiver.try_recv() {
+                        Ok(message) => {
+                            if message.token < playback_token.get() {
+                                continue;
+                            }
+                            match message.result {
+                                Ok(info) => {
+                                    playback.play_stream(&info, message.fallback_thumbnail.as_deref())
+                                }
+                                Err(error) => playback.show_error(&error),
+                            }
+                        }
+                        Err(mpsc::TryRecvError::Empty) => break,
+                        Err(mpsc::TryRecvError::Disconnected) => return ControlFlow::Break,
+                    }
+                }
+                ControlFlow::Continue
+            }
+        ),
+    );
+
+    search_entry.connect_changed(glib::clone!(
+        #[weak]
+        library_section,
+        #[weak]
+        library_list,
+        #[strong]
+        library_results,
+        #[strong]
+        database,
+        move |entry| {
+            clear_listbox(&library_list);
+            let songs = database
+                .search_library(entry.text().trim(), LIBRARY_RESULTS_LIMIT)
+                .unwrap_or_default();
+            let results: Vec<SearchResult> = songs
+                .into_iter()
+                .map(|song| SearchResult {
+                    video_id: song.video_id,
+                    title: song.title,
+                    artist: song.artist,
+                    duration: song.duration,
+                    thumbnail_url: song.thumbnail_url,
+                })
+                .collect();
+
+            let parent_widget = library_list.clone().upcast::<gtk4::Widget>();
+            for item in results.iter() {
+                library_list.append(&create_search_result_row(&database, item, &parent_widget));
+            }
+            library_section.set_visible(!results.is_empty());
+            *library_results.borrow_mut() = results;
+        }
+    ));
+
+    library_list.connect_row_activated(glib::clone!(
+        #[strong]
+        library_results,
+        #[strong]
+        playback,
+        #[strong]
+        sources,
+        move |_, row| {
+            let index = row.index();
+            if index < 0 {
+                return;
+            }
+            let results = library_results.borrow().clone();
+            let Some(item) = results.get(index as usize).cloned() else {
+                return;
+            };
+
+            playback.set_queue(results);
+            playback.set_current_index(index as usize);
+            playback.show_loading("Loading stream...");
+            let token = playback_token.get().saturating_add(1);
+            playback_token.set(token);
+            if playback.current_item().is_some_and(|item| playback.play_downloaded(&item)) {
+                return;
+            }
+
+            let sources = sources.clone();
+            let sender = sender.clone();
+            std::thread::spawn(move || {
+                let result = sources.resolve_stream(&item.video_id);
+                let _ = sender.send(PlaybackMessage {
+                    token,
+                    result,
+                    fallback_thumbnail: item.thumbnail_url.clone(),
+                });
+            });
+        }
+    ));
+}
+
 /// Creates a like button for a song
 fn create_like_button(database: &Rc<Database>, item: &SearchResult) -> gtk4::Button {
     let is_liked = database.is_song_liked(&item.video_id);
@@ -577,7 +996,7 @@ fn create_add_to_playlist_button(
     let item = item.clone();
     let parent = parent.clone();
     btn.connect_clicked(move |_| {
-        show_add_to_playlist_dialog(&parent, &db, &item);
+        show_add_to_playlist_dialog(&parent, &db, vec![result_song(&item)]);
     });
 
     btn
@@ -589,6 +1008,7 @@ fn create_search_result_row(
     parent: &gtk4::Widget,
 ) -> gtk4::ListBoxRow {
     let row = gtk4::ListBoxRow::new();
+    row.set_widget_name(&item.video_id);
     let action = adw::ActionRow::new();
 
     let duration_text = if item.duration.trim().is_empty() {
@@ -615,75 +1035,12 @@ fn create_search_result_row(
     row
 }
 
-fn show_add_to_playlist_dialog(
-    parent: &gtk4::Widget,
-    database: &Rc<Database>,
-    item: &SearchResult,
-) {
-    let playlists = database.get_playlists().unwrap_or_default();
-    let parent_window = parent.root().and_downcast::<gtk4::Window>();
-
-    if playlists.is_empty() {
-        let dialog = gtk4::Dialog::with_buttons(
-            Some("No playlists yet"),
-            parent_window.as_ref(),
-            gtk4::DialogFlags::MODAL,
-            &[("OK", gtk4::ResponseType::Ok)],
-        );
-        dialog.set_default_response(gtk4::ResponseType::Ok);
-
-        let content = dialog.content_area();
-        content.set_margin_top(12);
-        content.set_margin_bottom(12);
-        content.set_margin_start(12);
-        content.set_margin_end(12);
-        let label = gtk4::Label::new(Some("Create a playlist in Library first."));
-        label.set_wrap(true);
-        label.set_xalign(0.0);
-        content.append(&label);
-
-        dialog.connect_response(|dialog: &gtk4::Dialog, _| dialog.close());
-        dialog.present();
-        return;
+fn result_song(item: &SearchResult) -> Song {
+    Song {
+        video_id: item.video_id.clone(),
+        title: item.title.clone(),
+        artist: item.artist.clone(),
+        duration: item.duration.clone(),
+        thumbnail_url: item.thumbnail_url.clone(),
     }
-
-    let names: Vec<String> = playlists.iter().map(|p| p.name.clone()).collect();
-    let name_refs: Vec<&str> = names.iter().map(|name| name.as_str()).collect();
-    let dropdown = gtk4::DropDown::from_strings(&name_refs);
-
-    let dialog = gtk4::Dialog::with_buttons(
-        Some("Add to playlist"),
-        parent_window.as_ref(),
-        gtk4::DialogFlags::MODAL,
-        &[("Cancel", gtk4::ResponseType::Cancel), ("Add", gtk4::ResponseType::Ok)],
-    );
-    dialog.set_default_response(gtk4::ResponseType::Ok);
-
-    let content = dialog.content_area();
-    content.set_margin_top(12);
-    content.set_margin_bottom(12);
-    content.set_margin_start(12);
-    content.set_margin_end(12);
-    content.append(&dropdown);
-
-    let db = database.clone();
-    let item = item.clone();
-    dialog.connect_response(move |dialog: &gtk4::Dialog, response| {
-        if response == gtk4::ResponseType::Ok {
-            let index = dropdown.selected() as usize;
-            if let Some(playlist) = playlists.get(index) {
-                let song = Song {
-                    video_id: item.video_id.clone(),
-                    title: item.title.clone(),
-                    artist: item.artist.clone(),
-                    duration: item.duration.clone(),
-                    thumbnail_url: item.thumbnail_url.clone(),
-                };
-                let _ = db.add_song_to_playlist(playlist.id, &song);
-            }
-        }
-        dialog.close();
-    });
-
-    dialog.present();
 }
@@ -0,0 +1,66 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use gtk4::prelude::*;
+
+use crate::config::SEEK_INDICATOR_TIMEOUT_MS;
+use crate::formats::format_duration;
+use crate::playback::{PlaybackController, PlayerChange, SeekJump};
+
+/// Builds the label that briefly shows where a keyboard or scroll seek landed,
+/// to be laid over the window
+pub fn build_seek_indicator(playback: &PlaybackController) -> gtk4::Label {
+    let indicator = gtk4::Label::new(None);
+    indicator.add_css_class("osd");
+    indicator.add_css_class("seek-indicator");
+    indicator.set_halign(gtk4::Align::Center);
+    indicator.set_valign(gtk4::Align::End);
+    indicator.set_can_target(false);
+    indicator.set_visible(false);
+
+    // Only the timeout of the latest seek hides the label
+    let token = Rc::new(Cell::new(0u64));
+    playback.connect_changed(glib::clone!(
+        #[weak]
+        indicator,
+        #[strong]
+        playback,
+        move |change| {
+            let PlayerChange::Jumped(jump, position) = change else {
+                return;
+            };
+            let seek = match jump {
+                SeekJump::By(secs) if secs < 0 => format!("−{} s", -secs),
+                SeekJump::By(secs) => format!("+{} s", secs),
+                SeekJump::ToPercent(percent) => format!("{}%", percent),
+            };
+            indicator.set_text(&format!(
+                "{}  ·  {} / {}",
+                seek,
+                format_duration(position / 1_000_000),
+                format_duration(playback.duration() / 1_000_000)
+            ));
+            indicator.set_visible(true);
+
+            let shown = token.get().saturating_add(1);
+            token.set(shown);
+            glib::timeout_add_local_once(
+                Duration::from_millis(SEEK_INDICATOR_TIMEOUT_MS),
+                glib::clone!(
+                    #[weak]
+                    indicator,
+                    #[strong]
+                    token,
+                    move || {
+                        if token.get() == shown {
+                            indicator.set_visible(false);
+                        }
+                    }
+                ),
+            );
+        }
+    ));
+
+    indicator
+}
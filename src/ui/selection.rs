@@ -0,0 +1,366 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use glib::ControlFlow;
+use gtk4::prelude::*;
+use libadwaita as adw;
+use libadwaita::prelude::*;
+
+use crate::api::{SearchResult, StreamInfo};
+use crate::config::{MARGIN_TINY, POLL_INTERVAL_MS};
+use crate::playback::{DownloadManager, PlaybackController};
+use crate::sources::Sources;
+use crate::storage::{Database, Song};
+use crate::ui::components::{show_message_dialog, show_toast};
+
+/// Selection mode for a song list: a check box on every row and a bar of bulk actions.
+/// Rows are keyed by their widget name, which song lists set to the track URI.
+#[derive(Clone)]
+pub struct SongSelection {
+    active: Rc<Cell<bool>>,
+    selected: Rc<RefCell<Vec<String>>>,
+    checks: Rc<RefCell<Vec<glib::WeakRef<gtk4::CheckButton>>>>,
+    toggle: gtk4::ToggleButton,
+    bar: gtk4::Revealer,
+    count_label: gtk4::Label,
+    actions: gtk4::Box,
+}
+
+impl SongSelection {
+    pub fn new() -> Self {
+        let toggle = gtk4::ToggleButton::new();
+        toggle.set_icon_name("selection-mode-symbolic");
+        toggle.set_tooltip_text(Some("Select songs"));
+        toggle.add_css_class("flat");
+        toggle.add_css_class("circular");
+
+        let count_label = gtk4::Label::new(Some("0 selected"));
+        count_label.set_xalign(0.0);
+        count_label.set_hexpand(true);
+        count_label.add_css_class("dim-label");
+
+        let select_all_btn = gtk4::Button::with_label("Select all");
+        select_all_btn.add_css_class("flat");
+
+        let actions = gtk4::Box::new(gtk4::Orientation::Horizontal, 4);
+
+        let bar_content = gtk4::Box::new(gtk4::Orientation::Horizontal, MARGIN_TINY);
+        bar_content.append(&count_label);
+        bar_content.append(&select_all_btn);
+        bar_content.append(&actions);
+
+        let bar = gtk4::Revealer::new();
+        bar.set_transition_type(gtk4::RevealerTransitionType::SlideDown);
+        bar.set_child(Some(&bar_content));
+
+        let selection = Self {
+            active: Rc::new(Cell::new(false)),
+            selected: Rc::new(RefCell::new(Vec::new())),
+            checks: Rc::new(RefCell::new(Vec::new())),
+            toggle,
+            bar,
+            count_label,
+            actions,
+        };
+
+        selection.toggle.connect_toggled(glib::clone!(
+            #[strong]
+            selection,
+            move |toggle| {
+                let active = toggle.is_active();
+                selection.active.set(active);
+                selection.bar.set_reveal_child(active);
+                selection.clear();
+                for check in selection.live_checks() {
+                    check.set_visible(active);
+                }
+            }
+        ));
+
+        select_all_btn.connect_clicked(glib::clone!(
+            #[strong]
+            selection,
+            move |_| {
+                // Rows hidden by a filter are left alone
+                for check in selection.live_checks() {
+                    let visible = check
+                        .ancestor(gtk4::ListBoxRow::static_type())
+                        .is_some_and(|row| row.is_child_visible());
+                    if visible {
+                        check.set_active(true);
+                    }
+                }
+            }
+        ));
+
+        selection.update();
+        selection
+    }
+
+    /// Button that turns selection mode on and off
+    pub fn toggle_button(&self) -> &gtk4::ToggleButton {
+        &self.toggle
+    }
+
+    /// Bar with the selected count and bulk actions, revealed in selection mode
+    pub fn bar(&self) -> &gtk4::Revealer {
+        &self.bar
+    }
+
+    /// Add a bulk action button; it gets the selected keys and selection mode ends afterwards
+    pub fn add_action(
+        &self,
+        icon_name: &str,
+        tooltip: &str,
+        on_activate: impl Fn(&gtk4::Button, Vec<String>) + 'static,
+    ) -> gtk4::Button {
+        let button = gtk4::Button::from_icon_name(icon_name);
+        button.set_tooltip_text(Some(tooltip));
+        button.add_css_class("flat");
+        button.add_css_class("circular");
+        button.set_sensitive(!self.selected.borrow().is_empty());
+
+        let selection = self.clone();
+        button.connect_clicked(move |button| {
+            let keys = selection.selected.borrow().clone();
+            if keys.is_empty() {
+                return;
+            }
+            on_activate(button, keys);
+            selection.toggle.set_active(false);
+        });
+        self.actions.append(&button);
+        button
+    }
+
+    /// Give a freshly built song row its check box, keeping it checked if the song was selected
+    pub fn attach(&self, row: &gtk4::ListBoxRow) {
+        let Some(action) = row.child().and_downcast::<adw::ActionRow>() else {
+            return;
+        };
+        let key = row.widget_name().to_string();
+
+        let check = gtk4::CheckButton::new();
+        check.set_valign(gtk4::Align::Center);
+        check.set_visible(self.active.get());
+        check.set_active(self.selected.borrow().contains(&key));
+        check.connect_toggled(glib::clone!(
+            #[strong(rename_to = selection)]
+            self,
+            move |check| {
+                {
+                    let mut selected = selection.selected.borrow_mut();
+                    let position = selected.iter().position(|selected_key| *selected_key == key);
+                    match (check.is_active(), position) {
+                        (true, None) => selected.push(key.clone()),
+                        (false, Some(index)) => {
+                            selected.remove(index);
+                        }
+                        _ => {}
+                    }
+                }
+                selection.update();
+            }
+        ));
+        action.add_prefix(&check);
+
+        let mut checks = self.checks.borrow_mut();
+        checks.retain(|check| check.upgrade().is_some());
+        checks.push(check.downgrade());
+    }
+
+    /// Unselect every song, staying in selection mode
+    pub fn clear(&self) {
+        for check in self.live_checks() {
+            check.set_active(false);
+        }
+        self.selected.borrow_mut().clear();
+        self.update();
+    }
+
+    /// Toggle the row's check box when in selection mode; returns false when not in selection mode
+    pub fn toggle_row(&self, row: &gtk4::ListBoxRow) -> bool {
+        if !self.active.get() {
+            return false;
+        }
+        if let Some(check) = self.live_checks().into_iter().find(|check| check.is_ancestor(row)) {
+            check.set_active(!check.is_active());
+        }
+        true
+    }
+
+    fn live_checks(&self) -> Vec<gtk4::CheckButton> {
+        self.checks.borrow().iter().filter_map(|check| check.upgrade()).collect()
+    }
+
+    fn update(&self) {
+        let count = self.selected.borrow().len();
+        self.count_label.set_text(&format!("{} selected", count));
+        let mut child = self.actions.first_child();
+        while let Some(button) = child {
+            button.set_sensitive(count > 0);
+            child = button.next_sibling();
+        }
+    }
+}
+
+/// "1 song" or "N songs", for toasts about bulk actions
+pub fn song_count_label(count: usize) -> String {
+    if count == 1 {
+        "1 song".to_string()
+    } else {
+        format!("{} songs", count)
+    }
+}
+
+/// Songs whose video IDs were selected, in list order
+pub fn selected_songs(songs: &[Song], keys: &[String]) -> Vec<Song> {
+    songs
+        .iter()
+        .filter(|song| keys.contains(&song.video_id))
+        .cloned()
+        .collect()
+}
+
+/// Converts songs to queue items
+pub fn queue_items(songs: &[Song]) -> Vec<SearchResult> {
+    songs
+        .iter()
+        .map(|song| SearchResult {
+            video_id: song.video_id.clone(),
+            title: song.title.clone(),
+            artist: song.artist.clone(),
+            duration: song.duration.clone(),
+            thumbnail_url: song.thumbnail_url.clone(),
+        })
+        .collect()
+}
+
+struct SelectionPlaybackMessage {
+    token: u64,
+    result: Result<StreamInfo, String>,
+    fallback_thumbnail: Option<String>,
+}
+
+/// Returns a callback that replaces the queue with the given songs and plays the first one
+pub fn selection_player(playback: &Rc<PlaybackController>, sources: &Sources) -> Rc<dyn Fn(Vec<Song>)> {
+    let (sender, receiver) = mpsc::channel::<SelectionPlaybackMessage>();
+    let playback_token = Rc::new(Cell::new(0u64));
+
+    // Poll for playback results
+    glib::timeout_add_local(
+        Duration::from_millis(POLL_INTERVAL_MS),
+        glib::clone!(
+            #[strong]
+            playback,
+            #[strong]
+            playback_token,
+            move || {
+                loop {
+                    match receiver.try_recv() {
+                        Ok(message) => {
+                            if message.token < playback_token.get() {
+                                continue;
+                            }
+                            match message.result {
+                                Ok(info) => {
+                                    playback.play_stream(&info, message.fallback_thumbnail.as_deref())
+                                }
+                                Err(error) => playback.show_error(&error),
+                            }
+                        }
+                        Err(mpsc::TryRecvError::Empty) => break,
+                        Err(mpsc::TryRecvError::Disconnected) => return ControlFlow::Break,
+                    }
+                }
+                ControlFlow::Continue
+            }
+        ),
+    );
+
+    let playback = playback.clone();
+    let sources = sources.clone();
+    Rc::new(move |songs: Vec<Song>| {
+        let Some(song) = songs.first() else {
+            return;
+        };
+        let video_id = song.video_id.clone();
+        let thumbnail = song.thumbnail_url.clone();
+
+        playback.set_queue(queue_items(&songs));
+        playback.set_current_index(0);
+        playback.show_loading("Loading stream...");
+        let token = playback_token.get().saturating_add(1);
+        playback_token.set(token);
+        if playback.current_item().is_some_and(|item| playback.play_downloaded(&item)) {
+            return;
+        }
+
+        let sources = sources.clone();
+        let sender = sender.clone();
+        std::thread::spawn(move || {
+            let result = sources.resolve_stream(&video_id);
+            let _ = sender.send(SelectionPlaybackMessage {
+                token,
+                result,
+                fallback_thumbnail: thumbnail,
+            });
+        });
+    })
+}
+
+/// Asks which playlist to add the songs to and adds them in one transaction
+pub fn show_add_to_playlist_dialog(parent: &impl IsA<gtk4::Widget>, database: &Rc<Database>, songs: Vec<Song>) {
+    let playlists = database.get_playlists().unwrap_or_default();
+    if playlists.is_empty() {
+        show_message_dialog(parent, "No playlists yet", "Create a playlist in Library first.");
+        return;
+    }
+
+    let names: Vec<String> = playlists.iter().map(|p| p.name.clone()).collect();
+    let name_refs: Vec<&str> = names.iter().map(|name| name.as_str()).collect();
+    let dropdown = gtk4::DropDown::from_strings(&name_refs);
+
+    let parent_window = parent.root().and_downcast::<gtk4::Window>();
+    let dialog = gtk4::Dialog::with_buttons(
+        Some("Add to playlist"),
+        parent_window.as_ref(),
+        gtk4::DialogFlags::MODAL,
+        &[("Cancel", gtk4::ResponseType::Cancel), ("Add", gtk4::ResponseType::Ok)],
+    );
+    dialog.set_default_response(gtk4::ResponseType::Ok);
+
+    let content = dialog.content_area();
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    content.append(&dropdown);
+
+    let db = database.clone();
+    let parent = parent.clone().upcast::<gtk4::Widget>();
+    dialog.connect_response(move |dialog: &gtk4::Dialog, response| {
+        if response == gtk4::ResponseType::Ok {
+            let index = dropdown.selected() as usize;
+            if let Some(playlist) = playlists.get(index) {
+                if let Err(error) = db.add_songs_to_playlist(playlist.id, &songs) {
+                    show_message_dialog(&parent, "Could not add to playlist", &error);
+                }
+            }
+        }
+        dialog.close();
+    });
+
+    dialog.present();
+}
+
+/// Queues songs for offline playback and says how many were new
+pub fn download_songs(widget: &impl IsA<gtk4::Widget>, downloads: &DownloadManager, songs: &[Song]) {
+    match downloads.download(songs) {
+        Ok(0) => show_toast(widget, "Already downloaded"),
+        Ok(count) => show_toast(widget, &format!("Downloading {}", song_count_label(count))),
+        Err(error) => show_message_dialog(widget, "Could not download", &error),
+    }
+}
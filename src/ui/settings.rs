@@ -1,11 +1,37 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use glib::ControlFlow;
+
 use gtk4::prelude::*;
 use libadwaita as adw;
 use libadwaita::prelude::*;
 
-use crate::config::{APP_NAME, APP_VERSION, MARGIN_MEDIUM};
+use crate::api::{SubsonicClient, SubsonicCredentials};
+use crate::config::{APP_NAME, APP_VERSION, MARGIN_MEDIUM, POLL_INTERVAL_MS};
+use crate::local::LocalLibrary;
+use crate::playback::{
+    DownloadManager, OfflineMode, OutputDevice, PlaybackController, PlayerChange, notifications_enabled,
+    save_output_device, seek_step, set_notifications_enabled, set_seek_step,
+};
+use crate::sources::Sources;
+use crate::storage::{Database, Download, DownloadStatus, ImportMode, ImportSummary};
+use crate::ui::components::{choose_file, clear_listbox, show_message_dialog};
+
+const LIBRARY_EXPORT_FILE_NAME: &str = "musika-library.json";
 
 /// Builds the settings view
-pub fn build_settings_view() -> gtk4::Box {
+pub fn build_settings_view(
+    playback: PlaybackController,
+    database: Database,
+    downloads: DownloadManager,
+    offline: OfflineMode,
+    local_library: LocalLibrary,
+    sources: Sources,
+) -> gtk4::Box {
     let container = gtk4::Box::new(gtk4::Orientation::Vertical, 0);
 
     let scroller = gtk4::ScrolledWindow::new();
@@ -49,7 +75,94 @@ pub fn build_settings_view() -> gtk4::Box {
     quality_row.set_subtitle("Best available");
     quality_row.set_activatable(false);
 
+    let offline_switch = gtk4::Switch::new();
+    offline_switch.set_valign(gtk4::Align::Center);
+    offline_switch.set_active(offline.is_forced());
+
+    let offline_row = adw::ActionRow::new();
+    offline_row.set_title("Offline mode");
+    offline_row.add_suffix(&offline_switch);
+    offline_row.set_activatable_widget(Some(&offline_switch));
+
+    let update_offline_row = glib::clone!(
+        #[weak]
+        offline_row,
+        #[weak]
+        offline_switch,
+        #[strong]
+        offline,
+        move || {
+            offline_switch.set_active(offline.is_forced());
+            let subtitle = if offline.is_offline() && !offline.is_forced() {
+                "No network connection, so only downloaded songs play"
+            } else {
+                "Only play downloaded songs and stay off the network"
+            };
+            offline_row.set_subtitle(subtitle);
+        }
+    );
+    update_offline_row();
+    offline.connect_changed(move |_| update_offline_row());
+
+    offline_switch.connect_state_set(glib::clone!(
+        #[strong]
+        offline,
+        move |switch, forced| {
+            if let Err(error) = offline.set_forced(forced) {
+                show_message_dialog(switch, "Could not change offline mode", &error);
+                return glib::Propagation::Stop;
+            }
+            glib::Propagation::Proceed
+        }
+    ));
+
+    let notifications_switch = gtk4::Switch::new();
+    notifications_switch.set_valign(gtk4::Align::Center);
+    notifications_switch.set_active(notifications_enabled(&database));
+
+    let notifications_row = adw::ActionRow::new();
+    notifications_row.set_title("Track notifications");
+    notifications_row.set_subtitle("Show the new song when the window is in the background");
+    notifications_row.add_suffix(&notifications_switch);
+    notifications_row.set_activatable_widget(Some(&notifications_switch));
+
+    notifications_switch.connect_state_set(glib::clone!(
+        #[strong]
+        database,
+        move |switch, enabled| {
+            if let Err(error) = set_notifications_enabled(&database, enabled) {
+                show_message_dialog(switch, "Could not change notifications", &error);
+                return glib::Propagation::Stop;
+            }
+            glib::Propagation::Proceed
+        }
+    ));
+
+    let seek_step_button = gtk4::SpinButton::with_range(1.0, 60.0, 1.0);
+    seek_step_button.set_valign(gtk4::Align::Center);
+    seek_step_button.set_value(seek_step(&database));
+
+    let seek_step_row = adw::ActionRow::new();
+    seek_step_row.set_title("Seek step");
+    seek_step_row.set_subtitle("Seconds skipped by Shift+arrow keys and by scrolling over the progress bar");
+    seek_step_row.add_suffix(&seek_step_button);
+    seek_step_row.set_activatable_widget(Some(&seek_step_button));
+
+    seek_step_button.connect_value_changed(glib::clone!(
+        #[strong]
+        database,
+        move |button| {
+            if let Err(error) = set_seek_step(&database, button.value()) {
+                show_message_dialog(button, "Could not change seek step", &error);
+            }
+        }
+    ));
+
     playback_group.add(&quality_row);
+    playback_group.add(&build_output_device_row(&playback, &database));
+    playback_group.add(&offline_row);
+    playback_group.add(&notifications_row);
+    playback_group.add(&seek_step_row);
 
     // Interface section
     let interface_group = adw::PreferencesGroup::new();
@@ -63,12 +176,744 @@ pub fn build_settings_view() -> gtk4::Box {
 
     interface_group.add(&theme_row);
 
+    // Library backup section
+    let library_group = adw::PreferencesGroup::new();
+    library_group.set_title("Library");
+    library_group.set_description(Some("Back up or restore liked songs, playlists and history"));
+
+    let export_button = gtk4::Button::with_label("Export…");
+    export_button.set_valign(gtk4::Align::Center);
+
+    let export_row = adw::ActionRow::new();
+    export_row.set_title("Export library");
+    export_row.set_subtitle("Save everything to a JSON file");
+    export_row.add_suffix(&export_button);
+    export_row.set_activatable_widget(Some(&export_button));
+
+    let import_button = gtk4::Button::with_label("Import…");
+    import_button.set_valign(gtk4::Align::Center);
+
+    let import_row = adw::ActionRow::new();
+    import_row.set_title("Import library");
+    import_row.set_subtitle("Merge or replace with a previous export");
+    import_row.add_suffix(&import_button);
+    import_row.set_activatable_widget(Some(&import_button));
+
+    library_group.add(&export_row);
+    library_group.add(&import_row);
+
+    export_button.connect_clicked(glib::clone!(
+        #[strong]
+        database,
+        move |button| {
+            export_library(button, &database);
+        }
+    ));
+
+    import_button.connect_clicked(glib::clone!(
+        #[strong]
+        database,
+        move |button| {
+            import_library(button, &database);
+        }
+    ));
+
+    // Downloads section
+    let downloads_group = adw::PreferencesGroup::new();
+    downloads_group.set_title("Downloads");
+    downloads_group.set_description(Some("Songs saved for offline playback"));
+
+    let storage_row = adw::ActionRow::new();
+    storage_row.set_title("Storage used");
+    storage_row.set_activatable(false);
+
+    let downloads_list = gtk4::ListBox::new();
+    downloads_list.set_selection_mode(gtk4::SelectionMode::None);
+    downloads_list.add_css_class("boxed-list");
+    downloads_list.set_margin_top(12);
+
+    downloads_group.add(&storage_row);
+    downloads_group.add(&downloads_list);
+
+    // Progress bars of the rows on screen, updated in place while downloads run
+    let progress_bars: Rc<RefCell<HashMap<String, gtk4::ProgressBar>>> =
+        Rc::new(RefCell::new(HashMap::new()));
+
+    let refresh_downloads = glib::clone!(
+        #[weak]
+        downloads_list,
+        #[weak]
+        storage_row,
+        #[strong]
+        database,
+        #[strong]
+        downloads,
+        #[strong]
+        progress_bars,
+        move || {
+            load_downloads(&downloads_list, &storage_row, &database, &downloads, &progress_bars);
+        }
+    );
+    refresh_downloads();
+    downloads.connect_changed(refresh_downloads);
+    downloads.connect_progress(glib::clone!(
+        #[strong]
+        progress_bars,
+        move |video_id, downloaded, total| {
+            if let Some(bar) = progress_bars.borrow().get(video_id) {
+                bar.set_fraction(download_fraction(downloaded, total));
+            }
+        }
+    ));
+
+    // Music folders section
+    let folders_group = adw::PreferencesGroup::new();
+    folders_group.set_title("Music Folders");
+    folders_group.set_description(Some("Play FLAC, MP3, Opus and M4A files from this computer"));
+
+    let add_folder_button = gtk4::Button::with_label("Add…");
+    add_folder_button.set_valign(gtk4::Align::Center);
+    let rescan_button = gtk4::Button::from_icon_name("view-refresh-symbolic");
+    rescan_button.set_tooltip_text(Some("Rescan folders"));
+    rescan_button.set_valign(gtk4::Align::Center);
+    rescan_button.add_css_class("flat");
+    rescan_button.add_css_class("circular");
+
+    let scan_row = adw::ActionRow::new();
+    scan_row.set_title("Local files");
+    scan_row.add_suffix(&rescan_button);
+    scan_row.add_suffix(&add_folder_button);
+    scan_row.set_activatable(false);
+
+    let folders_list = gtk4::ListBox::new();
+    folders_list.set_selection_mode(gtk4::SelectionMode::None);
+    folders_list.add_css_class("boxed-list");
+    folders_list.set_margin_top(12);
+
+    folders_group.add(&scan_row);
+    folders_group.add(&folders_list);
+
+    let refresh_folders = glib::clone!(
+        #[weak]
+        folders_list,
+        #[weak]
+        scan_row,
+        #[weak]
+        rescan_button,
+        #[strong]
+        database,
+        #[strong]
+        local_library,
+        move || {
+            load_music_folders(&folders_list, &scan_row, &database, &local_library);
+            rescan_button.set_sensitive(!local_library.is_scanning());
+        }
+    );
+    refresh_folders();
+    local_library.connect_changed(refresh_folders);
+
+    add_folder_button.connect_clicked(glib::clone!(
+        #[strong]
+        local_library,
+        move |button| {
+            choose_file(
+                button,
+                "Add music folder",
+                gtk4::FileChooserAction::SelectFolder,
+                None,
+                None,
+                glib::clone!(
+                    #[strong]
+                    local_library,
+                    #[weak]
+                    button,
+                    move |path| {
+                        if let Err(error) = local_library.add_folder(&path) {
+                            show_message_dialog(&button, "Could not add folder", &error);
+                        }
+                    }
+                ),
+            );
+        }
+    ));
+
+    rescan_button.connect_clicked(glib::clone!(
+        #[strong]
+        local_library,
+        move |_| local_library.rescan()
+    ));
+
     content.append(&title);
     content.append(&about_group);
     content.append(&playback_group);
     content.append(&interface_group);
+    content.append(&library_group);
+    content.append(&folders_group);
+    content.append(&build_subsonic_group(&database, &sources));
+    content.append(&downloads_group);
 
     scroller.set_child(Some(&content));
     container.append(&scroller);
     container
 }
+
+/// Sink songs play through, listing devices as they are plugged in and removed
+fn build_output_device_row(playback: &PlaybackController, database: &Database) -> adw::ActionRow {
+    let device_names = gtk4::StringList::new(&[]);
+    let dropdown = gtk4::DropDown::builder().model(&device_names).build();
+    dropdown.set_valign(gtk4::Align::Center);
+
+    let row = adw::ActionRow::new();
+    row.set_title("Output device");
+    row.add_suffix(&dropdown);
+    row.set_activatable_widget(Some(&dropdown));
+    row.set_visible(playback.can_choose_output());
+
+    // Devices in the order listed after "System default"; a chosen device
+    // that is unplugged stays listed so it can still be seen and changed
+    let listed: Rc<RefCell<Vec<OutputDevice>>> = Rc::new(RefCell::new(Vec::new()));
+    let updating = Rc::new(Cell::new(false));
+
+    let refresh = glib::clone!(
+        #[weak]
+        row,
+        #[weak]
+        dropdown,
+        #[weak]
+        device_names,
+        #[strong]
+        listed,
+        #[strong]
+        updating,
+        #[strong]
+        playback,
+        move || {
+            let chosen = playback.output_device();
+            let connected = playback.is_output_connected();
+            let mut devices = playback.output_devices();
+            if let Some(chosen) = chosen.clone().filter(|_| !connected) {
+                devices.push(chosen);
+            }
+
+            let mut names = vec!["System default".to_string()];
+            names.extend(devices.iter().map(|device| match chosen {
+                Some(ref chosen) if !connected && chosen.id == device.id => {
+                    format!("{} (not connected)", device.name)
+                }
+                _ => device.name.clone(),
+            }));
+            let name_refs: Vec<&str> = names.iter().map(String::as_str).collect();
+            let selected = chosen
+                .as_ref()
+                .and_then(|chosen| devices.iter().position(|device| device.id == chosen.id))
+                .map_or(0, |index| index + 1);
+
+            updating.set(true);
+            device_names.splice(0, device_names.n_items(), &name_refs);
+            dropdown.set_selected(selected as u32);
+            updating.set(false);
+            *listed.borrow_mut() = devices;
+
+            let subtitle = if connected {
+                "Speakers or headphones that songs play through"
+            } else {
+                "Not connected, so songs play through the system default until it is"
+            };
+            row.set_subtitle(subtitle);
+        }
+    );
+    refresh();
+
+    dropdown.connect_selected_notify(glib::clone!(
+        #[strong]
+        listed,
+        #[strong]
+        updating,
+        #[strong]
+        playback,
+        #[strong]
+        database,
+        move |dropdown| {
+            if updating.get() {
+                return;
+            }
+            let device = (dropdown.selected() as usize)
+                .checked_sub(1)
+                .and_then(|index| listed.borrow().get(index).cloned());
+            if let Err(error) = save_output_device(&database, device.as_ref()) {
+                show_message_dialog(dropdown, "Could not change output device", &error);
+            }
+            playback.set_output_device(device);
+        }
+    ));
+
+    playback.connect_changed(move |change| {
+        if change == PlayerChange::OutputDevices {
+            refresh();
+        }
+    });
+
+    row
+}
+
+/// Server address and sign-in for a Subsonic server, whose songs then show up in search
+fn build_subsonic_group(database: &Database, sources: &Sources) -> adw::PreferencesGroup {
+    let group = adw::PreferencesGroup::new();
+    group.set_title("Subsonic Server");
+    group.set_description(Some("Search and play music from Navidrome or another Subsonic server"));
+
+    let saved = database.get_subsonic_credentials();
+
+    let url_row = adw::EntryRow::new();
+    url_row.set_title("Server address");
+    let username_row = adw::EntryRow::new();
+    username_row.set_title("Username");
+    if let Some(ref credentials) = saved {
+        url_row.set_text(&credentials.server_url);
+        username_row.set_text(&credentials.username);
+    }
+    let password_row = adw::PasswordEntryRow::new();
+    password_row.set_title("Password");
+
+    let connect_button = gtk4::Button::with_label("Connect");
+    connect_button.set_valign(gtk4::Align::Center);
+    connect_button.add_css_class("suggested-action");
+    let disconnect_button = gtk4::Button::with_label("Disconnect");
+    disconnect_button.set_valign(gtk4::Align::Center);
+
+    let status_row = adw::ActionRow::new();
+    status_row.set_title("Connection");
+    status_row.add_suffix(&disconnect_button);
+    status_row.add_suffix(&connect_button);
+    status_row.set_activatable(false);
+    show_subsonic_status(&status_row, &disconnect_button, saved.as_ref());
+
+    group.add(&url_row);
+    group.add(&username_row);
+    group.add(&password_row);
+    group.add(&status_row);
+
+    connect_button.connect_clicked(glib::clone!(
+        #[strong]
+        database,
+        #[strong]
+        sources,
+        #[weak]
+        url_row,
+        #[weak]
+        username_row,
+        #[weak]
+        password_row,
+        #[weak]
+        status_row,
+        #[weak]
+        disconnect_button,
+        move |button| {
+            if url_row.text().trim().is_empty() || username_row.text().trim().is_empty() {
+                show_message_dialog(button, "Could not connect", "Enter the server address and your username.");
+                return;
+            }
+            let credentials = match SubsonicCredentials::new(&url_row.text(), &username_row.text(), &password_row.text()) {
+                Ok(credentials) => credentials,
+                Err(error) => {
+                    show_message_dialog(button, "Could not connect", &error);
+                    return;
+                }
+            };
+
+            // Only keep the server once it accepts the sign-in
+            let (sender, receiver) = mpsc::channel::<Result<(), String>>();
+            let client = SubsonicClient::new(credentials.clone());
+            std::thread::spawn(move || {
+                let _ = sender.send(client.ping());
+            });
+
+            button.set_sensitive(false);
+            status_row.set_subtitle("Connecting…");
+
+            glib::timeout_add_local(
+                Duration::from_millis(POLL_INTERVAL_MS),
+                glib::clone!(
+                    #[weak_allow_none]
+                    button,
+                    #[strong]
+                    database,
+                    #[strong]
+                    sources,
+                    #[weak]
+                    password_row,
+                    #[weak]
+                    status_row,
+                    #[weak]
+                    disconnect_button,
+                    #[upgrade_or]
+                    ControlFlow::Break,
+                    move || {
+                        let result = match receiver.try_recv() {
+                            Ok(result) => result,
+                            Err(mpsc::TryRecvError::Empty) => return ControlFlow::Continue,
+                            Err(mpsc::TryRecvError::Disconnected) => return ControlFlow::Break,
+                        };
+                        let Some(button) = button else {
+                            return ControlFlow::Break;
+                        };
+                        button.set_sensitive(true);
+
+                        let saved = result.and_then(|()| database.set_subsonic_credentials(Some(&credentials)));
+                        match saved {
+                            Ok(()) => {
+                                password_row.set_text("");
+                                sources.set_subsonic(Some(credentials.clone()));
+                            }
+                            Err(ref error) => {
+                                show_message_dialog(&button, "Could not connect", error);
+                            }
+                        }
+                        let current = database.get_subsonic_credentials();
+                        show_subsonic_status(&status_row, &disconnect_button, current.as_ref());
+                        ControlFlow::Break
+                    }
+                ),
+            );
+        }
+    ));
+
+    disconnect_button.connect_clicked(glib::clone!(
+        #[strong]
+        database,
+        #[strong]
+        sources,
+        #[weak]
+        status_row,
+        move |button| {
+            if let Err(error) = database.set_subsonic_credentials(None) {
+                show_message_dialog(button, "Could not disconnect", &error);
+                return;
+            }
+            sources.set_subsonic(None);
+            show_subsonic_status(&status_row, button, None);
+        }
+    ));
+
+    group
+}
+
+fn show_subsonic_status(
+    row: &adw::ActionRow,
+    disconnect_button: &gtk4::Button,
+    credentials: Option<&SubsonicCredentials>,
+) {
+    let subtitle = match credentials {
+        Some(credentials) => format!("Signed in to {} as {}", credentials.server_url, credentials.username),
+        None => "Not connected".to_string(),
+    };
+    row.set_subtitle(&glib::markup_escape_text(&subtitle));
+    disconnect_button.set_visible(credentials.is_some());
+}
+
+fn load_downloads(
+    list: &gtk4::ListBox,
+    storage_row: &adw::ActionRow,
+    database: &Database,
+    downloads: &DownloadManager,
+    progress_bars: &Rc<RefCell<HashMap<String, gtk4::ProgressBar>>>,
+) {
+    clear_listbox(list);
+    progress_bars.borrow_mut().clear();
+
+    let items = database.get_downloads().unwrap_or_default();
+    let done = items
+        .iter()
+        .filter(|download| download.status == DownloadStatus::Done)
+        .count();
+    storage_row.set_subtitle(&format!(
+        "{} for {} downloaded songs",
+        glib::format_size(database.get_downloads_size().max(0) as u64),
+        done
+    ));
+
+    list.set_visible(!items.is_empty());
+    for download in items.iter() {
+        list.append(&create_download_row(download, downloads, progress_bars));
+    }
+}
+
+fn load_music_folders(
+    list: &gtk4::ListBox,
+    scan_row: &adw::ActionRow,
+    database: &Database,
+    local_library: &LocalLibrary,
+) {
+    clear_listbox(list);
+
+    let count = database.get_local_tracks().map(|tracks| tracks.len()).unwrap_or(0);
+    let subtitle = if local_library.is_scanning() {
+        "Scanning…".to_string()
+    } else {
+        match local_library.last_result() {
+            Some(Err(error)) => format!("Scan failed: {}", error),
+            _ => format!("{} tracks", count),
+        }
+    };
+    scan_row.set_subtitle(&glib::markup_escape_text(&subtitle));
+
+    let folders = local_library.folders();
+    list.set_visible(!folders.is_empty());
+    for folder in folders {
+        let row = gtk4::ListBoxRow::new();
+        row.set_activatable(false);
+        let action = adw::ActionRow::new();
+        action.set_title(&glib::markup_escape_text(&folder.to_string_lossy()));
+        if !folder.is_dir() {
+            action.set_subtitle("Not found; its tracks are kept until it is back");
+        }
+
+        let remove_btn = gtk4::Button::from_icon_name("user-trash-symbolic");
+        remove_btn.set_tooltip_text(Some("Remove folder"));
+        remove_btn.set_valign(gtk4::Align::Center);
+        remove_btn.add_css_class("flat");
+        remove_btn.add_css_class("circular");
+        remove_btn.connect_clicked(glib::clone!(
+            #[strong]
+            local_library,
+            move |button| {
+                if let Err(error) = local_library.remove_folder(&folder) {
+                    show_message_dialog(button, "Could not remove folder", &error);
+                }
+            }
+        ));
+        action.add_suffix(&remove_btn);
+
+        row.set_child(Some(&action));
+        list.append(&row);
+    }
+}
+
+fn create_download_row(
+    download: &Download,
+    downloads: &DownloadManager,
+    progress_bars: &Rc<RefCell<HashMap<String, gtk4::ProgressBar>>>,
+) -> gtk4::ListBoxRow {
+    let row = gtk4::ListBoxRow::new();
+    row.set_activatable(false);
+    let action = adw::ActionRow::new();
+    action.set_title(&glib::markup_escape_text(&download.song.title));
+    action.set_subtitle(&glib::markup_escape_text(&describe_download(download)));
+
+    let video_id = download.song.video_id.clone();
+
+    if matches!(download.status, DownloadStatus::Downloading | DownloadStatus::Paused) {
+        let progress = gtk4::ProgressBar::new();
+        progress.set_valign(gtk4::Align::Center);
+        progress.set_fraction(download_fraction(download.downloaded_bytes, download.total_bytes));
+        action.add_suffix(&progress);
+        progress_bars.borrow_mut().insert(video_id.clone(), progress);
+    }
+
+    // Pause running downloads; resume paused ones and retry failed ones
+    let toggle = match download.status {
+        DownloadStatus::Queued | DownloadStatus::Downloading => Some(("media-playback-pause-symbolic", "Pause")),
+        DownloadStatus::Paused => Some(("media-playback-start-symbolic", "Resume")),
+        DownloadStatus::Failed => Some(("view-refresh-symbolic", "Retry")),
+        DownloadStatus::Done => None,
+    };
+    if let Some((icon, tooltip)) = toggle {
+        let button = gtk4::Button::from_icon_name(icon);
+        button.set_tooltip_text(Some(tooltip));
+        button.set_valign(gtk4::Align::Center);
+        button.add_css_class("flat");
+        button.add_css_class("circular");
+        let status = download.status;
+        button.connect_clicked(glib::clone!(
+            #[strong]
+            downloads,
+            #[strong]
+            video_id,
+            move |button| {
+                let result = match status {
+                    DownloadStatus::Queued | DownloadStatus::Downloading => downloads.pause(&video_id),
+                    _ => downloads.resume(&video_id),
+                };
+                if let Err(error) = result {
+                    show_message_dialog(button, "Could not update download", &error);
+                }
+            }
+        ));
+        action.add_suffix(&button);
+    }
+
+    let remove_btn = gtk4::Button::from_icon_name("user-trash-symbolic");
+    remove_btn.set_tooltip_text(Some("Delete download"));
+    remove_btn.set_valign(gtk4::Align::Center);
+    remove_btn.add_css_class("flat");
+    remove_btn.add_css_class("circular");
+    remove_btn.connect_clicked(glib::clone!(
+        #[strong]
+        downloads,
+        move |button| {
+            if let Err(error) = downloads.remove(&video_id) {
+                show_message_dialog(button, "Could not delete download", &error);
+            }
+        }
+    ));
+    action.add_suffix(&remove_btn);
+
+    row.set_child(Some(&action));
+    row
+}
+
+fn describe_download(download: &Download) -> String {
+    let size = |bytes: i64| glib::format_size(bytes.max(0) as u64).to_string();
+    match download.status {
+        DownloadStatus::Queued => "Waiting to download".to_string(),
+        DownloadStatus::Downloading => match download.total_bytes {
+            Some(total) => format!("Downloading · {} of {}", size(download.downloaded_bytes), size(total)),
+            None => "Downloading".to_string(),
+        },
+        DownloadStatus::Paused => format!("Paused at {}", size(download.downloaded_bytes)),
+        DownloadStatus::Done => size(download.downloaded_bytes),
+        DownloadStatus::Failed => format!(
+            "Failed: {}",
+            download.error.as_deref().unwrap_or("Unknown error")
+        ),
+    }
+}
+
+fn download_fraction(downloaded: i64, total: Option<i64>) -> f64 {
+    match total {
+        Some(total) if total > 0 => (downloaded as f64 / total as f64).clamp(0.0, 1.0),
+        _ => 0.0,
+    }
+}
+
+fn export_library(button: &gtk4::Button, database: &Database) {
+    choose_file(
+        button,
+        "Export library",
+        gtk4::FileChooserAction::Save,
+        Some(LIBRARY_EXPORT_FILE_NAME),
+        Some(("JSON files", &["*.json"])),
+        glib::clone!(
+            #[strong]
+            database,
+            #[weak]
+            button,
+            move |path| {
+                let result = database
+                    .export_json()
+                    .and_then(|data| {
+                        std::fs::write(&path, data)
+                            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+                    });
+                match result {
+                    Ok(()) => show_message_dialog(
+                        &button,
+                        "Library exported",
+                        &format!("Saved to {}", path.display()),
+                    ),
+                    Err(error) => show_message_dialog(&button, "Export failed", &error),
+                }
+            }
+        ),
+    );
+}
+
+fn import_library(button: &gtk4::Button, database: &Database) {
+    choose_file(
+        button,
+        "Import library",
+        gtk4::FileChooserAction::Open,
+        None,
+        Some(("JSON files", &["*.json"])),
+        glib::clone!(
+            #[strong]
+            database,
+            #[weak]
+            button,
+            move |path| {
+                let data = match std::fs::read_to_string(&path) {
+                    Ok(data) => data,
+                    Err(e) => {
+                        show_message_dialog(
+                            &button,
+                            "Import failed",
+                            &format!("Failed to read {}: {}", path.display(), e),
+                        );
+                        return;
+                    }
+                };
+
+                show_import_mode_dialog(
+                    &button,
+                    glib::clone!(
+                        #[strong]
+                        database,
+                        #[weak]
+                        button,
+                        move |mode| match database.import_json(&data, mode) {
+                            Ok(summary) => show_message_dialog(
+                                &button,
+                                "Library imported",
+                                &describe_import(&summary),
+                            ),
+                            Err(error) => show_message_dialog(&button, "Import failed", &error),
+                        }
+                    ),
+                );
+            }
+        ),
+    );
+}
+
+fn show_import_mode_dialog(parent: &impl IsA<gtk4::Widget>, on_choose: impl Fn(ImportMode) + 'static) {
+    let parent_window = parent.root().and_downcast::<gtk4::Window>();
+    let dialog = gtk4::Dialog::with_buttons(
+        Some("Import library"),
+        parent_window.as_ref(),
+        gtk4::DialogFlags::MODAL,
+        &[
+            ("Cancel", gtk4::ResponseType::Cancel),
+            ("Replace", gtk4::ResponseType::Apply),
+            ("Merge", gtk4::ResponseType::Accept),
+        ],
+    );
+    dialog.set_default_response(gtk4::ResponseType::Accept);
+    if let Some(replace_button) = dialog.widget_for_response(gtk4::ResponseType::Apply) {
+        replace_button.add_css_class("destructive-action");
+    }
+
+    let content = dialog.content_area();
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    let label = gtk4::Label::new(Some(
+        "Merge adds the imported songs and playlists to your library. \
+         Replace removes your current liked songs, playlists and history first.",
+    ));
+    label.set_wrap(true);
+    label.set_xalign(0.0);
+    content.append(&label);
+
+    dialog.connect_response(move |dialog: &gtk4::Dialog, response| {
+        match response {
+            gtk4::ResponseType::Accept => on_choose(ImportMode::Merge),
+            gtk4::ResponseType::Apply => on_choose(ImportMode::Replace),
+            _ => {}
+        }
+        dialog.close();
+    });
+
+    dialog.present();
+}
+
+fn describe_import(summary: &ImportSummary) -> String {
+    format!(
+        "Added {} liked songs, {} playlists with {} songs, {} smart playlists and {} recent plays.",
+        summary.liked_songs,
+        summary.playlists,
+        summary.playlist_songs,
+        summary.smart_playlists,
+        summary.recent_plays
+    )
+}
@@ -0,0 +1,150 @@
+use gtk4::prelude::*;
+use libadwaita as adw;
+
+/// A keyboard shortcut for an action, as listed in the shortcuts window
+pub struct Shortcut {
+    pub action: &'static str,
+    pub accels: &'static [&'static str],
+    pub title: &'static str,
+}
+
+/// Takes the percentage to jump to, bound to Ctrl with each number key
+const SEEK_TO_PERCENT_ACTION: &str = "app.seek-to-percent";
+
+/// Opens the shortcuts dialog
+const SHORTCUTS_ACTION: &str = "app.show-shortcuts";
+
+/// Every keyboard shortcut, under the heading it is listed with.
+/// Shortcuts carry a modifier so they don't get in the way of typing in the search entry.
+pub const SHORTCUT_GROUPS: &[(&str, &[Shortcut])] = &[
+    (
+        "Playback",
+        &[
+            Shortcut {
+                action: "app.play-pause",
+                accels: &["<Control>space"],
+                title: "Play or pause",
+            },
+            Shortcut {
+                action: "app.next",
+                accels: &["<Control>Right"],
+                title: "Next song",
+            },
+            Shortcut {
+                action: "app.previous",
+                accels: &["<Control>Left"],
+                title: "Previous song",
+            },
+            Shortcut {
+                action: "app.seek-forward",
+                accels: &["<Shift>Right"],
+                title: "Seek forward by the seek step",
+            },
+            Shortcut {
+                action: "app.seek-back",
+                accels: &["<Shift>Left"],
+                title: "Seek back by the seek step",
+            },
+            Shortcut {
+                action: "app.seek-forward-long",
+                accels: &["<Shift><Control>Right"],
+                title: "Seek forward 30 seconds",
+            },
+            Shortcut {
+                action: "app.seek-back-long",
+                accels: &["<Shift><Control>Left"],
+                title: "Seek back 30 seconds",
+            },
+            Shortcut {
+                action: SEEK_TO_PERCENT_ACTION,
+                accels: &["<Control>0...9"],
+                title: "Jump to 0% to 90% of the song",
+            },
+            Shortcut {
+                action: "app.volume-up",
+                accels: &["<Control>Up"],
+                title: "Volume up",
+            },
+            Shortcut {
+                action: "app.volume-down",
+                accels: &["<Control>Down"],
+                title: "Volume down",
+            },
+            Shortcut {
+                action: "app.like-current",
+                accels: &["<Control>d"],
+                title: "Like or unlike the current song",
+            },
+        ],
+    ),
+    (
+        "Navigation",
+        &[
+            Shortcut {
+                action: "app.focus-search",
+                accels: &["<Control>f"],
+                title: "Search",
+            },
+            Shortcut {
+                action: "app.show-search",
+                accels: &["<Alt>1"],
+                title: "Go to Search",
+            },
+            Shortcut {
+                action: "app.show-library",
+                accels: &["<Alt>2"],
+                title: "Go to Library",
+            },
+            Shortcut {
+                action: "app.show-now-playing",
+                accels: &["<Alt>3"],
+                title: "Go to Now Playing",
+            },
+            Shortcut {
+                action: "app.show-settings",
+                accels: &["<Alt>4"],
+                title: "Go to Settings",
+            },
+            Shortcut {
+                action: SHORTCUTS_ACTION,
+                accels: &["<Control>question"],
+                title: "Keyboard shortcuts",
+            },
+        ],
+    ),
+];
+
+/// Bind every shortcut to its action
+pub fn set_shortcut_accels(app: &adw::Application) {
+    for (_, shortcuts) in SHORTCUT_GROUPS {
+        for shortcut in shortcuts.iter() {
+            // Listed as a range, but each number key needs its own binding
+            if shortcut.action == SEEK_TO_PERCENT_ACTION {
+                continue;
+            }
+            app.set_accels_for_action(shortcut.action, shortcut.accels);
+        }
+    }
+    for digit in 0..=9 {
+        app.set_accels_for_action(
+            &format!("{}({})", SEEK_TO_PERCENT_ACTION, digit * 10),
+            &[format!("<Control>{}", digit).as_str()],
+        );
+    }
+}
+
+/// Dialog listing the shortcuts, opened with `app.show-shortcuts`
+pub fn build_shortcuts_dialog() -> adw::ShortcutsDialog {
+    let dialog = adw::ShortcutsDialog::new();
+    for (title, shortcuts) in SHORTCUT_GROUPS {
+        let section = adw::ShortcutsSection::new(Some(title));
+        for shortcut in shortcuts.iter() {
+            section.add(adw::ShortcutsItem::new(
+                shortcut.title,
+                &shortcut.accels.join(" "),
+            ));
+        }
+        dialog.add(section);
+    }
+    dialog
+}
@@ -0,0 +1,129 @@
+use gtk4::gio;
+use gtk4::prelude::*;
+
+use crate::config::{ICON_SLEEP_TIMER, SLEEP_TIMER_MINUTES};
+use crate::formats::format_duration;
+use crate::playback::{PlaybackController, PlayerChange, SleepTimer};
+
+/// Stateful app action the sleep timer menus set, with a target naming the timer
+pub const SLEEP_TIMER_ACTION: &str = "sleep-timer";
+
+const TARGET_OFF: &str = "off";
+const TARGET_END_OF_TRACK: &str = "track";
+const TARGET_END_OF_QUEUE: &str = "queue";
+
+/// Action target for a sleep timer; a number of minutes, or a word for the others
+pub fn sleep_timer_target(timer: Option<SleepTimer>) -> String {
+    match timer {
+        None => TARGET_OFF.to_string(),
+        Some(SleepTimer::Minutes(minutes)) => minutes.to_string(),
+        Some(SleepTimer::EndOfTrack) => TARGET_END_OF_TRACK.to_string(),
+        Some(SleepTimer::EndOfQueue) => TARGET_END_OF_QUEUE.to_string(),
+    }
+}
+
+/// The sleep timer an action target names, or `None` to turn it off
+pub fn parse_sleep_timer_target(target: &str) -> Option<SleepTimer> {
+    match target {
+        TARGET_END_OF_TRACK => Some(SleepTimer::EndOfTrack),
+        TARGET_END_OF_QUEUE => Some(SleepTimer::EndOfQueue),
+        minutes => minutes.parse().ok().map(SleepTimer::Minutes),
+    }
+}
+
+/// Menu button for the sleep timer, followed by the time left while it runs
+pub fn build_sleep_timer_button(
+    controller: &PlaybackController,
+    css_classes: &[&str],
+) -> gtk4::Box {
+    let container = gtk4::Box::new(gtk4::Orientation::Horizontal, 4);
+    container.set_valign(gtk4::Align::Center);
+
+    let button = gtk4::MenuButton::new();
+    button.set_icon_name(ICON_SLEEP_TIMER);
+    button.set_tooltip_text(Some("Sleep timer"));
+    button.set_menu_model(Some(&build_sleep_timer_menu()));
+    for class in css_classes {
+        button.add_css_class(class);
+    }
+
+    let remaining = gtk4::Label::new(None);
+    remaining.add_css_class("caption");
+    remaining.add_css_class("sleep-timer-remaining");
+    remaining.set_visible(false);
+
+    container.append(&button);
+    container.append(&remaining);
+
+    controller.connect_changed(glib::clone!(
+        #[weak]
+        button,
+        #[weak]
+        remaining,
+        #[strong]
+        controller,
+        move |change| {
+            if change != PlayerChange::SleepTimer {
+                return;
+            }
+            let Some(timer) = controller.sleep_timer() else {
+                button.remove_css_class("sleep-timer-active");
+                remaining.set_visible(false);
+                return;
+            };
+            button.add_css_class("sleep-timer-active");
+            remaining.set_visible(true);
+
+            // Until the song's length is known, say what the timer waits for
+            let text = match (controller.sleep_remaining(), timer) {
+                (Some(usecs), _) => format_duration((usecs + 999_999) / 1_000_000),
+                (None, SleepTimer::EndOfQueue) => "End of queue".to_string(),
+                (None, _) => "End of song".to_string(),
+            };
+            remaining.set_text(&text);
+        }
+    ));
+
+    container
+}
+
+fn build_sleep_timer_menu() -> gio::Menu {
+    let durations = gio::Menu::new();
+    for minutes in SLEEP_TIMER_MINUTES {
+        durations.append(
+            Some(&minutes_label(*minutes)),
+            Some(&detailed_action(Some(SleepTimer::Minutes(*minutes)))),
+        );
+    }
+
+    let endings = gio::Menu::new();
+    endings.append(
+        Some("End of current song"),
+        Some(&detailed_action(Some(SleepTimer::EndOfTrack))),
+    );
+    endings.append(
+        Some("End of queue"),
+        Some(&detailed_action(Some(SleepTimer::EndOfQueue))),
+    );
+
+    let off = gio::Menu::new();
+    off.append(Some("Off"), Some(&detailed_action(None)));
+
+    let menu = gio::Menu::new();
+    menu.append_section(None, &durations);
+    menu.append_section(None, &endings);
+    menu.append_section(None, &off);
+    menu
+}
+
+fn detailed_action(timer: Option<SleepTimer>) -> String {
+    format!("app.{}::{}", SLEEP_TIMER_ACTION, sleep_timer_target(timer))
+}
+
+fn minutes_label(minutes: u32) -> String {
+    match minutes {
+        60 => "1 hour".to_string(),
+        minutes if minutes % 60 == 0 => format!("{} hours", minutes / 60),
+        minutes => format!("{} minutes", minutes),
+    }
+}
@@ -0,0 +1,198 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use gtk4::prelude::*;
+
+use crate::storage::{SmartPlaylist, SmartRule, SmartRuleInput, SmartRuleKind};
+
+/// Widgets making up one rule line in the editor
+struct RuleRow {
+    container: gtk4::Box,
+    kind: gtk4::DropDown,
+    value: gtk4::Entry,
+}
+
+impl RuleRow {
+    fn rule(&self) -> Option<SmartRule> {
+        let kind = SmartRuleKind::ALL.get(self.kind.selected() as usize)?;
+        SmartRule::new(*kind, &self.value.text())
+    }
+}
+
+/// Shows the rule editor, calling `on_save` with the name, rules and match-all flag
+pub fn show_smart_playlist_editor(
+    parent: &impl IsA<gtk4::Widget>,
+    existing: Option<&SmartPlaylist>,
+    on_save: impl Fn(String, Vec<SmartRule>, bool) + 'static,
+) {
+    let parent_window = parent.root().and_downcast::<gtk4::Window>();
+    let heading = if existing.is_some() {
+        "Edit smart playlist"
+    } else {
+        "New smart playlist"
+    };
+    let dialog = gtk4::Dialog::with_buttons(
+        Some(heading),
+        parent_window.as_ref(),
+        gtk4::DialogFlags::MODAL,
+        &[("Cancel", gtk4::ResponseType::Cancel), ("Save", gtk4::ResponseType::Ok)],
+    );
+    dialog.set_default_response(gtk4::ResponseType::Ok);
+    dialog.set_default_size(480, -1);
+
+    let content = dialog.content_area();
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    content.set_spacing(12);
+
+    let name_entry = gtk4::Entry::new();
+    name_entry.set_placeholder_text(Some("Playlist name"));
+    if let Some(playlist) = existing {
+        name_entry.set_text(&playlist.name);
+    }
+    content.append(&name_entry);
+
+    let match_dropdown = gtk4::DropDown::from_strings(&["Match all rules", "Match any rule"]);
+    match_dropdown.set_halign(gtk4::Align::Start);
+    if existing.is_some_and(|playlist| !playlist.match_all) {
+        match_dropdown.set_selected(1);
+    }
+    content.append(&match_dropdown);
+
+    let rules_box = gtk4::Box::new(gtk4::Orientation::Vertical, 6);
+    content.append(&rules_box);
+
+    let rows: Rc<RefCell<Vec<RuleRow>>> = Rc::new(RefCell::new(Vec::new()));
+    match existing {
+        Some(playlist) if !playlist.rules.is_empty() => {
+            for rule in &playlist.rules {
+                append_rule_row(&rules_box, &rows, Some(rule));
+            }
+        }
+        _ => append_rule_row(&rules_box, &rows, None),
+    }
+
+    let add_rule_btn = gtk4::Button::with_label("Add rule");
+    add_rule_btn.set_halign(gtk4::Align::Start);
+    add_rule_btn.add_css_class("flat");
+    add_rule_btn.connect_clicked(glib::clone!(
+        #[weak]
+        rules_box,
+        #[weak]
+        rows,
+        move |_| append_rule_row(&rules_box, &rows, None)
+    ));
+    content.append(&add_rule_btn);
+
+    let error_label = gtk4::Label::new(None);
+    error_label.set_xalign(0.0);
+    error_label.set_wrap(true);
+    error_label.add_css_class("error");
+    error_label.set_visible(false);
+    content.append(&error_label);
+
+    dialog.connect_response(move |dialog: &gtk4::Dialog, response| {
+        if response != gtk4::ResponseType::Ok {
+            dialog.close();
+            return;
+        }
+
+        let name = name_entry.text().trim().to_string();
+        let rules: Option<Vec<SmartRule>> = rows.borrow().iter().map(RuleRow::rule).collect();
+        let error = match &rules {
+            _ if name.is_empty() => Some("Enter a name for the playlist."),
+            None => Some("Every rule needs a valid value."),
+            Some(rules) if rules.is_empty() => Some("Add at least one rule."),
+            Some(_) => None,
+        };
+        if let Some(error) = error {
+            error_label.set_text(error);
+            error_label.set_visible(true);
+            return;
+        }
+
+        on_save(name, rules.unwrap_or_default(), match_dropdown.selected() == 0);
+        dialog.close();
+    });
+
+    dialog.present();
+}
+
+fn append_rule_row(rules_box: &gtk4::Box, rows: &Rc<RefCell<Vec<RuleRow>>>, rule: Option<&SmartRule>) {
+    let container = gtk4::Box::new(gtk4::Orientation::Horizontal, 6);
+
+    let labels: Vec<&str> = SmartRuleKind::ALL.iter().map(|kind| kind.label()).collect();
+    let kind = gtk4::DropDown::from_strings(&labels);
+    kind.set_hexpand(true);
+
+    let value = gtk4::Entry::new();
+    value.set_width_chars(10);
+
+    let remove_btn = gtk4::Button::from_icon_name("list-remove-symbolic");
+    remove_btn.add_css_class("flat");
+    remove_btn.add_css_class("circular");
+    remove_btn.set_tooltip_text(Some("Remove rule"));
+
+    if let Some(rule) = rule {
+        let index = SmartRuleKind::ALL
+            .iter()
+            .position(|kind| *kind == rule.kind())
+            .unwrap_or(0);
+        kind.set_selected(index as u32);
+        value.set_text(&rule.value_text());
+    }
+    update_value_entry(&kind, &value);
+
+    kind.connect_selected_notify(glib::clone!(
+        #[weak]
+        value,
+        move |kind| update_value_entry(kind, &value)
+    ));
+
+    remove_btn.connect_clicked(glib::clone!(
+        #[weak]
+        rules_box,
+        #[weak]
+        container,
+        #[weak]
+        rows,
+        move |_| {
+            rows.borrow_mut().retain(|row| row.container != container);
+            rules_box.remove(&container);
+        }
+    ));
+
+    container.append(&kind);
+    container.append(&value);
+    container.append(&remove_btn);
+    rules_box.append(&container);
+
+    rows.borrow_mut().push(RuleRow {
+        container,
+        kind,
+        value,
+    });
+}
+
+/// Show the value entry only for rules that take one, with a matching hint
+fn update_value_entry(kind: &gtk4::DropDown, value: &gtk4::Entry) {
+    let input = SmartRuleKind::ALL
+        .get(kind.selected() as usize)
+        .map_or(SmartRuleInput::None, |kind| kind.input());
+
+    match input {
+        SmartRuleInput::None => value.set_visible(false),
+        SmartRuleInput::Number => {
+            value.set_visible(true);
+            value.set_placeholder_text(Some("Number"));
+            value.set_input_purpose(gtk4::InputPurpose::Digits);
+        }
+        SmartRuleInput::Text => {
+            value.set_visible(true);
+            value.set_placeholder_text(Some("Text"));
+            value.set_input_purpose(gtk4::InputPurpose::FreeForm);
+        }
+    }
+}